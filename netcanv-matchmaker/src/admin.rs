@@ -0,0 +1,155 @@
+// the admin endpoint.
+// a tiny HTTP server that's only started when the matchmaker is launched with --admin. it serves:
+//  - GET /metrics: statistics in the Prometheus text format
+//  - GET /status: the same statistics in a human-readable form
+//  - POST /drain: stops accepting new rooms and shuts the matchmaker down once all existing rooms are closed, the same
+//    way it's shut down by SIGTERM
+// the endpoint has no authentication whatsoever, so it should only ever be bound to a local or otherwise trusted
+// address.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::{Error, Matchmaker};
+
+// how often a draining matchmaker checks whether all rooms have been closed
+const DRAIN_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// how long a connection may take to send its request. each connection is handled on its own thread, but this keeps
+// idle connections from piling up threads
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Stats {
+    rooms: usize,
+    public_rooms: usize,
+    connections: usize,
+    rejected_connections: u64,
+    relayed_packets: u64,
+    relayed_bytes: u64,
+    draining: bool,
+}
+
+impl Stats {
+
+    fn collect(mm: &Matchmaker) -> Self {
+        Self {
+            rooms: mm.rooms.len(),
+            public_rooms: mm.rooms.values().filter(|room| room.public_name.is_some()).count(),
+            connections: mm.connections,
+            rejected_connections: mm.rejected_connections,
+            relayed_packets: mm.relayed_packets,
+            relayed_bytes: mm.relayed_bytes,
+            draining: mm.draining,
+        }
+    }
+
+    fn prometheus(&self) -> String {
+        let metrics: [(&str, &str, &str, u64); 7] = [
+            ("netcanv_rooms", "gauge", "Number of open rooms.", self.rooms as u64),
+            ("netcanv_public_rooms", "gauge", "Number of publicly listed rooms.", self.public_rooms as u64),
+            ("netcanv_connections", "gauge", "Number of connected clients.", self.connections as u64),
+            ("netcanv_rejected_connections_total", "counter", "Connections turned away.", self.rejected_connections),
+            ("netcanv_relayed_packets_total", "counter", "Number of packets relayed.", self.relayed_packets),
+            ("netcanv_relayed_bytes_total", "counter", "Number of payload bytes relayed.", self.relayed_bytes),
+            ("netcanv_draining", "gauge", "Whether the matchmaker is shutting down.", self.draining as u64),
+        ];
+        let mut text = String::new();
+        for (name, kind, help, value) in &metrics {
+            text.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n{} {}\n", name, help, name, kind, name, value));
+        }
+        text
+    }
+
+    fn status(&self) -> String {
+        format!(
+            "rooms: {} ({} public)\nconnections: {} ({} rejected)\nrelayed: {} packets, {} bytes\nstatus: {}\n",
+            self.rooms,
+            self.public_rooms,
+            self.connections,
+            self.rejected_connections,
+            self.relayed_packets,
+            self.relayed_bytes,
+            if self.draining { "draining" } else { "running" },
+        )
+    }
+
+}
+
+fn respond(mut stream: &TcpStream, status: &str, content_type: &str, body: &str) -> Result<(), Error> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body,
+    )?;
+    Ok(())
+}
+
+// shuts the matchmaker down as soon as the last room is closed. clients that are still connected are told about the
+// shutdown first
+fn drain(mm: Arc<Mutex<Matchmaker>>, restart_delay: Option<Duration>) {
+    eprintln!("* draining: no new rooms will be accepted");
+    std::thread::spawn(move || loop {
+        let rooms = mm.lock().unwrap().rooms.len();
+        if rooms == 0 {
+            eprintln!("* all rooms are closed");
+            Matchmaker::shut_down(&mm, restart_delay);
+        }
+        std::thread::sleep(DRAIN_CHECK_INTERVAL);
+    });
+}
+
+fn handle_request(
+    mm: &Arc<Mutex<Matchmaker>>,
+    stream: TcpStream,
+    restart_delay: Option<Duration>,
+) -> Result<(), Error> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    match (method, path) {
+        ("GET", "/metrics") => {
+            let stats = Stats::collect(&mm.lock().unwrap());
+            respond(&stream, "200 OK", "text/plain; version=0.0.4", &stats.prometheus())
+        },
+        ("GET", "/status") => {
+            let stats = Stats::collect(&mm.lock().unwrap());
+            respond(&stream, "200 OK", "text/plain", &stats.status())
+        },
+        ("POST", "/drain") => {
+            let already_draining = std::mem::replace(&mut mm.lock().unwrap().draining, true);
+            if !already_draining {
+                drain(mm.clone(), restart_delay);
+            }
+            respond(&stream, "200 OK", "text/plain", "draining\n")
+        },
+        _ => respond(&stream, "404 Not Found", "text/plain", "not found\n"),
+    }
+}
+
+pub fn start(mm: Arc<Mutex<Matchmaker>>, addr: SocketAddr, restart_delay: Option<Duration>) -> Result<(), Error> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("Admin endpoint listening on {}", addr);
+    std::thread::spawn(move || {
+        for connection in listener.incoming() {
+            let mm = mm.clone();
+            // a slow or idle connection mustn't keep the next one from being served
+            std::thread::spawn(move || {
+                let _ = connection
+                    .map_err(Error::from)
+                    .and_then(|stream| handle_request(&mm, stream, restart_delay))
+                    .or_else(|error| -> Result<_, ()> {
+                        eprintln!("! error/admin: {}", error);
+                        Ok(())
+                    });
+            });
+        }
+    });
+    Ok(())
+}
@@ -1,28 +1,99 @@
 // the netcanv matchmaker server.
 // keeps track of open rooms and exchanges addresses between hosts and their clients
 
-use std::collections::{HashMap};
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::Entry;
 use std::error;
-use std::net::{AddrParseError, SocketAddr, TcpListener, TcpStream};
+use std::io::{ErrorKind, Write};
+use std::net::{AddrParseError, IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::num::ParseIntError;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 
 use thiserror::Error;
 
+use netcanv_protocol::build;
 use netcanv_protocol::matchmaker::*;
+use netcanv_protocol::version::{self, Handshake};
+use netcanv_protocol::version::features::{KEEPALIVE, PERSISTENT_ROOMS, SHUTDOWN_NOTICE, STRING_ROOM_IDS};
 
-const MAX_ROOM_ID: u32 = 9999;
+mod admin;
+mod rate_limit;
+mod room_id;
+mod storage;
+
+use rate_limit::{RateLimiter, Verdict};
+use room_id::{Alphabet, RoomIdFormat};
+use storage::{Storage, StoredRoom};
 
 #[derive(Clone, Debug)]
 struct Room {
     host: Arc<TcpStream>,
     clients: Vec<Weak<TcpStream>>,
-    id: u32,
+    id: String,
+    password: Option<String>,
+    // rooms with a name are listed publicly
+    public_name: Option<String>,
+    // the last time anyone joined the room or sent something through it
+    last_activity: Instant,
+    // the protocol version spoken by the host. only clients speaking the same version may join
+    version: u16,
+    // whether the room's canvas is kept in storage after the room closes
+    persistent: bool,
+    // the IP addresses the host banned from the room
+    banned: HashSet<IpAddr>,
+}
+
+#[derive(Clone, Copy)]
+struct Limits {
+    // rooms without activity for this long are closed
+    idle_timeout: Option<Duration>,
+    // the maximum number of people in a single room, including the host
+    max_clients: Option<usize>,
+    // the maximum rate at which a single client can send packets to be relayed
+    relay_packets_per_second: Option<u32>,
+    relay_bytes_per_second: Option<u32>,
+    // clients that support keepalive and don't send anything for this long, not even an answer to a ping, are
+    // disconnected
+    client_timeout: Option<Duration>,
+    // the maximum number of clients connected at once, in total and from a single IP address. connections over the
+    // limit are turned away right after they're accepted
+    max_connections: Option<usize>,
+    max_connections_per_ip: Option<usize>,
+    // clients that don't finish the handshake within this long are disconnected
+    handshake_timeout: Option<Duration>,
 }
 
 struct Matchmaker {
-    rooms: HashMap<u32, Room>,
-    host_rooms: HashMap<SocketAddr, u32>,
-    relay_clients: HashMap<SocketAddr, u32>, // mapping address → room ID
+    limits: Limits,
+    room_ids: RoomIdFormat,
+    // where persistent rooms' canvases are kept. None if rooms can't be persistent. the files are read and written
+    // outside of the lock, so that the disk doesn't hold up everyone else
+    storage: Option<Arc<Storage>>,
+    // stored rooms whose files are being read or removed outside of the lock. they can't be reopened or joined until
+    // that's done
+    busy_stored_rooms: HashSet<String>,
+    rooms: HashMap<String, Room>,
+    host_rooms: HashMap<SocketAddr, String>,
+    relay_clients: HashMap<SocketAddr, String>, // mapping address → room ID
+    // the passwords clients gave when they asked for a room's host. the password is checked again once they ask to be
+    // relayed to the room
+    join_passwords: HashMap<SocketAddr, Option<String>>,
+
+    connections_per_ip: HashMap<IpAddr, usize>,
+    // everyone who's finished the handshake, along with the features negotiated with them. used for telling everyone
+    // that the matchmaker is shutting down
+    streams: HashMap<SocketAddr, (Weak<TcpStream>, u32)>,
+
+    // statistics for the admin endpoint
+    connections: usize,
+    rejected_connections: u64,
+    relayed_packets: u64,
+    relayed_bytes: u64,
+    // when this is true, no new rooms are accepted, as the matchmaker is about to shut down
+    draining: bool,
 }
 
 #[derive(Debug, Error)]
@@ -37,33 +108,85 @@ enum Error {
     Serialize(#[from] bincode::Error),
     #[error("Invalid address: {0}")]
     InvalidAddr(#[from] AddrParseError),
+    #[error("Invalid number: {0}")]
+    InvalidNumber(#[from] ParseIntError),
+    #[error("Unknown argument: {0}")]
+    UnknownArgument(String),
+    #[error("Missing value for {0}")]
+    MissingValue(String),
+    #[error("Relay rate limit exceeded for too long")]
+    RateLimited,
+    #[error("Unsupported protocol version {0}")]
+    UnsupportedVersion(u16),
+    #[error("Unknown room ID alphabet: {0} (expected numeric or alphanumeric)")]
+    UnknownAlphabet(String),
+    #[error("Room IDs must be 1–{} characters long", RoomIdFormat::MAX_LENGTH)]
+    InvalidRoomIdLength,
+    #[error("{0} must be greater than 0")]
+    ZeroRate(String),
 }
 
 impl Matchmaker {
 
-    fn new() -> Self {
+    // how often rooms are checked for inactivity
+    const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+    // how long to wait between peeks while the first four bytes of a connection trickle in
+    const HANDSHAKE_PEEK_INTERVAL: Duration = Duration::from_millis(10);
+    // how long a rejected connection is given to say which version it speaks, so that it's told why in a format it
+    // understands. this is kept short, as connections are rejected on the thread that accepts them
+    const REJECT_PEEK_TIMEOUT: Duration = Duration::from_millis(200);
+    const UNSUPPORTED_LEGACY_CLIENT: &'static str =
+        "This matchmaker doesn't support your version of NetCanv anymore. Please update to the latest version";
+    // how many times a quiet client is pinged before it times out
+    const KEEPALIVE_PINGS_PER_TIMEOUT: u32 = 3;
+    // how often the signal thread checks whether a shutdown was requested
+    const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+    // how long to wait for clients to close their connections after being told about the shutdown
+    const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+    // how long sending the shutdown notice to a single client may take
+    const SHUTDOWN_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+    // how often stored canvases are checked for expiry
+    const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+    fn new(limits: Limits, room_ids: RoomIdFormat, storage: Option<Storage>) -> Self {
         Self {
+            limits,
+            room_ids,
+            storage: storage.map(Arc::new),
+            busy_stored_rooms: HashSet::new(),
             rooms: HashMap::new(),
             host_rooms: HashMap::new(),
             relay_clients: HashMap::new(),
+            join_passwords: HashMap::new(),
+            connections_per_ip: HashMap::new(),
+            streams: HashMap::new(),
+            connections: 0,
+            rejected_connections: 0,
+            relayed_packets: 0,
+            relayed_bytes: 0,
+            draining: false,
         }
     }
 
-    fn find_free_room_id(&self) -> Option<u32> {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-        for _ in 1..50 {
-            let id = rng.gen_range(0..=MAX_ROOM_ID);
-            if !self.rooms.contains_key(&id) {
-                return Some(id)
-            }
+    fn find_free_room_id(&self, legacy: bool) -> Option<String> {
+        self.room_ids.find_free(&mut rand::thread_rng(), legacy, |id| {
+            // closed persistent rooms keep their IDs, so that they can be reopened
+            self.rooms.contains_key(id) || self.storage.as_ref().is_some_and(|storage| storage.contains(id))
+        })
+    }
+
+    // our handshake. persistent rooms are only offered when there's somewhere to keep their canvases
+    fn our_handshake(&self) -> Handshake {
+        let mut ours = Handshake::ours();
+        if self.storage.is_none() {
+            ours.features &= !PERSISTENT_ROOMS;
         }
-        None
+        ours
     }
 
     fn send_packet(stream: &TcpStream, packet: Packet) -> Result<(), Error> {
         match &packet {
-            Packet::Relayed(..) => (),
+            Packet::Relayed(..) | Packet::Ping(_) | Packet::Pong(_) | Packet::StoredChunk(..) => (),
             packet => eprintln!("- sending packet {} -> {:?}", stream.peer_addr()?, packet),
         }
         bincode::serialize_into(stream, &packet)?;
@@ -71,33 +194,85 @@ impl Matchmaker {
     }
 
     fn send_error(stream: &TcpStream, error: &str) -> Result<(), Error> {
-        Self::send_packet(stream, error_packet(error))
+        Self::send_packet(stream, Packet::error(error))
     }
 
-    fn host(mm: Arc<Mutex<Self>>, peer_addr: SocketAddr, stream: Arc<TcpStream>) -> Result<(), Error> {
+    fn host(
+        mm: Arc<Mutex<Self>>,
+        peer_addr: SocketAddr,
+        stream: Arc<TcpStream>,
+        version: u16,
+        features: u32,
+        password: Option<String>,
+        public_name: Option<String>,
+    ) -> Result<(), Error> {
+        if let Some(name) = &public_name {
+            if name.is_empty() || name.chars().count() > MAX_ROOM_NAME_LENGTH {
+                let error = format!("Room names must be 1–{} characters long", MAX_ROOM_NAME_LENGTH);
+                return Self::send_error(&stream, &error)
+            }
+        }
         let mut mm = mm.lock().unwrap();
-        match mm.find_free_room_id() {
+        if mm.draining {
+            drop(mm);
+            return Self::send_error(&stream, "The matchmaker is shutting down and doesn't accept new rooms")
+        }
+        let string_ids = features & STRING_ROOM_IDS != 0;
+        match mm.find_free_room_id(!string_ids) {
             Some(room_id) => {
                 let room = Room {
                     host: stream.clone(),
                     clients: Vec::new(),
-                    id: room_id
+                    id: room_id.clone(),
+                    password,
+                    public_name,
+                    last_activity: Instant::now(),
+                    version,
+                    persistent: false,
+                    banned: HashSet::new(),
                 };
                 {
-                    mm.rooms.insert(room_id, room);
-                    mm.host_rooms.insert(peer_addr, room_id);
+                    mm.rooms.insert(room_id.clone(), room);
+                    mm.host_rooms.insert(peer_addr, room_id.clone());
                 }
                 drop(mm);
-                Self::send_packet(&stream, Packet::RoomId(room_id))?;
+                let packet =
+                    if string_ids { Packet::StringRoomId(room_id) }
+                    // legacy IDs always fit into a u32
+                    else { Packet::RoomId(room_id.parse().unwrap()) };
+                Self::send_packet(&stream, packet)?;
             },
             None => Self::send_error(&stream, "Could not find any more free rooms. Try again")?,
         }
         Ok(())
     }
 
-    fn join(mm: Arc<Mutex<Self>>, stream: &TcpStream, room_id: u32) -> Result<(), Error> {
-        let mm = mm.lock().unwrap();
-        let room = match mm.rooms.get(&room_id) {
+    fn join(
+        mm: Arc<Mutex<Self>>,
+        stream: &Arc<TcpStream>,
+        version: u16,
+        features: u32,
+        room_id: &str,
+        password: Option<String>,
+    ) -> Result<(), Error> {
+        let room_id = RoomIdFormat::normalize(room_id);
+        let mut mm_guard = mm.lock().unwrap();
+        if mm_guard.busy_stored_rooms.contains(&room_id) {
+            drop(mm_guard);
+            return Self::send_error(stream, "This room is being reopened. Try again in a moment")
+        }
+        if !mm_guard.rooms.contains_key(&room_id) && features & PERSISTENT_ROOMS != 0 {
+            if let Some(storage) = mm_guard.storage.clone().filter(|storage| storage.contains(&room_id)) {
+                mm_guard.busy_stored_rooms.insert(room_id.clone());
+                drop(mm_guard);
+                let result = Self::reopen(mm.clone(), &storage, stream.clone(), version, &room_id, password);
+                mm.lock().unwrap().busy_stored_rooms.remove(&room_id);
+                return result
+            }
+        }
+        let mut mm = mm_guard;
+        let max_clients = mm.limits.max_clients;
+        let room = match mm.rooms.get_mut(&room_id) {
             Some(room) => room,
             None => {
                 Self::send_error(stream,
@@ -106,28 +281,298 @@ impl Matchmaker {
             },
         };
         let client_addr = stream.peer_addr()?;
+        if let Err(error) = Self::check_join(room, client_addr, version, &password, max_clients) {
+            return Self::send_error(stream, error)
+        }
+        room.last_activity = Instant::now();
         let host_addr = room.host.peer_addr()?;
         Self::send_packet(&room.host, Packet::ClientAddress(client_addr))?;
-        Self::send_packet(stream, Packet::HostAddress(host_addr))
+        Self::send_packet(stream, Packet::HostAddress(host_addr))?;
+        mm.join_passwords.insert(client_addr, password);
+        Ok(())
+    }
+
+    // checks whether the client may join the room, returning the error to send them if they may not. this is done both
+    // when the client asks for the room's host and when it asks to be relayed to the room, so that asking to be
+    // relayed right away doesn't get around any of the checks
+    fn check_join(
+        room: &mut Room,
+        client_addr: SocketAddr,
+        version: u16,
+        password: &Option<String>,
+        max_clients: Option<usize>,
+    ) -> Result<(), &'static str> {
+        if room.password.is_some() && *password != room.password {
+            return Err(
+                if password.is_none() { "This room is protected with a password. Enter it to join" }
+                else { "Incorrect room password" }
+            )
+        }
+        if room.banned.contains(&client_addr.ip()) {
+            return Err("You have been banned from this room")
+        }
+        if room.version != version {
+            return Err(
+                if room.version > version { "This room was created with a newer version of NetCanv. Update to join" }
+                else { "This room was created with an older version of NetCanv" }
+            )
+        }
+        if let Some(max_clients) = max_clients {
+            // the host is a relay client too, so it's counted in here
+            room.clients.retain(|client| client.upgrade().is_some());
+            if room.clients.len() >= max_clients {
+                return Err("This room is full")
+            }
+        }
+        Ok(())
+    }
+
+    // reopens a closed persistent room with the client as its host, and sends it the stored canvas. this is done
+    // without holding the lock, with the room marked as busy so that nobody can join it before its host has all of
+    // the canvas
+    fn reopen(
+        mm: Arc<Mutex<Self>>,
+        storage: &Storage,
+        stream: Arc<TcpStream>,
+        version: u16,
+        room_id: &str,
+        password: Option<String>,
+    ) -> Result<(), Error> {
+        let stored = match storage.room(room_id)? {
+            Some(stored) => stored,
+            None => return Self::send_error(&stream, "No room found with the given ID"),
+        };
+        if stored.password.is_some() && password != stored.password {
+            let error =
+                if password.is_none() { "This room is protected with a password. Enter it to join" }
+                else { "Incorrect room password" };
+            return Self::send_error(&stream, error)
+        }
+        let chunks = storage.chunks(room_id)?;
+        // the room's been opened, so its expiry time starts over
+        storage.save_room(room_id, &stored)?;
+
+        {
+            let mut mm = mm.lock().unwrap();
+            if mm.draining {
+                drop(mm);
+                return Self::send_error(&stream, "The matchmaker is shutting down and doesn't accept new rooms")
+            }
+            eprintln!("* reopening room {} with {} stored chunks", room_id, chunks.len());
+            let room = Room {
+                host: stream.clone(),
+                clients: Vec::new(),
+                id: room_id.into(),
+                password: stored.password,
+                public_name: stored.public_name,
+                last_activity: Instant::now(),
+                version,
+                persistent: true,
+                banned: HashSet::new(),
+            };
+            mm.rooms.insert(room_id.into(), room);
+            mm.host_rooms.insert(stream.peer_addr()?, room_id.into());
+        }
+        Self::send_packet(&stream, Packet::Reopened(room_id.into()))?;
+        Self::send_packet(&stream, Packet::RoomPersisted(Some(storage.expiry_days())))?;
+        for (position, png_data) in chunks {
+            Self::send_packet(&stream, Packet::StoredChunk(position, png_data))?;
+        }
+        Ok(())
+    }
+
+    // starts keeping the canvas of the host's room, or stops keeping it and throws away what's been stored
+    fn persist(mm: Arc<Mutex<Self>>, peer_addr: SocketAddr, stream: &TcpStream, persist: bool) -> Result<(), Error> {
+        let (storage, room_id, stored, was_persistent) = {
+            let mm = mm.lock().unwrap();
+            let storage = match &mm.storage {
+                Some(storage) => storage.clone(),
+                None => {
+                    drop(mm);
+                    return Self::send_error(stream, "This matchmaker doesn't keep rooms' canvases")
+                },
+            };
+            let room = match mm.host_rooms.get(&peer_addr).and_then(|room_id| mm.rooms.get(room_id)) {
+                Some(room) => room,
+                None => {
+                    drop(mm);
+                    return Self::send_error(stream, "Only the host can choose whether the room's canvas is kept")
+                },
+            };
+            let stored = StoredRoom {
+                password: room.password.clone(),
+                public_name: room.public_name.clone(),
+            };
+            (storage, room.id.clone(), stored, room.persistent)
+        };
+        let set_persistent = |persistent: bool| {
+            if let Some(room) = mm.lock().unwrap().rooms.get_mut(&room_id) {
+                room.persistent = persistent;
+            }
+        };
+        if !persist {
+            set_persistent(false);
+            storage.remove_room(&room_id)?;
+            return Self::send_packet(stream, Packet::RoomPersisted(None))
+        }
+        if !was_persistent && storage.is_full()? {
+            return Self::send_error(stream, "The matchmaker can't keep any more canvases. Try again later")
+        }
+        storage.save_room(&room_id, &stored)?;
+        set_persistent(true);
+        Self::send_packet(stream, Packet::RoomPersisted(Some(storage.expiry_days())))
+    }
+
+    fn store_chunk(
+        mm: Arc<Mutex<Self>>,
+        peer_addr: SocketAddr,
+        stream: &TcpStream,
+        position: (i32, i32),
+        png_data: &[u8],
+    ) -> Result<(), Error> {
+        let target = {
+            let mm = mm.lock().unwrap();
+            let room = mm.host_rooms.get(&peer_addr).and_then(|room_id| mm.rooms.get(room_id));
+            match (room, &mm.storage) {
+                (Some(room), Some(storage)) if room.persistent => Some((storage.clone(), room.id.clone())),
+                _ => None,
+            }
+        };
+        match target {
+            Some((storage, room_id)) => {
+                if !storage.store_chunk(&room_id, position, png_data)? {
+                    eprintln!("- room {} is out of storage space, chunk {:?} wasn't stored", room_id, position);
+                }
+                Ok(())
+            },
+            // chunks may still be on their way after the host stopped keeping the canvas, so this isn't an error
+            None => {
+                eprintln!("- {} sent a chunk for a room whose canvas isn't being kept", stream.peer_addr()?);
+                Ok(())
+            },
+        }
     }
 
-    fn add_relay(mm: Arc<Mutex<Self>>, stream: Arc<TcpStream>, host_addr: Option<SocketAddr>) -> Result<(), Error> {
+    // cuts someone off from the host's room, and bans them from joining it again if asked to. everyone left in the
+    // room is told that they've disconnected
+    fn kick(
+        mm: Arc<Mutex<Self>>,
+        peer_addr: SocketAddr,
+        stream: &TcpStream,
+        addr: SocketAddr,
+        ban: bool,
+    ) -> Result<(), Error> {
+        let (kicked, clients) = {
+            let mut mm = mm.lock().unwrap();
+            let room_id = match mm.host_rooms.get(&peer_addr) {
+                Some(room_id) => room_id.clone(),
+                None => {
+                    drop(mm);
+                    return Self::send_error(stream, "Only the host can kick people from the room")
+                },
+            };
+            if addr == peer_addr {
+                return Ok(())
+            }
+            eprintln!("- {} {} {} from room {}", peer_addr, if ban { "banned" } else { "kicked" }, addr, room_id);
+            let is_relayed = mm.relay_clients.get(&addr) == Some(&room_id);
+            if is_relayed {
+                mm.relay_clients.remove(&addr);
+            }
+            let room = mm.rooms.get_mut(&room_id).unwrap();
+            if ban {
+                room.banned.insert(addr.ip());
+            }
+            if !is_relayed {
+                return Ok(())
+            }
+            let mut kicked = None;
+            let mut clients = Vec::new();
+            for client in room.clients.iter().filter_map(|client| client.upgrade()) {
+                if client.peer_addr().ok() == Some(addr) {
+                    kicked = Some(client);
+                } else {
+                    clients.push(client);
+                }
+            }
+            room.clients.retain(|client| {
+                client.upgrade().is_some_and(|client| client.peer_addr().ok() != Some(addr))
+            });
+            (kicked, clients)
+        };
+        for client in clients {
+            Self::send_packet(&client, Packet::Disconnected(addr))?;
+        }
+        // the host already told them, but they can't be trusted to actually leave
+        if let Some(kicked) = kicked {
+            let error = if ban { "You have been banned from the room" } else { "You have been kicked from the room" };
+            Self::send_error(&kicked, error)?;
+        }
+        Ok(())
+    }
+
+    fn list_rooms(mm: Arc<Mutex<Self>>, stream: &TcpStream, features: u32) -> Result<(), Error> {
+        let mut rooms: Vec<ListedRoom> = {
+            let mm = mm.lock().unwrap();
+            mm.rooms
+                .values()
+                .filter_map(|room| Some(ListedRoom {
+                    id: room.id.clone(),
+                    name: room.public_name.clone()?,
+                    // the host is a relay client too, so it's already counted in here
+                    peer_count: room.clients.iter().filter(|client| client.upgrade().is_some()).count() as u32,
+                    has_password: room.password.is_some(),
+                }))
+                .collect()
+        };
+        rooms.sort_by(|a, b| a.id.cmp(&b.id));
+        if features & STRING_ROOM_IDS != 0 {
+            return Self::send_packet(stream, Packet::ListedRooms(rooms))
+        }
+        // clients without string IDs can only be told about rooms with numeric IDs
+        let rooms = rooms
+            .into_iter()
+            .filter_map(|room| Some(PublicRoom {
+                id: room.id.parse().ok()?,
+                name: room.name,
+                peer_count: room.peer_count,
+                has_password: room.has_password,
+            }))
+            .collect();
+        Self::send_packet(stream, Packet::RoomList(rooms))
+    }
+
+    fn add_relay(
+        mm: Arc<Mutex<Self>>,
+        stream: Arc<TcpStream>,
+        version: u16,
+        host_addr: Option<SocketAddr>,
+    ) -> Result<(), Error> {
         let peer_addr = stream.peer_addr().unwrap();
         eprintln!("- relay requested from {}", peer_addr);
 
         let host_addr: SocketAddr = host_addr.unwrap_or(peer_addr);
         {
             let mut mm = mm.lock().unwrap();
-            let room_id: u32;
-            match mm.host_rooms.get(&host_addr) {
-                Some(id) => room_id = *id,
+            let max_clients = mm.limits.max_clients;
+            let password = mm.join_passwords.remove(&peer_addr).flatten();
+            let room_id = match mm.host_rooms.get(&host_addr) {
+                Some(id) => id.clone(),
                 None => {
                     Self::send_error(&stream, "The host seems to have disconnected")?;
                     return Ok(());
                 },
+            };
+            let room = mm.rooms.get_mut(&room_id).unwrap();
+            // the host doesn't have to be let into its own room
+            if host_addr != peer_addr {
+                if let Err(error) = Self::check_join(room, peer_addr, version, &password, max_clients) {
+                    drop(mm);
+                    return Self::send_error(&stream, error)
+                }
             }
-            mm.relay_clients.insert(peer_addr, room_id);
-            mm.rooms.get_mut(&room_id).unwrap().clients.push(Arc::downgrade(&stream));
+            room.clients.push(Arc::downgrade(&stream));
+            mm.relay_clients.insert(peer_addr, room_id.clone());
         }
 
         Ok(())
@@ -143,9 +588,13 @@ impl Matchmaker {
         // XXX: this can bottleneck the server if there are many relays running at the same time
         // because the mutex is locked for the entire duration of the server relaying packets!!!
         let mut mm = mm.lock().unwrap();
+        // everyone's being told about the shutdown, which mustn't be interleaved with relayed packets
+        if mm.draining {
+            return Ok(())
+        }
         let room_id =
             match mm.relay_clients.get(&addr) {
-                Some(id) => *id,
+                Some(id) => id.clone(),
                 None => {
                     Self::send_error(stream, "Only relay clients may send Relay packets")?;
                     return Ok(())
@@ -153,6 +602,7 @@ impl Matchmaker {
             };
         match mm.rooms.get_mut(&room_id) {
             Some(room) => {
+                room.last_activity = Instant::now();
                 let mut nclients = 0;
                 room.clients.retain(|client| client.upgrade().is_some());
                 for client in &room.clients {
@@ -168,6 +618,8 @@ impl Matchmaker {
                     }
                 }
                 eprintln!("- relayed from {} to {} clients", addr, nclients);
+                mm.relayed_packets += nclients;
+                mm.relayed_bytes += nclients * data.len() as u64;
             },
             None => {
                 Self::send_error(stream, "The host seems to have disconnected")?;
@@ -182,17 +634,30 @@ impl Matchmaker {
         mm: Arc<Mutex<Self>>,
         peer_addr: SocketAddr,
         stream: Arc<TcpStream>,
+        version: u16,
+        features: u32,
         packet: Packet
     ) -> Result<(), Error> {
         match &packet {
-            Packet::Relay(..) => (),
+            Packet::Relay(..) | Packet::Ping(_) | Packet::Pong(_) | Packet::StoreChunk(..) => (),
             packet => eprintln!("- incoming packet: {:?}", packet),
         }
         match packet {
-            Packet::Host => Self::host(mm, peer_addr, stream),
-            Packet::GetHost(room_id) => Self::join(mm, &stream, room_id),
-            Packet::RequestRelay(host_addr) => Self::add_relay(mm, stream, host_addr),
+            Packet::Host(password, public_name) =>
+                Self::host(mm, peer_addr, stream, version, features, password, public_name),
+            Packet::GetHost(room_id, password) =>
+                Self::join(mm, &stream, version, features, &room_id.to_string(), password),
+            Packet::GetHostByStringId(room_id, password) =>
+                Self::join(mm, &stream, version, features, &room_id, password),
+            Packet::ListRooms => Self::list_rooms(mm, &stream, features),
+            Packet::RequestRelay(host_addr) => Self::add_relay(mm, stream, version, host_addr),
             Packet::Relay(to, data) => Self::relay(mm, peer_addr, &stream, to, &data),
+            Packet::Ping(id) => Self::send_packet(&stream, Packet::Pong(id)),
+            Packet::PersistRoom(persist) => Self::persist(mm, peer_addr, &stream, persist),
+            Packet::StoreChunk(position, png_data) => Self::store_chunk(mm, peer_addr, &stream, position, &png_data),
+            Packet::Kick(addr, ban) => Self::kick(mm, peer_addr, &stream, addr, ban),
+            // answers to keepalive pings. receiving anything at all is enough to know the client is still there
+            Packet::Pong(_) => Ok(()),
             _ => {
                 eprintln!("! error/invalid packet: {:?}", packet);
                 Err(Error::InvalidPacket)
@@ -201,8 +666,17 @@ impl Matchmaker {
     }
 
     fn disconnect(&mut self, addr: SocketAddr) -> Result<(), Error> {
+        self.connections -= 1;
+        if let Entry::Occupied(mut count) = self.connections_per_ip.entry(addr.ip()) {
+            *count.get_mut() -= 1;
+            if *count.get() == 0 {
+                count.remove();
+            }
+        }
         if let Some(room_id) = self.host_rooms.remove(&addr) {
-            self.rooms.remove(&room_id);
+            if let Some(room) = self.rooms.remove(&room_id) {
+                Self::room_closed(self.storage.as_deref(), &room);
+            }
         }
         if let Some(room_id) = self.relay_clients.remove(&addr) {
             if let Some(room) = self.rooms.get_mut(&room_id) {
@@ -214,30 +688,370 @@ impl Matchmaker {
                 }
             }
         }
+        self.join_passwords.remove(&addr);
+        self.streams.remove(&addr);
+        Ok(())
+    }
+
+    // closes rooms nothing happened in for longer than the timeout. everyone in them is told about it after the lock is
+    // released, so that a client that isn't reading doesn't hold up the whole matchmaker
+    fn close_idle_rooms(mm: &Mutex<Self>, timeout: Duration) {
+        let (closed, storage) = {
+            let mut mm = mm.lock().unwrap();
+            let idle: Vec<String> = mm.rooms
+                .values()
+                .filter(|room| room.last_activity.elapsed() > timeout)
+                .map(|room| room.id.clone())
+                .collect();
+            let mut closed = Vec::new();
+            for room_id in idle {
+                let room = mm.rooms.remove(&room_id).unwrap();
+                eprintln!("* closing room {} due to inactivity", room_id);
+                mm.host_rooms.retain(|_, id| *id != room_id);
+                mm.relay_clients.retain(|_, id| *id != room_id);
+                closed.push(room);
+            }
+            (closed, mm.storage.clone())
+        };
+        for room in closed {
+            let clients = room.clients
+                .iter()
+                .filter_map(|client| client.upgrade())
+                .filter(|client| !Arc::ptr_eq(client, &room.host));
+            for stream in std::iter::once(room.host.clone()).chain(clients) {
+                if let Err(error) = Self::send_packet(&stream, Packet::RoomClosed(CloseReason::Inactivity)) {
+                    eprintln!("! error/while telling a client room {} closed: {}", room.id, error);
+                }
+            }
+            Self::room_closed(storage.as_deref(), &room);
+        }
+    }
+
+    // the expiry time of a persistent room's canvas counts from when the room was last open, so it starts over once
+    // the room closes
+    fn room_closed(storage: Option<&Storage>, room: &Room) {
+        if let (true, Some(storage)) = (room.persistent, storage) {
+            let stored = StoredRoom {
+                password: room.password.clone(),
+                public_name: room.public_name.clone(),
+            };
+            if let Err(error) = storage.save_room(&room.id, &stored) {
+                eprintln!("! error/while storing room {}: {}", room.id, error);
+            }
+        }
+    }
+
+    // throws away the canvases of closed rooms that haven't been opened for longer than the expiry time. each room is
+    // marked as busy while it's being looked at, so that it can't be reopened halfway through being removed
+    fn remove_expired_rooms(mm: &Mutex<Self>) -> Result<(), Error> {
+        let storage = match &mm.lock().unwrap().storage {
+            Some(storage) => storage.clone(),
+            None => return Ok(()),
+        };
+        for room_id in storage.room_ids()? {
+            {
+                let mut mm = mm.lock().unwrap();
+                // open rooms are in use, however long ago they were opened
+                if mm.rooms.contains_key(&room_id) || !mm.busy_stored_rooms.insert(room_id.clone()) {
+                    continue
+                }
+            }
+            let result = storage.is_expired(&room_id).and_then(|expired| {
+                if expired {
+                    eprintln!("* removing the stored canvas of room {}, as it expired", room_id);
+                    storage.remove_room(&room_id)?;
+                }
+                Ok(())
+            });
+            mm.lock().unwrap().busy_stored_rooms.remove(&room_id);
+            result?;
+        }
+        Ok(())
+    }
+
+    // tells everyone connected that the matchmaker is shutting down, and closes the sending half of their
+    // connections. nothing is relayed anymore once the matchmaker is draining, so the notices can be sent without
+    // holding the lock, and without being interleaved with relayed packets
+    fn notify_shutdown(mm: &Mutex<Self>, restart_delay: Option<Duration>) {
+        let streams: Vec<_> = {
+            let mut mm = mm.lock().unwrap();
+            mm.draining = true;
+            eprintln!("* shutting down, notifying {} clients", mm.connections);
+            mm.streams
+                .iter()
+                .filter_map(|(&addr, (stream, features))| Some((addr, stream.upgrade()?, *features)))
+                .collect()
+        };
+        for (addr, stream, features) in streams {
+            let packet =
+                if features & SHUTDOWN_NOTICE != 0 {
+                    Packet::ShuttingDown(restart_delay.map(|delay| delay.as_secs() as u32))
+                } else {
+                    Packet::error("The matchmaker is shutting down")
+                };
+            // a client that isn't reading what's sent to it mustn't hold up notifying everyone else
+            let _ = stream.set_write_timeout(Some(Self::SHUTDOWN_WRITE_TIMEOUT))
+                .map_err(Error::from)
+                .and_then(|_| Self::send_packet(&stream, packet))
+                .and_then(|_| Ok(stream.shutdown(Shutdown::Write)?))
+                .or_else(|error| -> Result<_, ()> {
+                    eprintln!("! error/while notifying {} about the shutdown: {}", addr, error);
+                    Ok(())
+                });
+        }
+    }
+
+    // shuts the matchmaker down gracefully once SIGINT or SIGTERM is received. everyone is notified first, and given
+    // some time to receive what's left of the data sent to them and disconnect. a second SIGINT exits immediately
+    fn start_signal_thread(mm: Arc<Mutex<Self>>, restart_delay: Option<Duration>) -> Result<(), Error> {
+        use signal_hook::consts::{SIGINT, SIGTERM};
+
+        let shutdown = Arc::new(AtomicBool::new(false));
+        // the conditional shutdown must be registered first, so that it only sees the flag set by earlier signals
+        signal_hook::flag::register_conditional_shutdown(SIGINT, 1, shutdown.clone())?;
+        signal_hook::flag::register(SIGINT, shutdown.clone())?;
+        signal_hook::flag::register(SIGTERM, shutdown.clone())?;
+
+        std::thread::spawn(move || {
+            while !shutdown.load(Ordering::Relaxed) {
+                std::thread::sleep(Self::SIGNAL_CHECK_INTERVAL);
+            }
+            Self::shut_down(&mm, restart_delay);
+        });
+        Ok(())
+    }
+
+    // notifies everyone about the shutdown, gives them some time to disconnect, and exits
+    fn shut_down(mm: &Mutex<Self>, restart_delay: Option<Duration>) -> ! {
+        Self::notify_shutdown(mm, restart_delay);
+        let deadline = Instant::now() + Self::SHUTDOWN_TIMEOUT;
+        while mm.lock().unwrap().connections > 0 && Instant::now() < deadline {
+            std::thread::sleep(Self::SIGNAL_CHECK_INTERVAL);
+        }
+        eprintln!("* bye");
+        std::process::exit(0);
+    }
+
+    fn start_idle_thread(mm: Arc<Mutex<Self>>, timeout: Duration) {
+        std::thread::spawn(move || loop {
+            std::thread::sleep(Self::IDLE_CHECK_INTERVAL);
+            Self::close_idle_rooms(&mm, timeout);
+        });
+    }
+
+    fn start_expiry_thread(mm: Arc<Mutex<Self>>) {
+        std::thread::spawn(move || loop {
+            if let Err(error) = Self::remove_expired_rooms(&mm) {
+                eprintln!("! error/while removing expired canvases: {}", error);
+            }
+            std::thread::sleep(Self::EXPIRY_CHECK_INTERVAL);
+        });
+    }
+
+    // checks a relayed packet against the client's rate limit, holding it back until the client is under the limit.
+    // the client isn't told about this, as it's not doing anything wrong by sending a lot of data every now and then
+    fn check_rate_limit(limiter: &mut RateLimiter, stream: &TcpStream, data: &[u8]) -> Result<(), Error> {
+        loop {
+            match limiter.check(data.len()) {
+                Verdict::Allow => return Ok(()),
+                Verdict::Delay { wait, first } => {
+                    if first {
+                        eprintln!("- rate limiting {}", stream.peer_addr()?);
+                    }
+                    std::thread::sleep(wait);
+                },
+                Verdict::Disconnect => {
+                    Self::send_error(stream, "You've been disconnected for sending too much data")?;
+                    stream.shutdown(Shutdown::Both)?;
+                    return Err(Error::RateLimited)
+                },
+            }
+        }
+    }
+
+    // waits for the first four bytes of the connection, without consuming them. returns None if they don't arrive
+    // before the deadline
+    fn peek_magic(stream: &TcpStream, deadline: Option<Instant>) -> Result<Option<[u8; 4]>, Error> {
+        // a read timeout of zero isn't allowed, so the last peek waits for at least one interval
+        let timeout = deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()).max(Self::HANDSHAKE_PEEK_INTERVAL));
+        stream.set_read_timeout(timeout)?;
+        let mut magic = [0; 4];
+        loop {
+            match stream.peek(&mut magic) {
+                Ok(0) => return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into()),
+                Ok(n) if n >= magic.len() => return Ok(Some(magic)),
+                Ok(_) => (),
+                Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => (),
+                Err(error) => return Err(error.into()),
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(None)
+            }
+            std::thread::sleep(Self::HANDSHAKE_PEEK_INTERVAL);
+        }
+    }
+
+    // tells a client from before versioning was introduced that it can't connect, and closes the connection
+    fn refuse_legacy(stream: &TcpStream, error: &str) -> Result<(), Error> {
+        (&*stream).write_all(&version::legacy_error(error))?;
+        stream.shutdown(Shutdown::Write)?;
+        Ok(())
+    }
+
+    // reads the client's handshake and answers it with ours, returning the client's handshake.
+    // clients from before versioning was introduced don't send a handshake, so if the connection doesn't start with
+    // the magic number, the client is told to update in a format it understands, and turned away.
+    // clients that take longer than the timeout are cut off, so that idle connections can't pile up
+    fn handshake(stream: &TcpStream, timeout: Option<Duration>, ours: Handshake) -> Result<Handshake, Error> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let magic = Self::peek_magic(stream, deadline)?
+            .ok_or_else(|| Error::from(std::io::Error::from(ErrorKind::TimedOut)))?;
+        if magic != version::MAGIC {
+            Self::refuse_legacy(stream, Self::UNSUPPORTED_LEGACY_CLIENT)?;
+            return Err(Error::UnsupportedVersion(1))
+        }
+
+        stream.set_read_timeout(timeout)?;
+        let theirs: Handshake = bincode::deserialize_from(stream)?;
+        bincode::serialize_into(stream, &ours)?;
+        if !theirs.is_supported() {
+            // Error packets decode the same way in every version with a handshake, so the client can still show this
+            let error = format!(
+                "This matchmaker doesn't support your version of NetCanv (protocol version {}, expected {}–{})",
+                theirs.version, version::OLDEST_SUPPORTED_VERSION, version::VERSION,
+            );
+            Self::send_error(stream, &error)?;
+            stream.shutdown(Shutdown::Both)?;
+            return Err(Error::UnsupportedVersion(theirs.version))
+        }
+        stream.set_read_timeout(None)?;
+        Ok(theirs)
+    }
+
+    // the reason why a new connection from the given address should be turned away, if it should be
+    fn connection_limit_error(&self, ip: IpAddr) -> Option<&'static str> {
+        if self.limits.max_connections.is_some_and(|max| self.connections >= max) {
+            return Some("The matchmaker is full. Try again later")
+        }
+        let connections_from_ip = self.connections_per_ip.get(&ip).copied().unwrap_or(0);
+        if self.limits.max_connections_per_ip.is_some_and(|max| connections_from_ip >= max) {
+            return Some("There are too many connections from your address")
+        }
+        None
+    }
+
+    // turns a connection away. this doesn't read the client's handshake, so that rejected clients can't tie up a
+    // thread; ours is sent anyway, as clients expect it before any packets. clients from before versioning was
+    // introduced can't read it, so they're sent an Error packet in the old format instead. a client that hasn't sent
+    // anything by the time the peek gives up is assumed to be a current one
+    fn reject(stream: &TcpStream, error: &str) -> Result<(), Error> {
+        let magic = Self::peek_magic(stream, Some(Instant::now() + Self::REJECT_PEEK_TIMEOUT))?;
+        if magic.is_some_and(|magic| magic != version::MAGIC) {
+            return Self::refuse_legacy(stream, error)
+        }
+        bincode::serialize_into(stream, &Handshake::ours())?;
+        Self::send_error(stream, error)?;
+        stream.shutdown(Shutdown::Write)?;
         Ok(())
     }
 
+    // whether reading a packet failed because the client didn't send anything for longer than the read timeout
+    fn timed_out(error: &build::Error) -> bool {
+        match error {
+            build::Error::Encode(error) => match &**error {
+                bincode::ErrorKind::Io(error) => matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
     fn start_client_thread(mm: Arc<Mutex<Self>>, stream: TcpStream) -> Result<(), Error> {
         let peer_addr = stream.peer_addr()?;
+        let (mut limiter, client_timeout, handshake_timeout, ours) = {
+            let mut mm = mm.lock().unwrap();
+            if let Some(error) = mm.connection_limit_error(peer_addr.ip()) {
+                mm.rejected_connections += 1;
+                drop(mm);
+                eprintln!("* turning {} away: {}", peer_addr, error);
+                return Self::reject(&stream, error)
+            }
+            mm.connections += 1;
+            *mm.connections_per_ip.entry(peer_addr.ip()).or_insert(0) += 1;
+            let limiter = RateLimiter::new(mm.limits.relay_packets_per_second, mm.limits.relay_bytes_per_second);
+            (limiter, mm.limits.client_timeout, mm.limits.handshake_timeout, mm.our_handshake())
+        };
         let stream = Arc::new(stream);
         eprintln!("* mornin' mr. {}", peer_addr);
         let _ = std::thread::spawn(move || {
+            let handshake = match Self::handshake(&stream, handshake_timeout, ours) {
+                Ok(handshake) => handshake,
+                Err(error) => {
+                    eprintln!("! error/handshake with {}: {}", peer_addr, error);
+                    // this decrements the connection count back, and there's nothing else to clean up yet
+                    let _ = mm.lock().unwrap().disconnect(peer_addr);
+                    return
+                },
+            };
+            let version = handshake.version;
+            let features = handshake.negotiate(&ours);
+            eprintln!("- {} speaks protocol version {}", peer_addr, version);
+            mm.lock().unwrap().streams.insert(peer_addr, (Arc::downgrade(&stream), features));
+            // clients that can't answer pings may stay quiet for as long as they like
+            let client_timeout = client_timeout.filter(|_| features & KEEPALIVE != 0);
+            // wake up a few times per timeout to check on the client
+            let check_interval = client_timeout.map(|timeout| timeout / Self::KEEPALIVE_PINGS_PER_TIMEOUT);
+            let mut last_received = Instant::now();
+            let disconnect = || {
+                let _ = mm.lock().unwrap().disconnect(peer_addr)
+                    .or_else(|error| -> Result<_, ()> {
+                        eprintln!("! error/while disconnecting {}: {}", peer_addr, error);
+                        Ok(())
+                    });
+            };
             loop {
+                // the check interval only applies while waiting for the next packet. giving up on reading a packet
+                // halfway through would leave the rest of it to be mistaken for the next one, so once a packet starts
+                // coming in, the client gets the whole timeout to send the rest of it
+                let _ = stream.set_read_timeout(check_interval);
                 let mut buf = [0; 1];
-                if let Ok(n) = stream.peek(&mut buf) {
-                    if n == 0 {
-                        let _ = mm.lock().unwrap().disconnect(peer_addr)
-                            .or_else(|error| -> Result<_, ()> {
-                                eprintln!("! error/while disconnecting {}: {}", peer_addr, error);
-                                Ok(())
-                            });
+                match stream.peek(&mut buf) {
+                    Ok(0) => {
+                        disconnect();
                         break
-                    }
+                    },
+                    Ok(_) => last_received = Instant::now(),
+                    Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                        // the client has been quiet for a while, so check whether it's still there
+                        if client_timeout.is_some_and(|timeout| last_received.elapsed() >= timeout) {
+                            eprintln!("* {} timed out", peer_addr);
+                            let _ = stream.shutdown(Shutdown::Both);
+                            disconnect();
+                            break
+                        }
+                        let _ = Self::send_packet(&stream, Packet::Ping(0));
+                        continue
+                    },
+                    Err(_) => (),
                 }
-                let _ = bincode::deserialize_from(&*stream) // what
-                    .map_err(|_| Error::Deserialize)
+                let _ = stream.set_read_timeout(client_timeout);
+                let decoded = match build::decode_from(&*stream) {
+                    Ok(decoded) => Ok(decoded),
+                    Err(error) if Self::timed_out(&error) => {
+                        eprintln!("* {} timed out in the middle of a packet", peer_addr);
+                        let _ = stream.shutdown(Shutdown::Both);
+                        disconnect();
+                        break
+                    },
+                    Err(_) => Err(Error::Deserialize),
+                };
+                let _ = decoded
                     .and_then(|decoded| {
-                        Self::incoming_packet(mm.clone(), peer_addr, stream.clone(), decoded)
+                        if let Packet::Relay(_, data) = &decoded {
+                            Self::check_rate_limit(&mut limiter, &stream, data)?;
+                        }
+                        Self::incoming_packet(mm.clone(), peer_addr, stream.clone(), version, features, decoded)
                     })
                     .or_else(|error| -> Result<_, ()> {
                         eprintln!("! error/packet decode from {}: {}", peer_addr, error);
@@ -251,26 +1065,179 @@ impl Matchmaker {
 
 }
 
-fn main() -> Result<(), Box<dyn error::Error>> {
-    let mut port: u16 = 62137;
-    let mut args = std::env::args();
-    args.next();
-    if let Some(port_str) = args.next() {
-        port = port_str.parse()?;
+struct Options {
+    port: u16,
+    // the address the admin endpoint is served on. None if it's disabled
+    admin_addr: Option<SocketAddr>,
+    limits: Limits,
+    room_ids: RoomIdFormat,
+    // how long the matchmaker is expected to be gone for after a shutdown, if it's only restarting. clients are told
+    // about this so that they know when to reconnect
+    restart_delay: Option<Duration>,
+    // the folder persistent rooms' canvases are kept in. None if rooms can't be persistent
+    storage_dir: Option<PathBuf>,
+    // how long a persistent room's canvas is kept for after the room was last open
+    storage_expiry: Duration,
+    // the maximum number of persistent rooms' canvases kept at once
+    max_stored_rooms: Option<usize>,
+    // the most bytes a single persistent room's canvas can take up
+    max_stored_room_bytes: u64,
+}
+
+impl Options {
+
+    // environment variables that can be used in place of some of the flags, which is handier when running in a
+    // container
+    const ENV_VARS: &'static [(&'static str, &'static str)] = &[
+        ("NETCANV_MAX_CONNECTIONS", "--max-connections"),
+        ("NETCANV_MAX_CONNECTIONS_PER_IP", "--max-connections-per-ip"),
+        ("NETCANV_HANDSHAKE_TIMEOUT", "--handshake-timeout"),
+    ];
+
+    // usage: netcanv-matchmaker [PORT] [--admin ADDRESS] [--idle-timeout SECONDS] [--max-clients COUNT]
+    //                           [--relay-packet-rate PACKETS_PER_SECOND] [--relay-byte-rate BYTES_PER_SECOND]
+    //                           [--client-timeout SECONDS] [--room-id-length LENGTH]
+    //                           [--room-id-alphabet numeric|alphanumeric] [--max-connections COUNT]
+    //                           [--max-connections-per-ip COUNT] [--handshake-timeout SECONDS]
+    //                           [--restart-delay SECONDS] [--storage DIRECTORY] [--storage-expiry DAYS]
+    //                           [--max-stored-rooms COUNT] [--max-stored-room-size MEGABYTES]
+    // flags take precedence over environment variables
+    fn parse(args: impl Iterator<Item = String>, env: impl Fn(&str) -> Option<String>) -> Result<Self, Error> {
+        let mut options = Self {
+            port: 62137,
+            admin_addr: None,
+            limits: Limits {
+                idle_timeout: None,
+                max_clients: None,
+                relay_packets_per_second: None,
+                relay_bytes_per_second: None,
+                client_timeout: None,
+                max_connections: None,
+                max_connections_per_ip: None,
+                handshake_timeout: Some(Duration::from_secs(10)),
+            },
+            room_ids: RoomIdFormat::default(),
+            restart_delay: None,
+            storage_dir: None,
+            storage_expiry: Duration::from_secs(30 * 24 * 60 * 60),
+            max_stored_rooms: None,
+            max_stored_room_bytes: 64 * 1024 * 1024,
+        };
+        for &(var, flag) in Self::ENV_VARS {
+            if let Some(value) = env(var) {
+                options.set(flag, value)?;
+            }
+        }
+        let mut args = args.peekable();
+        if let Some(port) = args.next_if(|arg| !arg.starts_with("--")) {
+            options.port = port.parse()?;
+        }
+        while let Some(flag) = args.next() {
+            let value = args.next().ok_or_else(|| Error::MissingValue(flag.clone()))?;
+            options.set(&flag, value)?;
+        }
+        Ok(options)
+    }
+
+    fn set(&mut self, flag: &str, value: String) -> Result<(), Error> {
+        match flag {
+            "--admin" => self.admin_addr = Some(value.parse()?),
+            "--idle-timeout" => self.limits.idle_timeout = Some(Duration::from_secs(value.parse()?)),
+            "--max-clients" => self.limits.max_clients = Some(value.parse()?),
+            "--relay-packet-rate" => self.limits.relay_packets_per_second = Some(Self::parse_rate(flag, &value)?),
+            "--relay-byte-rate" => self.limits.relay_bytes_per_second = Some(Self::parse_rate(flag, &value)?),
+            // 0 never times clients out, like leaving the option out
+            "--client-timeout" => {
+                let seconds = value.parse()?;
+                self.limits.client_timeout = Some(Duration::from_secs(seconds)).filter(|_| seconds > 0);
+            },
+            "--room-id-length" => {
+                let length = value.parse()?;
+                if !(1..=RoomIdFormat::MAX_LENGTH).contains(&length) {
+                    return Err(Error::InvalidRoomIdLength)
+                }
+                self.room_ids.length = length;
+            },
+            "--room-id-alphabet" => {
+                self.room_ids.alphabet = Alphabet::parse(&value).ok_or(Error::UnknownAlphabet(value))?;
+            },
+            "--max-connections" => self.limits.max_connections = Some(value.parse()?),
+            "--max-connections-per-ip" => self.limits.max_connections_per_ip = Some(value.parse()?),
+            // 0 waits for the handshake forever
+            "--handshake-timeout" => {
+                let seconds = value.parse()?;
+                self.limits.handshake_timeout = Some(Duration::from_secs(seconds)).filter(|_| seconds > 0);
+            },
+            "--restart-delay" => self.restart_delay = Some(Duration::from_secs(value.parse()?)),
+            "--storage" => self.storage_dir = Some(PathBuf::from(value)),
+            "--storage-expiry" => {
+                let days: u64 = value.parse()?;
+                self.storage_expiry = Duration::from_secs(days * 24 * 60 * 60);
+            },
+            "--max-stored-rooms" => self.max_stored_rooms = Some(value.parse()?),
+            "--max-stored-room-size" => {
+                let megabytes: u64 = value.parse()?;
+                self.max_stored_room_bytes = megabytes * 1024 * 1024;
+            },
+            _ => return Err(Error::UnknownArgument(flag.into())),
+        }
+        Ok(())
+    }
+
+    // a rate of 0 would hold back every relayed packet forever, so it's refused rather than taken literally
+    fn parse_rate(flag: &str, value: &str) -> Result<u32, Error> {
+        match value.parse()? {
+            0 => Err(Error::ZeroRate(flag.into())),
+            rate => Ok(rate),
+        }
     }
 
+}
+
+fn main() -> Result<(), Box<dyn error::Error>> {
+    let Options {
+        port,
+        admin_addr,
+        limits,
+        room_ids,
+        restart_delay,
+        storage_dir,
+        storage_expiry,
+        max_stored_rooms,
+        max_stored_room_bytes,
+    } = Options::parse(std::env::args().skip(1), |var| std::env::var(var).ok())?;
+
     eprintln!("NetCanv Matchmaker: starting on port {}", port);
 
     let localhost = SocketAddr::from(([0, 0, 0, 0], port));
     let listener = TcpListener::bind(localhost)?;
 
-    let state = Arc::new(Mutex::new(Matchmaker::new()));
+    let storage = match storage_dir {
+        Some(dir) => {
+            eprintln!("Keeping persistent rooms' canvases in {}", dir.display());
+            Some(Storage::new(dir, storage_expiry, max_stored_rooms, max_stored_room_bytes)?)
+        },
+        None => None,
+    };
+    let persistent_rooms = storage.is_some();
+    let state = Arc::new(Mutex::new(Matchmaker::new(limits, room_ids, storage)));
+    if persistent_rooms {
+        Matchmaker::start_expiry_thread(state.clone());
+    }
+    if let Some(idle_timeout) = limits.idle_timeout {
+        Matchmaker::start_idle_thread(state.clone(), idle_timeout);
+    }
+    if let Some(admin_addr) = admin_addr {
+        admin::start(state.clone(), admin_addr, restart_delay)?;
+    }
+    Matchmaker::start_signal_thread(state.clone(), restart_delay)?;
 
-    eprintln!("Listening for incoming connections");
+    // the port is printed again, as it's picked by the OS when 0 is given. the tests rely on this
+    eprintln!("Listening for incoming connections on port {}", listener.local_addr()?.port());
 
     for connection in listener.incoming() {
         connection
-            .map_err(|error| Error::from(error))
+            .map_err(Error::from)
             .and_then(|stream| Matchmaker::start_client_thread(state.clone(), stream))
             .or_else(|error| -> Result<_, ()> {
                 eprintln!("! error/connect: {}", error);
@@ -282,3 +1249,29 @@ fn main() -> Result<(), Box<dyn error::Error>> {
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, Error> {
+        Options::parse(args.iter().map(|arg| arg.to_string()), |_| None)
+    }
+
+    #[test]
+    fn relay_rates_must_be_positive() {
+        assert!(matches!(parse(&["--relay-packet-rate", "0"]), Err(Error::ZeroRate(_))));
+        assert!(matches!(parse(&["--relay-byte-rate", "0"]), Err(Error::ZeroRate(_))));
+        assert!(matches!(parse(&["--relay-byte-rate", "-1"]), Err(Error::InvalidNumber(_))));
+        let options = parse(&["--relay-packet-rate", "1", "--relay-byte-rate", "1024"]).unwrap();
+        assert_eq!(options.limits.relay_packets_per_second, Some(1));
+        assert_eq!(options.limits.relay_bytes_per_second, Some(1024));
+    }
+
+    #[test]
+    fn room_ids_must_not_be_empty() {
+        assert!(matches!(parse(&["--room-id-length", "0"]), Err(Error::InvalidRoomIdLength)));
+        let too_long = (RoomIdFormat::MAX_LENGTH + 1).to_string();
+        assert!(matches!(parse(&["--room-id-length", &too_long]), Err(Error::InvalidRoomIdLength)));
+        assert_eq!(parse(&["--room-id-length", "1"]).unwrap().room_ids.length, 1);
+    }
+}
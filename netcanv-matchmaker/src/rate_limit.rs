@@ -0,0 +1,151 @@
+// rate limiting for relayed packets.
+// every connection gets its own limiter, so that a single misbehaving client can't saturate the server by flooding
+// it with Relay packets. packets over the limit are held back until the client is under the limit again, which slows
+// the client down through TCP's flow control without losing anything it sent. clients that stay over the limit for
+// far too long are disconnected.
+
+use std::time::{Duration, Instant};
+
+// a classic token bucket. tokens are refilled continuously, up to one second's worth of them.
+// the amount of tokens is allowed to go negative so that packets larger than the bucket can still get through
+// eventually, they just have to wait for the debt to be paid off
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+
+    fn new(rate: u32) -> Self {
+        Self {
+            rate: rate as f64,
+            tokens: rate as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = f64::min(self.tokens + elapsed * self.rate, self.rate);
+        self.last_refill = now;
+    }
+
+    // how long until there's a token available again
+    fn wait_time(&mut self) -> Duration {
+        self.refill();
+        if self.tokens > 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / self.rate)
+        }
+    }
+
+    fn take(&mut self, amount: usize) {
+        self.tokens -= amount as f64;
+    }
+
+}
+
+pub enum Verdict {
+    Allow,
+    // the packet should be held back for the given amount of time and checked again. `first` is true if this is the
+    // first packet held back in a while
+    Delay { wait: Duration, first: bool },
+    // the client has been over the limit for too long and should be disconnected
+    Disconnect,
+}
+
+pub struct RateLimiter {
+    packets: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+    // when the client went over the limit, and when the last packet was held back
+    limited_since: Option<Instant>,
+    last_delay: Instant,
+}
+
+impl RateLimiter {
+
+    // the client is considered to be back under the limit if no packets were held back for this long
+    const COOLDOWN: Duration = Duration::from_secs(1);
+    // clients that are over the limit for this long get disconnected. this is quite long, as hosts sending the canvas
+    // to newcomers can easily go over the limit for a while without doing anything wrong
+    const MAX_LIMITED_TIME: Duration = Duration::from_secs(120);
+
+    pub fn new(packets_per_second: Option<u32>, bytes_per_second: Option<u32>) -> Self {
+        Self {
+            packets: packets_per_second.map(TokenBucket::new),
+            bytes: bytes_per_second.map(TokenBucket::new),
+            limited_since: None,
+            last_delay: Instant::now(),
+        }
+    }
+
+    // decides what to do with a relayed packet with a payload of the given size
+    pub fn check(&mut self, size: usize) -> Verdict {
+        let packets_wait = self.packets.as_mut().map_or(Duration::ZERO, |bucket| bucket.wait_time());
+        let bytes_wait = self.bytes.as_mut().map_or(Duration::ZERO, |bucket| bucket.wait_time());
+        let wait = packets_wait.max(bytes_wait);
+        if wait.is_zero() {
+            if let Some(bucket) = &mut self.packets { bucket.take(1) }
+            if let Some(bucket) = &mut self.bytes { bucket.take(size) }
+            if self.last_delay.elapsed() > Self::COOLDOWN {
+                self.limited_since = None;
+            }
+            return Verdict::Allow
+        }
+
+        let now = Instant::now();
+        if now.duration_since(self.last_delay) > Self::COOLDOWN {
+            self.limited_since = None;
+        }
+        self.last_delay = now;
+        match self.limited_since {
+            None => {
+                self.limited_since = Some(now);
+                Verdict::Delay { wait, first: true }
+            },
+            Some(since) if now.duration_since(since) > Self::MAX_LIMITED_TIME => Verdict::Disconnect,
+            Some(_) => Verdict::Delay { wait, first: false },
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // pretends the bucket was last refilled some time ago, so that tests don't have to sleep
+    fn rewind(bucket: &mut TokenBucket, by: Duration) {
+        bucket.last_refill -= by;
+    }
+
+    #[test]
+    fn full_buckets_do_not_wait() {
+        let mut bucket = TokenBucket::new(10);
+        assert_eq!(bucket.wait_time(), Duration::ZERO);
+    }
+
+    #[test]
+    fn wait_time_pays_off_the_debt() {
+        let mut bucket = TokenBucket::new(10);
+        // two tokens in debt, so three tokens must be refilled for one to be available
+        bucket.take(12);
+        let wait = bucket.wait_time();
+        assert!(wait > Duration::from_millis(290) && wait <= Duration::from_millis(300), "{:?}", wait);
+    }
+
+    #[test]
+    fn refilling_is_continuous_and_capped() {
+        let mut bucket = TokenBucket::new(10);
+        bucket.take(15);
+        rewind(&mut bucket, Duration::from_millis(500));
+        bucket.refill();
+        assert!(bucket.tokens >= 0.0 && bucket.tokens < 0.1, "{}", bucket.tokens);
+        rewind(&mut bucket, Duration::from_secs(60));
+        assert_eq!(bucket.wait_time(), Duration::ZERO);
+        assert_eq!(bucket.tokens, 10.0);
+    }
+}
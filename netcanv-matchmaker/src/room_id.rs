@@ -0,0 +1,142 @@
+// room IDs. they never start with a zero, so that numeric IDs survive being sent as numbers to older clients
+
+use netcanv_protocol::matchmaker::MAX_ROOM_ID_LENGTH;
+use rand::Rng;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Alphabet {
+    Numeric,
+    Alphanumeric,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RoomIdFormat {
+    pub length: usize,
+    pub alphabet: Alphabet,
+}
+
+impl Alphabet {
+
+    // letters that are easily mistaken for digits (I and O) are left out, and so are 0 and 1 in alphanumeric IDs
+    fn characters(self) -> &'static [u8] {
+        match self {
+            Self::Numeric => b"0123456789",
+            Self::Alphanumeric => b"23456789ABCDEFGHJKLMNPQRSTUVWXYZ",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "numeric" => Some(Self::Numeric),
+            "alphanumeric" => Some(Self::Alphanumeric),
+            _ => None,
+        }
+    }
+
+}
+
+impl RoomIdFormat {
+
+    pub const MAX_LENGTH: usize = MAX_ROOM_ID_LENGTH;
+    // the longest numeric ID that still fits into a u32
+    const MAX_LEGACY_LENGTH: usize = 9;
+    // how many random IDs are tried before giving up on finding one that isn't taken
+    const MAX_ATTEMPTS: usize = 50;
+
+    // generates a random room ID. legacy IDs are numeric and fit into a u32, for hosts that don't support string IDs
+    pub fn generate(&self, rng: &mut impl Rng, legacy: bool) -> String {
+        let (length, alphabet) =
+            if legacy { (usize::min(self.length, Self::MAX_LEGACY_LENGTH), Alphabet::Numeric) }
+            else { (self.length, self.alphabet) };
+        random_id(rng, alphabet.characters(), length)
+    }
+
+    // generates room IDs until one that isn't taken comes up. returns None if none did after a while
+    pub fn find_free(&self, rng: &mut impl Rng, legacy: bool, is_taken: impl Fn(&str) -> bool) -> Option<String> {
+        (0..Self::MAX_ATTEMPTS).map(|_| self.generate(rng, legacy)).find(|id| !is_taken(id))
+    }
+
+    // brings an ID typed in by someone into the form it's stored in. letters are uppercased, and leading zeros are
+    // stripped, as clients show numeric IDs padded with zeros
+    pub fn normalize(id: &str) -> String {
+        let id = id.trim().to_ascii_uppercase();
+        match id.trim_start_matches('0') {
+            "" => id,
+            trimmed => trimmed.to_owned(),
+        }
+    }
+
+}
+
+// a random ID made of the given characters, which doesn't start with a zero
+fn random_id(rng: &mut impl Rng, characters: &[u8], length: usize) -> String {
+    (0..length)
+        .map(|i| {
+            let start = if i == 0 && characters[0] == b'0' { 1 } else { 0 };
+            characters[rng.gen_range(start..characters.len())] as char
+        })
+        .collect()
+}
+
+impl Default for RoomIdFormat {
+    fn default() -> Self {
+        Self {
+            length: 4,
+            alphabet: Alphabet::Numeric,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn rng() -> StdRng {
+        StdRng::seed_from_u64(0)
+    }
+
+    #[test]
+    fn ids_are_made_of_the_alphabet() {
+        let mut rng = rng();
+        assert_eq!(random_id(&mut rng, b"7", 4), "7777");
+        for _ in 0..100 {
+            let id = random_id(&mut rng, Alphabet::Numeric.characters(), 2);
+            assert_eq!(id.len(), 2);
+            assert!(!id.starts_with('0'));
+            assert!(id.bytes().all(|c| c.is_ascii_digit()));
+        }
+    }
+
+    #[test]
+    fn empty_ids_can_be_generated() {
+        let format = RoomIdFormat { length: 0, alphabet: Alphabet::Alphanumeric };
+        assert_eq!(format.generate(&mut rng(), false), "");
+        assert_eq!(format.generate(&mut rng(), true), "");
+    }
+
+    #[test]
+    fn legacy_ids_fit_into_a_u32() {
+        let format = RoomIdFormat { length: RoomIdFormat::MAX_LENGTH, alphabet: Alphabet::Alphanumeric };
+        let mut rng = rng();
+        for _ in 0..100 {
+            assert!(format.generate(&mut rng, true).parse::<u32>().is_ok());
+        }
+    }
+
+    #[test]
+    fn taken_ids_are_retried() {
+        // the only single-digit ID that isn't taken is 5
+        let format = RoomIdFormat { length: 1, alphabet: Alphabet::Numeric };
+        let attempts = std::cell::Cell::new(0);
+        let id = format.find_free(&mut rng(), false, |id| {
+            attempts.set(attempts.get() + 1);
+            id != "5"
+        });
+        assert_eq!(id.as_deref(), Some("5"));
+        assert!(attempts.get() > 1);
+        assert_eq!(format.find_free(&mut rng(), false, |_| true), None);
+    }
+}
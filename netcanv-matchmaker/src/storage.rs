@@ -0,0 +1,188 @@
+// canvas storage for persistent rooms.
+// only used when the matchmaker is launched with --storage. each persistent room gets a folder named after its ID,
+// containing a room.bin file with the room's settings and one PNG image per chunk of its canvas, named after the
+// chunk's position like in NetCanv's project format. a room's canvas is kept after the room closes, and thrown away
+// once it hasn't been opened for longer than the expiry time.
+// hosts can't be trusted with the disk, so only PNG images the size of a chunk are stored, and each room's canvas can
+// only take up so much space.
+
+use std::convert::TryInto;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+const ROOM_FILE: &str = "room.bin";
+// the width and height of a chunk of the canvas, in pixels. this has to match NetCanv's chunk size
+const CHUNK_SIZE: u32 = 256;
+
+// a chunk's position along with its PNG data
+type StoredChunk = ((i32, i32), Vec<u8>);
+
+// the settings a persistent room is reopened with
+#[derive(Serialize, Deserialize)]
+pub struct StoredRoom {
+    pub password: Option<String>,
+    pub public_name: Option<String>,
+}
+
+pub struct Storage {
+    dir: PathBuf,
+    // stored canvases whose room wasn't opened for this long are removed
+    expiry: Duration,
+    // the maximum number of rooms whose canvases are stored at once
+    max_rooms: Option<usize>,
+    // the most bytes a single room's chunks can take up
+    max_room_bytes: u64,
+}
+
+fn chunk_path(dir: &Path, (x, y): (i32, i32)) -> PathBuf {
+    dir.join(format!("{},{}.png", x, y))
+}
+
+// returns the position of the chunk stored in the given file, or None if it's not a chunk image
+fn chunk_position(path: &Path) -> Option<(i32, i32)> {
+    if path.extension()? != "png" { return None }
+    let stem = path.file_stem()?.to_str()?;
+    let (x, y) = stem.split_at(stem.find(',')?);
+    Some((x.parse().ok()?, y[1..].parse().ok()?))
+}
+
+// whether the data looks like a PNG image the size of a chunk. only the image's header is checked, the image itself
+// isn't decoded
+fn is_chunk_png(data: &[u8]) -> bool {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    // the signature is followed by the IHDR chunk's length and type, and then the image's width and height
+    if data.len() < 24 || &data[..8] != SIGNATURE || &data[12..16] != b"IHDR" {
+        return false
+    }
+    let width = u32::from_be_bytes(data[16..20].try_into().unwrap());
+    let height = u32::from_be_bytes(data[20..24].try_into().unwrap());
+    (width, height) == (CHUNK_SIZE, CHUNK_SIZE)
+}
+
+impl Storage {
+
+    pub fn new(dir: PathBuf, expiry: Duration, max_rooms: Option<usize>, max_room_bytes: u64) -> Result<Self, Error> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir, expiry, max_rooms, max_room_bytes })
+    }
+
+    // the folder the room's canvas is stored in. room IDs come from clients, so anything that isn't a valid room ID
+    // is refused here rather than being let anywhere near the file system
+    fn room_dir(&self, room_id: &str) -> Option<PathBuf> {
+        if room_id.is_empty() || !room_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None
+        }
+        Some(self.dir.join(room_id))
+    }
+
+    pub fn contains(&self, room_id: &str) -> bool {
+        self.room_dir(room_id).is_some_and(|dir| dir.join(ROOM_FILE).is_file())
+    }
+
+    // the IDs of all rooms with a stored canvas
+    pub fn room_ids(&self) -> Result<Vec<String>, Error> {
+        let mut room_ids = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if entry.path().join(ROOM_FILE).is_file() {
+                room_ids.extend(entry.file_name().to_str().map(String::from));
+            }
+        }
+        Ok(room_ids)
+    }
+
+    // whether there's space left for storing another room's canvas
+    pub fn is_full(&self) -> Result<bool, Error> {
+        Ok(match self.max_rooms {
+            Some(max_rooms) => self.room_ids()?.len() >= max_rooms,
+            None => false,
+        })
+    }
+
+    pub fn room(&self, room_id: &str) -> Result<Option<StoredRoom>, Error> {
+        let path = match self.room_dir(room_id) {
+            Some(dir) => dir.join(ROOM_FILE),
+            None => return Ok(None),
+        };
+        if !path.is_file() {
+            return Ok(None)
+        }
+        Ok(Some(bincode::deserialize_from(File::open(path)?)?))
+    }
+
+    // saves the room's settings. this also counts as the room being opened, so the expiry time starts over
+    pub fn save_room(&self, room_id: &str, room: &StoredRoom) -> Result<(), Error> {
+        let dir = self.room_dir(room_id).ok_or(Error::InvalidPacket)?;
+        std::fs::create_dir_all(&dir)?;
+        bincode::serialize_into(File::create(dir.join(ROOM_FILE))?, room)?;
+        Ok(())
+    }
+
+    pub fn remove_room(&self, room_id: &str) -> Result<(), Error> {
+        if let Some(dir) = self.room_dir(room_id).filter(|dir| dir.is_dir()) {
+            std::fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    // the number of bytes the room's chunks take up
+    fn room_size(dir: &Path) -> Result<u64, Error> {
+        let mut size = 0;
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            if chunk_position(&entry.path()).is_some() {
+                size += entry.metadata()?.len();
+            }
+        }
+        Ok(size)
+    }
+
+    // stores a chunk of the room's canvas. returns false if the chunk wasn't stored because the room is out of space
+    pub fn store_chunk(&self, room_id: &str, position: (i32, i32), png_data: &[u8]) -> Result<bool, Error> {
+        let dir = self.room_dir(room_id).ok_or(Error::InvalidPacket)?;
+        if !is_chunk_png(png_data) {
+            return Err(Error::InvalidPacket)
+        }
+        let path = chunk_path(&dir, position);
+        let replaced_size = std::fs::metadata(&path).map_or(0, |metadata| metadata.len());
+        if Self::room_size(&dir)? - replaced_size + png_data.len() as u64 > self.max_room_bytes {
+            return Ok(false)
+        }
+        std::fs::write(path, png_data)?;
+        Ok(true)
+    }
+
+    // reads all chunks of the room's stored canvas
+    pub fn chunks(&self, room_id: &str) -> Result<Vec<StoredChunk>, Error> {
+        let dir = self.room_dir(room_id).ok_or(Error::InvalidPacket)?;
+        let mut chunks = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if let Some(position) = chunk_position(&path) {
+                chunks.push((position, std::fs::read(path)?));
+            }
+        }
+        Ok(chunks)
+    }
+
+    // whether the room wasn't opened for longer than the expiry time
+    pub fn is_expired(&self, room_id: &str) -> Result<bool, Error> {
+        let path = match self.room_dir(room_id) {
+            Some(dir) => dir.join(ROOM_FILE),
+            None => return Ok(false),
+        };
+        let modified = std::fs::metadata(path)?.modified()?;
+        Ok(SystemTime::now().duration_since(modified).is_ok_and(|age| age > self.expiry))
+    }
+
+    // the expiry time in whole days, as told to hosts
+    pub fn expiry_days(&self) -> u32 {
+        (self.expiry.as_secs() / (24 * 60 * 60)) as u32
+    }
+
+}
@@ -0,0 +1,215 @@
+// the integration test harness.
+// starts the matchmaker on a port picked by the OS, and connects simulated peers to it. the peers speak the same
+// protocol as the app, but instead of a real paint canvas, each of them keeps a map of the stroke points it has drawn
+// and received, which is enough to check whether everyone in a room ends up seeing the same thing.
+// these tests cover the matchmaker itself. NetCanv's own networking code is tested against a real matchmaker in
+// src/net/tests.rs.
+
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+
+use netcanv_protocol::client as cl;
+use netcanv_protocol::matchmaker as mm;
+use netcanv_protocol::version::{self, Handshake};
+
+mod process;
+
+pub use process::Matchmaker;
+
+// how long to wait for a packet before failing the test, so that a lost packet doesn't hang the whole test suite
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl Matchmaker {
+
+    pub fn start() -> Self {
+        Self::start_with_args(&[])
+    }
+
+    pub fn start_with_args(args: &[&str]) -> Self {
+        Self::spawn(Path::new(env!("CARGO_BIN_EXE_netcanv-matchmaker")), args)
+    }
+
+}
+
+// the canvas of a simulated peer, mapping stroke point positions to their colors
+pub type Canvas = BTreeMap<(i32, i32), u32>;
+
+pub struct Peer {
+    stream: TcpStream,
+    // the peer's address, as seen by the matchmaker and the other peers
+    pub addr: SocketAddr,
+    pub canvas: Canvas,
+}
+
+impl Peer {
+
+    // connects to the matchmaker and exchanges handshakes
+    pub fn connect(matchmaker: &Matchmaker) -> Self {
+        Self::connect_with_features(matchmaker, version::FEATURES)
+    }
+
+    // connects to the matchmaker like a client that only supports some of the optional features
+    pub fn connect_with_features(matchmaker: &Matchmaker, features: u32) -> Self {
+        let peer = Self::connect_legacy(matchmaker);
+        bincode::serialize_into(&peer.stream, &Handshake { features, ..Handshake::ours() }).unwrap();
+        let theirs: Handshake = bincode::deserialize_from(&peer.stream).unwrap();
+        assert!(theirs.is_supported(), "the matchmaker speaks an unsupported version {}", theirs.version);
+        peer
+    }
+
+    // connects to the matchmaker like a client from before versioning was introduced, without a handshake
+    pub fn connect_legacy(matchmaker: &Matchmaker) -> Self {
+        let stream = TcpStream::connect(matchmaker.addr).unwrap();
+        stream.set_read_timeout(Some(RECEIVE_TIMEOUT)).unwrap();
+        stream.set_nodelay(true).unwrap();
+        Self {
+            addr: stream.local_addr().unwrap(),
+            stream,
+            canvas: Canvas::new(),
+        }
+    }
+
+    // hosts a new room and returns the host along with the room's ID
+    pub fn host(matchmaker: &Matchmaker, password: Option<&str>) -> (Self, String) {
+        let mut host = Self::connect(matchmaker);
+        host.send(mm::Packet::host(password, None).unwrap());
+        let room_id = match host.recv() {
+            mm::Packet::StringRoomId(id) => id,
+            packet => panic!("expected a room ID, got {:?}", packet),
+        };
+        host.send(mm::Packet::RequestRelay(None));
+        (host, room_id)
+    }
+
+    // joins a room and says hello to everyone inside. the host and everyone else already in the room must then
+    // accept the new peer with expect_hello
+    pub fn join(matchmaker: &Matchmaker, room_id: &str, password: Option<&str>) -> Self {
+        let mut peer = Self::connect(matchmaker);
+        let host_addr = match peer.try_join(room_id, password) {
+            Ok(addr) => addr,
+            Err(error) => panic!("could not join room {}: {}", room_id, error),
+        };
+        peer.send(mm::Packet::RequestRelay(Some(host_addr)));
+        peer.send_relayed(None, cl::Packet::Hello("peer".into()));
+        peer
+    }
+
+    // asks the matchmaker for the host of a room, returning the host's address or the matchmaker's error
+    pub fn try_join(&mut self, room_id: &str, password: Option<&str>) -> Result<SocketAddr, String> {
+        self.send(mm::Packet::GetHostByStringId(room_id.into(), password.map(String::from)));
+        match self.recv() {
+            mm::Packet::HostAddress(addr) => Ok(addr),
+            mm::Packet::Error(error) => Err(error),
+            packet => panic!("expected the host's address, got {:?}", packet),
+        }
+    }
+
+    pub fn send(&self, packet: mm::Packet) {
+        bincode::serialize_into(&self.stream, &packet).unwrap();
+    }
+
+    // sends bytes that aren't necessarily a valid packet
+    pub fn send_raw(&self, data: &[u8]) {
+        (&self.stream).write_all(data).unwrap();
+    }
+
+    pub fn send_relayed(&self, to: Option<SocketAddr>, packet: cl::Packet) {
+        self.send(mm::Packet::relay(to, &packet).unwrap());
+    }
+
+    // receives the next packet from the matchmaker, skipping over keepalive pings
+    pub fn recv(&mut self) -> mm::Packet {
+        loop {
+            match bincode::deserialize_from(&self.stream) {
+                Ok(mm::Packet::Ping(id)) => self.send(mm::Packet::Pong(id)),
+                Ok(packet) => return packet,
+                Err(error) => panic!("{} did not receive a packet: {}", self.addr, error),
+            }
+        }
+    }
+
+    // receives an Error packet the way clients from before versioning was introduced decode it
+    pub fn recv_legacy_error(&mut self) -> String {
+        let (variant, error): (u32, String) = bincode::deserialize_from(&self.stream).unwrap();
+        assert_eq!(variant, 9, "expected an Error packet in the old format");
+        error
+    }
+
+    // receives the next relayed packet, along with its sender
+    pub fn recv_relayed(&mut self) -> (SocketAddr, cl::Packet) {
+        match self.recv() {
+            mm::Packet::Relayed(from, payload) => (from, bincode::deserialize(&payload).unwrap()),
+            packet => panic!("expected a relayed packet, got {:?}", packet),
+        }
+    }
+
+    // waits for someone to say hello, returning their address
+    pub fn expect_hello(&mut self) -> SocketAddr {
+        match self.recv() {
+            // the host is told about the new peer before they say hello
+            mm::Packet::ClientAddress(_) => self.expect_hello(),
+            mm::Packet::Relayed(from, payload) => match bincode::deserialize(&payload).unwrap() {
+                cl::Packet::Hello(_) => from,
+                packet => panic!("expected a hello, got {:?}", packet),
+            },
+            packet => panic!("expected a hello, got {:?}", packet),
+        }
+    }
+
+    fn apply(&mut self, points: &[cl::StrokePoint]) {
+        for point in points {
+            self.canvas.insert((point.x, point.y), point.color);
+        }
+    }
+
+    // draws a horizontal line onto the peer's canvas and sends it to everyone in the room
+    pub fn draw_line(&mut self, (x, y): (i32, i32), length: i32, color: u32) {
+        let points: Vec<_> = (x..x + length)
+            .map(|x| cl::StrokePoint {
+                x,
+                y,
+                color,
+                brush_size: 8,
+                softness: 0,
+            })
+            .collect();
+        self.apply(&points);
+        self.send_relayed(None, cl::Packet::Stroke(points));
+    }
+
+    // sends the peer's whole canvas to a single person, like the host does to people who have just joined
+    pub fn send_canvas(&self, to: SocketAddr) {
+        let points = self.canvas
+            .iter()
+            .map(|(&(x, y), &color)| cl::StrokePoint {
+                x,
+                y,
+                color,
+                brush_size: 8,
+                softness: 0,
+            })
+            .collect();
+        self.send_relayed(Some(to), cl::Packet::Stroke(points));
+    }
+
+    // receives the given number of strokes and paints them onto the peer's canvas
+    pub fn receive_strokes(&mut self, count: usize) {
+        for _ in 0..count {
+            match self.recv_relayed() {
+                (_, cl::Packet::Stroke(points)) => self.apply(&points),
+                (from, packet) => panic!("expected a stroke from {}, got {:?}", from, packet),
+            }
+        }
+    }
+
+    // closes the connection to the matchmaker
+    pub fn disconnect(self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+
+}
@@ -0,0 +1,64 @@
+// the matchmaker's process, started for a test.
+// this is shared by the matchmaker's own tests and by the tests of NetCanv's networking code in src/net/tests.rs,
+// which includes this file by its path.
+
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+pub struct Matchmaker {
+    process: Child,
+    pub addr: SocketAddr,
+}
+
+impl Matchmaker {
+
+    // starts the matchmaker binary on a port picked by the OS, and waits until it's listening
+    pub fn spawn(binary: &Path, args: &[&str]) -> Self {
+        let mut process = Command::new(binary)
+            .arg("0")
+            .args(args)
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("could not start the matchmaker");
+        let mut stderr = BufReader::new(process.stderr.take().unwrap());
+        let mut port = None;
+        let mut line = String::new();
+        while port.is_none() {
+            line.clear();
+            if stderr.read_line(&mut line).unwrap() == 0 {
+                panic!("the matchmaker exited before it started listening");
+            }
+            port = line.trim().strip_prefix("Listening for incoming connections on port ").map(|port| port.parse());
+        }
+        // the matchmaker logs every packet, so its output must be read, or it'll eventually block once the pipe is
+        // full
+        std::thread::spawn(move || {
+            for line in stderr.lines() {
+                if line.is_err() { break }
+            }
+        });
+        Self {
+            process,
+            addr: SocketAddr::from(([127, 0, 0, 1], port.unwrap().unwrap())),
+        }
+    }
+
+    // asks the matchmaker to shut down gracefully
+    #[cfg(unix)]
+    pub fn terminate(&self) {
+        let status = Command::new("kill").args(["-TERM", &self.process.id().to_string()]).status().unwrap();
+        assert!(status.success(), "could not send SIGTERM to the matchmaker");
+    }
+
+}
+
+impl Drop for Matchmaker {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
@@ -0,0 +1,249 @@
+// scenarios involving hosting, joining, drawing in, and leaving rooms.
+
+mod harness;
+
+use std::time::Duration;
+
+use netcanv_protocol::client as cl;
+use netcanv_protocol::matchmaker as mm;
+use netcanv_protocol::version;
+
+use harness::{Matchmaker, Peer};
+
+#[test]
+fn joining_introduces_the_host_and_the_client() {
+    let matchmaker = Matchmaker::start();
+    let (mut host, room_id) = Peer::host(&matchmaker, None);
+    let mut client = Peer::connect(&matchmaker);
+    assert_eq!(client.try_join(&room_id, None), Ok(host.addr));
+    assert_eq!(host.recv(), mm::Packet::ClientAddress(client.addr));
+}
+
+#[test]
+fn strokes_converge() {
+    let matchmaker = Matchmaker::start();
+    let (mut host, room_id) = Peer::host(&matchmaker, None);
+    let mut alice = Peer::join(&matchmaker, &room_id, None);
+    host.expect_hello();
+    let mut bob = Peer::join(&matchmaker, &room_id, None);
+    host.expect_hello();
+    alice.expect_hello();
+
+    host.draw_line((0, 0), 16, 0xff0000ff);
+    alice.draw_line((0, 8), 16, 0x00ff00ff);
+    bob.draw_line((0, 16), 16, 0x0000ffff);
+    host.receive_strokes(2);
+    alice.receive_strokes(2);
+    bob.receive_strokes(2);
+
+    assert_eq!(host.canvas.len(), 48);
+    assert_eq!(alice.canvas, host.canvas);
+    assert_eq!(bob.canvas, host.canvas);
+}
+
+#[test]
+fn late_joiners_receive_the_canvas_from_the_host() {
+    let matchmaker = Matchmaker::start();
+    let (mut host, room_id) = Peer::host(&matchmaker, None);
+    let mut alice = Peer::join(&matchmaker, &room_id, None);
+    host.expect_hello();
+    host.draw_line((0, 0), 16, 0xff0000ff);
+    alice.receive_strokes(1);
+
+    let mut bob = Peer::join(&matchmaker, &room_id, None);
+    let bob_addr = host.expect_hello();
+    alice.expect_hello();
+    host.send_canvas(bob_addr);
+    bob.receive_strokes(1);
+    assert_eq!(bob.canvas, host.canvas);
+
+    // the canvas is only sent to the person who joined, so the next thing anyone else receives is the next stroke
+    bob.draw_line((0, 8), 16, 0x00ff00ff);
+    alice.receive_strokes(1);
+    host.receive_strokes(1);
+    assert_eq!(alice.canvas, bob.canvas);
+    assert_eq!(host.canvas, bob.canvas);
+}
+
+#[test]
+fn leaving_is_announced_to_everyone_in_the_room() {
+    let matchmaker = Matchmaker::start();
+    let (mut host, room_id) = Peer::host(&matchmaker, None);
+    let mut alice = Peer::join(&matchmaker, &room_id, None);
+    host.expect_hello();
+    let bob = Peer::join(&matchmaker, &room_id, None);
+    host.expect_hello();
+    alice.expect_hello();
+
+    let bob_addr = bob.addr;
+    bob.disconnect();
+    assert_eq!(host.recv(), mm::Packet::Disconnected(bob_addr));
+    assert_eq!(alice.recv(), mm::Packet::Disconnected(bob_addr));
+}
+
+#[test]
+fn rooms_close_when_the_host_leaves() {
+    let matchmaker = Matchmaker::start();
+    let (host, room_id) = Peer::host(&matchmaker, None);
+    host.disconnect();
+    // the room is closed once the matchmaker notices the host is gone, which doesn't happen right away
+    let mut client = Peer::connect(&matchmaker);
+    let closed = (0..50).any(|_| {
+        std::thread::sleep(Duration::from_millis(10));
+        client.try_join(&room_id, None).is_err()
+    });
+    assert!(closed, "the room was still open after its host left");
+}
+
+#[test]
+fn passwords_are_checked() {
+    let matchmaker = Matchmaker::start();
+    let (_host, room_id) = Peer::host(&matchmaker, Some("hunter2"));
+    let mut client = Peer::connect(&matchmaker);
+    assert!(client.try_join(&room_id, None).is_err());
+    assert!(client.try_join(&room_id, Some("hunter3")).is_err());
+    assert!(client.try_join(&room_id, Some("hunter2")).is_ok());
+}
+
+#[test]
+fn relaying_into_a_room_is_checked_like_joining() {
+    let matchmaker = Matchmaker::start_with_args(&["--max-clients", "2"]);
+    let (host, _) = Peer::host(&matchmaker, Some("hunter2"));
+    let mut sneaky = Peer::connect(&matchmaker);
+    sneaky.send(mm::Packet::RequestRelay(Some(host.addr)));
+    assert_eq!(sneaky.recv(), mm::Packet::Error("This room is protected with a password. Enter it to join".into()));
+
+    let (host, room_id) = Peer::host(&matchmaker, None);
+    let _alice = Peer::join(&matchmaker, &room_id, None);
+    let mut bob = Peer::connect(&matchmaker);
+    assert_eq!(bob.try_join(&room_id, None), Err("This room is full".into()));
+    bob.send(mm::Packet::RequestRelay(Some(host.addr)));
+    assert_eq!(bob.recv(), mm::Packet::Error("This room is full".into()));
+}
+
+#[test]
+fn unknown_rooms_cannot_be_joined() {
+    let matchmaker = Matchmaker::start();
+    let mut client = Peer::connect(&matchmaker);
+    assert!(client.try_join("0000", None).is_err());
+}
+
+#[test]
+fn clients_without_string_room_ids_get_numeric_room_ids() {
+    let matchmaker = Matchmaker::start_with_args(&["--room-id-length", "6", "--room-id-alphabet", "alphanumeric"]);
+    let features = version::FEATURES & !version::features::STRING_ROOM_IDS;
+    let mut host = Peer::connect_with_features(&matchmaker, features);
+    host.send(mm::Packet::Host(None, None));
+    let room_id = match host.recv() {
+        mm::Packet::RoomId(id) => id,
+        packet => panic!("expected a numeric room ID, got {:?}", packet),
+    };
+    assert_eq!(room_id.to_string().len(), 6);
+
+    let mut client = Peer::connect_with_features(&matchmaker, features);
+    client.send(mm::Packet::GetHost(room_id, None));
+    assert_eq!(client.recv(), mm::Packet::HostAddress(host.addr));
+}
+
+#[test]
+fn legacy_clients_are_told_to_update() {
+    let matchmaker = Matchmaker::start();
+    let mut client = Peer::connect_legacy(&matchmaker);
+    // Host, as clients from before versioning was introduced encode it
+    client.send_raw(&0u32.to_le_bytes());
+    assert!(client.recv_legacy_error().contains("update"));
+}
+
+#[test]
+fn rejected_legacy_clients_are_told_why() {
+    let matchmaker = Matchmaker::start_with_args(&["--max-connections", "0"]);
+    let mut client = Peer::connect_legacy(&matchmaker);
+    client.send_raw(&0u32.to_le_bytes());
+    assert!(client.recv_legacy_error().contains("full"));
+}
+
+#[test]
+fn relayed_packets_reach_only_their_recipient() {
+    let matchmaker = Matchmaker::start();
+    let (mut host, room_id) = Peer::host(&matchmaker, None);
+    let mut alice = Peer::join(&matchmaker, &room_id, None);
+    let alice_addr = host.expect_hello();
+    let mut bob = Peer::join(&matchmaker, &room_id, None);
+    host.expect_hello();
+    alice.expect_hello();
+
+    host.send_relayed(Some(alice_addr), cl::Packet::HiThere("host".into()));
+    host.send_relayed(None, cl::Packet::Idle(true));
+    assert_eq!(alice.recv_relayed(), (host.addr, cl::Packet::HiThere("host".into())));
+    assert_eq!(alice.recv_relayed(), (host.addr, cl::Packet::Idle(true)));
+    assert_eq!(bob.recv_relayed(), (host.addr, cl::Packet::Idle(true)));
+}
+
+#[test]
+fn kicked_peers_are_cut_off_from_the_room() {
+    let matchmaker = Matchmaker::start();
+    let (mut host, room_id) = Peer::host(&matchmaker, None);
+    let mut alice = Peer::join(&matchmaker, &room_id, None);
+    let alice_addr = host.expect_hello();
+    let mut bob = Peer::join(&matchmaker, &room_id, None);
+    host.expect_hello();
+    alice.expect_hello();
+
+    host.send(mm::Packet::Kick(alice_addr, true));
+    assert_eq!(alice.recv(), mm::Packet::Error("You have been banned from the room".into()));
+    assert_eq!(bob.recv(), mm::Packet::Disconnected(alice_addr));
+    assert_eq!(host.recv(), mm::Packet::Disconnected(alice_addr));
+    // everyone in the tests connects from the same IP address, so the ban applies to any newcomer
+    let mut client = Peer::connect(&matchmaker);
+    assert_eq!(client.try_join(&room_id, None), Err("You have been banned from this room".into()));
+}
+
+#[cfg(unix)]
+#[test]
+fn shutting_down_is_announced() {
+    let matchmaker = Matchmaker::start_with_args(&["--restart-delay", "30"]);
+    let (mut host, room_id) = Peer::host(&matchmaker, None);
+    let mut alice = Peer::join(&matchmaker, &room_id, None);
+    host.expect_hello();
+
+    matchmaker.terminate();
+    assert_eq!(host.recv(), mm::Packet::ShuttingDown(Some(30)));
+    assert_eq!(alice.recv(), mm::Packet::ShuttingDown(Some(30)));
+}
+
+// the start of a PNG image the size of a chunk. the matchmaker only looks at the header, so this is enough
+fn chunk_png() -> Vec<u8> {
+    let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+    png.extend_from_slice(&256u32.to_be_bytes());
+    png.extend_from_slice(&256u32.to_be_bytes());
+    png.extend_from_slice(&[8, 6, 0, 0, 0]);
+    png
+}
+
+#[test]
+fn persistent_rooms_reopen_with_their_canvas() {
+    let storage = std::env::temp_dir().join(format!("netcanv-matchmaker-storage-{}", std::process::id()));
+    let matchmaker = Matchmaker::start_with_args(&["--storage", storage.to_str().unwrap()]);
+    let (mut host, room_id) = Peer::host(&matchmaker, Some("hunter2"));
+    host.send(mm::Packet::PersistRoom(true));
+    assert_eq!(host.recv(), mm::Packet::RoomPersisted(Some(30)));
+    host.send(mm::Packet::StoreChunk((-1, 2), chunk_png()));
+    // anything that isn't a chunk isn't stored
+    host.send(mm::Packet::StoreChunk((0, 0), vec![1, 2, 3]));
+    host.disconnect();
+
+    // whoever joins the closed room becomes its new host, and gets the stored canvas
+    let mut client = Peer::connect(&matchmaker);
+    let reopened = (0..50).any(|_| {
+        std::thread::sleep(Duration::from_millis(10));
+        client.send(mm::Packet::GetHostByStringId(room_id.clone(), Some("hunter2".into())));
+        match client.recv() {
+            mm::Packet::Reopened(id) => id == room_id,
+            _ => false,
+        }
+    });
+    assert!(reopened, "the room wasn't reopened after its host left");
+    assert_eq!(client.recv(), mm::Packet::RoomPersisted(Some(30)));
+    assert_eq!(client.recv(), mm::Packet::StoredChunk((-1, 2), chunk_png()));
+    let _ = std::fs::remove_dir_all(storage);
+}
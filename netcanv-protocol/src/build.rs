@@ -0,0 +1,257 @@
+// building and encoding packets.
+// the packets can be constructed by hand, but going through the constructors on the Packet enums and the functions
+// here checks them against the protocol's limits first. that way a packet that's too large or malformed is an error
+// on the sending side, rather than something the matchmaker or the people in the room have to deal with.
+
+use std::io::Read;
+
+use serde::{Serialize, de::DeserializeOwned};
+use bincode::Options;
+use thiserror::Error;
+
+// the largest a single packet may be once encoded, in bytes. this is generous, as canvas data, stamps and reference
+// images are all sent as PNGs in a single packet, but it keeps a bogus length from making anyone allocate gigabytes
+pub const MAX_PACKET_SIZE: u64 = 32 * 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("The packet is too large ({0} bytes, at most {} are allowed)", MAX_PACKET_SIZE)]
+    TooLarge(u64),
+    #[error("Encoding error: {0}")]
+    Encode(#[from] bincode::Error),
+    #[error("Room names must be 1–{} characters long", crate::matchmaker::MAX_ROOM_NAME_LENGTH)]
+    InvalidRoomName,
+    #[error("Room IDs must be 1–{} letters or digits long", crate::matchmaker::MAX_ROOM_ID_LENGTH)]
+    InvalidRoomId,
+}
+
+// the bincode options packets are encoded with. these are the same as the ones used by bincode::serialize and
+// bincode::deserialize, only with the size limit
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(MAX_PACKET_SIZE)
+}
+
+// encodes a packet, checking that it fits within MAX_PACKET_SIZE
+pub fn encode(packet: &impl Serialize) -> Result<Vec<u8>, Error> {
+    let size = bincode::serialized_size(packet)?;
+    if size > MAX_PACKET_SIZE {
+        return Err(Error::TooLarge(size))
+    }
+    Ok(options().serialize(packet)?)
+}
+
+// decodes a packet, refusing anything that claims to be larger than MAX_PACKET_SIZE
+pub fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+    Ok(options().deserialize(data)?)
+}
+
+// decodes a packet straight from a stream, refusing anything that claims to be larger than MAX_PACKET_SIZE
+pub fn decode_from<T: DeserializeOwned>(reader: impl Read) -> Result<T, Error> {
+    Ok(options().deserialize_from(reader)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::*;
+    use crate::client as cl;
+    use crate::matchmaker as mm;
+
+    fn round_trip<T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug>(packets: &[T]) {
+        for packet in packets {
+            let encoded = encode(packet).unwrap();
+            assert_eq!(encoded, bincode::serialize(packet).unwrap(), "{:?} isn't encoded like bincode does", packet);
+            assert_eq!(&decode::<T>(&encoded).unwrap(), packet);
+        }
+    }
+
+    // the matches below have no wildcard arms, so that adding a packet fails to compile until it's given an index here.
+    // the counts must be bumped along with them
+    const MM_VARIANTS: usize = 25;
+    const CL_VARIANTS: usize = 16;
+
+    fn mm_variant(packet: &mm::Packet) -> usize {
+        use mm::Packet::*;
+        match packet {
+            Host(..) => 0,
+            RoomId(_) => 1,
+            GetHost(..) => 2,
+            HostAddress(_) => 3,
+            ClientAddress(_) => 4,
+            ListRooms => 5,
+            RoomList(_) => 6,
+            RequestRelay(_) => 7,
+            Relay(..) => 8,
+            Relayed(..) => 9,
+            Disconnected(_) => 10,
+            RoomClosed(_) => 11,
+            Error(_) => 12,
+            Ping(_) => 13,
+            Pong(_) => 14,
+            StringRoomId(_) => 15,
+            GetHostByStringId(..) => 16,
+            ListedRooms(_) => 17,
+            ShuttingDown(_) => 18,
+            PersistRoom(_) => 19,
+            RoomPersisted(_) => 20,
+            StoreChunk(..) => 21,
+            Reopened(_) => 22,
+            StoredChunk(..) => 23,
+            Kick(..) => 24,
+        }
+    }
+
+    fn cl_variant(packet: &cl::Packet) -> usize {
+        use cl::Packet::*;
+        match packet {
+            Hello(_) => 0,
+            HiThere(_) => 1,
+            Kick(_) => 2,
+            ChunkCount(_) => 3,
+            CanvasData(..) => 4,
+            ChunkTiles(..) => 5,
+            Cursor(..) => 6,
+            Viewport(..) => 7,
+            Stroke(_) => 8,
+            Tool(..) => 9,
+            Idle(_) => 10,
+            Background(_) => 11,
+            Tools(_) => 12,
+            NicknameTaken(_) => 13,
+            Renamed(..) => 14,
+            Lock(..) => 15,
+        }
+    }
+
+    // checks that every variant is among the packets, going by the variant indices
+    fn assert_covers_all<T>(packets: &[T], variant: fn(&T) -> usize, count: usize) {
+        let mut covered: Vec<usize> = packets.iter().map(variant).collect();
+        covered.sort_unstable();
+        covered.dedup();
+        assert_eq!(covered, (0..count).collect::<Vec<_>>(), "not all packets are round-tripped");
+    }
+
+    #[test]
+    fn matchmaker_packets_round_trip() {
+        let addr: SocketAddr = "127.0.0.1:62137".parse().unwrap();
+        let room = mm::PublicRoom {
+            id: 1234,
+            name: "a room".into(),
+            peer_count: 3,
+            has_password: true,
+        };
+        let packets = [
+            mm::Packet::Host(Some("password".into()), Some("a room".into())),
+            mm::Packet::Host(None, None),
+            mm::Packet::RoomId(1234),
+            mm::Packet::GetHost(1234, None),
+            mm::Packet::HostAddress(addr),
+            mm::Packet::ClientAddress(addr),
+            mm::Packet::ListRooms,
+            mm::Packet::RoomList(vec![room.clone()]),
+            mm::Packet::RequestRelay(Some(addr)),
+            mm::Packet::RequestRelay(None),
+            mm::Packet::Relay(Some(addr), vec![1, 2, 3]),
+            mm::Packet::Relay(None, Vec::new()),
+            mm::Packet::Relayed(addr, vec![4, 5, 6]),
+            mm::Packet::Disconnected(addr),
+            mm::Packet::RoomClosed(mm::CloseReason::Inactivity),
+            mm::Packet::error("something went wrong"),
+            mm::Packet::Ping(7),
+            mm::Packet::Pong(7),
+            mm::Packet::StringRoomId("ABC123".into()),
+            mm::Packet::GetHostByStringId("ABC123".into(), Some("password".into())),
+            mm::Packet::ListedRooms(vec![room.into()]),
+            mm::Packet::ShuttingDown(Some(30)),
+            mm::Packet::ShuttingDown(None),
+            mm::Packet::PersistRoom(true),
+            mm::Packet::RoomPersisted(Some(30)),
+            mm::Packet::RoomPersisted(None),
+            mm::Packet::StoreChunk((-1, 2), vec![0x89, b'P', b'N', b'G']),
+            mm::Packet::Reopened("ABC123".into()),
+            mm::Packet::StoredChunk((3, -4), vec![0x89, b'P', b'N', b'G']),
+            mm::Packet::Kick(addr, true),
+        ];
+        assert_covers_all(&packets, mm_variant, MM_VARIANTS);
+        round_trip(&packets);
+    }
+
+    #[test]
+    fn client_packets_round_trip() {
+        let addr: SocketAddr = "[::1]:62137".parse().unwrap();
+        let point = cl::StrokePoint {
+            x: -8,
+            y: 16,
+            color: 0xff0000ff,
+            brush_size: 8,
+            softness: 50,
+        };
+        let packets = [
+            cl::Packet::Hello("nickname".into()),
+            cl::Packet::HiThere("nickname".into()),
+            cl::Packet::Kick(true),
+            cl::Packet::ChunkCount(42),
+            cl::Packet::CanvasData((-1, 2), vec![0x89, b'P', b'N', b'G']),
+            cl::Packet::ChunkTiles((3, -4), vec![(0, vec![1]), (63, vec![2, 3])]),
+            cl::Packet::Cursor(-80, 160, 8),
+            cl::Packet::Viewport(-100, -50, 1820, 1030),
+            cl::Packet::Stroke(vec![point.clone(), point]),
+            cl::Packet::tool("brush", &(1u8, "payload")).unwrap(),
+            cl::Packet::Idle(false),
+            cl::Packet::Background(Some(0xffffffff)),
+            cl::Packet::Background(None),
+            cl::Packet::Tools(vec![("brush".into(), 1), ("fill".into(), 2)]),
+            cl::Packet::NicknameTaken("nickname 2".into()),
+            cl::Packet::Renamed(addr, "nickname 2".into()),
+            cl::Packet::Lock(Some((-256, 0, 128, 512)), 60_000),
+            cl::Packet::Lock(None, 0),
+        ];
+        assert_covers_all(&packets, cl_variant, CL_VARIANTS);
+        round_trip(&packets);
+    }
+
+    #[test]
+    fn relayed_packets_decode_on_the_other_end() {
+        let packet = cl::Packet::Hello("nickname".into());
+        let relay = mm::Packet::relay(None, &packet).unwrap();
+        match decode(&encode(&relay).unwrap()).unwrap() {
+            mm::Packet::Relay(None, payload) => assert_eq!(decode::<cl::Packet>(&payload).unwrap(), packet),
+            other => panic!("expected a relay packet, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn oversized_packets_are_refused() {
+        let payload = vec![0; MAX_PACKET_SIZE as usize];
+        let packet = cl::Packet::CanvasData((0, 0), payload);
+        assert!(matches!(mm::Packet::relay(None, &packet), Err(Error::TooLarge(_))));
+        assert!(matches!(cl::Packet::tool("stamp", &vec![0u8; MAX_PACKET_SIZE as usize]), Err(Error::TooLarge(_))));
+
+        // a length prefix claiming more data than the limit allows is refused without allocating it
+        let mut bogus = bincode::serialize(&0u32).unwrap(); // Hello
+        bogus.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(decode::<cl::Packet>(&bogus).is_err());
+        assert!(decode_from::<cl::Packet>(&bogus[..]).is_err());
+    }
+
+    #[test]
+    fn room_packets_are_validated() {
+        assert!(mm::Packet::host(None, Some("a room")).is_ok());
+        assert!(matches!(mm::Packet::host(None, Some("")), Err(Error::InvalidRoomName)));
+        let long_name = "a".repeat(mm::MAX_ROOM_NAME_LENGTH + 1);
+        assert!(matches!(mm::Packet::host(None, Some(&long_name)), Err(Error::InvalidRoomName)));
+
+        assert_eq!(
+            mm::Packet::join(" abc123 ", None).unwrap(),
+            mm::Packet::GetHostByStringId("abc123".into(), None),
+        );
+        assert!(matches!(mm::Packet::join("", None), Err(Error::InvalidRoomId)));
+        assert!(matches!(mm::Packet::join("abc-123", None), Err(Error::InvalidRoomId)));
+        let long_id = "1".repeat(mm::MAX_ROOM_ID_LENGTH + 1);
+        assert!(matches!(mm::Packet::join(&long_id, None), Err(Error::InvalidRoomId)));
+    }
+}
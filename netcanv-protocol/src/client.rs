@@ -1,7 +1,11 @@
 // client (p2p) packets
 
+use std::net::SocketAddr;
+
 use serde::{Serialize, Deserialize};
 
+use crate::build::{self, Error};
+
 // stroke packet information
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct StrokePoint {
@@ -13,6 +17,8 @@ pub struct StrokePoint {
     pub color: u32,
     // 15.1 fixed-point brush size
     pub brush_size: i16,
+    // the softness of the brush's edge, in percent
+    pub softness: u8,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -27,9 +33,21 @@ pub enum Packet {
     // response from the other clients with their nicknames
     HiThere(String),
 
-    // image data sent to a client by the host when it first joins
+    // sent by the host to someone who should leave the room. the bool is true if they're also banned, in which case
+    // the host refuses any further attempts to join from their address
+    Kick(bool),
+
+    // the number of chunks the host is about to send to a client that has just joined
+    ChunkCount(u32),
+
+    // image data sent to a client by the host when it first joins.
+    // also broadcast to everyone after most of a chunk is changed by undoing or redoing an operation
     CanvasData((i32, i32), Vec<u8>),
 
+    // the parts of a chunk that changed after undoing or redoing an operation, as (tile index, PNG data) pairs.
+    // tiles are 32x32 pixels and indexed row by row, starting from the top left corner of the chunk
+    ChunkTiles((i32, i32), Vec<(u8, Vec<u8>)>),
+
     //
     // painting
     // --------
@@ -39,8 +57,73 @@ pub enum Packet {
     // cursor packet containing fixed-point 29.3 coordinates and a fixed-point 31.1 brush size
     Cursor(i32, i32, i16),
 
+    // the part of the canvas visible on the sender's screen, as the left, top, right, and bottom edges in pixels.
+    // only sent when it changes
+    Viewport(i32, i32, i32, i32),
+
     // a paint stroke
     Stroke(Vec<StrokePoint>),
+
+    // tool-specific data. the string is the name of the tool the payload is meant for, and the payload's format is
+    // decided by the tool itself
+    Tool(String, Vec<u8>),
+
+    // whether the sender is idle, ie. hasn't touched their mouse or keyboard for a while. sent when it changes, and
+    // to everyone when someone joins
+    Idle(bool),
+
+    //
+    // room settings
+    // -------------
+    // these can only be changed by the host
+    //
+
+    // the canvas's background color as 0xRRGGBBAA, or None if the background is transparent. sent to clients that
+    // have just joined, and to everyone when the host changes it
+    Background(Option<u32>),
+
+    //
+    // capabilities
+    // ------------
+    //
+
+    // the tools the sender has, as (name, version) pairs. sent right after Hello and HiThere, so that people can be
+    // told when someone uses a tool they don't have, or a version of it whose packets they can't read
+    Tools(Vec<(String, u16)>),
+
+    //
+    // nicknames
+    // ---------
+    // nicknames are kept unique by the host
+    //
+
+    // sent by the host to someone who joined with a nickname that's already taken, with the nickname they were given
+    // instead
+    NicknameTaken(String),
+
+    // sent by the host to everyone when it gives someone who just joined a different nickname, with that person's
+    // address and their new nickname
+    Renamed(SocketAddr, String),
+
+    //
+    // region locks
+    // ------------
+    //
+
+    // the region of the canvas the sender locked, as the left, top, right, and bottom edges in pixels, and the number
+    // of milliseconds until the lock expires. other people's edits are kept out of the region until then. locks last at
+    // most a minute. the region is None when the sender unlocks it. sent when the lock changes, and to everyone when
+    // someone joins
+    Lock(Option<(i32, i32, i32, i32)>, u32),
+}
+
+impl Packet {
+
+    // a tool packet carrying the given payload
+    pub fn tool(name: &str, payload: &impl Serialize) -> Result<Self, Error> {
+        Ok(Self::Tool(name.into(), build::encode(payload)?))
+    }
+
 }
 
 /// converts a float to a fixed-point 29.3
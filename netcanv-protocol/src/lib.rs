@@ -1,2 +1,4 @@
+pub mod build;
 pub mod client;
 pub mod matchmaker;
+pub mod version;
@@ -4,23 +4,80 @@ use std::net::SocketAddr;
 
 use serde::{Serialize, Deserialize};
 
+use crate::build::{self, Error};
+use crate::client as cl;
+
+// the longest public room name the matchmaker accepts, in characters
+pub const MAX_ROOM_NAME_LENGTH: usize = 32;
+// the longest room ID a matchmaker can hand out
+pub const MAX_ROOM_ID_LENGTH: usize = 16;
+
+// a room listed in the public room browser, as sent to clients that don't support STRING_ROOM_IDS. only rooms with
+// numeric IDs are listed for them
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct PublicRoom {
+    pub id: u32,
+    pub name: String,
+    // the number of people in the room, including the host
+    pub peer_count: u32,
+    pub has_password: bool,
+}
+
+// a room listed in the public room browser, as sent to clients that support STRING_ROOM_IDS
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ListedRoom {
+    pub id: String,
+    pub name: String,
+    // the number of people in the room, including the host
+    pub peer_count: u32,
+    pub has_password: bool,
+}
+
+impl From<PublicRoom> for ListedRoom {
+    fn from(room: PublicRoom) -> Self {
+        Self {
+            id: format!("{:04}", room.id),
+            name: room.name,
+            peer_count: room.peer_count,
+            has_password: room.has_password,
+        }
+    }
+}
+
+// the reason why the matchmaker closed a room
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum CloseReason {
+    // nothing happened in the room for longer than the matchmaker's idle timeout
+    Inactivity,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum Packet {
     //
     // initial hosting procedure
     //
 
-    // request from the host to the matchmaker for a free ID
-    Host,
+    // request from the host to the matchmaker for a free ID. the first string is an optional password that's required
+    // to join the room, and the second one is the room's name if it should be listed publicly
+    Host(Option<String>, Option<String>),
     // response from the matchmaker to the host containing the ID
     RoomId(u32),
-    // request from a client to join a room with the given ID
-    GetHost(u32),
+    // request from a client to join a room with the given ID and password
+    GetHost(u32, Option<String>),
     // response from the matchmaker to the client containing the host's IP address and port
     HostAddress(SocketAddr),
     // notification from the matchmaker to the host with a connecting client's IP address and port
     ClientAddress(SocketAddr),
 
+    //
+    // public rooms
+    //
+
+    // request for the list of public rooms
+    ListRooms,
+    // response from the matchmaker containing all public rooms
+    RoomList(Vec<PublicRoom>),
+
     //
     // packet relay
     //
@@ -37,15 +94,99 @@ pub enum Packet {
     // peers has disconnected
     Disconnected(SocketAddr),
 
+    // notification sent to everyone in a room that was closed by the matchmaker
+    RoomClosed(CloseReason),
+
     //
     // other
     //
 
     // an error occured
     Error(String),
+
+    // a ping, which the matchmaker answers with a Pong carrying the same number. used for measuring the round trip
+    // time to the matchmaker, and for checking whether the connection is still alive. the matchmaker can also ping
+    // clients that support the KEEPALIVE feature, in which case they answer
+    Ping(u32),
+    Pong(u32),
+
+    //
+    // string room IDs, for clients that support STRING_ROOM_IDS. these replace RoomId, GetHost, and RoomList, which
+    // can only carry numeric IDs
+    //
+
+    // response from the matchmaker to the host containing the ID
+    StringRoomId(String),
+    // request from a client to join a room with the given ID and password
+    GetHostByStringId(String, Option<String>),
+    // response from the matchmaker containing all public rooms
+    ListedRooms(Vec<ListedRoom>),
+
+    //
+    // shutdown notices, for clients that support SHUTDOWN_NOTICE
+    //
+
+    // the matchmaker is shutting down and is about to close the connection. carries the number of seconds after
+    // which it's expected to be back, if it's only restarting
+    ShuttingDown(Option<u32>),
+
+    //
+    // persistent rooms, for clients that support PERSISTENT_ROOMS
+    //
+
+    // request from the host to keep the room's canvas on the matchmaker after the room closes, or to stop keeping it
+    // and throw away what's been stored
+    PersistRoom(bool),
+    // response from the matchmaker to the host, with the number of days the canvas is kept for after the room closes.
+    // None if the canvas isn't being kept
+    RoomPersisted(Option<u32>),
+    // a chunk of the room's canvas as PNG data, sent by the host of a persistent room to be stored
+    StoreChunk((i32, i32), Vec<u8>),
+    // response from the matchmaker to a client joining a room that's closed, but whose canvas is stored. the client
+    // reopens the room with the given ID as its host, and is sent the stored canvas in StoredChunk packets
+    Reopened(String),
+    // a chunk of a reopened room's stored canvas, as PNG data
+    StoredChunk((i32, i32), Vec<u8>),
+    // request from the host to cut someone off from the room's relay. if the bool is true, their IP address is also
+    // banned from joining the room again until it closes
+    Kick(SocketAddr, bool),
 }
 
-// fast way to create an error packet
-pub fn error_packet(message: &str) -> Packet {
-    Packet::Error(message.to_string())
+impl Packet {
+
+    // a request to host a room. public rooms' names are checked against MAX_ROOM_NAME_LENGTH
+    pub fn host(password: Option<&str>, public_name: Option<&str>) -> Result<Self, Error> {
+        if let Some(name) = public_name {
+            if name.is_empty() || name.chars().count() > MAX_ROOM_NAME_LENGTH {
+                return Err(Error::InvalidRoomName)
+            }
+        }
+        Ok(Self::Host(password.map(String::from), public_name.map(String::from)))
+    }
+
+    // a request to join the room with the given ID, for matchmakers that support STRING_ROOM_IDS
+    pub fn join(room_id: &str, password: Option<&str>) -> Result<Self, Error> {
+        let room_id = room_id.trim();
+        if room_id.is_empty()
+            || room_id.len() > MAX_ROOM_ID_LENGTH
+            || !room_id.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(Error::InvalidRoomId)
+        }
+        Ok(Self::GetHostByStringId(room_id.into(), password.map(String::from)))
+    }
+
+    // a packet to be relayed to someone in the room, or everyone if `to` is None
+    pub fn relay(to: Option<SocketAddr>, packet: &cl::Packet) -> Result<Self, Error> {
+        let payload = build::encode(packet)?;
+        // the relay packet itself adds a few bytes on top of the payload
+        let relay = Self::Relay(to, payload);
+        build::encode(&relay)?;
+        Ok(relay)
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::Error(message.into())
+    }
+
 }
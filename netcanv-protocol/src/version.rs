@@ -0,0 +1,83 @@
+// protocol versioning.
+// every connection to the matchmaker starts with both sides sending a Handshake, saying which version of the protocol
+// they speak and which optional features they support. the matchmaker only lets people who speak the same version
+// into a room, so everyone in a room can rely on the others understanding the same packets.
+//
+// clients from before versioning was introduced (version 1) don't send a handshake, and start with a packet right
+// away. the matchmaker tells them apart by the magic number, which is never the start of a valid packet. version 2
+// changed packets they rely on, so they can't be served anymore; they're sent an Error packet in the old format and
+// disconnected. new packets and features must be added in a way that keeps older version 2 clients working, which
+// means packets may only be added at the end of the Packet enums; anything else needs a new version.
+
+use serde::{Serialize, Deserialize};
+
+// read as a bincode enum variant index, this is far out of range for any of the Packet enums
+pub const MAGIC: [u8; 4] = *b"NCpv";
+
+pub const VERSION: u16 = 2;
+
+// the oldest version the matchmaker still accepts
+pub const OLDEST_SUPPORTED_VERSION: u16 = 2;
+
+// optional features, as bit flags
+pub mod features {
+    // Ping and Pong packets, for measuring the round trip time to the matchmaker
+    pub const PING: u32 = 1 << 0;
+    // the matchmaker may ping quiet clients to check whether they're still there, which they answer with a Pong
+    pub const KEEPALIVE: u32 = 1 << 1;
+    // room IDs are strings rather than numbers, so that matchmakers can hand out longer or alphanumeric IDs
+    pub const STRING_ROOM_IDS: u32 = 1 << 2;
+    // the matchmaker sends a ShuttingDown packet before it shuts down, rather than just closing the connection
+    pub const SHUTDOWN_NOTICE: u32 = 1 << 3;
+    // the matchmaker can keep rooms' canvases after they close, so that they can be reopened later. matchmakers only
+    // offer this when they're set up to store canvases
+    pub const PERSISTENT_ROOMS: u32 = 1 << 4;
+    // the matchmaker enforces kicks and bans: the host's Kick packets cut people off from the room's relay, and banned
+    // people can't join the room again
+    pub const KICK: u32 = 1 << 5;
+}
+
+// all the features this version supports
+pub const FEATURES: u32 =
+    features::PING
+    | features::KEEPALIVE
+    | features::STRING_ROOM_IDS
+    | features::SHUTDOWN_NOTICE
+    | features::PERSISTENT_ROOMS
+    | features::KICK;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Handshake {
+    pub magic: [u8; 4],
+    pub version: u16,
+    pub features: u32,
+}
+
+impl Handshake {
+
+    pub fn ours() -> Self {
+        Self {
+            magic: MAGIC,
+            version: VERSION,
+            features: FEATURES,
+        }
+    }
+
+    // whether the matchmaker can talk to someone with this handshake
+    pub fn is_supported(&self) -> bool {
+        self.magic == MAGIC && (OLDEST_SUPPORTED_VERSION ..= VERSION).contains(&self.version)
+    }
+
+    // the features supported by both sides
+    pub fn negotiate(&self, other: &Handshake) -> u32 {
+        self.features & other.features
+    }
+
+}
+
+// encodes an Error packet the way clients from before versioning decode it, so that they can be told why they can't
+// connect. back then, Error was the tenth matchmaker packet, and packets were encoded with bincode's default options
+pub fn legacy_error(message: &str) -> Vec<u8> {
+    const LEGACY_ERROR_VARIANT: u32 = 9;
+    bincode::serialize(&(LEGACY_ERROR_VARIANT, message)).unwrap()
+}
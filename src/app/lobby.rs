@@ -1,14 +1,26 @@
 use std::error::Error;
 use std::fmt::Display;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 
 use skulpin::skia_safe::*;
 
 use crate::app::{AppState, StateArgs, paint};
 use crate::assets::Assets;
+use crate::autosave;
+use crate::cli::JoinOptions;
+use crate::color_schemes;
+use crate::i18n::{self, Language};
+use crate::logging;
+use crate::project;
+use crate::config::{RecentRoom, UiConfig, UserConfig};
+use crate::file_dialog::PendingDialog;
 use crate::ui::*;
+use crate::update_check::{Release, UpdateCheck};
 use crate::util::get_window_size;
 use crate::net::{Message, Peer};
+use crate::net::room_list::{ListedRoom, RoomList};
+use netcanv_protocol::matchmaker::MAX_ROOM_ID_LENGTH;
 
 #[derive(Debug)]
 enum Status {
@@ -25,6 +37,7 @@ impl<T: Error + Display> From<T> for Status {
 
 pub struct State {
     assets: Assets,
+    config: UserConfig,
     ui: Ui,
 
     // UI elements
@@ -32,34 +45,120 @@ pub struct State {
     nickname_field: TextField,
     matchmaker_field: TextField,
     room_id_field: TextField,
+    join_password_field: TextField,
+    host_password_field: TextField,
+    room_name_field: TextField,
 
     join_expand: Expand,
     host_expand: Expand,
+    browse_expand: Expand,
+    recent_expand: Expand,
 
     // net
     status: Status,
     peer: Option<Peer>,
     connected: bool, // when this is true, the state is transitioned to paint::State
+    // the saved canvas to open once the room is hosted
+    project: Option<PathBuf>,
+    // the dialog for picking a saved canvas to host, while it's open
+    project_dialog: Option<PendingDialog>,
+    // whether there's a canvas that can be recovered after NetCanv didn't shut down properly
+    recoverable: bool,
+    // the room list being fetched from the matchmaker, and the last list that was fetched
+    room_list: Option<RoomList>,
+    public_rooms: Option<Vec<ListedRoom>>,
+    // the check for a newer version of NetCanv while it's running, and the newer version once one's been found
+    update_check: Option<UpdateCheck>,
+    update: Option<Release>,
+    changelog_open: bool,
+    about_open: bool,
+    toasts: Toasts,
+    // the UI scale, in percent
+    ui_scale_slider: Slider,
 }
 
 impl State {
 
-    pub fn new(assets: Assets, error: Option<&str>) -> Self {
+    // the maximum amount of rooms shown in the room browser
+    const MAX_LISTED_ROOMS: usize = 5;
+    // how often the matchmaker is polled while waiting for it, in seconds
+    const POLL_INTERVAL: f32 = 0.05;
+
+    pub fn new(assets: Assets, config: UserConfig, error: Option<&str>) -> Self {
+        let nickname_field = TextField::new(Some(&config.lobby.nickname));
+        let matchmaker_field = TextField::new(Some(&config.lobby.matchmaker));
+        let update_check =
+            if config.updates.check_for_updates == Some(true) { UpdateCheck::start_once() } else { None };
+        let ui_scale_slider = Slider::new(
+            config.ui.scale() * 100.0,
+            UiConfig::MIN_SCALE * 100.0,
+            UiConfig::MAX_SCALE * 100.0,
+            SliderStep::Discrete(25.0),
+        );
         Self {
             assets,
+            config,
             ui: Ui::new(),
-            nickname_field: TextField::new(Some("Anon")),
-            matchmaker_field: TextField::new(None),
+            nickname_field,
+            matchmaker_field,
             room_id_field: TextField::new(None),
+            join_password_field: TextField::new_masked(),
+            host_password_field: TextField::new_masked(),
+            room_name_field: TextField::new(None),
             join_expand: Expand::new(true),
             host_expand: Expand::new(false),
+            browse_expand: Expand::new(false),
+            recent_expand: Expand::new(false),
             status: match error {
                 Some(err) => Status::Error(err.into()),
                 None => Status::None,
             },
             peer: None,
             connected: false,
+            project: None,
+            project_dialog: None,
+            recoverable: autosave::recoverable(),
+            room_list: None,
+            public_rooms: None,
+            update_check,
+            update: None,
+            changelog_open: false,
+            about_open: false,
+            toasts: Toasts::new(),
+            ui_scale_slider,
+        }
+    }
+
+    // creates the lobby pre-filled with the options passed on the command line. if a room ID was given, the room is
+    // joined right away
+    pub fn with_options(assets: Assets, config: UserConfig, options: JoinOptions) -> Self {
+        let mut state = Self::new(assets, config, None);
+        let fields = vec![
+            (&mut state.nickname_field, &options.nickname),
+            (&mut state.matchmaker_field, &options.matchmaker),
+            (&mut state.room_id_field, &options.room_id),
+            (&mut state.join_password_field, &options.password),
+        ];
+        for (field, text) in fields {
+            if let Some(text) = text {
+                field.set_text(text);
+            }
+        }
+        // the config isn't saved here, so that launching from a shortcut doesn't overwrite what was last used
+        // in the lobby
+        if options.room_id.is_some() {
+            match Self::join_room(
+                &state.assets.language,
+                state.nickname_field.text(),
+                state.matchmaker_field.text(),
+                state.room_id_field.text(),
+                state.join_password_field.text(),
+            ) {
+                Ok(peer) => state.peer = Some(peer),
+                Err(status) => state.status = status,
+            }
         }
+        state
     }
 
     fn process_header(&mut self, canvas: &mut Canvas) {
@@ -73,7 +172,7 @@ impl State {
         self.ui.push_group((self.ui.width(), self.ui.remaining_height()), Layout::Freeform);
         self.ui.text(
             canvas,
-            "Welcome! Host a room or join an existing one to start painting.",
+            self.assets.language.tr("lobby.welcome"),
             self.assets.colors.text,
             (AlignH::Left, AlignV::Middle),
         );
@@ -85,6 +184,11 @@ impl State {
     fn process_menu(&mut self, canvas: &mut Canvas, input: &mut Input) -> Option<Box<dyn AppState>> {
         self.ui.push_group((self.ui.width(), self.ui.remaining_height()), Layout::Vertical);
 
+        let mut host = false;
+        // the ID of a room picked from one of the lists
+        let mut join_listed = None;
+
+        let lang = &self.assets.language;
         let button = ButtonArgs {
             height: 32.0,
             colors: &self.assets.colors.button,
@@ -103,13 +207,13 @@ impl State {
 
         // nickname, matchmaker
         self.ui.push_group((self.ui.width(), TextField::labelled_height(&self.ui)), Layout::Horizontal);
-        self.nickname_field.with_label(&mut self.ui, canvas, input, "Nickname", TextFieldArgs {
-            hint: Some("Name shown to others"),
+        self.nickname_field.with_label(&mut self.ui, canvas, input, lang.tr("lobby.nickname"), TextFieldArgs {
+            hint: Some(lang.tr("lobby.nickname_hint")),
             .. textfield
         });
         self.ui.space(16.0);
-        self.matchmaker_field.with_label(&mut self.ui, canvas, input, "Matchmaker", TextFieldArgs {
-            hint: Some("IP address"),
+        self.matchmaker_field.with_label(&mut self.ui, canvas, input, lang.tr("lobby.matchmaker"), TextFieldArgs {
+            hint: Some(lang.tr("lobby.matchmaker_hint")),
             .. textfield
         });
         self.ui.pop_group();
@@ -117,35 +221,46 @@ impl State {
 
         // join room
         if self.join_expand.process(&mut self.ui, canvas, input, ExpandArgs {
-            label: "Join an existing room",
+            label: lang.tr("lobby.join_room"),
             .. expand
         })
             .mutually_exclude(&mut self.host_expand)
+            .mutually_exclude(&mut self.browse_expand)
+            .mutually_exclude(&mut self.recent_expand)
             .expanded()
         {
             self.ui.push_group(self.ui.remaining_size(), Layout::Vertical);
             self.ui.offset((32.0, 8.0));
 
-            self.ui.paragraph(canvas, self.assets.colors.text, AlignH::Left, None, &[
-                "Ask your friend for the Room ID",
-                "and enter it into the text field below."
-            ]);
+            let lines: Vec<_> = lang.tr("lobby.join_help").lines().collect();
+            self.ui.paragraph(canvas, self.assets.colors.text, AlignH::Left, None, &lines);
             self.ui.space(16.0);
             self.ui.push_group((0.0, TextField::labelled_height(&self.ui)), Layout::Horizontal);
-            self.room_id_field.with_label(&mut self.ui, canvas, input, "Room ID", TextFieldArgs {
-                hint: Some("4–6 digits"),
+            self.room_id_field.with_label(&mut self.ui, canvas, input, lang.tr("lobby.room_id"), TextFieldArgs {
+                hint: Some(lang.tr("lobby.room_id_hint")),
+                .. textfield
+            });
+            self.ui.space(16.0);
+            self.join_password_field.with_label(&mut self.ui, canvas, input, lang.tr("lobby.password"), TextFieldArgs {
+                hint: Some(lang.tr("lobby.join_password_hint")),
                 .. textfield
             });
             self.ui.offset((16.0, 16.0));
-            if Button::with_text(&mut self.ui, canvas, input, button, "Join").clicked() {
+            if Button::with_text(&mut self.ui, canvas, input, button, lang.tr("lobby.join")).clicked() {
                 match Self::join_room(
+                    lang,
                     self.nickname_field.text(),
                     self.matchmaker_field.text(),
-                    self.room_id_field.text()
+                    self.room_id_field.text(),
+                    self.join_password_field.text(),
                 ) {
                     Ok(peer) => {
                         self.peer = Some(peer);
-                        self.status = Status::None;
+                        self.status = Self::save_config(
+                            &mut self.config,
+                            self.nickname_field.text(),
+                            self.matchmaker_field.text(),
+                        );
                     },
                     Err(status) => self.status = status,
                 }
@@ -159,45 +274,571 @@ impl State {
 
         // host room
         if self.host_expand.process(&mut self.ui, canvas, input, ExpandArgs {
-            label: "Host a new room",
+            label: lang.tr("lobby.host_room"),
             .. expand
         })
             .mutually_exclude(&mut self.join_expand)
+            .mutually_exclude(&mut self.browse_expand)
+            .mutually_exclude(&mut self.recent_expand)
             .expanded()
         {
             self.ui.push_group(self.ui.remaining_size(), Layout::Vertical);
             self.ui.offset((32.0, 8.0));
 
-            self.ui.paragraph(canvas, self.assets.colors.text, AlignH::Left, None, &[
-                "Click 'Host' and share the Room ID",
-                "with your friends.",
-            ]);
+            let lines: Vec<_> = lang.tr("lobby.host_help").lines().collect();
+            self.ui.paragraph(canvas, self.assets.colors.text, AlignH::Left, None, &lines);
             self.ui.space(16.0);
-            if Button::with_text(&mut self.ui, canvas, input, button, "Host").clicked() {
-                match Self::host_room(self.nickname_field.text(), self.matchmaker_field.text()) {
-                    Ok(peer) => {
-                        self.peer = Some(peer);
-                        self.status = Status::None;
+            self.ui.push_group((0.0, TextField::labelled_height(&self.ui)), Layout::Horizontal);
+            self.room_name_field.with_label(&mut self.ui, canvas, input, lang.tr("lobby.public_name"), TextFieldArgs {
+                hint: Some(lang.tr("lobby.public_name_hint")),
+                .. textfield
+            });
+            self.ui.space(16.0);
+            self.host_password_field.with_label(&mut self.ui, canvas, input, lang.tr("lobby.password"), TextFieldArgs {
+                hint: Some(lang.tr("lobby.host_password_hint")),
+                .. textfield
+            });
+            self.ui.offset((16.0, 16.0));
+            if Button::with_text(&mut self.ui, canvas, input, button, lang.tr("lobby.host")).clicked() {
+                host = true;
+                self.project = None;
+            }
+            self.ui.space(8.0);
+            if Button::with_text(&mut self.ui, canvas, input, button, lang.tr("lobby.host_saved_canvas")).clicked() {
+                self.project_dialog = Some(PendingDialog::spawn(|dialog| dialog.show_open_single_dir()));
+            }
+            self.ui.pop_group();
+
+            self.ui.fit();
+            self.ui.pop_group();
+        }
+
+        self.ui.space(16.0);
+
+        // browse public rooms
+        if self.browse_expand.process(&mut self.ui, canvas, input, ExpandArgs {
+            label: lang.tr("lobby.browse_rooms"),
+            .. expand
+        })
+            .mutually_exclude(&mut self.join_expand)
+            .mutually_exclude(&mut self.host_expand)
+            .mutually_exclude(&mut self.recent_expand)
+            .expanded()
+        {
+            self.ui.push_group(self.ui.remaining_size(), Layout::Vertical);
+            self.ui.offset((32.0, 8.0));
+
+            let mut refresh = self.room_list.is_none() && self.public_rooms.is_none();
+
+            self.ui.push_group((self.ui.width(), button.height), Layout::Horizontal);
+            if Button::with_text(&mut self.ui, canvas, input, button, lang.tr("lobby.refresh")).clicked() {
+                refresh = true;
+            }
+            self.ui.space(16.0);
+            let text = match &self.public_rooms {
+                None => lang.tr("lobby.loading").to_owned(),
+                Some(rooms) if rooms.is_empty() => lang.tr("lobby.no_public_rooms").to_owned(),
+                Some(rooms) => lang.format("lobby.public_rooms", &[&rooms.len()]),
+            };
+            self.ui.push_group((self.ui.remaining_width(), self.ui.height()), Layout::Freeform);
+            self.ui.text(canvas, &text, self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+            self.ui.pop_group();
+            self.ui.pop_group();
+            self.ui.space(8.0);
+
+            for room in self.public_rooms.iter().flatten().take(Self::MAX_LISTED_ROOMS) {
+                let text = format!(
+                    "{}  {}  ({} {}){}",
+                    room.id,
+                    room.name,
+                    room.peer_count,
+                    lang.tr(if room.peer_count == 1 { "lobby.person" } else { "lobby.people" }),
+                    if room.has_password { lang.tr("lobby.password_protected") } else { "" },
+                );
+                if Button::with_text(&mut self.ui, canvas, input, ButtonArgs { height: 24.0, .. button }, &text)
+                    .clicked()
+                {
+                    join_listed = Some(room.id.clone());
+                }
+                self.ui.space(4.0);
+            }
+
+            self.ui.pop_group();
+
+            if refresh {
+                match RoomList::fetch(self.matchmaker_field.text()) {
+                    Ok(room_list) => {
+                        self.room_list = Some(room_list);
+                        self.public_rooms = None;
+                    },
+                    Err(error) => {
+                        self.status = error.into();
+                        // don't try again until the user asks to
+                        self.public_rooms = Some(Vec::new());
                     },
-                    Err(status) => self.status = status,
                 }
             }
+        }
+
+        self.ui.space(16.0);
+
+        // recently joined rooms
+        if self.recent_expand.process(&mut self.ui, canvas, input, ExpandArgs {
+            label: lang.tr("lobby.recent_rooms"),
+            .. expand
+        })
+            .mutually_exclude(&mut self.join_expand)
+            .mutually_exclude(&mut self.host_expand)
+            .mutually_exclude(&mut self.browse_expand)
+            .expanded()
+        {
+            self.ui.push_group(self.ui.remaining_size(), Layout::Vertical);
+            self.ui.offset((32.0, 8.0));
+
+            let mut join_recent = None;
+            if self.config.lobby.recent_rooms.is_empty() {
+                self.ui.push_group((self.ui.width(), 24.0), Layout::Freeform);
+                let text = lang.tr("lobby.no_recent_rooms");
+                self.ui.text(canvas, text, self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+                self.ui.pop_group();
+            }
+            for room in &self.config.lobby.recent_rooms {
+                let text = format!("{}  {}  ({})", room.room_id, room.matchmaker, Self::format_age(lang, room));
+                if Button::with_text(&mut self.ui, canvas, input, ButtonArgs { height: 24.0, .. button }, &text)
+                    .clicked()
+                {
+                    join_recent = Some(room.clone());
+                }
+                self.ui.space(4.0);
+            }
 
-            self.ui.fit();
             self.ui.pop_group();
+
+            if let Some(room) = join_recent {
+                self.matchmaker_field.set_text(&room.matchmaker);
+                join_listed = Some(room.room_id);
+            }
         }
 
         self.ui.pop_group();
 
-        chain_focus(input, &mut [
-            &mut self.nickname_field,
-            &mut self.matchmaker_field,
-            &mut self.room_id_field,
-        ]);
+        if host {
+            self.host();
+        }
+        if let Some(room_id) = join_listed {
+            self.join_listed_room(&room_id);
+        }
 
         None
     }
 
+    // joins a room picked from one of the lists, using the nickname, matchmaker and password from the forms
+    fn join_listed_room(&mut self, room_id: &str) {
+        // the room ID is also filled into the join form, in case the room turns out to need a password
+        self.room_id_field.set_text(room_id);
+        match Self::join_room(
+            &self.assets.language,
+            self.nickname_field.text(),
+            self.matchmaker_field.text(),
+            self.room_id_field.text(),
+            self.join_password_field.text(),
+        ) {
+            Ok(peer) => {
+                self.peer = Some(peer);
+                self.status = Self::save_config(
+                    &mut self.config,
+                    self.nickname_field.text(),
+                    self.matchmaker_field.text(),
+                );
+            },
+            Err(status) => self.status = status,
+        }
+    }
+
+    // describes how long ago a recent room was joined
+    fn format_age(lang: &Language, room: &RecentRoom) -> String {
+        const MINUTE: u64 = 60;
+        const HOUR: u64 = 60 * MINUTE;
+        const DAY: u64 = 24 * HOUR;
+        match room.age().as_secs() {
+            age if age < MINUTE => lang.tr("lobby.just_now").into(),
+            age if age < HOUR => lang.format("lobby.minutes_ago", &[&(age / MINUTE)]),
+            age if age < DAY => lang.format("lobby.hours_ago", &[&(age / HOUR)]),
+            age => lang.format("lobby.days_ago", &[&(age / DAY)]),
+        }
+    }
+
+    // hosts a room using the settings from the host form
+    fn host(&mut self) {
+        match Self::host_room(
+            &self.assets.language,
+            self.nickname_field.text(),
+            self.matchmaker_field.text(),
+            self.host_password_field.text(),
+            self.room_name_field.text(),
+        ) {
+            Ok(peer) => {
+                self.peer = Some(peer);
+                self.status = Self::save_config(
+                    &mut self.config,
+                    self.nickname_field.text(),
+                    self.matchmaker_field.text(),
+                );
+            },
+            Err(status) => self.status = status,
+        }
+    }
+
+    // the prompt offering to restore the autosaved canvas, shown if NetCanv didn't shut down properly
+    fn process_recovery(&mut self, canvas: &mut Canvas, input: &Input) {
+        if !self.recoverable { return }
+
+        let button = ButtonArgs {
+            height: 24.0,
+            colors: &self.assets.colors.button,
+        };
+        let text = self.assets.language.tr("lobby.recovery");
+        self.ui.push_group((self.ui.width(), 24.0), Layout::Horizontal);
+        self.ui.push_group((self.ui.text_size(text).0 + 16.0, self.ui.height()), Layout::Freeform);
+        self.ui.text(canvas, text, self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+        self.ui.pop_group();
+        let restore = Button::with_text(&mut self.ui, canvas, input, button, self.assets.language.tr("lobby.restore"))
+            .clicked();
+        self.ui.space(8.0);
+        let discard = Button::with_text(&mut self.ui, canvas, input, button, self.assets.language.tr("lobby.discard"))
+            .clicked();
+        self.ui.pop_group();
+        self.ui.space(8.0);
+
+        if restore {
+            self.recoverable = false;
+            match autosave::take_recovered() {
+                Ok(dir) => {
+                    self.project = Some(dir);
+                    self.host();
+                },
+                Err(error) => self.status = error.into(),
+            }
+        } else if discard {
+            self.recoverable = false;
+            if let Err(error) = autosave::discard() {
+                self.status = error.into();
+            }
+        }
+    }
+
+    // the line asking whether to check for updates, shown until it's answered. nothing is sent to GitHub before then
+    fn process_update_prompt(&mut self, canvas: &mut Canvas, input: &Input) {
+        if self.config.updates.check_for_updates.is_some() { return }
+
+        let button = ButtonArgs {
+            height: 24.0,
+            colors: &self.assets.colors.button,
+        };
+        let lang = &self.assets.language;
+        let text = lang.tr("lobby.update_prompt");
+        self.ui.push_group((self.ui.width(), 24.0), Layout::Horizontal);
+        self.ui.push_group((self.ui.text_size(text).0 + 16.0, self.ui.height()), Layout::Freeform);
+        self.ui.text(canvas, text, self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+        self.ui.pop_group();
+        let yes = Button::with_text(&mut self.ui, canvas, input, button, lang.tr("lobby.yes")).clicked();
+        self.ui.space(8.0);
+        let no = Button::with_text(&mut self.ui, canvas, input, button, lang.tr("lobby.no")).clicked();
+        self.ui.pop_group();
+        self.ui.space(8.0);
+
+        if yes || no {
+            self.config.updates.check_for_updates = Some(yes);
+            if let Err(error) = self.config.save() {
+                self.status = error.into();
+            }
+            if yes {
+                self.update_check = UpdateCheck::start_once();
+            }
+        }
+    }
+
+    // splits the Markdown changelog into lines that fit within the given width. the Markdown is only cleaned up
+    // enough to be readable as plain text
+    fn changelog_lines(&self, changelog: &str, width: f32) -> Vec<String> {
+        let mut lines = Vec::new();
+        for line in changelog.lines() {
+            let line = line.trim_end().trim_start_matches('#').trim_start();
+            let line = match line.strip_prefix("* ").or_else(|| line.strip_prefix("- ")) {
+                Some(item) => format!("• {}", item),
+                None => line.to_owned(),
+            };
+            if line.is_empty() {
+                if lines.last().is_some_and(|last: &String| !last.is_empty()) {
+                    lines.push(String::new());
+                }
+                continue
+            }
+            let mut wrapped = String::new();
+            for word in line.split(' ') {
+                let candidate = if wrapped.is_empty() { word.to_owned() } else { format!("{} {}", wrapped, word) };
+                if self.ui.text_size(&candidate).0 > width && !wrapped.is_empty() {
+                    lines.push(std::mem::replace(&mut wrapped, word.to_owned()));
+                } else {
+                    wrapped = candidate;
+                }
+            }
+            lines.push(wrapped);
+        }
+        lines
+    }
+
+    // the changelog of the newer version, shown on top of the lobby after clicking "What's new"
+    fn process_changelog(&mut self, canvas: &mut Canvas, input: &Input) {
+        if !self.changelog_open { return }
+        let release = match &self.update {
+            Some(release) => release.clone(),
+            None => return,
+        };
+
+        let dim = Paint::new(Color4f::from(self.assets.colors.panel.with_a(192)), None);
+        self.ui.draw_on_canvas(canvas, |canvas| {
+            canvas.draw_paint(&dim);
+        });
+
+        let line_height = 16.0;
+        let (width, height) = (480.0, 360.0);
+        self.ui.push_group(self.ui.size(), Layout::Freeform);
+        self.ui.push_group((width, height), Layout::Vertical);
+        self.ui.align((AlignH::Center, AlignV::Middle));
+        self.ui.fill(canvas, self.assets.colors.panel);
+        self.ui.outline(canvas, self.assets.colors.separator, 1.0);
+        self.ui.pad((16.0, 16.0));
+
+        let lang = &self.assets.language;
+        let title = lang.format("lobby.changelog_title", &[&release.version]);
+        self.ui.push_group((self.ui.width(), line_height), Layout::Freeform);
+        self.ui.set_font(self.assets.sans_bold.clone());
+        self.ui.text(canvas, &title, self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+        self.ui.pop_group();
+        self.ui.set_font(self.assets.sans.clone());
+        self.ui.space(8.0);
+
+        // whatever doesn't fit is cut off, the rest can be read on the release's page
+        let mut lines = self.changelog_lines(&release.changelog, self.ui.width());
+        let max_lines = ((self.ui.remaining_height() - 64.0) / line_height) as usize;
+        if lines.len() > max_lines {
+            lines.truncate(max_lines.saturating_sub(1));
+            lines.push("…".into());
+        }
+        self.ui.push_group((self.ui.width(), self.ui.remaining_height() - 64.0), Layout::Vertical);
+        for line in &lines {
+            self.ui.push_group((self.ui.width(), line_height), Layout::Freeform);
+            self.ui.text(canvas, line, self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+            self.ui.pop_group();
+        }
+        self.ui.pop_group();
+        self.ui.space(8.0);
+
+        let link = lang.format("lobby.changelog_link", &[&release.url]);
+        self.ui.push_group((self.ui.width(), line_height), Layout::Freeform);
+        self.ui.text(canvas, &link, self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+        self.ui.pop_group();
+        self.ui.space(16.0);
+
+        let button = ButtonArgs {
+            height: 24.0,
+            colors: &self.assets.colors.button,
+        };
+        self.ui.push_group((self.ui.width(), button.height), Layout::Horizontal);
+        if Button::with_text(&mut self.ui, canvas, input, button, lang.tr("lobby.close")).clicked() {
+            self.changelog_open = false;
+        }
+        self.ui.pop_group();
+
+        self.ui.pop_group();
+        self.ui.pop_group();
+    }
+
+    // the about screen, with the version and credits
+    fn process_about(&mut self, canvas: &mut Canvas, input: &Input) {
+        if !self.about_open { return }
+
+        let dim = Paint::new(Color4f::from(self.assets.colors.panel.with_a(192)), None);
+        self.ui.draw_on_canvas(canvas, |canvas| {
+            canvas.draw_paint(&dim);
+        });
+
+        let lang = &self.assets.language;
+        // cargo separates multiple authors with colons
+        let authors = env!("CARGO_PKG_AUTHORS").replace(':', ", ");
+        let lines = [
+            lang.format("about.version", &[&env!("CARGO_PKG_VERSION")]),
+            lang.format("about.authors", &[&authors]),
+            String::new(),
+            lang.tr("about.font").to_owned(),
+            lang.tr("about.icons").to_owned(),
+            lang.tr("about.libraries").to_owned(),
+        ];
+        let line_height = 16.0;
+        let width = lines.iter().map(|line| self.ui.text_size(line).0).fold(320.0, f32::max) + 32.0;
+        let height = 32.0 + line_height + 8.0 + lines.len() as f32 * line_height + 16.0 + 24.0;
+        self.ui.push_group(self.ui.size(), Layout::Freeform);
+        self.ui.push_group((width, height), Layout::Vertical);
+        self.ui.align((AlignH::Center, AlignV::Middle));
+        self.ui.fill(canvas, self.assets.colors.panel);
+        self.ui.outline(canvas, self.assets.colors.separator, 1.0);
+        self.ui.pad((16.0, 16.0));
+
+        self.ui.push_group((self.ui.width(), line_height), Layout::Freeform);
+        self.ui.set_font(self.assets.sans_bold.clone());
+        self.ui.text(canvas, lang.tr("about.title"), self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+        self.ui.pop_group();
+        self.ui.set_font(self.assets.sans.clone());
+        self.ui.space(8.0);
+
+        for line in &lines {
+            self.ui.push_group((self.ui.width(), line_height), Layout::Freeform);
+            self.ui.text(canvas, line, self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+            self.ui.pop_group();
+        }
+        self.ui.space(16.0);
+
+        let button = ButtonArgs {
+            height: 24.0,
+            colors: &self.assets.colors.button,
+        };
+        self.ui.push_group((self.ui.width(), button.height), Layout::Horizontal);
+        if Button::with_text(&mut self.ui, canvas, input, button, lang.tr("about.close")).clicked() {
+            self.about_open = false;
+        }
+        self.ui.space(8.0);
+        if Button::with_text(&mut self.ui, canvas, input, button, lang.tr("about.open_logs")).clicked() {
+            if let Err(error) = logging::open_dir() {
+                self.status = error.into();
+            }
+        }
+        self.ui.pop_group();
+
+        self.ui.pop_group();
+        self.ui.pop_group();
+    }
+
+    // remembers the nickname and matchmaker for the next time the app is launched
+    fn save_config(config: &mut UserConfig, nickname: &str, matchmaker: &str) -> Status {
+        config.lobby.nickname = nickname.into();
+        config.lobby.matchmaker = matchmaker.into();
+        match config.save() {
+            Ok(()) => Status::None,
+            Err(error) => error.into(),
+        }
+    }
+
+    // the buttons in the top right corner that switch between languages and color schemes
+    fn process_settings(&mut self, canvas: &mut Canvas, input: &Input) {
+        let lang = &self.assets.language;
+        let language_text = lang.format("lobby.language", &[&lang.name()]);
+        let color_scheme_text = lang.format("lobby.color_scheme", &[&self.config.ui.color_scheme]);
+        let about_text = lang.tr("lobby.about");
+        let whats_new_text = lang.tr("lobby.whats_new");
+        let ui_scale_text = lang.tr("lobby.ui_scale");
+        let button = ButtonArgs {
+            height: 32.0,
+            colors: &self.assets.colors.button,
+        };
+        let language_width = self.ui.text_size(&language_text).0 + button.height;
+        let color_scheme_width = self.ui.text_size(&color_scheme_text).0 + button.height;
+        let about_width = self.ui.text_size(about_text).0 + button.height;
+        // "What's new" is only there once a newer version has been found
+        let whats_new_width =
+            if self.update.is_some() { self.ui.text_size(whats_new_text).0 + button.height + 8.0 } else { 0.0 };
+        let ui_scale_label_width = self.ui.text_size(ui_scale_text).0 + 8.0;
+        // wide enough for the widest percentage, so that the row doesn't shift around while the slider is dragged
+        let ui_scale_width = ui_scale_label_width + 96.0 + 8.0 + 40.0;
+
+        self.ui.push_group(
+            (
+                ui_scale_width + 16.0 + language_width + 8.0 + color_scheme_width + 8.0 + about_width +
+                    whats_new_width,
+                button.height,
+            ),
+            Layout::Horizontal,
+        );
+        self.ui.align((AlignH::Right, AlignV::Top));
+        self.ui.push_group((ui_scale_label_width, button.height), Layout::Freeform);
+        self.ui.text(canvas, ui_scale_text, self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+        self.ui.pop_group();
+        self.ui.push_group((96.0, button.height), Layout::Freeform);
+        self.ui_scale_slider.process(&mut self.ui, canvas, input, SliderArgs {
+            width: 96.0,
+            color: self.assets.colors.slider,
+        });
+        self.ui.pop_group();
+        self.ui.space(8.0);
+        self.ui.push_group((40.0, button.height), Layout::Freeform);
+        let ui_scale_percent = format!("{}%", self.ui_scale_slider.value());
+        self.ui.text(canvas, &ui_scale_percent, self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+        self.ui.pop_group();
+        self.ui.space(16.0);
+        self.ui.push_group((language_width, button.height), Layout::Freeform);
+        let switch_language = Button::with_text(&mut self.ui, canvas, input, button, &language_text).clicked();
+        self.ui.pop_group();
+        self.ui.space(8.0);
+        self.ui.push_group((color_scheme_width, button.height), Layout::Freeform);
+        let switch_color_scheme = Button::with_text(&mut self.ui, canvas, input, button, &color_scheme_text).clicked();
+        self.ui.pop_group();
+        self.ui.space(8.0);
+        self.ui.push_group((about_width, button.height), Layout::Freeform);
+        if Button::with_text(&mut self.ui, canvas, input, button, about_text).clicked() {
+            self.about_open = true;
+        }
+        self.ui.pop_group();
+        if self.update.is_some() {
+            self.ui.space(8.0);
+            self.ui.push_group((whats_new_width - 8.0, button.height), Layout::Freeform);
+            if Button::with_text(&mut self.ui, canvas, input, button, whats_new_text).clicked() {
+                self.changelog_open = true;
+            }
+            self.ui.pop_group();
+        }
+        self.ui.pop_group();
+
+        if switch_language {
+            let languages: Vec<_> = i18n::available().collect();
+            let next = languages
+                .iter()
+                .position(|&code| code == self.assets.language.code())
+                .map_or(0, |i| (i + 1) % languages.len());
+            self.assets.language = Language::load(languages[next]);
+            self.config.ui.language = languages[next].to_owned();
+            if let Err(error) = self.config.save() {
+                self.status = error.into();
+            }
+        }
+        if switch_color_scheme {
+            let schemes = color_schemes::available();
+            let next = schemes
+                .iter()
+                .position(|name| *name == self.config.ui.color_scheme)
+                .map_or(0, |i| (i + 1) % schemes.len());
+            let name = &schemes[next];
+            match color_schemes::load(name) {
+                Ok(colors) => {
+                    self.assets.set_color_scheme(name, colors);
+                    self.config.ui.color_scheme = name.clone();
+                    if let Err(error) = self.config.save() {
+                        self.status = error.into();
+                    }
+                },
+                Err(error) => self.status = error.into(),
+            }
+        }
+        // the scale is only applied once the slider is let go of. rescaling everything while the slider's being
+        // dragged would move the slider out from under the mouse cursor
+        let ui_scale = self.ui_scale_slider.value() / 100.0;
+        if ui_scale != self.config.ui.scale && !input.mouse_button_is_down(MouseButton::Left) {
+            self.config.ui.scale = ui_scale;
+            if let Err(error) = self.config.save() {
+                self.status = error.into();
+            }
+        }
+    }
+
     fn process_status(&mut self, canvas: &mut Canvas) {
         if !matches!(self.status, Status::None) {
             self.ui.push_group((self.ui.width(), 24.0), Layout::Horizontal);
@@ -227,29 +868,85 @@ impl State {
         }
     }
 
-    fn validate_nickname(nickname: &str) -> Result<(), Status> {
+    fn validate_nickname(language: &Language, nickname: &str) -> Result<(), Status> {
+        const MAX_NICKNAME_LENGTH: usize = 16;
         if nickname.is_empty() {
-            return Err(Status::Error("Nickname must not be empty".into()))
+            return Err(Status::Error(language.tr("lobby.nickname_empty").into()))
         }
-        if nickname.len() > 16 {
-            return Err(Status::Error("The maximum length of a nickname is 16 characters".into()))
+        if nickname.len() > MAX_NICKNAME_LENGTH {
+            return Err(Status::Error(language.format("lobby.nickname_too_long", &[&MAX_NICKNAME_LENGTH])))
         }
         Ok(())
     }
 
-    fn host_room(nickname: &str, matchmaker_addr_str: &str) -> Result<Peer, Status> {
-        Self::validate_nickname(nickname)?;
-        Ok(Peer::host(nickname, matchmaker_addr_str)?)
+    // hosts a room with the saved canvas picked in the project dialog, once the dialog is closed
+    fn poll_project_dialog(&mut self) {
+        let result = match self.project_dialog.as_ref().and_then(|dialog| dialog.poll()) {
+            Some(result) => result,
+            None => return,
+        };
+        self.project_dialog = None;
+        match result {
+            Ok(Some(dir)) if !project::exists(&dir) => self.status = project::Error::InvalidCanvasFolder(dir).into(),
+            Ok(Some(dir)) => {
+                self.project = Some(dir);
+                self.host();
+            },
+            Ok(None) => (),
+            Err(error) => self.status = error.into(),
+        }
+    }
+
+    // dims the lobby while a file dialog is open, as nothing can be done until it's closed
+    fn process_dialog_overlay(&mut self, canvas: &mut Canvas) {
+        if self.project_dialog.is_none() { return }
+        let dim = Paint::new(Color4f::from(self.assets.colors.panel.with_a(192)), None);
+        self.ui.draw_on_canvas(canvas, |canvas| {
+            canvas.draw_paint(&dim);
+        });
+        // the lobby is padded evenly on all sides, so its middle is also the middle of the window
+        let text = self.assets.language.tr("lobby.waiting_for_dialog");
+        self.ui.text(canvas, text, self.assets.colors.text, (AlignH::Center, AlignV::Middle));
+    }
+
+    // empty passwords mean that the room isn't protected
+    fn password(password: &str) -> Option<&str> {
+        if password.is_empty() { None } else { Some(password) }
+    }
+
+    // empty names mean that the room isn't listed publicly
+    fn public_name(name: &str) -> Option<&str> {
+        if name.is_empty() { None } else { Some(name) }
     }
 
-    fn join_room(nickname: &str, matchmaker_addr_str: &str, room_id_str: &str) -> Result<Peer, Status> {
-        if !matches!(room_id_str.len(), 4..=6) {
-            return Err(Status::Error("Room ID must be a number with 4–6 digits".into()))
+    fn host_room(
+        language: &Language,
+        nickname: &str,
+        matchmaker_addr_str: &str,
+        password: &str,
+        name: &str,
+    ) -> Result<Peer, Status> {
+        Self::validate_nickname(language, nickname)?;
+        Ok(Peer::host(nickname, matchmaker_addr_str, Self::password(password), Self::public_name(name))?)
+    }
+
+    fn join_room(
+        language: &Language,
+        nickname: &str,
+        matchmaker_addr_str: &str,
+        room_id_str: &str,
+        password: &str,
+    ) -> Result<Peer, Status> {
+        let room_id = room_id_str.trim();
+        // the matchmaker decides on the exact format, this only catches obvious typos
+        if room_id.is_empty() || room_id.len() > MAX_ROOM_ID_LENGTH {
+            return Err(Status::Error(language.format("lobby.room_id_length", &[&MAX_ROOM_ID_LENGTH])))
         }
-        Self::validate_nickname(nickname)?;
-        let room_id: u32 = room_id_str.parse()
-            .map_err(|_| Status::Error("Room ID must be an integer".into()))?;
-        Ok(Peer::join(nickname, matchmaker_addr_str, room_id)?)
+        if !room_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(Status::Error(language.tr("lobby.room_id_characters").into()))
+        }
+        Self::validate_nickname(language, nickname)?;
+        Ok(Peer::join(nickname, matchmaker_addr_str, room_id, Self::password(password))?)
     }
 
 }
@@ -264,14 +961,53 @@ impl AppState for State {
             input,
         }: StateArgs,
     ) {
+        if let Err(error) = self.assets.reload_color_scheme() {
+            self.status = error.into();
+        }
         canvas.clear(self.assets.colors.panel);
 
+        if let Some(room_list) = &self.room_list {
+            match room_list.poll() {
+                Ok(Some(rooms)) => {
+                    self.public_rooms = Some(rooms);
+                    self.room_list = None;
+                },
+                Ok(None) => (),
+                Err(error) => {
+                    self.status = error.into();
+                    self.public_rooms = Some(Vec::new());
+                    self.room_list = None;
+                },
+            }
+        }
+
+        self.poll_project_dialog();
+
+        if let Some(result) = self.update_check.as_ref().and_then(|check| check.poll()) {
+            match result {
+                Ok(release) => {
+                    if let Some(release) = &release {
+                        let text = self.assets.language.format("lobby.update_available", &[&release.version]);
+                        self.toasts.push(Severity::Info, text);
+                    }
+                    self.update = release;
+                },
+                // not being able to check for updates isn't worth bothering anyone with
+                Err(error) => log::warn!("{}", error),
+            }
+            self.update_check = None;
+        }
+
         if let Some(peer) = &mut self.peer {
             match peer.tick() {
                 Ok(messages) => for message in messages {
                     match message {
                         Message::Error(error) => self.status = Status::Error(error.into()),
-                        Message::Connected => self.connected = true,
+                        Message::Connected => {
+                            self.connected = true;
+                            // whatever comes after is meant for the room, and is left for it to receive
+                            break
+                        },
                         _ => (),
                     }
                 },
@@ -279,30 +1015,81 @@ impl AppState for State {
                     self.status = error.into();
                 },
             }
+            // only rooms that could actually be joined are worth remembering. hosted rooms are gone once they're
+            // left, so there's no point in remembering them either
+            if let (true, false, Some(room_id)) = (self.connected, peer.is_host(), peer.room_id()) {
+                self.config.lobby.remember_room(peer.matchmaker_addr(), room_id);
+                if let Err(error) = self.config.save() {
+                    log::error!("could not save the recent rooms: {}", error);
+                }
+            }
+        }
+
+        // keep polling while waiting for the matchmaker, a file dialog, or the update check. changes to the color
+        // scheme are picked up even when idle, just not as quickly
+        if self.peer.is_some()
+            || self.room_list.is_some()
+            || self.project_dialog.is_some()
+            || self.update_check.is_some()
+        {
+            input.request_redraw_after(Self::POLL_INTERVAL);
+        } else {
+            input.request_redraw_after(1.0);
         }
 
-        self.ui.begin(get_window_size(&coordinate_system_helper), Layout::Freeform);
+        self.ui.begin(get_window_size(&coordinate_system_helper, input.ui_scale()), Layout::Freeform);
+        self.ui.process_focus(input);
         self.ui.set_font(self.assets.sans.clone());
         self.ui.set_font_size(14.0);
 
         self.ui.pad((64.0, 64.0));
 
+        // nothing can be clicked or focused while a file dialog, the changelog, or the about screen is open
+        if self.project_dialog.is_some() || self.changelog_open || self.about_open {
+            input.lock_mouse_buttons();
+            self.ui.lock_focus();
+        }
+
         self.ui.push_group((self.ui.width(), 384.0), Layout::Vertical);
         self.ui.align((AlignH::Left, AlignV::Middle));
         self.process_header(canvas);
         self.ui.space(24.0);
         self.process_menu(canvas, input);
         self.ui.space(24.0);
+        self.process_recovery(canvas, input);
+        self.process_update_prompt(canvas, input);
         self.process_status(canvas);
         self.ui.pop_group();
+        // the settings are processed after the menu, so that they come last when tabbing through the lobby
+        self.process_settings(canvas, input);
+
+        input.unlock_mouse_buttons();
+        self.ui.unlock_focus();
+        self.process_dialog_overlay(canvas);
+        self.process_changelog(canvas, input);
+        self.process_about(canvas, input);
+        self.toasts.process(&mut self.ui, canvas, input, ToastArgs {
+            fill: self.assets.colors.panel,
+            outline: self.assets.colors.separator,
+            text: self.assets.colors.text,
+            error: self.assets.colors.error,
+        });
     }
 
     fn next_state(self: Box<Self>) -> Box<dyn AppState> {
         if self.connected {
-            Box::new(paint::State::new(self.assets, self.peer.unwrap()))
+            let mut state = paint::State::new(self.assets, self.config, self.peer.unwrap());
+            if let Some(dir) = &self.project {
+                state.load_project(dir);
+            }
+            Box::new(state)
         } else {
             self
         }
     }
 
+    fn ui_scale(&self) -> f32 {
+        self.config.ui.scale()
+    }
+
 }
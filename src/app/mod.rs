@@ -3,4 +3,5 @@ pub use state::*;
 
 pub mod lobby;
 pub mod paint;
+pub mod replay;
 
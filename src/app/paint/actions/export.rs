@@ -0,0 +1,202 @@
+// the export action. saves the selected region (or the whole canvas, if nothing is selected) to a PNG file, optionally
+// scaled up or down.
+
+use std::path::PathBuf;
+
+use image::ImageFormat;
+use image::imageops::{self, FilterType};
+use skulpin::skia_safe::*;
+use thiserror::Error;
+
+use crate::assets::Assets;
+use crate::file_dialog::{DialogResult, PendingDialog};
+use crate::paint_canvas::PaintCanvas;
+use crate::ui::*;
+use super::{Action, ActionArgs};
+
+#[derive(Debug, Error)]
+enum Error {
+    #[error("There's nothing to export, the canvas is empty")]
+    NothingToExport,
+    #[error("Could not open the file dialog: {0}")]
+    Dialog(#[from] native_dialog::Error),
+    #[error("Could not save the image: {0}")]
+    Image(#[from] image::ImageError),
+}
+
+pub struct ExportAction {
+    panel_open: bool,
+    scale_slider: Slider,
+    // the dialog asking where to save the image, along with the region and scale to export, which are decided when the
+    // export button is clicked
+    save_dialog: Option<(PendingDialog, IRect, f32)>,
+}
+
+impl ExportAction {
+
+    pub fn new() -> Self {
+        Self {
+            panel_open: false,
+            scale_slider: Slider::new(1.0, 0.25, 8.0, SliderStep::Discrete(0.25)),
+            save_dialog: None,
+        }
+    }
+
+    fn scaled_size(rect: IRect, scale: f32) -> (u32, u32) {
+        let scale = |x: i32| f32::max((x as f32 * scale).round(), 1.0) as u32;
+        (scale(rect.width()), scale(rect.height()))
+    }
+
+    // saves the region to the file picked in the save dialog. returns the path the image was saved to, or None if
+    // the file dialog was cancelled
+    fn export(
+        paint_canvas: &mut PaintCanvas,
+        rect: IRect,
+        scale: f32,
+        picked: DialogResult,
+    ) -> Result<Option<PathBuf>, Error> {
+        let path = match picked? {
+            Some(path) if path.extension().is_none() => path.with_extension("png"),
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        let mut image = paint_canvas.capture(rect);
+        paint_canvas.background().fill_behind(&mut image);
+        if scale != 1.0 {
+            let (width, height) = Self::scaled_size(rect, scale);
+            // nearest neighbor keeps pixels crisp when scaling up
+            let filter = if scale > 1.0 { FilterType::Nearest } else { FilterType::Triangle };
+            image = imageops::resize(&image, width, height, filter);
+        }
+        image.save_with_format(&path, ImageFormat::Png)?;
+        Ok(Some(path))
+    }
+
+}
+
+impl Action for ExportAction {
+
+    fn name(&self) -> &'static str {
+        "export"
+    }
+
+    fn icon<'a>(&self, assets: &'a Assets) -> &'a Image {
+        &assets.icons.actions.export
+    }
+
+    fn perform(&mut self, _args: ActionArgs) {
+        self.panel_open = !self.panel_open;
+    }
+
+    fn process_panel(
+        &mut self,
+        ActionArgs { ui, input, assets, paint_canvas, selection, log, .. }: ActionArgs,
+        canvas: &mut Canvas,
+    ) {
+        let lang = &assets.language;
+        if let Some(picked) = self.save_dialog.as_ref().and_then(|(dialog, ..)| dialog.poll()) {
+            let (_, rect, scale) = self.save_dialog.take().unwrap();
+            match Self::export(paint_canvas, rect, scale, picked) {
+                Ok(Some(path)) => notify_tr!(log, lang, Success, "notifications.exported", path.display()),
+                Ok(None) => (),
+                Err(error) => notify!(log, Error, "{}", error),
+            }
+        }
+
+        if !self.panel_open { return }
+
+        let region = selection.or_else(|| paint_canvas.bounds());
+        let scale = self.scale_slider.value();
+        let row_height = 24.0;
+
+        ui.push_group(ui.size(), Layout::Freeform);
+        ui.push_group((320.0, 140.0), Layout::Vertical);
+        ui.align((AlignH::Center, AlignV::Middle));
+        ui.fill(canvas, assets.colors.panel);
+        ui.outline(canvas, assets.colors.separator, 1.0);
+        ui.pad((16.0, 16.0));
+
+        let title = lang.tr(if selection.is_some() { "export.export_selection" } else { "export.export_canvas" });
+        ui.push_group((ui.width(), 16.0), Layout::Freeform);
+        ui.set_font(assets.sans_bold.clone());
+        ui.text(canvas, title, assets.colors.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+        ui.space(8.0);
+
+        // scale
+
+        ui.push_group((ui.width(), row_height), Layout::Horizontal);
+        ui.push_group((48.0, ui.height()), Layout::Freeform);
+        ui.text(canvas, lang.tr("export.scale"), assets.colors.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+        self.scale_slider.process(ui, canvas, input, SliderArgs {
+            width: 192.0,
+            color: assets.colors.slider,
+        });
+        ui.space(8.0);
+        let scale_string = format!("{}×", scale);
+        ui.push_group((ui.remaining_width(), ui.height()), Layout::Freeform);
+        ui.set_font(assets.sans_bold.clone());
+        ui.text(canvas, &scale_string, assets.colors.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+        ui.pop_group();
+        ui.space(8.0);
+
+        // resulting image size
+
+        let size_string = match region {
+            Some(rect) => {
+                let (width, height) = Self::scaled_size(rect, scale);
+                lang.format("export.image_size", &[&width, &height])
+            },
+            None => lang.tr("export.empty_canvas").into(),
+        };
+        ui.push_group((ui.width(), 16.0), Layout::Freeform);
+        ui.text(canvas, &size_string, assets.colors.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+        ui.space(12.0);
+
+        // buttons
+
+        let button = ButtonArgs {
+            height: row_height,
+            colors: &assets.colors.button,
+        };
+        let mut export = false;
+        ui.push_group((ui.width(), row_height), Layout::Horizontal);
+        if Button::with_text(ui, canvas, input, button, lang.tr("export.export")).clicked() {
+            export = true;
+        }
+        ui.space(8.0);
+        if Button::with_text(ui, canvas, input, button, lang.tr("export.cancel")).clicked() {
+            self.panel_open = false;
+        }
+        ui.pop_group();
+
+        ui.pop_group();
+        ui.pop_group();
+
+        // only one save dialog is shown at a time
+        if export && self.save_dialog.is_none() {
+            self.panel_open = false;
+            match region {
+                Some(rect) => {
+                    let dialog = PendingDialog::spawn(|dialog| {
+                        dialog
+                            .set_filename("canvas.png")
+                            .add_filter("PNG image", &["png"])
+                            .show_save_single_file()
+                    });
+                    self.save_dialog = Some((dialog, rect, scale));
+                },
+                None => notify!(log, Error, "{}", Error::NothingToExport),
+            }
+        }
+    }
+
+    fn panel_open(&self) -> bool {
+        self.panel_open
+    }
+
+}
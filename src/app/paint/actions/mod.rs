@@ -0,0 +1,54 @@
+// actions.
+// unlike tools, actions don't take over the paint canvas. they're one-off operations that are performed by clicking
+// their icon in the toolbar, and may show a panel with some options on top of the canvas before doing their thing.
+
+mod export;
+mod print;
+mod save;
+mod screenshot;
+mod statistics;
+
+pub use export::*;
+pub use print::*;
+pub use save::*;
+pub use screenshot::*;
+pub use statistics::*;
+
+use skulpin::skia_safe::*;
+
+use crate::assets::Assets;
+use crate::paint_canvas::PaintCanvas;
+use crate::ui::*;
+use super::Log;
+
+pub struct ActionArgs<'a> {
+    pub ui: &'a mut Ui,
+    pub input: &'a Input,
+    pub assets: &'a Assets,
+    pub paint_canvas: &'a mut PaintCanvas<'static>,
+    // the region selected with the selection tool
+    pub selection: Option<IRect>,
+    // the area of the canvas that's visible in the window
+    pub viewport: Rect,
+    pub log: &'a mut Log,
+}
+
+pub trait Action {
+    // the name used for looking up the action's tooltip in the language file
+    fn name(&self) -> &'static str;
+
+    // the icon shown in the toolbar
+    fn icon<'a>(&self, assets: &'a Assets) -> &'a Image;
+
+    // called when the action's icon is clicked
+    fn perform(&mut self, args: ActionArgs);
+
+    // draws the action's panel on top of the paint canvas, if it has one open. called every frame
+    fn process_panel(&mut self, _args: ActionArgs, _canvas: &mut Canvas) {}
+
+    // whether the action's panel is open. the current tool doesn't receive input on the paint canvas while this is
+    // true
+    fn panel_open(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,459 @@
+// the print export action. exports the selection or the whole canvas as PDF pages or an SVG of raster tiles
+
+use std::fmt::Write as _;
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use image::{ColorType, ImageError, RgbaImage};
+use image::codecs::png::PngEncoder;
+use skulpin::skia_safe::*;
+use thiserror::Error;
+
+use crate::assets::Assets;
+use crate::file_dialog::{DialogResult, PendingDialog};
+use crate::paint_canvas::{Background, PaintCanvas};
+use crate::ui::*;
+use super::{Action, ActionArgs};
+
+#[derive(Debug, Error)]
+enum Error {
+    #[error("There's nothing to export, the canvas is empty")]
+    NothingToExport,
+    #[error("Could not open the file dialog: {0}")]
+    Dialog(#[from] native_dialog::Error),
+    #[error("Could not encode the image: {0}")]
+    Image(#[from] ImageError),
+    #[error("Could not save the document: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Pdf,
+    Svg,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PageSize {
+    A4,
+    A3,
+    Letter,
+}
+
+const POINTS_PER_INCH: f32 = 72.0;
+const MILLIMETERS_PER_INCH: f32 = 25.4;
+// the blank space left around the edges of each page, in points. most printers can't print right up to the edge
+const MARGIN: f32 = 36.0;
+
+impl Format {
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Pdf => "pdf",
+            Self::Svg => "svg",
+        }
+    }
+
+}
+
+impl PageSize {
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::A4 => "A4",
+            Self::A3 => "A3",
+            Self::Letter => "Letter",
+        }
+    }
+
+    // the width and height of the page in points, in portrait orientation
+    fn size(self) -> (f32, f32) {
+        match self {
+            Self::A4 => (595.28, 841.89),
+            Self::A3 => (841.89, 1190.55),
+            Self::Letter => (612.0, 792.0),
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Self::A4 => Self::A3,
+            Self::A3 => Self::Letter,
+            Self::Letter => Self::A4,
+        }
+    }
+
+}
+
+// a PDF document that's written out object by object
+struct Pdf {
+    data: Vec<u8>,
+    // the byte offsets of the objects, for the cross-reference table
+    offsets: Vec<usize>,
+}
+
+impl Pdf {
+
+    fn new() -> Self {
+        Self {
+            data: b"%PDF-1.4\n".to_vec(),
+            offsets: Vec::new(),
+        }
+    }
+
+    // writes the next object. objects are numbered from 1, in the order they're written
+    fn object(&mut self, contents: &[u8]) {
+        self.offsets.push(self.data.len());
+        self.data.extend_from_slice(format!("{} 0 obj\n", self.offsets.len()).as_bytes());
+        self.data.extend_from_slice(contents);
+        self.data.extend_from_slice(b"\nendobj\n");
+    }
+
+    fn stream(&mut self, dictionary: &str, stream: &[u8]) {
+        let mut contents = format!("<< {} /Length {} >>\nstream\n", dictionary, stream.len()).into_bytes();
+        contents.extend_from_slice(stream);
+        contents.extend_from_slice(b"\nendstream");
+        self.object(&contents);
+    }
+
+    // writes the cross-reference table and the trailer. the first object has to be the document catalog
+    fn finish(mut self) -> Vec<u8> {
+        let xref = self.data.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            let _ = writeln!(table, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(table, "trailer\n<< /Size {} /Root 1 0 R >>\n", self.offsets.len() + 1);
+        let _ = write!(table, "startxref\n{}\n%%EOF\n", xref);
+        self.data.extend_from_slice(table.as_bytes());
+        self.data
+    }
+
+}
+
+fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, ImageError> {
+    let mut bytes = Vec::new();
+    PngEncoder::new(Cursor::new(&mut bytes)).encode(image, image.width(), image.height(), ColorType::Rgba8)?;
+    Ok(bytes)
+}
+
+// the part of the rectangle that's also inside the other one, if any
+fn intersection(a: IRect, b: IRect) -> Option<IRect> {
+    let rect = IRect::new(a.left.max(b.left), a.top.max(b.top), a.right.min(b.right), a.bottom.min(b.bottom));
+    if rect.left < rect.right && rect.top < rect.bottom { Some(rect) } else { None }
+}
+
+// what gets exported once the save dialog is closed. this is decided when the export button is clicked
+#[derive(Clone, Copy)]
+struct Export {
+    region: IRect,
+    format: Format,
+    page_size: PageSize,
+    dpi: f32,
+}
+
+pub struct PrintAction {
+    panel_open: bool,
+    format: Format,
+    page_size: PageSize,
+    dpi_slider: Slider,
+    save_dialog: Option<(PendingDialog, Export)>,
+}
+
+impl PrintAction {
+
+    pub fn new() -> Self {
+        Self {
+            panel_open: false,
+            format: Format::Pdf,
+            page_size: PageSize::A4,
+            dpi_slider: Slider::new(150.0, 72.0, 600.0, SliderStep::Discrete(1.0)),
+            save_dialog: None,
+        }
+    }
+
+    // splits the region into the parts printed on each page, row by row
+    fn pages(region: IRect, page_size: PageSize, dpi: f32) -> Vec<IRect> {
+        let (width, height) = page_size.size();
+        let to_pixels = |points: f32| i32::max(((points - 2.0 * MARGIN) / POINTS_PER_INCH * dpi).floor() as i32, 1);
+        let (page_width, page_height) = (to_pixels(width), to_pixels(height));
+        let mut pages = Vec::new();
+        for top in (region.top..region.bottom).step_by(page_height as usize) {
+            for left in (region.left..region.right).step_by(page_width as usize) {
+                let right = i32::min(left + page_width, region.right);
+                let bottom = i32::min(top + page_height, region.bottom);
+                pages.push(IRect::new(left, top, right, bottom));
+            }
+        }
+        pages
+    }
+
+    fn pdf(paint_canvas: &mut PaintCanvas, region: IRect, page_size: PageSize, dpi: f32) -> Result<Vec<u8>, Error> {
+        let pages = Self::pages(region, page_size, dpi);
+        let (page_width, page_height) = page_size.size();
+        let mut pdf = Pdf::new();
+        // every page takes up three objects - the page itself, its contents, and its image - after the catalog and
+        // the page tree
+        let page_object = |index: usize| 3 + index * 3;
+        pdf.object(b"<< /Type /Catalog /Pages 2 0 R >>");
+        let kids: Vec<_> = (0..pages.len()).map(|index| format!("{} 0 R", page_object(index))).collect();
+        pdf.object(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()).as_bytes());
+
+        for (index, &page) in pages.iter().enumerate() {
+            let object = page_object(index);
+            pdf.object(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /XObject << /Tile {} 0 R >> >> \
+                 /Contents {} 0 R >>",
+                page_width, page_height, object + 2, object + 1,
+            ).as_bytes());
+
+            // the image goes into the top left corner of the page, inside the margins
+            let width = page.width() as f32 / dpi * POINTS_PER_INCH;
+            let height = page.height() as f32 / dpi * POINTS_PER_INCH;
+            let y = page_height - MARGIN - height;
+            let contents = format!("q {} 0 0 {} {} {} cm /Tile Do Q", width, height, MARGIN, y);
+            pdf.stream("", contents.as_bytes());
+
+            let mut image = paint_canvas.capture(page);
+            paint_canvas.background().fill_behind(&mut image);
+            // PDF images don't have an alpha channel, so anything transparent ends up on white paper
+            let mut rgb = Vec::with_capacity(image.len() / 4 * 3);
+            for pixel in image.pixels() {
+                let [r, g, b, a] = pixel.0;
+                let over_white = |c: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+                rgb.extend_from_slice(&[over_white(r), over_white(g), over_white(b)]);
+            }
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&rgb)?;
+            let compressed = encoder.finish()?;
+            pdf.stream(&format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8 \
+                 /Filter /FlateDecode",
+                image.width(), image.height(),
+            ), &compressed);
+        }
+        Ok(pdf.finish())
+    }
+
+    fn svg(paint_canvas: &mut PaintCanvas, region: IRect, dpi: f32) -> Result<String, Error> {
+        let (width, height) = (region.width(), region.height());
+        let mut svg = format!(
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" "#,
+                r#"width="{}in" height="{}in" viewBox="{} {} {} {}">"#,
+                "\n",
+            ),
+            width as f32 / dpi, height as f32 / dpi, region.left, region.top, width, height,
+        );
+        // chunks that don't exist aren't embedded, so the background is drawn once below all of them
+        if let Background::Solid(color) = paint_canvas.background() {
+            let _ = writeln!(
+                svg,
+                r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#{:02x}{:02x}{:02x}"/>"##,
+                region.left, region.top, width, height, color.r(), color.g(), color.b(),
+            );
+        }
+        let mut chunks: Vec<_> = paint_canvas.chunk_positions().collect();
+        chunks.sort_unstable_by_key(|&(x, y)| (y, x));
+        for chunk in chunks {
+            let tile = match intersection(PaintCanvas::chunk_rect(chunk), region) {
+                Some(tile) => tile,
+                None => continue,
+            };
+            let png = encode_png(&paint_canvas.capture(tile))?;
+            let _ = writeln!(
+                svg,
+                concat!(
+                    r#"<image x="{}" y="{}" width="{}" height="{}" style="image-rendering: pixelated" "#,
+                    r#"xlink:href="data:image/png;base64,{}"/>"#,
+                ),
+                tile.left, tile.top, tile.width(), tile.height(), base64::encode(&png),
+            );
+        }
+        svg.push_str("</svg>\n");
+        Ok(svg)
+    }
+
+    // asks for a file name to export the region to
+    fn show_save_dialog(export: Export) -> PendingDialog {
+        PendingDialog::spawn(move |dialog| {
+            let extension = export.format.extension();
+            let (filter, default_name) = match export.format {
+                Format::Pdf => ("PDF document", "canvas.pdf"),
+                Format::Svg => ("SVG document", "canvas.svg"),
+            };
+            dialog.set_filename(default_name).add_filter(filter, &[extension]).show_save_single_file()
+        })
+    }
+
+    // exports the region to the file picked in the save dialog. returns the path the document was saved to, or None
+    // if the file dialog was cancelled
+    fn export(paint_canvas: &mut PaintCanvas, export: Export, picked: DialogResult) -> Result<Option<PathBuf>, Error> {
+        let extension = export.format.extension();
+        let path = match picked? {
+            Some(path) if path.extension().is_none() => path.with_extension(extension),
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        let document = match export.format {
+            Format::Pdf => Self::pdf(paint_canvas, export.region, export.page_size, export.dpi)?,
+            Format::Svg => Self::svg(paint_canvas, export.region, export.dpi)?.into_bytes(),
+        };
+        std::fs::write(&path, document)?;
+        Ok(Some(path))
+    }
+
+}
+
+impl Action for PrintAction {
+
+    fn name(&self) -> &'static str {
+        "print"
+    }
+
+    fn icon<'a>(&self, assets: &'a Assets) -> &'a Image {
+        &assets.icons.actions.print
+    }
+
+    fn perform(&mut self, _args: ActionArgs) {
+        self.panel_open = !self.panel_open;
+    }
+
+    fn process_panel(
+        &mut self,
+        ActionArgs { ui, input, assets, paint_canvas, selection, log, .. }: ActionArgs,
+        canvas: &mut Canvas,
+    ) {
+        let lang = &assets.language;
+        if let Some(picked) = self.save_dialog.as_ref().and_then(|(dialog, _)| dialog.poll()) {
+            let (_, export) = self.save_dialog.take().unwrap();
+            match Self::export(paint_canvas, export, picked) {
+                Ok(Some(path)) => notify_tr!(log, lang, Success, "notifications.exported", path.display()),
+                Ok(None) => (),
+                Err(error) => notify!(log, Error, "{}", error),
+            }
+        }
+
+        if !self.panel_open { return }
+
+        let region = selection.or_else(|| paint_canvas.bounds());
+        let dpi = self.dpi_slider.value();
+        let row_height = 24.0;
+        // the page size only matters for PDFs
+        let height = if self.format == Format::Pdf { 204.0 } else { 172.0 };
+
+        ui.push_group(ui.size(), Layout::Freeform);
+        ui.push_group((320.0, height), Layout::Vertical);
+        ui.align((AlignH::Center, AlignV::Middle));
+        ui.fill(canvas, assets.colors.panel);
+        ui.outline(canvas, assets.colors.separator, 1.0);
+        ui.pad((16.0, 16.0));
+
+        ui.push_group((ui.width(), 16.0), Layout::Freeform);
+        ui.set_font(assets.sans_bold.clone());
+        ui.text(canvas, lang.tr("print.title"), assets.colors.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+        ui.space(8.0);
+
+        let button = ButtonArgs {
+            height: row_height,
+            colors: &assets.colors.button,
+        };
+
+        // format and page size
+
+        let format_text = lang.tr(match self.format {
+            Format::Pdf => "print.format_pdf",
+            Format::Svg => "print.format_svg",
+        });
+        ui.push_group((ui.width(), row_height), Layout::Horizontal);
+        if Button::with_text(ui, canvas, input, button, format_text).clicked() {
+            self.format = match self.format {
+                Format::Pdf => Format::Svg,
+                Format::Svg => Format::Pdf,
+            };
+        }
+        ui.pop_group();
+        ui.space(8.0);
+
+        if self.format == Format::Pdf {
+            let page_size_text = lang.format("print.page_size", &[&self.page_size.name()]);
+            ui.push_group((ui.width(), row_height), Layout::Horizontal);
+            if Button::with_text(ui, canvas, input, button, &page_size_text).clicked() {
+                self.page_size = self.page_size.next();
+            }
+            ui.pop_group();
+            ui.space(8.0);
+        }
+
+        // DPI
+
+        ui.push_group((ui.width(), row_height), Layout::Horizontal);
+        ui.push_group((48.0, ui.height()), Layout::Freeform);
+        ui.text(canvas, lang.tr("print.dpi"), assets.colors.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+        self.dpi_slider.process(ui, canvas, input, SliderArgs {
+            width: 192.0,
+            color: assets.colors.slider,
+        });
+        ui.space(8.0);
+        let dpi_string = dpi.to_string();
+        ui.push_group((ui.remaining_width(), ui.height()), Layout::Freeform);
+        ui.set_font(assets.sans_bold.clone());
+        ui.text(canvas, &dpi_string, assets.colors.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+        ui.pop_group();
+        ui.space(8.0);
+
+        // what the document will be like
+
+        let size_string = match (region, self.format) {
+            (Some(rect), Format::Pdf) => lang.format("print.pages", &[&Self::pages(rect, self.page_size, dpi).len()]),
+            (Some(rect), Format::Svg) => {
+                let to_centimeters = |pixels: i32| (pixels as f32 / dpi * MILLIMETERS_PER_INCH).round() / 10.0;
+                lang.format("print.document_size", &[&to_centimeters(rect.width()), &to_centimeters(rect.height())])
+            },
+            (None, _) => lang.tr("export.empty_canvas").into(),
+        };
+        ui.push_group((ui.width(), 16.0), Layout::Freeform);
+        ui.text(canvas, &size_string, assets.colors.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+        ui.space(12.0);
+
+        // buttons
+
+        let mut export = false;
+        ui.push_group((ui.width(), row_height), Layout::Horizontal);
+        if Button::with_text(ui, canvas, input, button, lang.tr("print.export")).clicked() {
+            export = true;
+        }
+        ui.space(8.0);
+        if Button::with_text(ui, canvas, input, button, lang.tr("print.cancel")).clicked() {
+            self.panel_open = false;
+        }
+        ui.pop_group();
+
+        ui.pop_group();
+        ui.pop_group();
+
+        // only one save dialog is shown at a time
+        if export && self.save_dialog.is_none() {
+            self.panel_open = false;
+            match region {
+                Some(region) => {
+                    let export = Export { region, format: self.format, page_size: self.page_size, dpi };
+                    self.save_dialog = Some((Self::show_save_dialog(export), export));
+                },
+                None => notify!(log, Error, "{}", Error::NothingToExport),
+            }
+        }
+    }
+
+    fn panel_open(&self) -> bool {
+        self.panel_open
+    }
+
+}
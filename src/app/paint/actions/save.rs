@@ -0,0 +1,107 @@
+// the save action. saves the whole canvas as a project folder, which can later be opened from the lobby to host a
+// room with it.
+
+use std::path::PathBuf;
+
+use skulpin::skia_safe::*;
+use thiserror::Error;
+
+use crate::app::paint::Log;
+use crate::assets::Assets;
+use crate::file_dialog::{DialogResult, PendingDialog};
+use crate::i18n::Language;
+use crate::paint_canvas::PaintCanvas;
+use crate::project;
+use super::{Action, ActionArgs};
+
+#[derive(Debug, Error)]
+enum Error {
+    #[error("There's nothing to save, the canvas is empty")]
+    NothingToSave,
+    #[error("Could not open the file dialog: {0}")]
+    Dialog(#[from] native_dialog::Error),
+    #[error("Could not save the canvas: {0}")]
+    Project(#[from] project::Error),
+}
+
+pub struct SaveAction {
+    folder_dialog: Option<PendingDialog>,
+}
+
+impl SaveAction {
+
+    pub fn new() -> Self {
+        Self {
+            folder_dialog: None,
+        }
+    }
+
+    // asks for a folder to save the canvas into. returns None if there's nothing to save, in which case the user is
+    // told so instead
+    pub fn show_folder_dialog(paint_canvas: &PaintCanvas, log: &mut Log) -> Option<PendingDialog> {
+        if paint_canvas.bounds().is_none() {
+            notify!(log, Error, "{}", Error::NothingToSave);
+            return None
+        }
+        Some(PendingDialog::spawn(|dialog| dialog.show_open_single_dir()))
+    }
+
+    // saves the canvas into the folder picked in the folder dialog. returns the folder, or None if the dialog was
+    // cancelled
+    fn save(paint_canvas: &mut PaintCanvas, picked: DialogResult) -> Result<Option<PathBuf>, Error> {
+        let dir = match picked? {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+        project::save(paint_canvas, &dir)?;
+        paint_canvas.mark_saved();
+        Ok(Some(dir))
+    }
+
+    // saves the canvas and tells the user how it went. returns whether the canvas was saved
+    pub fn save_and_report(
+        paint_canvas: &mut PaintCanvas,
+        picked: DialogResult,
+        log: &mut Log,
+        language: &Language,
+    ) -> bool {
+        match Self::save(paint_canvas, picked) {
+            Ok(Some(dir)) => {
+                notify_tr!(log, language, Success, "notifications.canvas_saved", dir.display());
+                true
+            },
+            Ok(None) => false,
+            Err(error) => {
+                notify!(log, Error, "{}", error);
+                false
+            },
+        }
+    }
+
+}
+
+impl Action for SaveAction {
+
+    fn name(&self) -> &'static str {
+        "save"
+    }
+
+    fn icon<'a>(&self, assets: &'a Assets) -> &'a Image {
+        &assets.icons.actions.save
+    }
+
+    fn perform(&mut self, ActionArgs { paint_canvas, log, .. }: ActionArgs) {
+        if self.folder_dialog.is_none() {
+            self.folder_dialog = Self::show_folder_dialog(paint_canvas, log);
+        }
+    }
+
+    // the save action doesn't have a panel, but this is where the folder dialog is checked on
+    fn process_panel(&mut self, ActionArgs { assets, paint_canvas, log, .. }: ActionArgs, _canvas: &mut Canvas) {
+        if let Some(picked) = self.folder_dialog.as_ref().and_then(PendingDialog::poll) {
+            self.folder_dialog = None;
+            Self::save_and_report(paint_canvas, picked, log, &assets.language);
+        }
+    }
+
+}
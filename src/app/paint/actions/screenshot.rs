@@ -0,0 +1,167 @@
+// the screenshot action. captures the part of the canvas that's currently visible in the window, along with the
+// background, and copies it to the clipboard or saves it to a PNG file. handy for quickly sharing how a drawing is
+// coming along.
+// only the canvas itself is captured, without any cursors, reference images, or UI on top of it.
+
+use std::path::PathBuf;
+
+use image::{ImageFormat, RgbaImage};
+use skulpin::skia_safe::*;
+use thiserror::Error;
+
+use crate::assets::Assets;
+use crate::clipboard;
+use crate::file_dialog::{DialogResult, PendingDialog};
+use crate::paint_canvas::PaintCanvas;
+use crate::ui::*;
+use super::{Action, ActionArgs};
+
+#[derive(Debug, Error)]
+enum Error {
+    #[error("Could not open the file dialog: {0}")]
+    Dialog(#[from] native_dialog::Error),
+    #[error("Could not save the screenshot: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("Could not copy the screenshot: {0}")]
+    Clipboard(#[from] clipboard::Error),
+}
+
+pub struct ScreenshotAction {
+    panel_open: bool,
+    // the dialog asking where to save a screenshot, and the screenshot, which is taken when the save button's clicked
+    save_dialog: Option<(PendingDialog, RgbaImage)>,
+}
+
+impl ScreenshotAction {
+
+    pub fn new() -> Self {
+        Self {
+            panel_open: false,
+            save_dialog: None,
+        }
+    }
+
+    fn capture(paint_canvas: &mut PaintCanvas, viewport: IRect) -> RgbaImage {
+        let mut image = paint_canvas.capture(viewport);
+        paint_canvas.background().fill_behind(&mut image);
+        image
+    }
+
+    // saves the screenshot to the file picked in the save dialog. returns the path the screenshot was saved to, or
+    // None if the file dialog was cancelled
+    fn save(image: &RgbaImage, picked: DialogResult) -> Result<Option<PathBuf>, Error> {
+        let path = match picked? {
+            Some(path) if path.extension().is_none() => path.with_extension("png"),
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        image.save_with_format(&path, ImageFormat::Png)?;
+        Ok(Some(path))
+    }
+
+}
+
+impl Action for ScreenshotAction {
+
+    fn name(&self) -> &'static str {
+        "screenshot"
+    }
+
+    fn icon<'a>(&self, assets: &'a Assets) -> &'a Image {
+        &assets.icons.actions.screenshot
+    }
+
+    fn perform(&mut self, _args: ActionArgs) {
+        self.panel_open = !self.panel_open;
+    }
+
+    fn process_panel(
+        &mut self,
+        ActionArgs { ui, input, assets, paint_canvas, viewport, log, .. }: ActionArgs,
+        canvas: &mut Canvas,
+    ) {
+        let lang = &assets.language;
+        if let Some(picked) = self.save_dialog.as_ref().and_then(|(dialog, _)| dialog.poll()) {
+            let (_, image) = self.save_dialog.take().unwrap();
+            match Self::save(&image, picked) {
+                Ok(Some(path)) => notify_tr!(log, lang, Success, "notifications.screenshot_saved", path.display()),
+                Ok(None) => (),
+                Err(error) => notify!(log, Error, "{}", error),
+            }
+        }
+
+        if !self.panel_open { return }
+
+        let viewport = viewport.round();
+        let row_height = 24.0;
+
+        ui.push_group(ui.size(), Layout::Freeform);
+        ui.push_group((320.0, 104.0), Layout::Vertical);
+        ui.align((AlignH::Center, AlignV::Middle));
+        ui.fill(canvas, assets.colors.panel);
+        ui.outline(canvas, assets.colors.separator, 1.0);
+        ui.pad((16.0, 16.0));
+
+        ui.push_group((ui.width(), 16.0), Layout::Freeform);
+        ui.set_font(assets.sans_bold.clone());
+        ui.text(canvas, lang.tr("screenshot.title"), assets.colors.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+        ui.space(8.0);
+
+        let size_string = lang.format("screenshot.image_size", &[&viewport.width(), &viewport.height()]);
+        ui.push_group((ui.width(), 16.0), Layout::Freeform);
+        ui.text(canvas, &size_string, assets.colors.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+        ui.space(12.0);
+
+        // buttons
+
+        let button = ButtonArgs {
+            height: row_height,
+            colors: &assets.colors.button,
+        };
+        let (mut copy, mut save) = (false, false);
+        ui.push_group((ui.width(), row_height), Layout::Horizontal);
+        if Button::with_text(ui, canvas, input, button, lang.tr("screenshot.copy")).clicked() {
+            copy = true;
+        }
+        ui.space(8.0);
+        if Button::with_text(ui, canvas, input, button, lang.tr("screenshot.save")).clicked() {
+            save = true;
+        }
+        ui.space(8.0);
+        if Button::with_text(ui, canvas, input, button, lang.tr("screenshot.cancel")).clicked() {
+            self.panel_open = false;
+        }
+        ui.pop_group();
+
+        ui.pop_group();
+        ui.pop_group();
+
+        if copy {
+            self.panel_open = false;
+            let image = Self::capture(paint_canvas, viewport);
+            match clipboard::copy_image(&image) {
+                Ok(()) => notify_tr!(log, lang, Success, "notifications.screenshot_copied"),
+                Err(error) => notify!(log, Error, "{}", Error::from(error)),
+            }
+        }
+        // only one save dialog is shown at a time
+        if save && self.save_dialog.is_none() {
+            self.panel_open = false;
+            let image = Self::capture(paint_canvas, viewport);
+            let dialog = PendingDialog::spawn(|dialog| {
+                dialog
+                    .set_filename("screenshot.png")
+                    .add_filter("PNG image", &["png"])
+                    .show_save_single_file()
+            });
+            self.save_dialog = Some((dialog, image));
+        }
+    }
+
+    fn panel_open(&self) -> bool {
+        self.panel_open
+    }
+
+}
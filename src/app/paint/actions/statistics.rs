@@ -0,0 +1,147 @@
+// the statistics action. shows how big the canvas is and how much memory it takes up, which helps with figuring out
+// why a big canvas is slow.
+// the numbers are collected when the panel is opened rather than every frame, as that goes through all pixels of the
+// canvas.
+
+use std::time::Instant;
+
+use skulpin::skia_safe::*;
+
+use crate::assets::Assets;
+use crate::paint_canvas::Statistics;
+use crate::ui::*;
+use super::{Action, ActionArgs};
+
+pub struct StatisticsAction {
+    // the statistics shown in the panel. None while the panel is closed
+    statistics: Option<Statistics>,
+}
+
+fn format_size(bytes: usize) -> String {
+    let bytes = bytes as f32;
+    if bytes < 1024.0 {
+        format!("{} B", bytes)
+    } else if bytes < 1024.0 * 1024.0 {
+        format!("{:.1} KiB", bytes / 1024.0)
+    } else {
+        format!("{:.1} MiB", bytes / 1024.0 / 1024.0)
+    }
+}
+
+impl StatisticsAction {
+
+    const LINE_HEIGHT: f32 = 16.0;
+
+    pub fn new() -> Self {
+        Self {
+            statistics: None,
+        }
+    }
+
+    fn lines(statistics: &Statistics, assets: &Assets) -> Vec<String> {
+        let lang = &assets.language;
+        let non_empty = statistics.chunk_count - statistics.empty_chunk_count;
+        let extents = match statistics.bounds {
+            Some(rect) => lang.format(
+                "statistics.extents",
+                &[&rect.width(), &rect.height(), &rect.left, &rect.top, &rect.right, &rect.bottom],
+            ),
+            None => lang.tr("statistics.empty_canvas").into(),
+        };
+        let last_saved = match statistics.last_saved {
+            Some(time) => lang.format("statistics.last_saved", &[&((Instant::now() - time).as_secs() / 60)]),
+            None => lang.tr("statistics.never_saved").into(),
+        };
+        vec![
+            lang.format("statistics.chunks", &[&non_empty, &statistics.empty_chunk_count]),
+            lang.format("statistics.evicted_chunks", &[&statistics.evicted_chunk_count]),
+            extents,
+            lang.format("statistics.memory", &[&format_size(statistics.pixel_bytes)]),
+            lang.format("statistics.png_data", &[&format_size(statistics.png_bytes)]),
+            last_saved,
+        ]
+    }
+
+}
+
+impl Action for StatisticsAction {
+
+    fn name(&self) -> &'static str {
+        "statistics"
+    }
+
+    fn icon<'a>(&self, assets: &'a Assets) -> &'a Image {
+        &assets.icons.actions.statistics
+    }
+
+    fn perform(&mut self, ActionArgs { paint_canvas, .. }: ActionArgs) {
+        self.statistics = match self.statistics {
+            Some(_) => None,
+            None => Some(paint_canvas.statistics()),
+        };
+    }
+
+    fn process_panel(&mut self, ActionArgs { ui, input, assets, paint_canvas, .. }: ActionArgs, canvas: &mut Canvas) {
+        let statistics = match &self.statistics {
+            Some(statistics) => statistics,
+            None => return,
+        };
+
+        let lines = Self::lines(statistics, assets);
+        let row_height = 24.0;
+        let height = 16.0 + 16.0 + 8.0 + lines.len() as f32 * Self::LINE_HEIGHT + 12.0 + row_height + 16.0;
+
+        ui.push_group(ui.size(), Layout::Freeform);
+        ui.push_group((384.0, height), Layout::Vertical);
+        ui.align((AlignH::Center, AlignV::Middle));
+        ui.fill(canvas, assets.colors.panel);
+        ui.outline(canvas, assets.colors.separator, 1.0);
+        ui.pad((16.0, 16.0));
+
+        let lang = &assets.language;
+        ui.push_group((ui.width(), 16.0), Layout::Freeform);
+        ui.set_font(assets.sans_bold.clone());
+        ui.text(canvas, lang.tr("statistics.title"), assets.colors.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+        ui.space(8.0);
+
+        for line in &lines {
+            ui.push_group((ui.width(), Self::LINE_HEIGHT), Layout::Freeform);
+            ui.text(canvas, line, assets.colors.text, (AlignH::Left, AlignV::Middle));
+            ui.pop_group();
+        }
+        ui.space(12.0);
+
+        // buttons
+
+        let button = ButtonArgs {
+            height: row_height,
+            colors: &assets.colors.button,
+        };
+        let (mut refresh, mut close) = (false, false);
+        ui.push_group((ui.width(), row_height), Layout::Horizontal);
+        if Button::with_text(ui, canvas, input, button, lang.tr("statistics.refresh")).clicked() {
+            refresh = true;
+        }
+        ui.space(8.0);
+        if Button::with_text(ui, canvas, input, button, lang.tr("statistics.close")).clicked() {
+            close = true;
+        }
+        ui.pop_group();
+
+        ui.pop_group();
+        ui.pop_group();
+
+        if refresh {
+            self.statistics = Some(paint_canvas.statistics());
+        }
+        if close {
+            self.statistics = None;
+        }
+    }
+
+    fn panel_open(&self) -> bool {
+        self.statistics.is_some()
+    }
+
+}
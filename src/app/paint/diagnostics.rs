@@ -0,0 +1,177 @@
+// the diagnostics overlay, toggled with F3. shows how much is being sent and received, the round trip time to the
+// matchmaker (which relays everything sent to the room), how many chunks are still waiting to be sent to people who
+// just joined, and a graph of recent frame times.
+// rates are counted over whole seconds, so they lag behind by up to a second.
+// when network conditions are being simulated (see net::socket::SimulatedConditions), they're shown too.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use skulpin::skia_safe::*;
+
+use crate::assets::Assets;
+use crate::net::{self, Peer};
+use crate::net::socket::TrafficTotals;
+use crate::ui::*;
+
+pub struct Diagnostics {
+    open: bool,
+    // the durations of the most recent frames in seconds, oldest first
+    frame_times: VecDeque<f32>,
+    last_frame: Option<Instant>,
+    // the traffic totals at the start of the current second, and when that second started
+    sample: Option<(TrafficTotals, Instant)>,
+    // the traffic during the last full second
+    rates: TrafficTotals,
+}
+
+fn format_rate(bytes: u64) -> String {
+    let bytes = bytes as f32;
+    if bytes < 1024.0 {
+        format!("{} B/s", bytes)
+    } else if bytes < 1024.0 * 1024.0 {
+        format!("{:.1} KiB/s", bytes / 1024.0)
+    } else {
+        format!("{:.1} MiB/s", bytes / 1024.0 / 1024.0)
+    }
+}
+
+impl Diagnostics {
+
+    const WIDTH: f32 = 256.0;
+    const LINE_HEIGHT: f32 = 16.0;
+    const GRAPH_HEIGHT: f32 = 48.0;
+    const FRAME_COUNT: usize = 128;
+    // the frame time at the top of the graph. longer frames are cut off
+    const GRAPH_MAX_FRAME_TIME: f32 = 0.1;
+    const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            frame_times: VecDeque::new(),
+            last_frame: None,
+            sample: None,
+            rates: TrafficTotals::default(),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        // the numbers collected before the overlay was closed are stale
+        self.frame_times.clear();
+        self.last_frame = None;
+        self.sample = None;
+        self.rates = TrafficTotals::default();
+    }
+
+    // collects the numbers shown in the overlay. called once per frame
+    pub fn tick(&mut self, peer: &mut Peer) -> Result<(), net::Error> {
+        if !self.open { return Ok(()) }
+
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            self.frame_times.push_back((now - last_frame).as_secs_f32());
+            if self.frame_times.len() > Self::FRAME_COUNT {
+                self.frame_times.pop_front();
+            }
+        }
+        self.last_frame = Some(now);
+
+        let totals = peer.traffic().unwrap_or_default();
+        match self.sample {
+            Some((sample, time)) if now - time >= Self::SAMPLE_INTERVAL => {
+                self.rates = TrafficTotals {
+                    bytes_sent: totals.bytes_sent - sample.bytes_sent,
+                    bytes_received: totals.bytes_received - sample.bytes_received,
+                    packets_sent: totals.packets_sent - sample.packets_sent,
+                    packets_received: totals.packets_received - sample.packets_received,
+                };
+                self.sample = Some((totals, now));
+                peer.ping()?;
+            },
+            Some(_) => (),
+            None => {
+                self.sample = Some((totals, now));
+                peer.ping()?;
+            },
+        }
+        Ok(())
+    }
+
+    fn line(ui: &mut Ui, canvas: &mut Canvas, assets: &Assets, text: &str) {
+        ui.push_group((ui.width(), Self::LINE_HEIGHT), Layout::Freeform);
+        ui.text(canvas, text, assets.colors.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+    }
+
+    fn frame_time_graph(&self, ui: &mut Ui, canvas: &mut Canvas, assets: &Assets) {
+        ui.push_group((ui.width(), Self::GRAPH_HEIGHT), Layout::Freeform);
+        ui.outline(canvas, assets.colors.separator, 1.0);
+        let (width, height) = ui.size();
+        ui.draw_on_canvas(canvas, |canvas| {
+            let paint = Paint::new(Color4f::from(assets.colors.text), None);
+            let bar_width = width / Self::FRAME_COUNT as f32;
+            // the newest frame is on the right
+            let start = Self::FRAME_COUNT - self.frame_times.len();
+            for (i, &frame_time) in self.frame_times.iter().enumerate() {
+                let bar_height = f32::min(frame_time / Self::GRAPH_MAX_FRAME_TIME, 1.0) * height;
+                let x = (start + i) as f32 * bar_width;
+                canvas.draw_rect(Rect::from_xywh(x, height - bar_height, bar_width, bar_height), &paint);
+            }
+        });
+        ui.pop_group();
+    }
+
+    pub fn process(&self, ui: &mut Ui, canvas: &mut Canvas, assets: &Assets, peer: &Peer, queued_chunks: usize) {
+        if !self.open { return }
+
+        let rates = self.rates;
+        let rtt = match peer.round_trip_time() {
+            Some(rtt) => format!("{} ms", rtt.as_millis()),
+            None => "–".into(),
+        };
+        let (average, longest) = if self.frame_times.is_empty() {
+            (0.0, 0.0)
+        } else {
+            let sum: f32 = self.frame_times.iter().sum();
+            (sum / self.frame_times.len() as f32, self.frame_times.iter().copied().fold(0.0, f32::max))
+        };
+        let mut lines = vec![
+            format!("Upload: {}, {} packets/s", format_rate(rates.bytes_sent), rates.packets_sent),
+            format!("Download: {}, {} packets/s", format_rate(rates.bytes_received), rates.packets_received),
+            format!("Relay round trip: {}", rtt),
+            format!("Chunks waiting to be sent: {}", queued_chunks),
+            format!("Chunks left to download: {}", peer.download().map_or(0, |d| d.expected() - d.received())),
+            format!("Frame time: {:.1} ms avg, {:.1} ms max", average * 1000.0, longest * 1000.0),
+        ];
+        // a reminder that the lag is self-inflicted
+        if let Some(conditions) = peer.simulated_conditions() {
+            lines.push(format!(
+                "Simulated: {}±{} ms, {:.0}% drop, {:.0}% reorder",
+                conditions.latency.as_millis(),
+                conditions.jitter.as_millis(),
+                conditions.drop_chance * 100.0,
+                conditions.reorder_chance * 100.0,
+            ));
+        }
+
+        let height = 16.0 + lines.len() as f32 * Self::LINE_HEIGHT + 8.0 + Self::GRAPH_HEIGHT + 16.0;
+        ui.push_group(ui.size(), Layout::Freeform);
+        ui.pad((16.0, 16.0));
+        ui.push_group((Self::WIDTH, height), Layout::Vertical);
+        ui.fill(canvas, assets.colors.panel);
+        ui.outline(canvas, assets.colors.separator, 1.0);
+        ui.pad((16.0, 16.0));
+
+        for line in &lines {
+            Self::line(ui, canvas, assets, line);
+        }
+        ui.space(8.0);
+        self.frame_time_graph(ui, canvas, assets);
+
+        ui.pop_group();
+        ui.pop_group();
+    }
+
+}
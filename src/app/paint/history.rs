@@ -0,0 +1,176 @@
+// undo/redo history.
+// each operation remembers what the chunks it touched looked like before it was performed. undoing an operation
+// swaps those pixels with the ones currently on the canvas, which turns it into an operation that can be redone.
+
+use std::collections::HashMap;
+
+use crate::paint_canvas::PaintCanvas;
+
+type ChunkPosition = (i32, i32);
+
+#[derive(Default)]
+struct Operation {
+    // None means the chunk didn't exist before the operation
+    chunks: HashMap<ChunkPosition, Option<Vec<u8>>>,
+}
+
+pub struct History {
+    undo_stack: Vec<Operation>,
+    redo_stack: Vec<Operation>,
+    current: Option<Operation>,
+}
+
+impl Operation {
+
+    // swaps the saved chunks with the canvas's chunks and returns all the chunks that changed, along with their
+    // pixels from before the swap
+    fn swap(&mut self, canvas: &mut PaintCanvas) -> Vec<(ChunkPosition, Option<Vec<u8>>)> {
+        let mut changed = Vec::new();
+        for (position, pixels) in &mut self.chunks {
+            let current_pixels = canvas.chunk_pixels(*position);
+            canvas.set_chunk_pixels(*position, pixels.as_deref());
+            *pixels = current_pixels.clone();
+            changed.push((*position, current_pixels));
+        }
+        changed
+    }
+
+}
+
+impl History {
+
+    // the maximum amount of operations that can be undone
+    const MAX_OPERATIONS: usize = 64;
+
+    pub fn new() -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            current: None,
+        }
+    }
+
+    // starts recording a new operation
+    pub fn begin(&mut self) {
+        self.current = Some(Operation::default());
+    }
+
+    // saves chunks that are about to be modified by the current operation.
+    // chunks that have already been saved by the current operation are left untouched
+    pub fn save_chunks(&mut self, canvas: &mut PaintCanvas, positions: impl Iterator<Item = ChunkPosition>) {
+        if let Some(operation) = &mut self.current {
+            for position in positions {
+                operation.chunks.entry(position).or_insert_with(|| canvas.chunk_pixels(position));
+            }
+        }
+    }
+
+    // finishes recording the current operation and pushes it onto the undo stack
+    pub fn end(&mut self) {
+        if let Some(operation) = self.current.take() {
+            if operation.chunks.is_empty() { return }
+            self.undo_stack.push(operation);
+            if self.undo_stack.len() > Self::MAX_OPERATIONS {
+                self.undo_stack.remove(0);
+            }
+            self.redo_stack.clear();
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.current.is_some()
+    }
+
+    // undoes the last operation. returns the chunks that were changed, along with their previous pixels
+    pub fn undo(&mut self, canvas: &mut PaintCanvas) -> Vec<(ChunkPosition, Option<Vec<u8>>)> {
+        match self.undo_stack.pop() {
+            Some(mut operation) => {
+                let changed = operation.swap(canvas);
+                self.redo_stack.push(operation);
+                changed
+            },
+            None => Vec::new(),
+        }
+    }
+
+    // redoes the last undone operation. returns the chunks that were changed, along with their previous pixels
+    pub fn redo(&mut self, canvas: &mut PaintCanvas) -> Vec<(ChunkPosition, Option<Vec<u8>>)> {
+        match self.redo_stack.pop() {
+            Some(mut operation) => {
+                let changed = operation.swap(canvas);
+                self.undo_stack.push(operation);
+                changed
+            },
+            None => Vec::new(),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{Rgba, RgbaImage};
+
+    const CHUNK: ChunkPosition = (0, 0);
+
+    // fills the chunk with a color as a single operation, and returns the chunk's pixels afterwards
+    fn paint(history: &mut History, canvas: &mut PaintCanvas, color: [u8; 4]) -> Option<Vec<u8>> {
+        let (width, height) = PaintCanvas::chunk_size();
+        history.begin();
+        history.save_chunks(canvas, std::iter::once(CHUNK));
+        canvas.set_chunk_image(CHUNK, &RgbaImage::from_pixel(width as u32, height as u32, Rgba(color)));
+        history.end();
+        canvas.chunk_pixels(CHUNK)
+    }
+
+    #[test]
+    fn undo_and_redo_go_through_operations_in_order() {
+        let (mut history, mut canvas) = (History::new(), PaintCanvas::new());
+        let red = paint(&mut history, &mut canvas, [255, 0, 0, 255]);
+        let green = paint(&mut history, &mut canvas, [0, 255, 0, 255]);
+        let blue = paint(&mut history, &mut canvas, [0, 0, 255, 255]);
+
+        history.undo(&mut canvas);
+        assert!(canvas.chunk_pixels(CHUNK) == green);
+        history.undo(&mut canvas);
+        assert!(canvas.chunk_pixels(CHUNK) == red);
+        history.redo(&mut canvas);
+        assert!(canvas.chunk_pixels(CHUNK) == green);
+        history.redo(&mut canvas);
+        assert!(canvas.chunk_pixels(CHUNK) == blue);
+        assert!(history.redo(&mut canvas).is_empty());
+        assert!(canvas.chunk_pixels(CHUNK) == blue);
+    }
+
+    #[test]
+    fn new_operations_cannot_be_redone_over() {
+        let (mut history, mut canvas) = (History::new(), PaintCanvas::new());
+        let red = paint(&mut history, &mut canvas, [255, 0, 0, 255]);
+        paint(&mut history, &mut canvas, [0, 255, 0, 255]);
+        history.undo(&mut canvas);
+        let blue = paint(&mut history, &mut canvas, [0, 0, 255, 255]);
+
+        assert!(history.redo(&mut canvas).is_empty());
+        assert!(canvas.chunk_pixels(CHUNK) == blue);
+        history.undo(&mut canvas);
+        assert!(canvas.chunk_pixels(CHUNK) == red);
+    }
+
+    #[test]
+    fn operations_undo_to_before_their_first_change() {
+        let (mut history, mut canvas) = (History::new(), PaintCanvas::new());
+        let red = paint(&mut history, &mut canvas, [255, 0, 0, 255]);
+        let (width, height) = PaintCanvas::chunk_size();
+        history.begin();
+        for &color in &[[0, 255, 0, 255], [0, 0, 255, 255]] {
+            history.save_chunks(&mut canvas, std::iter::once(CHUNK));
+            canvas.set_chunk_image(CHUNK, &RgbaImage::from_pixel(width as u32, height as u32, Rgba(color)));
+        }
+        history.end();
+
+        history.undo(&mut canvas);
+        assert!(canvas.chunk_pixels(CHUNK) == red);
+    }
+}
@@ -0,0 +1,152 @@
+// the minimap. shows a scaled down view of the whole canvas in the top right corner, along with the part of it that's
+// visible on screen and the parts visible to other people in the room. clicking or dragging on the minimap moves the
+// view to the point under the mouse.
+
+use skulpin::skia_safe::*;
+use skulpin::skia_safe::paint as skpaint;
+
+use crate::assets::Assets;
+use crate::net::Peer;
+use crate::paint_canvas::PaintCanvas;
+use crate::ui::*;
+
+pub struct Minimap {
+    // the area of the canvas shown in the minimap. this is Some while the minimap is being dragged on, and doesn't
+    // change in the meantime so that the map doesn't move around under the mouse
+    dragging: Option<Rect>,
+}
+
+impl Minimap {
+
+    const SIZE: (f32, f32) = (192.0, 144.0);
+    // the amount of space shown around the painted area, in canvas pixels
+    const MARGIN: f32 = 128.0;
+
+    pub fn new() -> Self {
+        Self {
+            dragging: None,
+        }
+    }
+
+    // whether the minimap is being dragged on. the current tool doesn't receive input while this is true
+    pub fn has_mouse(&self) -> bool {
+        self.dragging.is_some()
+    }
+
+    fn push_group(ui: &mut Ui) {
+        ui.push_group(ui.size(), Layout::Freeform);
+        ui.pad((16.0, 16.0));
+        ui.push_group(Self::SIZE, Layout::Freeform);
+        ui.align((AlignH::Right, AlignV::Top));
+    }
+
+    fn pop_group(ui: &mut Ui) {
+        ui.pop_group();
+        ui.pop_group();
+    }
+
+    // the area of the canvas the minimap shows: everything that's been painted, and everything people can see
+    fn area(&self, paint_canvas: &PaintCanvas, peer: &Peer, viewport: Rect) -> Rect {
+        if let Some(area) = self.dragging {
+            return area
+        }
+        let painted = paint_canvas.bounds().map(Rect::from_irect);
+        let viewports = peer.mates().values().filter_map(|mate| mate.viewport);
+        let area = painted
+            .into_iter()
+            .chain(viewports)
+            .fold(viewport, |area, rect| Rect::new(
+                f32::min(area.left, rect.left),
+                f32::min(area.top, rect.top),
+                f32::max(area.right, rect.right),
+                f32::max(area.bottom, rect.bottom),
+            ));
+        area.with_outset((Self::MARGIN, Self::MARGIN))
+    }
+
+    // returns the scale and offset that transform canvas coordinates into minimap coordinates
+    fn transform(area: Rect) -> (f32, Vector) {
+        let (width, height) = Self::SIZE;
+        let scale = f32::min(width / area.width(), height / area.height());
+        // the area is centered within the minimap
+        let offset = Vector::new(
+            (width - area.width() * scale) / 2.0 - area.left * scale,
+            (height - area.height() * scale) / 2.0 - area.top * scale,
+        );
+        (scale, offset)
+    }
+
+    // handles clicking and dragging on the minimap. returns the point on the canvas the view should be centered on,
+    // if it should move
+    pub fn process_input(
+        &mut self,
+        ui: &mut Ui,
+        input: &Input,
+        paint_canvas: &PaintCanvas,
+        peer: &Peer,
+        viewport: Rect,
+    ) -> Option<Point> {
+        Self::push_group(ui);
+        if ui.has_mouse(input) && input.mouse_button_just_pressed(MouseButton::Left) {
+            self.dragging = Some(self.area(paint_canvas, peer, viewport));
+        }
+        if !input.mouse_button_is_down(MouseButton::Left) {
+            self.dragging = None;
+        }
+        let target = self.dragging.map(|area| {
+            let (scale, offset) = Self::transform(area);
+            (ui.mouse_position(input) - offset) * (1.0 / scale)
+        });
+        Self::pop_group(ui);
+        target
+    }
+
+    pub fn process(
+        &self,
+        ui: &mut Ui,
+        canvas: &mut Canvas,
+        assets: &Assets,
+        paint_canvas: &PaintCanvas,
+        peer: &Peer,
+        viewport: Rect,
+    ) {
+        let area = self.area(paint_canvas, peer, viewport);
+        let (scale, offset) = Self::transform(area);
+        let to_minimap = |rect: Rect| Rect::new(
+            rect.left * scale + offset.x,
+            rect.top * scale + offset.y,
+            rect.right * scale + offset.x,
+            rect.bottom * scale + offset.y,
+        );
+
+        Self::push_group(ui);
+        ui.fill(canvas, assets.colors.panel2);
+        ui.draw_on_canvas(canvas, |canvas| {
+            let size = Self::SIZE;
+            canvas.save();
+            canvas.clip_rect(Rect::from_point_and_size((0.0, 0.0), size), ClipOp::Intersect, false);
+
+            canvas.save();
+            canvas.translate(offset);
+            canvas.scale((scale, scale));
+            paint_canvas.draw_to(canvas);
+            canvas.restore();
+
+            let mut paint = Paint::new(Color4f::from(assets.colors.text.with_a(96)), None);
+            paint.set_anti_alias(false);
+            paint.set_style(skpaint::Style::Stroke);
+            for mate in peer.mates().values() {
+                if let Some(mate_viewport) = mate.viewport {
+                    canvas.draw_rect(to_minimap(mate_viewport), &paint);
+                }
+            }
+            paint.set_color(assets.colors.text);
+            canvas.draw_rect(to_minimap(viewport), &paint);
+
+            canvas.restore();
+        });
+        ui.outline(canvas, assets.colors.separator, 1.0);
+        Self::pop_group(ui);
+    }
+
+}
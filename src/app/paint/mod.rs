@@ -0,0 +1,1730 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+
+use skulpin::skia_safe::*;
+use skulpin::skia_safe::paint as skpaint;
+
+use crate::app::*;
+use crate::assets::*;
+use crate::audio::{self, Cue};
+use crate::autosave::Autosave;
+use crate::clipboard;
+use crate::config::{ToolbarPosition, UserConfig};
+use crate::file_dialog::PendingDialog;
+use crate::font_fallback;
+use crate::paint_canvas::*;
+use crate::project;
+use crate::recording::{self, Recording};
+use crate::replay::Recorder;
+use crate::ui::*;
+use crate::util::*;
+use crate::net::{self, Activity, Mate, Message, Peer, Timer};
+use crate::net::encoder::ChunkEncoder;
+use crate::net::room_storage::RoomStorage;
+use crate::net::transfer::ChunkUploads;
+
+// shows a toast of the given severity
+macro_rules! notify {
+    ($log:expr, $severity:ident, $($arg:tt)*) => {
+        $log.push(crate::ui::Severity::$severity, format!($($arg)*))
+    };
+}
+
+// shows a toast with a translated message. the arguments replace the {}s in the translation, in order
+macro_rules! notify_tr {
+    ($log:expr, $language:expr, $severity:ident, $key:literal $(, $arg:expr)* $(,)?) => {
+        $log.push(crate::ui::Severity::$severity, $language.format($key, &[$(&$arg),*]))
+    };
+}
+
+macro_rules! log_tr {
+    ($log:expr, $language:expr, $key:literal $(, $arg:expr)* $(,)?) => {
+        notify_tr!($log, $language, Info, $key $(, $arg)*)
+    };
+}
+
+macro_rules! ok_or_log {
+    ($log:expr, $exp:expr) => {
+        match $exp {
+            Ok(x) => x,
+            Err(e) => notify!($log, Error, "{}", e),
+        }
+    };
+}
+
+mod actions;
+mod diagnostics;
+mod history;
+mod minimap;
+mod palette_window;
+mod qr_code;
+pub mod tools;
+pub mod viewport;
+
+use actions::*;
+use diagnostics::Diagnostics;
+use history::History;
+use minimap::Minimap;
+use palette_window::PaletteWindow;
+use qr_code::QrCodePanel;
+use tools::*;
+use viewport::Viewport;
+
+// everything worth telling the user about is shown as a toast
+type Log = Toasts;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum GridMode {
+    Hidden,
+    Visible,
+    // the grid is visible, and drawing snaps to it
+    Snap,
+}
+
+pub struct State {
+    assets: Assets,
+    config: UserConfig,
+
+    ui: Ui,
+    paint_canvas: PaintCanvas<'static>,
+    history: History,
+    peer: Peer,
+    update_timer: Timer,
+
+    tools: Vec<Box<dyn Tool>>,
+    current_tool: usize,
+    actions: Vec<Box<dyn Action>>,
+    selection: Option<IRect>,
+    // whether the mouse button was pressed down on the canvas and hasn't been released yet.
+    // other UI elements ignore the mouse while this is true
+    canvas_has_mouse: bool,
+    minimap: Minimap,
+    palette_window: PaletteWindow,
+    diagnostics: Diagnostics,
+    qr_code: QrCodePanel,
+    // the last viewport sent to other people in the room
+    sent_viewport: Option<Rect>,
+    // the last idle state sent to other people in the room
+    sent_idle: Option<bool>,
+    // the region of the canvas we locked, and when the lock expires
+    region_lock: Option<(IRect, Instant)>,
+    // the last lock sent to other people in the room
+    sent_lock: Option<Option<IRect>>,
+    // whether the mouse was pressed inside a region someone else locked. the tool doesn't get any input until the
+    // mouse button is released
+    blocked_by_lock: bool,
+
+    // the canvas revision up to which all of our changes were sent to the others
+    synced_revision: u64,
+    // the revision the changes sent this frame bring the others up to, if any were sent
+    sent_revision: Option<u64>,
+    // when the connection couldn't keep up and some of our changes were refused, the revision after which the others'
+    // canvases may be out of sync with ours. the chunks changed since then are sent again once the connection catches
+    // up
+    resync_since: Option<u64>,
+
+    canvas_data_queue: VecDeque<SocketAddr>,
+    uploads: ChunkUploads,
+    encoder: ChunkEncoder,
+    // sends the canvas to the matchmaker while the room is persistent
+    room_storage: RoomStorage,
+
+    // None if autosaving couldn't be started
+    autosave: Option<Autosave>,
+    recording: Option<Recording>,
+    // the dialog asking where to save a stopped recording's timelapse
+    timelapse_dialog: Option<(PendingDialog, Recording)>,
+    // receives the result of saving a timelapse, which is done on a separate thread
+    timelapse_saved: Option<Receiver<Result<PathBuf, recording::Error>>>,
+
+    error: Option<String>,
+    cancelled: bool,
+    log: Log,
+    people_list_open: bool,
+    // the volume of sound cues, in percent
+    volume_slider: Slider,
+    tooltip: Tooltip,
+    context_menu: ContextMenu,
+    // asks whether to save the canvas when the window is closed with unsaved changes
+    quit_dialog: Dialog,
+    // the folder dialog opened by choosing to save in the quit dialog
+    quit_save_dialog: Option<PendingDialog>,
+    // set once it's okay to close the window
+    quitting: bool,
+    // the point on the canvas the context menu was opened at
+    context_menu_point: Point,
+    // whether the toolbar is being dragged to another edge of the window
+    dragging_toolbar: bool,
+
+    panning: bool,
+    viewport: Viewport,
+    // the area of the canvas that was visible in the window on the last frame
+    visible_rect: Rect,
+    grid_mode: GridMode,
+    // the point on the canvas the viewport should jump to on the next frame
+    jump_to: Option<Point>,
+    // the person whose viewport is being followed. following stops as soon as the mouse is used on the canvas
+    following: Option<SocketAddr>,
+}
+
+impl State {
+
+    const BAR_SIZE: f32 = 32.0;
+    const TOOLBAR_SIZE: f32 = 40.0;
+    const TIME_PER_UPDATE: Duration = Duration::from_millis(50);
+    // how often the network is polled while there's no input
+    const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+    const ACTIVITY_DOT_SIZE: f32 = 8.0;
+    // the smallest the grid's cells can be on screen, in pixels, for the grid to still be drawn
+    const MIN_GRID_CELL_SIZE: f32 = 4.0;
+    // the backgrounds the host can pick from, along with the keys of their names
+    const BACKGROUNDS: &'static [(Background, &'static str)] = &[
+        (Background::Solid(Color::WHITE), "paint.background_white"),
+        (Background::Solid(Color::new(0xff202020)), "paint.background_dark"),
+        (Background::Solid(Color::BLACK), "paint.background_black"),
+        (Background::Transparent, "paint.background_transparent"),
+    ];
+
+    pub fn new(assets: Assets, config: UserConfig, peer: Peer) -> Self {
+        let tools = tools::all(&assets);
+        let viewport = Viewport::new(config.ui.smooth_viewport);
+        let volume_slider = Slider::new(config.audio.volume * 100.0, 0.0, 100.0, SliderStep::Discrete(5.0));
+        let mut paint_canvas = PaintCanvas::new();
+        paint_canvas.set_memory_budget(Some(config.canvas.memory_budget()));
+        let mut this = Self {
+            assets,
+            config,
+
+            ui: Ui::new(),
+            paint_canvas,
+            history: History::new(),
+            peer,
+            update_timer: Timer::new(Self::TIME_PER_UPDATE),
+
+            tools,
+            current_tool: 0,
+            actions: vec![
+                Box::new(SaveAction::new()),
+                Box::new(ExportAction::new()),
+                Box::new(ScreenshotAction::new()),
+                Box::new(PrintAction::new()),
+                Box::new(StatisticsAction::new()),
+            ],
+            selection: None,
+            canvas_has_mouse: false,
+            minimap: Minimap::new(),
+            palette_window: PaletteWindow::new(),
+            diagnostics: Diagnostics::new(),
+            qr_code: QrCodePanel::new(),
+            sent_viewport: None,
+            sent_idle: None,
+            region_lock: None,
+            sent_lock: None,
+            blocked_by_lock: false,
+
+            synced_revision: 0,
+            sent_revision: None,
+            resync_since: None,
+
+            canvas_data_queue: VecDeque::new(),
+            uploads: ChunkUploads::new(),
+            encoder: ChunkEncoder::new(),
+            room_storage: RoomStorage::new(),
+
+            autosave: None,
+            recording: None,
+            timelapse_dialog: None,
+            timelapse_saved: None,
+
+            error: None,
+            cancelled: false,
+            log: Log::new(),
+            people_list_open: false,
+            volume_slider,
+            tooltip: Tooltip::new(),
+            context_menu: ContextMenu::new(),
+            quit_dialog: Dialog::new(),
+            quit_save_dialog: None,
+            quitting: false,
+            context_menu_point: Point::new(0.0, 0.0),
+            dragging_toolbar: false,
+
+            panning: false,
+            viewport,
+            visible_rect: Rect::new_empty(),
+            grid_mode: GridMode::Hidden,
+            jump_to: None,
+            following: None,
+        };
+        match Autosave::start() {
+            Ok(autosave) => this.autosave = Some(autosave),
+            Err(error) => notify!(this.log, Error, "{}", error),
+        }
+        this.peer.set_keepalive(this.config.connection.keepalive());
+        this.peer.set_tools(tools::capabilities(&this.tools));
+        if this.config.replay.record_sessions {
+            match Recorder::start() {
+                Ok(recorder) => this.peer.record_session(recorder),
+                Err(error) =>
+                    notify_tr!(this.log, this.assets.language, Error, "notifications.recording_session_failed", error),
+            }
+        }
+        if this.peer.is_host() {
+            log_tr!(this.log, this.assets.language, "notifications.welcome");
+            log_tr!(this.log, this.assets.language, "notifications.invite_hint");
+        }
+        this
+    }
+
+    // loads a saved project onto the canvas. this should only be done by the host right after creating the room,
+    // as the loaded chunks aren't sent to anyone who's already in the room
+    pub fn load_project(&mut self, dir: &Path) {
+        match project::load(&mut self.paint_canvas, dir) {
+            Ok(()) => {
+                self.paint_canvas.mark_saved();
+                notify_tr!(self.log, self.assets.language, Success, "notifications.canvas_opened", dir.display());
+            },
+            Err(error) => notify_tr!(self.log, self.assets.language, Error, "notifications.open_canvas_failed", error),
+        }
+        if let Some(recorder) = self.peer.recorder() {
+            recorder.record_canvas(&mut self.paint_canvas);
+        }
+    }
+
+    fn canvas_data(log: &mut Log, canvas: &mut PaintCanvas, chunk_position: (i32, i32), png_image: &[u8]) {
+        log::debug!("received canvas data for chunk {:?}", chunk_position);
+        ok_or_log!(log, canvas.decode_png_data(chunk_position, png_image));
+    }
+
+    // sends a chunk to a newcomer. when the connection is backed up, the chunk is sent again later
+    fn send_chunk(&mut self, to: SocketAddr, position: (i32, i32), png_data: Vec<u8>) {
+        log::debug!("sending chunk {:?}", position);
+        match self.peer.send_canvas_data(to, position, png_data) {
+            Ok(true) => (),
+            Ok(false) => self.uploads.retry(to, position),
+            Err(error) => notify!(self.log, Error, "{}", error),
+        }
+    }
+
+    fn tool_packet(
+        tools: &mut [Box<dyn Tool>],
+        log: &mut Log,
+        canvas: &mut PaintCanvas<'static>,
+        sender: SocketAddr,
+        name: &str,
+        payload: &[u8],
+    ) {
+        ok_or_log!(log, tools::receive(tools, canvas, sender, name, payload));
+    }
+
+    // the nickname of the person who locked the region of the canvas the point is in, if anyone did
+    fn locked_by(&self, point: Point) -> Option<String> {
+        self.peer
+            .mates()
+            .values()
+            .find(|mate| match mate.locked_region() {
+                Some(region) => point.x >= region.left && point.x < region.right &&
+                    point.y >= region.top && point.y < region.bottom,
+                None => false,
+            })
+            .map(|mate| mate.nickname.clone())
+    }
+
+    // the regions of the canvas people locked, including our own lock, in the form PaintCanvas::set_locks takes
+    fn locks(&self) -> Vec<(Option<SocketAddr>, Rect)> {
+        let mut locks = self.peer.locks();
+        locks.extend(self.region_lock.map(|(region, _)| (None, Rect::from_irect(region))));
+        locks
+    }
+
+    fn draw_lock(canvas: &mut Canvas, region: Rect, color: Color) {
+        let mut paint = Paint::new(Color4f::from(color.with_a(24)), None);
+        canvas.draw_rect(region, &paint);
+        paint.set_color(color.with_a(160));
+        paint.set_anti_alias(true);
+        paint.set_style(skpaint::Style::Stroke);
+        paint.set_stroke_width(2.0);
+        canvas.draw_rect(region, &paint);
+    }
+
+    // the grid's cell size, if drawing should snap to it
+    fn snap_to_grid(&self) -> Option<f32> {
+        if self.grid_mode == GridMode::Snap { Some(self.config.grid.cell_size()) } else { None }
+    }
+
+    fn draw_grid(canvas: &mut Canvas, viewport: Rect, cell_size: f32) {
+        // grids too dense to make out would just cover everything up
+        const MIN_CELL_SIZE: f32 = 4.0;
+        if cell_size < MIN_CELL_SIZE { return }
+
+        let mut paint = Paint::new(Color4f::from(Color::WHITE.with_a(48)), None);
+        paint.set_anti_alias(false);
+        paint.set_blend_mode(BlendMode::Difference);
+        paint.set_style(skpaint::Style::Stroke);
+        let mut x = (viewport.left / cell_size).floor() * cell_size;
+        while x < viewport.right {
+            canvas.draw_line((x, viewport.top), (x, viewport.bottom), &paint);
+            x += cell_size;
+        }
+        let mut y = (viewport.top / cell_size).floor() * cell_size;
+        while y < viewport.bottom {
+            canvas.draw_line((viewport.left, y), (viewport.right, y), &paint);
+            y += cell_size;
+        }
+    }
+
+    fn process_undo_redo(&mut self, input: &Input) {
+        if self.history.is_recording() ||
+            self.tools[self.current_tool].captures_keyboard() ||
+            !input.ctrl_is_down() ||
+            !input.key_just_typed(VirtualKeyCode::Z)
+        {
+            return
+        }
+        let changed_chunks =
+            if input.shift_is_down() { self.history.redo(&mut self.paint_canvas) }
+            else { self.history.undo(&mut self.paint_canvas) };
+        // other people's canvases are brought in sync by sending them the parts of the chunks that changed.
+        // this also overwrites anything they painted over these parts in the meantime, but at least everyone
+        // ends up seeing the same thing. whole chunks are only accepted from the host
+        let allow_full = self.peer.is_host();
+        for (chunk_position, previous_pixels) in changed_chunks {
+            match self.paint_canvas.chunk_delta(chunk_position, previous_pixels.as_deref(), allow_full) {
+                ChunkDelta::Unchanged => (),
+                ChunkDelta::Tiles(tiles) =>
+                    ok_or_log!(self.log, self.peer.send_chunk_tiles(chunk_position, tiles)),
+                ChunkDelta::Full(png_data) =>
+                    ok_or_log!(self.log, self.peer.send_chunk_update(chunk_position, png_data)),
+            }
+        }
+    }
+
+    // keeps track of whether the others' canvases are in sync with ours. when some of our changes didn't make it into
+    // the send queue, the chunks changed since then are sent in full once the connection catches up. this overwrites
+    // anything the others painted over them in the meantime, but at least everyone ends up seeing the same thing
+    fn resync(&mut self) {
+        let resyncing = match self.resync_since {
+            Some(since) if self.peer.can_send_canvas_data() => {
+                for chunk_position in self.paint_canvas.chunks_changed_since(since) {
+                    let tiles = self.paint_canvas.chunk_tiles(chunk_position);
+                    ok_or_log!(self.log, self.peer.send_chunk_tiles(chunk_position, tiles));
+                }
+                true
+            },
+            _ => false,
+        };
+        let sent_revision = self.sent_revision.take();
+        if self.peer.take_paint_refused() {
+            if self.resync_since.is_none() {
+                notify_tr!(self.log, self.assets.language, Error, "notifications.connection_behind");
+            }
+            let since = self.resync_since.map_or(self.synced_revision, |since| since.min(self.synced_revision));
+            self.resync_since = Some(since);
+        } else {
+            if resyncing {
+                self.resync_since = None;
+            }
+            if let Some(revision) = sent_revision {
+                self.synced_revision = revision;
+            }
+        }
+    }
+
+    // stops the recording and asks for a file to save the timelapse to
+    fn stop_recording(&mut self) {
+        let mut recording = match self.recording.take() {
+            Some(recording) => recording,
+            None => return,
+        };
+        recording.stop(&mut self.paint_canvas);
+
+        let dialog = PendingDialog::spawn(|dialog| {
+            dialog.set_filename("timelapse.gif").add_filter("GIF animation", &["gif"]).show_save_single_file()
+        });
+        self.timelapse_dialog = Some((dialog, recording));
+    }
+
+    // saves the timelapse to the file picked in the save dialog, if it's been closed
+    fn poll_timelapse_dialog(&mut self) {
+        let path = match self.timelapse_dialog.as_ref().and_then(|(dialog, _)| dialog.poll()) {
+            Some(path) => path,
+            None => return,
+        };
+        let (_, recording) = self.timelapse_dialog.take().unwrap();
+        let path = match path {
+            Ok(Some(path)) => path,
+            Ok(None) => return,
+            Err(error) => {
+                notify_tr!(self.log, self.assets.language, Error, "notifications.file_dialog_failed", error);
+                return
+            },
+        };
+
+        log_tr!(self.log, self.assets.language, "notifications.saving_timelapse");
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(Self::save_timelapse(recording, path));
+        });
+        self.timelapse_saved = Some(receiver);
+    }
+
+    // saves the timelapse as a GIF. this takes a while, so it's always done on a separate thread
+    fn save_timelapse(recording: Recording, path: PathBuf) -> Result<PathBuf, recording::Error> {
+        let path = if path.extension().is_none() { path.with_extension("gif") } else { path };
+        let result = recording.save_gif(&path).map(|_| path);
+        // the room may have been left in the meantime, in which case there's nowhere else to report errors
+        if let Err(error) = &result {
+            log::error!("error while saving timelapse: {}", error);
+        }
+        result
+    }
+
+    fn process_download_progress(&mut self, canvas: &mut Canvas, input: &Input) {
+        let (text, fraction) = match self.peer.download() {
+            Some(download) => (
+                self.assets.language.format("paint.downloading", &[&download.received(), &download.expected()]),
+                download.fraction(),
+            ),
+            None => return,
+        };
+
+        self.ui.push_group(self.ui.size(), Layout::Freeform);
+        self.ui.push_group((256.0, 88.0), Layout::Vertical);
+        self.ui.align((AlignH::Center, AlignV::Middle));
+        self.ui.fill(canvas, self.assets.colors.panel);
+        self.ui.outline(canvas, self.assets.colors.separator, 1.0);
+        self.ui.pad((16.0, 16.0));
+
+        self.ui.push_group((self.ui.width(), 16.0), Layout::Freeform);
+        self.ui.text(canvas, &text, self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+        self.ui.pop_group();
+        self.ui.space(8.0);
+
+        // progress bar
+        self.ui.push_group((self.ui.width(), 4.0), Layout::Freeform);
+        self.ui.fill(canvas, self.assets.colors.slider.with_a(96));
+        self.ui.push_group((self.ui.width() * fraction, self.ui.height()), Layout::Freeform);
+        self.ui.fill(canvas, self.assets.colors.slider);
+        self.ui.pop_group();
+        self.ui.pop_group();
+        self.ui.space(12.0);
+
+        if Button::with_text(&mut self.ui, canvas, input, ButtonArgs {
+            height: 24.0,
+            colors: &self.assets.colors.button,
+        }, self.assets.language.tr("paint.cancel")).clicked() {
+            self.cancelled = true;
+        }
+
+        self.ui.pop_group();
+        self.ui.pop_group();
+    }
+
+    fn process_people_list(&mut self, canvas: &mut Canvas, input: &Input) {
+        if !self.people_list_open { return }
+
+        let mates: Vec<(SocketAddr, String, Activity)> = self.peer.mates()
+            .iter()
+            .map(|(&addr, mate)| (addr, mate.nickname.clone(), mate.activity()))
+            .collect();
+        let row_height = 24.0;
+        let can_persist = self.peer.can_persist();
+        // the header, a row for each person, whether the canvas is kept for hosts, and the sound settings at the bottom
+        let height = 32.0 + 24.0 + usize::max(mates.len(), 1) as f32 * (row_height + 4.0) + 4.0 + row_height
+            + if can_persist { row_height + 8.0 } else { 0.0 };
+
+        self.ui.push_group(self.ui.size(), Layout::Freeform);
+        self.ui.pad((32.0, 32.0));
+        self.ui.push_group((352.0, height), Layout::Vertical);
+        self.ui.align((AlignH::Right, AlignV::Bottom));
+        self.ui.fill(canvas, self.assets.colors.panel);
+        self.ui.outline(canvas, self.assets.colors.separator, 1.0);
+        self.ui.pad((16.0, 16.0));
+
+        self.ui.push_group((self.ui.width(), 16.0), Layout::Freeform);
+        self.ui.set_font(self.assets.sans_bold.clone());
+        let lang = &self.assets.language;
+        self.ui.text(canvas, lang.tr("paint.people_in_room"), self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+        self.ui.pop_group();
+        self.ui.space(8.0);
+
+        if mates.is_empty() {
+            self.ui.push_group((self.ui.width(), row_height), Layout::Freeform);
+            self.ui.text(canvas, lang.tr("paint.nobody_here"), self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+            self.ui.pop_group();
+        }
+
+        let button = ButtonArgs {
+            height: row_height,
+            colors: &self.assets.colors.button,
+        };
+        let mut kick = None;
+        let mut jump_to = None;
+        let mut follow = None;
+        for (addr, nickname, activity) in &mates {
+            let following = self.following == Some(*addr);
+            let follow_label = lang.tr(if following { "paint.unfollow" } else { "paint.follow" });
+            // only the host can kick and ban people
+            let mut labels = vec![lang.tr("paint.go_to"), follow_label];
+            if self.peer.is_host() {
+                labels.extend(&[lang.tr("paint.kick"), lang.tr("paint.ban")]);
+            }
+            let buttons_width: f32 = labels
+                .iter()
+                .map(|label| self.ui.text_size(label).0 + row_height + 4.0)
+                .sum();
+
+            self.ui.push_group((self.ui.width(), row_height), Layout::Horizontal);
+            // the activity indicator: a dot in the color of what the person is up to
+            let (activity_color, activity_text) = match activity {
+                Activity::Drawing => (Color::new(0xff2dd70e), "paint.drawing"),
+                Activity::Watching => (Color::new(0xff03cbfb), "paint.watching"),
+                Activity::Idle => (Color::new(0xff808080), "paint.idle"),
+            };
+            self.ui.push_group((Self::ACTIVITY_DOT_SIZE * 2.0, row_height), Layout::Freeform);
+            self.ui.draw_on_canvas(canvas, |canvas| {
+                let mut paint = Paint::new(Color4f::from(activity_color), None);
+                paint.set_anti_alias(true);
+                let radius = Self::ACTIVITY_DOT_SIZE / 2.0;
+                canvas.draw_circle((radius, row_height / 2.0), radius, &paint);
+            });
+            self.tooltip.hover(&self.ui, input, lang.tr(activity_text));
+            self.ui.pop_group();
+            let name_width = self.ui.width() - buttons_width - Self::ACTIVITY_DOT_SIZE * 2.0;
+            self.ui.push_group((name_width, row_height), Layout::Freeform);
+            // a bar in the person's color, so that they can be found on the canvas
+            let color = net::mate_color(*addr);
+            self.ui.draw_on_canvas(canvas, |canvas| {
+                let paint = Paint::new(Color4f::from(color), None);
+                canvas.draw_rect(Rect::from_xywh(0.0, 4.0, 3.0, row_height - 8.0), &paint);
+            });
+            self.ui.pad((16.0, 0.0));
+            self.ui.text(canvas, nickname, self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+            self.ui.pop_group();
+            if Button::with_text(&mut self.ui, canvas, input, button, lang.tr("paint.go_to")).clicked() {
+                jump_to = Some(*addr);
+            }
+            self.ui.space(4.0);
+            if Button::with_text(&mut self.ui, canvas, input, button, follow_label).clicked() {
+                follow = Some(if following { None } else { Some((*addr, nickname)) });
+            }
+            if self.peer.is_host() {
+                self.ui.space(4.0);
+                if Button::with_text(&mut self.ui, canvas, input, button, lang.tr("paint.kick")).clicked() {
+                    kick = Some((*addr, nickname, false));
+                }
+                self.ui.space(4.0);
+                if Button::with_text(&mut self.ui, canvas, input, button, lang.tr("paint.ban")).clicked() {
+                    kick = Some((*addr, nickname, true));
+                }
+            }
+            self.ui.pop_group();
+            self.ui.space(4.0);
+        }
+
+        // keeping the canvas on the matchmaker
+
+        let mut toggle_persistence = false;
+        if can_persist {
+            self.ui.space(4.0);
+            self.ui.push_group((self.ui.width(), row_height), Layout::Horizontal);
+            let persistence = self.peer.persistence();
+            let label = lang.tr(if persistence.is_some() { "paint.keep_canvas_on" } else { "paint.keep_canvas_off" });
+            toggle_persistence = Button::with_text(&mut self.ui, canvas, input, button, label).clicked();
+            if let Some(days) = persistence {
+                self.ui.space(16.0);
+                let text = lang.format("paint.kept_for", &[&days]);
+                self.ui.push_group((self.ui.remaining_width(), row_height), Layout::Freeform);
+                self.ui.text(canvas, &text, self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+                self.ui.pop_group();
+            }
+            self.ui.pop_group();
+            self.ui.space(4.0);
+        }
+
+        // sound cues
+
+        self.ui.space(4.0);
+        self.ui.push_group((self.ui.width(), row_height), Layout::Horizontal);
+        let sounds_label = lang.tr(if self.config.audio.muted { "paint.sounds_off" } else { "paint.sounds_on" });
+        let toggle_sounds = Button::with_text(&mut self.ui, canvas, input, button, sounds_label).clicked();
+        self.ui.space(16.0);
+        self.ui.push_group((64.0, row_height), Layout::Freeform);
+        self.ui.text(canvas, lang.tr("paint.volume"), self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+        self.ui.pop_group();
+        self.volume_slider.process(&mut self.ui, canvas, input, SliderArgs {
+            width: 96.0,
+            color: self.assets.colors.slider,
+        });
+        self.ui.space(8.0);
+        let volume_text = format!("{}%", self.volume_slider.value());
+        self.ui.push_group((self.ui.remaining_width(), row_height), Layout::Freeform);
+        self.ui.text(canvas, &volume_text, self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+        self.ui.pop_group();
+        self.ui.pop_group();
+
+        self.ui.pop_group();
+        self.ui.pop_group();
+
+        if toggle_persistence {
+            let persist = self.peer.persistence().is_none();
+            if persist {
+                // the matchmaker starts out with nothing, so all of the canvas has to be sent
+                self.room_storage.clear();
+            }
+            ok_or_log!(self.log, self.peer.persist(persist));
+        }
+        if toggle_sounds {
+            self.config.audio.muted = !self.config.audio.muted;
+            ok_or_log!(self.log, self.config.save());
+        }
+        // the volume is saved once the slider is let go of, rather than on every step it's dragged through. a cue is
+        // played right away, so that it's clear how loud the sounds are going to be
+        let volume = self.volume_slider.value() / 100.0;
+        if volume != self.config.audio.volume && input.mouse_button_just_released(MouseButton::Left) {
+            self.config.audio.volume = volume;
+            ok_or_log!(self.log, self.config.save());
+            audio::play(&self.config.audio, Cue::Joined);
+        }
+
+        if let Some(addr) = jump_to {
+            self.jump_to = self.peer.mates().get(&addr).map(|mate| mate.cursor);
+        }
+        if let Some(follow) = follow {
+            self.following = follow.map(|(addr, _)| addr);
+            if let Some((_, nickname)) = follow {
+                log_tr!(self.log, self.assets.language, "notifications.following", nickname);
+            }
+        }
+        if let Some((addr, nickname, ban)) = kick {
+            match self.peer.kick(addr, ban) {
+                Ok(()) if ban => log_tr!(self.log, self.assets.language, "notifications.banned", nickname),
+                Ok(()) => log_tr!(self.log, self.assets.language, "notifications.kicked", nickname),
+                Err(error) => notify!(self.log, Error, "{}", error),
+            }
+        }
+    }
+
+    fn process_toolbar(&mut self, canvas: &mut Canvas, input: &Input) {
+        let vertical = self.config.ui.toolbar_position.is_vertical();
+        if vertical {
+            self.ui.push_group((Self::TOOLBAR_SIZE, self.ui.height()), Layout::Vertical);
+        } else {
+            self.ui.push_group((self.ui.width(), Self::TOOLBAR_SIZE), Layout::Horizontal);
+        }
+        self.ui.fill(canvas, self.assets.colors.panel);
+        self.ui.pad((8.0, 8.0));
+        let button_size = if vertical { self.ui.width() } else { self.ui.height() };
+
+        for (i, tool) in self.tools.iter().enumerate() {
+            self.ui.push_group((button_size, button_size), Layout::Freeform);
+            if i == self.current_tool {
+                self.ui.fill(canvas, self.assets.colors.button.pressed);
+            } else if self.ui.has_mouse(input) {
+                self.ui.fill(canvas, self.assets.colors.button.hover);
+                if input.mouse_button_just_pressed(MouseButton::Left) {
+                    self.current_tool = i;
+                }
+            }
+            self.ui.icon(canvas, tool.icon(&self.assets), self.assets.colors.text, Some(self.ui.size()));
+            let shortcut = format!("{:?}", tool.key_shortcut());
+            let tooltip = self.assets.language.format(&format!("toolbar.{}", tool.name()), &[&shortcut]);
+            self.tooltip.hover(&self.ui, input, &tooltip);
+            self.ui.pop_group();
+            self.ui.space(4.0);
+        }
+
+        self.ui.space(12.0);
+        for action in &mut self.actions {
+            self.ui.push_group((button_size, button_size), Layout::Freeform);
+            if action.panel_open() {
+                self.ui.fill(canvas, self.assets.colors.button.pressed);
+            } else if self.ui.has_mouse(input) {
+                self.ui.fill(canvas, self.assets.colors.button.hover);
+            }
+            self.ui.icon(canvas, action.icon(&self.assets), self.assets.colors.text, Some(self.ui.size()));
+            self.tooltip.hover(&self.ui, input, self.assets.language.tr(&format!("toolbar.{}", action.name())));
+            let clicked = self.ui.has_mouse(input) && input.mouse_button_just_pressed(MouseButton::Left);
+            self.ui.pop_group();
+            if clicked {
+                action.perform(ActionArgs {
+                    ui: &mut self.ui,
+                    input,
+                    assets: &self.assets,
+                    paint_canvas: &mut self.paint_canvas,
+                    selection: self.selection,
+                    viewport: self.visible_rect,
+                    log: &mut self.log,
+                });
+            }
+            self.ui.space(4.0);
+        }
+
+        // the empty rest of the toolbar is a handle for dragging it to another edge of the window
+        self.ui.push_group(self.ui.remaining_size(), Layout::Freeform);
+        if self.ui.has_mouse(input) {
+            self.tooltip.hover(&self.ui, input, self.assets.language.tr("toolbar.move"));
+            if input.mouse_button_just_pressed(MouseButton::Left) {
+                self.dragging_toolbar = true;
+            }
+        }
+        self.ui.pop_group();
+
+        self.ui.pop_group();
+
+        if !input.ctrl_is_down() && !self.tools[self.current_tool].captures_keyboard() {
+            for (i, tool) in self.tools.iter().enumerate() {
+                if input.key_just_typed(tool.key_shortcut()) {
+                    self.current_tool = i;
+                }
+            }
+        }
+    }
+
+    fn process_toolbar_with_lock(&mut self, canvas: &mut Canvas, input: &mut Input) {
+        if self.canvas_has_mouse || self.context_menu.is_open() || self.quit_dialog.is_open() {
+            input.lock_mouse_buttons();
+        }
+        self.process_toolbar(canvas, input);
+        input.unlock_mouse_buttons();
+    }
+
+    fn process_canvas(&mut self, canvas: &mut Canvas, input: &Input) {
+        // leave space for the toolbar if it comes after the canvas
+        let (mut width, mut height) = self.ui.remaining_size();
+        match self.config.ui.toolbar_position {
+            ToolbarPosition::Right => width -= Self::TOOLBAR_SIZE,
+            ToolbarPosition::Bottom => height -= Self::TOOLBAR_SIZE,
+            _ => (),
+        }
+        self.ui.push_group((width, height), Layout::Freeform);
+
+        //
+        // input
+        //
+
+        if self.ui.has_mouse(input) &&
+            (input.mouse_button_just_pressed(MouseButton::Left) || input.mouse_button_just_pressed(MouseButton::Right))
+        {
+            self.canvas_has_mouse = true;
+        }
+        if !input.mouse_button_is_down(MouseButton::Left) && !input.mouse_button_is_down(MouseButton::Right) {
+            self.canvas_has_mouse = false;
+        }
+
+        // minimap
+
+        // following
+
+        if self.canvas_has_mouse {
+            self.following = None;
+        }
+        if let Some(addr) = self.following {
+            match self.peer.mates().get(&addr) {
+                Some(mate) => if let Some(viewport) = mate.viewport {
+                    self.viewport.center_on(Point::new(viewport.center_x(), viewport.center_y()), self.ui.size());
+                },
+                None => self.following = None,
+            }
+        }
+        if let Some(point) = self.jump_to.take() {
+            self.viewport.center_on(point, self.ui.size());
+        }
+
+        // zooming. the mouse wheel zooms around the mouse, and the keyboard shortcuts around the center of the screen
+        let panel_open = self.actions.iter().any(|action| action.panel_open());
+        let scroll = input.mouse_scroll().y;
+        if scroll != 0.0 && self.ui.has_mouse(input) && !panel_open && !self.minimap.has_mouse() {
+            self.viewport.zoom_by(Viewport::ZOOM_STEP.powf(scroll), self.ui.mouse_position(input));
+        }
+        if input.ctrl_is_down() && !self.tools[self.current_tool].captures_keyboard() {
+            let center = Point::new(self.ui.width() / 2.0, self.ui.height() / 2.0);
+            if input.key_just_typed(VirtualKeyCode::Equals) || input.key_just_typed(VirtualKeyCode::NumpadAdd) {
+                self.viewport.zoom_by(Viewport::ZOOM_STEP, center);
+            }
+            if input.key_just_typed(VirtualKeyCode::Minus) || input.key_just_typed(VirtualKeyCode::NumpadSubtract) {
+                self.viewport.zoom_by(1.0 / Viewport::ZOOM_STEP, center);
+            }
+            if input.key_just_typed(VirtualKeyCode::Key0) || input.key_just_typed(VirtualKeyCode::Numpad0) {
+                self.viewport.zoom_to(1.0, center);
+            }
+        }
+
+        self.viewport.tick(input.time_in_seconds());
+        if self.viewport.is_animating() {
+            input.request_redraw();
+        }
+        let viewport = self.viewport.rect(self.ui.size());
+        self.visible_rect = viewport;
+        self.paint_canvas.manage_memory(viewport);
+        if let Some(center) =
+            self.minimap.process_input(&mut self.ui, input, &self.paint_canvas, &self.peer, viewport)
+        {
+            self.following = None;
+            self.viewport.center_on(center, self.ui.size());
+        }
+
+        // tools
+
+        let mouse = self.viewport.to_canvas(self.ui.mouse_position(input));
+        let previous_mouse = self.viewport.to_canvas(self.ui.previous_mouse_position(input));
+        let screen_origin = self.ui.mouse_position(input) - input.mouse_position();
+        let viewport = &self.viewport;
+        let pointer_events: Vec<_> = input.pointer_events().iter()
+            .map(|event| PointerEvent { position: viewport.to_canvas(event.position + screen_origin), ..*event })
+            .collect();
+
+        // nothing can be started inside a region someone else locked
+        let pressed = input.mouse_button_just_pressed(MouseButton::Left) ||
+            input.mouse_button_just_pressed(MouseButton::Right);
+        if self.ui.has_mouse(input) && pressed {
+            if let Some(nickname) = self.locked_by(mouse) {
+                self.blocked_by_lock = true;
+                log_tr!(self.log, self.assets.language, "notifications.region_locked_by", nickname);
+            }
+        }
+        if !input.mouse_button_is_down(MouseButton::Left) && !input.mouse_button_is_down(MouseButton::Right) {
+            self.blocked_by_lock = false;
+        }
+        if self.region_lock.is_some_and(|(_, expires)| Instant::now() >= expires) {
+            self.region_lock = None;
+            log_tr!(self.log, self.assets.language, "notifications.lock_expired");
+        }
+
+        let snap_to_grid = self.snap_to_grid();
+        let tool = &mut self.tools[self.current_tool];
+        let blocked = self.minimap.has_mouse() ||
+            self.log.has_mouse() ||
+            tool.panel_has_mouse() ||
+            panel_open ||
+            self.blocked_by_lock;
+        if !blocked {
+            tool.process_paint_canvas_input(ToolArgs {
+                ui: &mut self.ui,
+                input,
+                pressure: input.pen_pressure().unwrap_or(1.0),
+                pointer_events: &pointer_events,
+                assets: &self.assets,
+                config: &mut self.config,
+                paint_canvas: &mut self.paint_canvas,
+                selection: &mut self.selection,
+                snap_to_grid,
+                history: &mut self.history,
+                log: &mut self.log,
+            }, mouse, previous_mouse);
+        }
+
+        for _ in self.update_timer.tick() {
+            self.sent_revision = Some(self.paint_canvas.revision());
+            if input.previous_mouse_position() != input.mouse_position() {
+                ok_or_log!(self.log, self.peer.send_cursor(mouse, tool.cursor_size()));
+            }
+            ok_or_log!(self.log, tool.network_send(&self.peer));
+            let viewport = self.viewport.rect(self.ui.size());
+            if self.sent_viewport != Some(viewport) {
+                ok_or_log!(self.log, self.peer.send_viewport(viewport));
+                self.sent_viewport = Some(viewport);
+            }
+            let lock = self.region_lock.map(|(region, _)| region);
+            if self.sent_lock != Some(lock) {
+                let expires_in = self.region_lock
+                    .map_or(Duration::from_secs(0), |(_, expires)| expires.saturating_duration_since(Instant::now()));
+                ok_or_log!(self.log, self.peer.send_lock(lock, expires_in));
+                self.sent_lock = Some(lock);
+            }
+        }
+
+        // panning
+
+        if self.ui.has_mouse(input) && input.mouse_button_just_pressed(MouseButton::Middle) {
+            self.panning = true;
+            self.following = None;
+        }
+        if input.mouse_button_just_released(MouseButton::Middle) {
+            self.panning = false;
+        }
+
+        if self.panning {
+            let delta_pan = input.mouse_position() - input.previous_mouse_position();
+            self.viewport.pan_by(delta_pan);
+        }
+
+        // context menu
+
+        if self.ui.has_mouse(input) &&
+            input.mouse_button_just_released(MouseButton::Right) &&
+            !self.tools[self.current_tool].uses_right_mouse_button() &&
+            !self.minimap.has_mouse() &&
+            !self.actions.iter().any(|action| action.panel_open())
+        {
+            self.context_menu.open(input.mouse_position());
+            self.context_menu_point = mouse;
+        }
+
+        //
+        // rendering
+        //
+
+        let paint_canvas = &self.paint_canvas;
+        let peer = &self.peer;
+        let assets = &self.assets;
+        let viewport = &self.viewport;
+        let visible_rect = self.viewport.rect(self.ui.size());
+        let selection = self.selection;
+        let size = self.ui.size();
+        let grid_cell_size = if self.grid_mode != GridMode::Hidden { Some(self.config.grid.cell_size()) } else { None };
+        let tools = &mut self.tools;
+        let current_tool = self.current_tool;
+        let region_lock = self.region_lock;
+        self.ui.draw_on_canvas(canvas, |canvas| {
+            canvas.save();
+            canvas.clip_rect(Rect::from_point_and_size((0.0, 0.0), size), ClipOp::Intersect, false);
+            canvas.save();
+            canvas.translate(viewport.pan());
+            canvas.scale((viewport.zoom(), viewport.zoom()));
+
+            let mut paint = Paint::new(Color4f::from(Color::WHITE.with_a(192)), None);
+            paint.set_anti_alias(true);
+            paint.set_blend_mode(BlendMode::Difference);
+
+            paint_canvas.background().draw(canvas, visible_rect);
+            paint_canvas.draw_to(canvas);
+            for tool in tools.iter() {
+                tool.draw_layers(canvas);
+            }
+            // locked regions are outlined in the color of whoever locked them
+            for (&addr, mate) in peer.mates() {
+                if let Some(region) = mate.locked_region() {
+                    Self::draw_lock(canvas, region, net::mate_color(addr));
+                }
+            }
+            if let Some((region, _)) = region_lock {
+                Self::draw_lock(canvas, Rect::from_irect(region), Color::from_rgb(128, 128, 128));
+            }
+            for mate in peer.mates().values() {
+                paint.set_style(skpaint::Style::Stroke);
+                canvas.draw_circle(mate.cursor, mate.brush_size * 0.5, &paint);
+            }
+
+            if let Some(cell_size) = grid_cell_size {
+                // cells that are too small to tell apart on screen would only turn the canvas gray
+                if cell_size * viewport.zoom() >= Self::MIN_GRID_CELL_SIZE {
+                    Self::draw_grid(canvas, visible_rect, cell_size);
+                }
+            }
+
+            tools[current_tool].process_paint_canvas_overlays(canvas, input, mouse);
+
+            if let Some(rect) = selection {
+                paint.set_anti_alias(false);
+                paint.set_style(skpaint::Style::Stroke);
+                canvas.draw_rect(Rect::from_irect(rect), &paint);
+            }
+
+            canvas.restore();
+
+            // nicknames are drawn on top of everything else, and stay the same size no matter the zoom
+            let font = assets.sans.borrow();
+            let (_, metrics) = font.metrics();
+            for (&addr, mate) in peer.mates() {
+                let corner = mate.cursor + Point::new(mate.brush_size, mate.brush_size) * 0.5;
+                let text_position = viewport.to_screen(corner) + Point::new(4.0, 14.0);
+                // the nickname sits on a tag in the mate's color, the same one their selections are outlined with
+                let text_width = font_fallback::measure_str(&mate.nickname, &font);
+                let tag = Rect::new(
+                    text_position.x - 4.0,
+                    text_position.y + metrics.ascent - 2.0,
+                    text_position.x + text_width + 4.0,
+                    text_position.y + metrics.descent + 2.0,
+                );
+                let mut tag_paint = Paint::new(Color4f::from(net::mate_color(addr)), None);
+                tag_paint.set_anti_alias(true);
+                canvas.draw_rrect(RRect::new_rect_xy(tag, 4.0, 4.0), &tag_paint);
+                tag_paint.set_color(Color::WHITE);
+                font_fallback::draw_str(canvas, &mate.nickname, text_position, &font, &tag_paint);
+            }
+            drop(font);
+
+            canvas.restore();
+        });
+        if self.panning {
+            let top_left = self.viewport.to_canvas(Point::new(0.0, 0.0));
+            let position = format!("{}, {}", f32::floor(top_left.x / 256.0), f32::floor(top_left.y / 256.0));
+            self.ui.push_group(self.ui.size(), Layout::Freeform);
+            self.ui.pad((32.0, 32.0));
+            self.ui.push_group((72.0, 32.0), Layout::Freeform);
+            self.ui.fill(canvas, Color::BLACK.with_a(128));
+            self.ui.text(canvas, &position, Color::WHITE, (AlignH::Center, AlignV::Middle));
+            self.ui.pop_group();
+            self.ui.pop_group();
+        }
+
+        let viewport = self.viewport.rect(self.ui.size());
+        self.minimap.process(&mut self.ui, canvas, &self.assets, &self.paint_canvas, &self.peer, viewport);
+
+        let snap_to_grid = self.snap_to_grid();
+        self.tools[self.current_tool].process_panel(ToolArgs {
+            ui: &mut self.ui,
+            input,
+            pressure: input.pen_pressure().unwrap_or(1.0),
+            pointer_events: &[],
+            assets: &self.assets,
+            config: &mut self.config,
+            paint_canvas: &mut self.paint_canvas,
+            selection: &mut self.selection,
+            snap_to_grid,
+            history: &mut self.history,
+            log: &mut self.log,
+        }, canvas);
+        for action in &mut self.actions {
+            action.process_panel(ActionArgs {
+                ui: &mut self.ui,
+                input,
+                assets: &self.assets,
+                paint_canvas: &mut self.paint_canvas,
+                selection: self.selection,
+                viewport,
+                log: &mut self.log,
+            }, canvas);
+        }
+
+        self.process_people_list(canvas, input);
+        self.qr_code.process(&mut self.ui, canvas, &self.assets);
+        self.process_download_progress(canvas, input);
+        self.log.process(&mut self.ui, canvas, input, ToastArgs {
+            fill: self.assets.colors.panel,
+            outline: self.assets.colors.separator,
+            text: self.assets.colors.text,
+            error: self.assets.colors.error,
+        });
+        match self.log.take_alert() {
+            Some(Severity::Error) => audio::play(&self.config.audio, Cue::Error),
+            Some(Severity::Success) => audio::play(&self.config.audio, Cue::Success),
+            _ => (),
+        }
+        self.diagnostics.process(&mut self.ui, canvas, &self.assets, &self.peer, self.uploads.len());
+
+        self.ui.pop_group();
+    }
+
+    // the edge of the window closest to the point
+    fn nearest_edge(point: Point, (width, height): (f32, f32)) -> ToolbarPosition {
+        let distances = [
+            (point.x, ToolbarPosition::Left),
+            (point.y, ToolbarPosition::Top),
+            (width - point.x, ToolbarPosition::Right),
+            (height - point.y, ToolbarPosition::Bottom),
+        ];
+        distances
+            .iter()
+            .min_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .map_or(ToolbarPosition::Left, |&(_, edge)| edge)
+    }
+
+    // while the toolbar is being dragged, shows where it's going to end up, and docks it there once it's dropped
+    fn process_toolbar_drag(&mut self, canvas: &mut Canvas, input: &Input) {
+        if !self.dragging_toolbar { return }
+
+        self.ui.push_overlay_group();
+        let window_size = self.ui.size();
+        let edge = Self::nearest_edge(input.mouse_position(), window_size);
+        let (x, y, width, height) = match edge {
+            ToolbarPosition::Left => (0.0, 0.0, Self::TOOLBAR_SIZE, window_size.1),
+            ToolbarPosition::Top => (0.0, 0.0, window_size.0, Self::TOOLBAR_SIZE),
+            ToolbarPosition::Right => (window_size.0 - Self::TOOLBAR_SIZE, 0.0, Self::TOOLBAR_SIZE, window_size.1),
+            ToolbarPosition::Bottom => (0.0, window_size.1 - Self::TOOLBAR_SIZE, window_size.0, Self::TOOLBAR_SIZE),
+        };
+        self.ui.offset((x, y));
+        self.ui.push_group((width, height), Layout::Freeform);
+        self.ui.fill(canvas, self.assets.colors.button.pressed);
+        self.ui.outline(canvas, self.assets.colors.button.outline, 1.0);
+        self.ui.pop_group();
+        self.ui.pop_group();
+
+        if !input.mouse_button_is_down(MouseButton::Left) {
+            self.dragging_toolbar = false;
+            if edge != self.config.ui.toolbar_position {
+                self.config.ui.toolbar_position = edge;
+                ok_or_log!(self.log, self.config.save());
+            }
+        }
+    }
+
+    fn process_context_menu(&mut self, canvas: &mut Canvas, input: &Input) {
+        #[derive(Clone, Copy)]
+        enum Item {
+            CopyRegion,
+            LockRegion,
+            UnlockRegion,
+            JumpHere,
+        }
+
+        let language = &self.assets.language;
+        let mut items = Vec::new();
+        // copying and locking only make sense if something's selected
+        if self.selection.is_some() {
+            items.push(Item::CopyRegion);
+        }
+        if self.region_lock.is_some() {
+            items.push(Item::UnlockRegion);
+        } else if self.selection.is_some() {
+            items.push(Item::LockRegion);
+        }
+        items.push(Item::JumpHere);
+        let texts: Vec<_> = items
+            .iter()
+            .map(|item| language.tr(match item {
+                Item::CopyRegion => "paint.copy_region",
+                Item::LockRegion => "paint.lock_region",
+                Item::UnlockRegion => "paint.unlock_region",
+                Item::JumpHere => "paint.jump_here",
+            }))
+            .collect();
+
+        let clicked = self.context_menu.process(&mut self.ui, canvas, input, ContextMenuArgs {
+            width: 160.0,
+            item_height: 32.0,
+            fill: self.assets.colors.panel,
+            colors: &self.assets.colors.button,
+        }, &texts);
+        match clicked.map(|i| items[i]) {
+            Some(Item::CopyRegion) => {
+                SelectionTool::copy(self.selection, &mut self.paint_canvas, &mut self.log, &self.assets.language)
+            },
+            Some(Item::LockRegion) => {
+                match self.selection {
+                    Some(region) if net::is_valid_lock(Rect::from_irect(region)) => {
+                        self.region_lock = Some((region, Instant::now() + Mate::LOCK_DURATION));
+                        let seconds = Mate::LOCK_DURATION.as_secs();
+                        log_tr!(self.log, self.assets.language, "notifications.region_locked", seconds);
+                    },
+                    _ => log_tr!(self.log, self.assets.language, "notifications.region_too_big"),
+                }
+            },
+            Some(Item::UnlockRegion) => self.region_lock = None,
+            Some(Item::JumpHere) => self.jump_to = Some(self.context_menu_point),
+            None => (),
+        }
+    }
+
+    fn process_quit_dialog(&mut self, canvas: &mut Canvas, input: &Input) {
+        let lang = &self.assets.language;
+        // if saving fails or the folder dialog is cancelled, the window stays open so that nothing is lost
+        if let Some(picked) = self.quit_save_dialog.as_ref().and_then(PendingDialog::poll) {
+            self.quit_save_dialog = None;
+            self.quitting = SaveAction::save_and_report(&mut self.paint_canvas, picked, &mut self.log, lang);
+        }
+
+        let buttons = [lang.tr("paint.save"), lang.tr("paint.discard"), lang.tr("paint.cancel")];
+        let clicked = self.quit_dialog.process(&mut self.ui, canvas, input, DialogArgs {
+            fill: self.assets.colors.panel,
+            outline: self.assets.colors.separator,
+            text: self.assets.colors.text,
+            button: ButtonArgs {
+                height: 32.0,
+                colors: &self.assets.colors.button,
+            },
+        }, lang.tr("paint.save_before_quitting"), &buttons);
+        match clicked {
+            Some(0) if self.quit_save_dialog.is_none() => {
+                self.quit_save_dialog = SaveAction::show_folder_dialog(&self.paint_canvas, &mut self.log);
+            },
+            Some(1) => self.quitting = true,
+            _ => (),
+        }
+    }
+
+    fn process_bar(&mut self, canvas: &mut Canvas, input: &mut Input) {
+        if self.canvas_has_mouse || self.context_menu.is_open() || self.dragging_toolbar || self.quit_dialog.is_open() {
+            input.lock_mouse_buttons();
+        }
+
+        self.ui.push_group((self.ui.width(), self.ui.remaining_height()), Layout::Horizontal);
+        self.ui.fill(canvas, self.assets.colors.panel);
+        self.ui.pad((16.0, 0.0));
+
+        // tool settings
+
+        let snap_to_grid = self.snap_to_grid();
+        let tool = &mut self.tools[self.current_tool];
+        tool.process_bottom_bar(ToolArgs {
+            ui: &mut self.ui,
+            input,
+            pressure: input.pen_pressure().unwrap_or(1.0),
+            pointer_events: &[],
+            assets: &self.assets,
+            config: &mut self.config,
+            paint_canvas: &mut self.paint_canvas,
+            selection: &mut self.selection,
+            snap_to_grid,
+            history: &mut self.history,
+            log: &mut self.log,
+        }, canvas);
+
+        //
+        // right side
+        //
+
+        let button = ButtonArgs {
+            height: self.ui.height(),
+            colors: &self.assets.colors.button,
+        };
+
+        let right_width =
+            96.0 + 8.0 + 96.0 + 8.0 + 96.0 + 8.0 + 128.0 + 8.0 + 96.0 +
+            if self.peer.is_host() { 128.0 + 8.0 + 128.0 } else { 0.0 };
+        self.ui.push_group((self.ui.remaining_width(), self.ui.height()), Layout::Freeform);
+        self.ui.push_group((right_width, self.ui.height()), Layout::Horizontal);
+        self.ui.align((AlignH::Right, AlignV::Top));
+
+        // background. only the host gets to pick it
+
+        if self.peer.is_host() {
+            let background = self.paint_canvas.background();
+            let current = Self::BACKGROUNDS.iter().position(|&(b, _)| b == background);
+            let name = self.assets.language.tr(current.map_or("paint.background_custom", |i| Self::BACKGROUNDS[i].1));
+            let background_text = self.assets.language.format("paint.background", &[&name]);
+            self.ui.push_group((128.0, self.ui.height()), Layout::Freeform);
+            if Button::with_text(&mut self.ui, canvas, input, button, &background_text).clicked() {
+                let next = current.map_or(0, |i| (i + 1) % Self::BACKGROUNDS.len());
+                let (background, _) = Self::BACKGROUNDS[next];
+                self.paint_canvas.set_background(background);
+                ok_or_log!(self.log, self.peer.send_background(None, background.to_packet()));
+            }
+            self.ui.pop_group();
+            self.ui.space(8.0);
+        }
+
+        // palette window toggle
+
+        self.ui.push_group((96.0, self.ui.height()), Layout::Freeform);
+        if Button::with_text(&mut self.ui, canvas, input, button, self.assets.language.tr("paint.palette")).clicked() {
+            self.palette_window.set_open(!self.palette_window.is_open());
+        }
+        self.ui.pop_group();
+        self.ui.space(8.0);
+
+        // grid
+
+        let grid_text = match self.grid_mode {
+            GridMode::Hidden => "paint.grid_off",
+            GridMode::Visible => "paint.grid_on",
+            GridMode::Snap => "paint.grid_snap",
+        };
+        let grid_text = self.assets.language.tr(grid_text);
+        self.ui.push_group((96.0, self.ui.height()), Layout::Freeform);
+        if Button::with_text(&mut self.ui, canvas, input, button, grid_text).clicked() {
+            self.grid_mode = match self.grid_mode {
+                GridMode::Hidden => GridMode::Visible,
+                GridMode::Visible => GridMode::Snap,
+                GridMode::Snap => GridMode::Hidden,
+            };
+        }
+        self.ui.pop_group();
+        self.ui.space(8.0);
+
+        // timelapse recording
+
+        let recording_text =
+            self.assets.language.tr(if self.recording.is_some() { "paint.stop_recording" } else { "paint.record" });
+        self.ui.push_group((96.0, self.ui.height()), Layout::Freeform);
+        let toggle_recording = Button::with_text(&mut self.ui, canvas, input, button, recording_text).clicked();
+        self.ui.pop_group();
+        self.ui.space(8.0);
+
+        // invite link
+
+        self.ui.push_group((128.0, self.ui.height()), Layout::Freeform);
+        if Button::with_text(&mut self.ui, canvas, input, button, self.assets.language.tr("paint.copy_invite_link"))
+            .clicked()
+        {
+            if let Some(link) = self.peer.invite_link() {
+                match clipboard::copy_text(&link) {
+                    Ok(()) => notify_tr!(self.log, self.assets.language, Success, "notifications.invite_link_copied"),
+                    Err(error) => notify!(self.log, Error, "{}", error),
+                }
+            }
+        }
+        self.ui.pop_group();
+        self.ui.space(8.0);
+
+        // people list toggle
+
+        let people_text = self.assets.language.format("paint.people", &[&(self.peer.mates().len() + 1)]);
+        self.ui.push_group((96.0, self.ui.height()), Layout::Freeform);
+        if Button::with_text(&mut self.ui, canvas, input, button, &people_text).clicked() {
+            self.people_list_open = !self.people_list_open;
+            // both panels are in the same corner
+            self.qr_code.close();
+        }
+        self.ui.pop_group();
+
+        // room ID
+
+        if self.peer.is_host() {
+            // "Room ID" text
+            self.ui.push_group((64.0, self.ui.height()), Layout::Freeform);
+            let room_id_text = self.assets.language.tr("paint.room_id");
+            self.ui.text(canvas, room_id_text, self.assets.colors.text, (AlignH::Center, AlignV::Middle));
+            self.ui.pop_group();
+
+            // the room ID itself, which can be clicked to copy it
+            let id_text = self.peer.room_id().unwrap().to_owned();
+            // some matchmakers hand out longer IDs
+            let id_width = f32::max(self.ui.text_size(&id_text).0 + 16.0, 64.0);
+            self.ui.push_group((id_width, self.ui.height()), Layout::Freeform);
+            self.ui.set_font(self.assets.sans_bold.clone());
+            self.ui.text(canvas, &id_text, self.assets.colors.text, (AlignH::Center, AlignV::Middle));
+            self.tooltip.hover(&self.ui, input, self.assets.language.tr("paint.click_to_copy"));
+            if self.ui.has_mouse(input) && input.mouse_button_just_pressed(MouseButton::Left) {
+                match clipboard::copy_text(&id_text) {
+                    Ok(()) => notify_tr!(self.log, self.assets.language, Success, "notifications.room_id_copied"),
+                    Err(error) => notify!(self.log, Error, "{}", error),
+                }
+            }
+            self.ui.pop_group();
+
+            // QR code toggle
+            self.ui.push_group((72.0, self.ui.height()), Layout::Freeform);
+            if Button::with_text(&mut self.ui, canvas, input, button, self.assets.language.tr("paint.qr_code"))
+                .clicked()
+            {
+                if let Some(link) = self.peer.invite_link() {
+                    ok_or_log!(self.log, self.qr_code.toggle(link));
+                    self.people_list_open = false;
+                }
+            }
+            self.ui.pop_group();
+        }
+
+        self.ui.pop_group();
+        self.ui.pop_group();
+
+        self.ui.pop_group();
+
+        input.unlock_mouse_buttons();
+
+        if toggle_recording {
+            if self.recording.is_some() {
+                self.stop_recording();
+            } else if self.timelapse_dialog.is_none() {
+                // a new recording is only started once the last one's been saved, so that it isn't lost
+                self.recording = Some(Recording::start(&mut self.paint_canvas));
+                log_tr!(self.log, self.assets.language, "notifications.recording_timelapse");
+            }
+        }
+    }
+
+}
+
+impl AppState for State {
+
+    fn process(
+        &mut self,
+        StateArgs {
+            canvas,
+            coordinate_system_helper,
+            input,
+        }: StateArgs,
+    ) {
+        canvas.clear(Color::WHITE);
+        ok_or_log!(self.log, self.assets.reload_color_scheme());
+
+        // network
+
+        // incoming packets wake the event loop up by themselves. what we send only changes with input, so the network
+        // only has to be polled at the update rate for a little while after it, so that the last of it is sent out.
+        // otherwise, frames only have to come often enough for pings and locks expiring
+        if let Some(waker) = input.waker() {
+            self.peer.set_waker(waker);
+        }
+        if input.time_since_input() < Self::TIME_PER_UPDATE * 2 {
+            input.request_redraw_after(Self::TIME_PER_UPDATE.as_secs_f32());
+        } else {
+            input.request_redraw_after(Self::IDLE_POLL_INTERVAL.as_secs_f32());
+        }
+        if input.key_just_typed(VirtualKeyCode::F3) {
+            self.diagnostics.toggle();
+        }
+        ok_or_log!(self.log, self.diagnostics.tick(&mut self.peer));
+        let mut remember_room = false;
+        // nobody's edits can reach into regions locked by someone else
+        self.paint_canvas.set_locks(self.locks());
+        match self.peer.tick() {
+            Ok(messages) => for message in messages {
+                input.request_redraw();
+                self.paint_canvas.set_editor(match &message {
+                    Message::Stroke(sender, _) |
+                    Message::ChunkTiles(sender, ..) |
+                    Message::Tool(sender, ..) |
+                    Message::Left(sender, _) => Some(*sender),
+                    _ => None,
+                });
+                match message {
+                    Message::Stroke(_, points) => self.paint_canvas.stroke_points(&points),
+
+                    Message::NewMate(addr) => self.canvas_data_queue.push_back(addr),
+                    Message::CanvasData(chunk, png) =>
+                        Self::canvas_data(&mut self.log, &mut self.paint_canvas, chunk, &png),
+                    Message::ChunkTiles(_, chunk, tiles) =>
+                        ok_or_log!(self.log, self.paint_canvas.decode_tile_data(chunk, &tiles)),
+                    Message::Tool(sender, name, payload) =>
+                        Self::tool_packet(&mut self.tools, &mut self.log, &mut self.paint_canvas, sender, &name, &payload),
+                    Message::Background(color) => self.paint_canvas.set_background(Background::from_packet(color)),
+                    Message::StoredChunk(chunk, png) => {
+                        Self::canvas_data(&mut self.log, &mut self.paint_canvas, chunk, &png);
+                        self.room_storage.mark_stored(chunk, &png);
+                    },
+                    Message::Persisted(Some(days)) => {
+                        log_tr!(self.log, self.assets.language, "notifications.canvas_kept", days);
+                        remember_room = true;
+                    },
+                    Message::Persisted(None) =>
+                        log_tr!(self.log, self.assets.language, "notifications.canvas_not_kept"),
+
+                    Message::Joined(nickname) => {
+                        log_tr!(self.log, self.assets.language, "notifications.joined", nickname);
+                        audio::play(&self.config.audio, Cue::Joined);
+                        // make sure the newcomer gets to know our viewport, whether we're idle, and our lock
+                        self.sent_viewport = None;
+                        self.sent_idle = None;
+                        self.sent_lock = None;
+                    },
+                    Message::Left(addr, nickname) => {
+                        log_tr!(self.log, self.assets.language, "notifications.left", nickname);
+                        tools::left(&mut self.tools, &mut self.paint_canvas, addr);
+                        audio::play(&self.config.audio, Cue::Left);
+                    },
+                    Message::MissingTools(nickname, tools) => log_tr!(
+                        self.log,
+                        self.assets.language,
+                        "notifications.missing_tools",
+                        nickname,
+                        tools.join(", "),
+                    ),
+                    Message::UnsupportedTool(nickname, tool) => log_tr!(
+                        self.log,
+                        self.assets.language,
+                        "notifications.unsupported_tool",
+                        nickname,
+                        tool,
+                    ),
+                    Message::NicknameTaken(nickname) => log_tr!(
+                        self.log,
+                        self.assets.language,
+                        "notifications.nickname_taken",
+                        nickname,
+                    ),
+
+                    Message::Error(error) => self.error = Some(error),
+                    x => log::warn!("unknown message: {:?}", x),
+                }
+            },
+            Err(error) => {
+                log::error!("{}", error);
+            },
+        }
+        // persistent rooms can be reopened later, which makes them worth remembering
+        if let (true, Some(room_id)) = (remember_room, self.peer.room_id()) {
+            self.config.lobby.remember_room(self.peer.matchmaker_addr(), room_id);
+            ok_or_log!(self.log, self.config.save());
+        }
+        if tools::tick(&mut self.tools, &mut self.paint_canvas) {
+            input.request_redraw();
+        }
+        // everything else painted during the frame is our own doing
+        self.paint_canvas.set_editor(None);
+
+        if let Some(autosave) = &mut self.autosave {
+            ok_or_log!(self.log, autosave.tick(&mut self.paint_canvas));
+        }
+        if self.peer.is_host() && self.peer.persistence().is_some() {
+            ok_or_log!(self.log, self.room_storage.tick(&mut self.paint_canvas, &self.peer));
+        }
+
+        // idle detection
+
+        let idle = self.config.presence.idle_after().is_some_and(|after| input.time_since_input() >= after);
+        if self.sent_idle != Some(idle) {
+            ok_or_log!(self.log, self.peer.send_idle(idle));
+            self.sent_idle = Some(idle);
+        }
+
+        // timelapse
+
+        if let Some(recording) = &mut self.recording {
+            recording.tick(&mut self.paint_canvas);
+        }
+        self.poll_timelapse_dialog();
+        if let Some(result) = self.timelapse_saved.as_ref().and_then(|receiver| receiver.try_recv().ok()) {
+            self.timelapse_saved = None;
+            match result {
+                Ok(path) => {
+                    let path = path.display();
+                    notify_tr!(self.log, self.assets.language, Success, "notifications.timelapse_saved", path)
+                },
+                Err(error) => notify!(self.log, Error, "{}", error),
+            }
+        }
+
+        if !self.uploads.is_empty() {
+            input.request_redraw();
+        }
+        for addr in self.canvas_data_queue.drain(..) {
+            let background = self.paint_canvas.background().to_packet();
+            ok_or_log!(self.log, self.peer.send_background(Some(addr), background));
+            let chunks: Vec<_> = self.paint_canvas.chunk_positions().collect();
+            ok_or_log!(self.log, self.peer.send_chunk_count(addr, chunks.len() as u32));
+            self.uploads.start(addr, chunks);
+        }
+        // when the connection can't keep up, the chunks are left in the queue until it catches up
+        let next_uploads = if self.peer.can_send_canvas_data() { self.uploads.next(self.peer.mates()) } else { vec![] };
+        for (addr, chunk_position) in next_uploads {
+            match self.paint_canvas.cached_png_data(chunk_position) {
+                Some(png_data) => self.send_chunk(addr, chunk_position, png_data),
+                None => if let Some(pixels) = self.paint_canvas.chunk_pixels(chunk_position) {
+                    self.encoder.encode(addr, chunk_position, pixels);
+                },
+            }
+        }
+        for encoded in self.encoder.finished() {
+            let (to, position) = (encoded.to, encoded.position);
+            let png_data = match &encoded.png_data {
+                Some(png_data) => png_data,
+                None => {
+                    let position = format!("{}, {}", position.0, position.1);
+                    notify_tr!(self.log, self.assets.language, Error, "notifications.encode_chunk_failed", position);
+                    continue
+                },
+            };
+            if self.paint_canvas.cache_png_data(position, &encoded.pixels, png_data) {
+                // the encoded data is cached, so sending it again later won't need encoding it again
+                let png_data = png_data.clone();
+                self.send_chunk(to, position, png_data);
+            } else if encoded.attempt + 1 >= ChunkEncoder::MAX_ATTEMPTS {
+                // the chunk keeps getting drawn to faster than it can be encoded in the background. encoding it here
+                // instead is guaranteed to capture its current state, at the cost of a hitch
+                if let Some(png_data) = self.paint_canvas.chunk_png_data(position) {
+                    self.send_chunk(to, position, png_data);
+                }
+            } else if let Some(pixels) = self.paint_canvas.chunk_pixels(position) {
+                // the chunk was drawn to while it was being encoded. sending the outdated version would overwrite the
+                // strokes the receiver gets in the meantime, so it has to be encoded again
+                self.encoder.encode_again(&encoded, pixels);
+            }
+        }
+
+        // UI setup
+        self.ui.begin(get_window_size(&coordinate_system_helper, input.ui_scale()), Layout::Vertical);
+        // tools that take keyboard input, like the text tool while typing, get Tab and Enter to themselves
+        if self.tools[self.current_tool].captures_keyboard() {
+            self.ui.clear_focus();
+        } else {
+            self.ui.process_focus(input);
+        }
+        self.ui.set_font(self.assets.sans.clone());
+        self.ui.set_font_size(14.0);
+
+        // toolbar and canvas. the toolbar is laid out before the canvas if it's docked to the left or top edge of the
+        // window, and after it otherwise
+        let toolbar_position = self.config.ui.toolbar_position;
+        let layout = if toolbar_position.is_vertical() { Layout::Horizontal } else { Layout::Vertical };
+        let toolbar_first = matches!(toolbar_position, ToolbarPosition::Left | ToolbarPosition::Top);
+        self.ui.push_group((self.ui.width(), self.ui.height() - Self::BAR_SIZE), layout);
+        if toolbar_first {
+            self.process_toolbar_with_lock(canvas, input);
+        }
+        // the context menu and the quit dialog take over the mouse while they're open
+        if self.context_menu.is_open() || self.dragging_toolbar || self.quit_dialog.is_open() {
+            input.lock_mouse_buttons();
+        }
+        self.process_canvas(canvas, input);
+        input.unlock_mouse_buttons();
+        if !toolbar_first {
+            self.process_toolbar_with_lock(canvas, input);
+        }
+        self.ui.pop_group();
+        self.process_undo_redo(input);
+        self.resync();
+
+        // bar
+        self.process_bar(canvas, input);
+
+        // overlays
+        self.process_toolbar_drag(canvas, input);
+        self.process_context_menu(canvas, input);
+        self.tooltip.process(&mut self.ui, canvas, input, TooltipArgs {
+            fill: self.assets.colors.panel,
+            outline: self.assets.colors.separator,
+            text: self.assets.colors.text,
+        });
+        self.process_quit_dialog(canvas, input);
+    }
+
+    fn close_requested(&mut self) -> bool {
+        if self.paint_canvas.has_unsaved_changes() {
+            self.quit_dialog.open();
+            false
+        } else {
+            true
+        }
+    }
+
+    fn wants_exit(&self) -> bool {
+        self.quitting
+    }
+
+    fn wants_panel_window(&self) -> bool {
+        self.palette_window.is_open()
+    }
+
+    fn process_panel_window(
+        &mut self,
+        StateArgs {
+            canvas,
+            coordinate_system_helper,
+            input,
+        }: StateArgs,
+    ) {
+        let tool = &mut self.tools[self.current_tool];
+        self.palette_window.process(canvas, coordinate_system_helper, input, &self.assets, &self.config, tool.as_mut());
+    }
+
+    fn panel_window_closed(&mut self) {
+        self.palette_window.set_open(false);
+    }
+
+    fn ui_scale(&self) -> f32 {
+        self.config.ui.scale()
+    }
+
+    fn next_state(mut self: Box<Self>) -> Box<dyn AppState> {
+        // the timelapse is saved when leaving the room, so that it doesn't get lost
+        if self.error.is_some() || self.cancelled {
+            self.stop_recording();
+            // the save dialog stays open after the room is left, so the timelapse is saved once it's closed
+            if let Some((dialog, recording)) = self.timelapse_dialog.take() {
+                std::thread::spawn(move || match dialog.wait() {
+                    Ok(Some(path)) => {
+                        let _ = Self::save_timelapse(recording, path);
+                    },
+                    Ok(None) => (),
+                    Err(error) => log::error!("could not open the file dialog: {}", error),
+                });
+            }
+        }
+
+        if let Some(error) = self.error {
+            Box::new(lobby::State::new(self.assets, self.config, Some(&error)))
+        } else if self.cancelled {
+            Box::new(lobby::State::new(self.assets, self.config, None))
+        } else {
+            self
+        }
+    }
+
+}
@@ -0,0 +1,128 @@
+// the palette window. a secondary OS window with the palette in it, for people with more than one monitor who'd
+// rather not have the colors take up space in the bottom bar. picking a color changes the color of the current tool.
+
+use skulpin::CoordinateSystemHelper;
+use skulpin::skia_safe::*;
+
+use crate::assets::Assets;
+use crate::config::UserConfig;
+use crate::ui::*;
+use crate::util::*;
+use super::tools::{COLOR_PALETTE, Tool};
+
+pub struct PaletteWindow {
+    // the window has its own UI, since it has a separate canvas of a different size
+    ui: Ui,
+    open: bool,
+}
+
+impl PaletteWindow {
+
+    const SWATCH_SIZE: f32 = 24.0;
+    const SWATCH_SPACING: f32 = 4.0;
+
+    pub fn new() -> Self {
+        Self {
+            ui: Ui::new(),
+            open: false,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+    }
+
+    // processes swatches for the colors, wrapped into rows that fit the current group. returns the color that was
+    // clicked, if any
+    fn process_swatches(
+        ui: &mut Ui,
+        canvas: &mut Canvas,
+        input: &Input,
+        assets: &Assets,
+        colors: &[u32],
+        selected: Color4f,
+    ) -> Option<Color4f> {
+        let per_row = ((ui.width() + Self::SWATCH_SPACING) / (Self::SWATCH_SIZE + Self::SWATCH_SPACING)).max(1.0);
+        let mut clicked = None;
+        for row in colors.chunks(per_row as usize) {
+            ui.push_group((ui.width(), Self::SWATCH_SIZE), Layout::Horizontal);
+            for &hex_color in row {
+                let color = hex_color4f(hex_color);
+                ui.push_group((Self::SWATCH_SIZE, Self::SWATCH_SIZE), Layout::Freeform);
+                ui.fill(canvas, color);
+                if selected == color {
+                    ui.outline(canvas, assets.colors.text, 2.0);
+                } else if ui.has_mouse(input) {
+                    ui.outline(canvas, assets.colors.separator, 1.0);
+                }
+                if ui.has_mouse(input) && input.mouse_button_just_pressed(MouseButton::Left) {
+                    clicked = Some(color);
+                }
+                ui.pop_group();
+                ui.space(Self::SWATCH_SPACING);
+            }
+            ui.pop_group();
+            ui.space(Self::SWATCH_SPACING);
+        }
+        clicked
+    }
+
+    fn process_heading(ui: &mut Ui, canvas: &mut Canvas, assets: &Assets, text: &str) {
+        ui.push_group((ui.width(), 24.0), Layout::Freeform);
+        ui.text(canvas, text, assets.colors.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+    }
+
+    pub fn process(
+        &mut self,
+        canvas: &mut Canvas,
+        coordinate_system_helper: &CoordinateSystemHelper,
+        input: &Input,
+        assets: &Assets,
+        config: &UserConfig,
+        tool: &mut dyn Tool,
+    ) {
+        canvas.clear(assets.colors.panel);
+
+        let ui = &mut self.ui;
+        ui.begin(get_window_size(coordinate_system_helper, input.ui_scale()), Layout::Vertical);
+        ui.set_font(assets.sans.clone());
+        ui.set_font_size(14.0);
+        ui.pad((24.0, 24.0));
+
+        let selected = match tool.color() {
+            Some(color) => color,
+            None => {
+                let text = assets.language.tr("palette.no_color");
+                ui.push_group(ui.size(), Layout::Freeform);
+                ui.text(canvas, text, assets.colors.text, (AlignH::Center, AlignV::Top));
+                ui.pop_group();
+                return
+            },
+        };
+
+        let mut clicked = None;
+        let sections = [
+            ("palette.builtin", COLOR_PALETTE),
+            ("palette.saved", config.brush.palette.as_slice()),
+            ("palette.recent", config.brush.recent_colors.as_slice()),
+        ];
+        for &(heading, colors) in &sections {
+            if colors.is_empty() { continue }
+            Self::process_heading(ui, canvas, assets, assets.language.tr(heading));
+            if let Some(color) = Self::process_swatches(ui, canvas, input, assets, colors, selected) {
+                clicked = Some(color);
+            }
+            ui.space(12.0);
+        }
+
+        if let Some(color) = clicked {
+            tool.set_color(color);
+        }
+    }
+
+}
@@ -0,0 +1,99 @@
+// the QR code panel. shows the room's invite link as a QR code, so that it can be scanned with a phone and passed on
+// to whoever should join.
+
+use qrcode::{Color as Module, QrCode};
+use skulpin::skia_safe::*;
+
+use crate::assets::Assets;
+use crate::ui::*;
+
+pub struct QrCodePanel {
+    // the invite link and its QR code. None while the panel is closed
+    code: Option<(String, QrCode)>,
+}
+
+impl QrCodePanel {
+
+    const MODULE_SIZE: f32 = 4.0;
+    // the light border around the code, in modules. scanners need it to find the code
+    const QUIET_ZONE: usize = 4;
+
+    pub fn new() -> Self {
+        Self {
+            code: None,
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.code = None;
+    }
+
+    // opens the panel with the given invite link, or closes it if it's already open
+    pub fn toggle(&mut self, link: String) -> Result<(), qrcode::types::QrError> {
+        if self.code.is_some() {
+            self.code = None;
+        } else {
+            let code = QrCode::new(link.as_bytes())?;
+            self.code = Some((link, code));
+        }
+        Ok(())
+    }
+
+    pub fn process(&self, ui: &mut Ui, canvas: &mut Canvas, assets: &Assets) {
+        let (link, code) = match &self.code {
+            Some(code) => code,
+            None => return,
+        };
+
+        let modules = code.width() + Self::QUIET_ZONE * 2;
+        let code_size = modules as f32 * Self::MODULE_SIZE;
+        let width = f32::max(code_size, ui.text_size(link).0) + 32.0;
+        let height = 16.0 + 16.0 + 8.0 + code_size + 8.0 + 16.0 + 16.0;
+
+        ui.push_group(ui.size(), Layout::Freeform);
+        ui.pad((32.0, 32.0));
+        ui.push_group((width, height), Layout::Vertical);
+        ui.align((AlignH::Right, AlignV::Bottom));
+        ui.fill(canvas, assets.colors.panel);
+        ui.outline(canvas, assets.colors.separator, 1.0);
+        ui.pad((16.0, 16.0));
+
+        ui.push_group((ui.width(), 16.0), Layout::Freeform);
+        ui.set_font(assets.sans_bold.clone());
+        ui.text(canvas, assets.language.tr("paint.scan_to_join"), assets.colors.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+        ui.space(8.0);
+
+        // the code is always dark on light, no matter the color scheme, as that's what scanners expect
+        ui.push_group((ui.width(), code_size), Layout::Freeform);
+        ui.draw_on_canvas(canvas, |canvas| {
+            let x_offset = ((width - 32.0 - code_size) / 2.0).round();
+            let light = Paint::new(Color4f::from(Color::WHITE), None);
+            let dark = Paint::new(Color4f::from(Color::BLACK), None);
+            canvas.draw_rect(Rect::from_xywh(x_offset, 0.0, code_size, code_size), &light);
+            for y in 0..code.width() {
+                for x in 0..code.width() {
+                    if code[(x, y)] == Module::Dark {
+                        let rect = Rect::from_xywh(
+                            x_offset + (x + Self::QUIET_ZONE) as f32 * Self::MODULE_SIZE,
+                            (y + Self::QUIET_ZONE) as f32 * Self::MODULE_SIZE,
+                            Self::MODULE_SIZE,
+                            Self::MODULE_SIZE,
+                        );
+                        canvas.draw_rect(rect, &dark);
+                    }
+                }
+            }
+        });
+        ui.pop_group();
+        ui.space(8.0);
+
+        ui.push_group((ui.width(), 16.0), Layout::Freeform);
+        ui.text(canvas, link, assets.colors.text, (AlignH::Center, AlignV::Middle));
+        ui.pop_group();
+
+        ui.pop_group();
+        ui.pop_group();
+    }
+
+}
@@ -0,0 +1,908 @@
+// the brush tool. paints strokes as smooth curves, which are sent to everyone as vector data so that they look the
+// same everywhere. other people's strokes are painted slightly delayed, spread out over the time they took to draw
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use ::image::{ImageDecoder, ImageError, ImageFormat, Rgba};
+use ::image::codecs::png::PngDecoder;
+use ::image::error::{LimitError, LimitErrorKind};
+use netcanv_protocol::build;
+use serde::{Deserialize, Serialize};
+use skulpin::skia_safe::*;
+use skulpin::skia_safe::paint as skpaint;
+
+use crate::app::paint::Log;
+use crate::app::paint::history::History;
+use crate::assets::Assets;
+use crate::i18n::Language;
+use crate::paint_canvas::*;
+use crate::net::{self, Peer};
+use crate::ui::*;
+use crate::util::*;
+use super::{COLOR_PALETTE, LabeledSlider, Tool, ToolArgs, process_color_swatch, process_labeled_slider};
+use super::selection::encode_png;
+
+#[derive(PartialEq, Eq)]
+enum PaintMode {
+    None,
+    Paint,
+    Erase,
+}
+
+// what the pen pressure affects
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum PressureMode {
+    Off,
+    Size,
+    Opacity,
+}
+
+// a single imprint of the stamp
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct Dab {
+    center: (f32, f32),
+    // the length of the stamp's longer side
+    size: f32,
+    opacity: f32,
+}
+
+// a point the brush passed, in canvas coordinates
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct VectorPoint {
+    x: f32,
+    y: f32,
+    pressure: f32,
+}
+
+// the brush settings a stroke is painted with
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct StrokeStyle {
+    // the color as 0xRRGGBBAA, with the opacity already applied. None erases
+    color: Option<u32>,
+    size: f32,
+    softness: f32,
+    pressure_mode: PressureMode,
+}
+
+// a run of consecutive points of a stroke. like any curve, it's painted between its second and second to last point,
+// so consecutive runs of the same stroke overlap by three points
+#[derive(Clone, Serialize, Deserialize)]
+struct VectorStroke {
+    // runs of the same stroke share its ID, which is unique among the strokes painted by the sender
+    id: u32,
+    style: StrokeStyle,
+    points: Vec<VectorPoint>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    // the stamp the sender's following dabs are painted with, PNG-encoded with straight alpha
+    Stamp(Vec<u8>),
+    Dabs(Vec<Dab>),
+    Stroke(VectorStroke),
+}
+
+impl StrokeStyle {
+
+    // the brush a point with the given pressure is painted with
+    fn brush(&self, pressure: f32) -> Brush {
+        let (stroke_width, opacity) = match self.pressure_mode {
+            PressureMode::Off => (self.size, 1.0),
+            PressureMode::Size => (f32::max(self.size * pressure, 1.0), 1.0),
+            PressureMode::Opacity => (self.size, pressure),
+        };
+        match self.color {
+            Some(color) => {
+                let color = hex_color4f(color);
+                Brush::Draw {
+                    color: Color4f { a: color.a * opacity, ..color },
+                    stroke_width,
+                    softness: self.softness,
+                }
+            },
+            // erasing isn't affected by the opacity
+            None => Brush::Erase {
+                stroke_width,
+                softness: self.softness,
+            },
+        }
+    }
+
+}
+
+impl VectorStroke {
+
+    // clamps the stroke's settings to what the brush's controls allow, and returns whether its points can be painted.
+    // strokes come from other people, so there's no telling what's in them
+    fn sanitize(&mut self) -> bool {
+        let style = &mut self.style;
+        if !style.size.is_finite() || !style.softness.is_finite() {
+            return false
+        }
+        style.size = style.size.clamp(BrushTool::MIN_SIZE, BrushTool::MAX_SIZE);
+        style.softness = style.softness.clamp(0.0, 1.0);
+        for point in &mut self.points {
+            if !PaintCanvas::is_in_bounds(Point::new(point.x, point.y)) || !point.pressure.is_finite() {
+                return false
+            }
+            point.pressure = point.pressure.clamp(0.0, 1.0);
+        }
+        // without this, the curve between two points could be made of so many lines that painting it would never end
+        self.points.windows(2).all(|pair| {
+            let (a, b) = (pair[0], pair[1]);
+            Point::distance(Point::new(a.x, a.y), Point::new(b.x, b.y)) <= BrushTool::MAX_POINT_DISTANCE
+        })
+    }
+
+    // splits the run into the segments of its curve, each of which is four consecutive points
+    fn segments(&self) -> impl Iterator<Item = [VectorPoint; 4]> + '_ {
+        self.points.windows(4).map(|window| [window[0], window[1], window[2], window[3]])
+    }
+
+    fn stroke_points(&self) -> Vec<StrokePoint> {
+        self.points
+            .iter()
+            .map(|point| StrokePoint {
+                point: Point::new(point.x, point.y),
+                brush: self.style.brush(point.pressure),
+            })
+            .collect()
+    }
+
+}
+
+// a segment of someone else's stroke that's yet to be painted
+struct PendingSegment {
+    paint_at: Instant,
+    style: StrokeStyle,
+    // the four points whose curve is the segment
+    points: [VectorPoint; 4],
+}
+
+// the strokes received from someone else that are yet to be painted
+struct RemoteStrokes {
+    segments: VecDeque<PendingSegment>,
+    // when the last run of strokes was received
+    last_received: Instant,
+}
+
+struct Stamp {
+    image: Image,
+    png: Vec<u8>,
+    // whether the stamp was sent to everyone in the room yet
+    sent: bool,
+}
+
+pub struct BrushTool {
+    paint_mode: PaintMode,
+    paint_color: Color4f,
+    color_picker: ColorPicker,
+    brush_size_slider: Slider,
+    // the opacity and softness in percent
+    opacity_slider: Slider,
+    softness_slider: Slider,
+    pressure_mode: PressureMode,
+    // the stabilizer strength in percent
+    stabilizer_slider: Slider,
+    // the position of the brush. this lags behind the mouse if the stabilizer is enabled
+    position: Point,
+    // the last few points of the stroke being painted, whose curve can't be painted yet
+    curve: Vec<VectorPoint>,
+    // the ID of the stroke being painted, and the one after it
+    stroke_id: u32,
+    next_stroke_id: u32,
+    // the runs of strokes painted since the last network tick
+    stroke_buffer: Vec<VectorStroke>,
+
+    // the stamp replacing the round tip, if any
+    stamp: Option<Stamp>,
+    // the distance the brush has to travel before the next dab is stamped
+    distance_to_dab: f32,
+    dab_buffer: Vec<Dab>,
+    // the people who've received our stamp. anyone who joins later gets it sent separately
+    stamp_sent_to: HashSet<SocketAddr>,
+    // the stamps other people in the room paint with
+    stamps: HashMap<SocketAddr, Image>,
+
+    // other people's strokes that are held back to be painted smoothly
+    remote_strokes: HashMap<SocketAddr, RemoteStrokes>,
+}
+
+impl Dab {
+
+    // clamps the size and opacity of a dab received from someone else to what the brush allows, and returns whether
+    // the dab can be drawn
+    fn sanitize(&mut self) -> bool {
+        if !self.size.is_finite() || !self.opacity.is_finite() {
+            return false
+        }
+        self.size = self.size.clamp(BrushTool::MIN_SIZE, BrushTool::MAX_SIZE);
+        self.opacity = self.opacity.clamp(0.0, 1.0);
+        PaintCanvas::is_in_bounds(Point::from(self.center))
+    }
+
+    fn rect(&self, image: &Image) -> Rect {
+        let scale = self.size / i32::max(image.width(), image.height()) as f32;
+        let (width, height) = (image.width() as f32 * scale, image.height() as f32 * scale);
+        let (x, y) = self.center;
+        Rect::from_xywh(x - width / 2.0, y - height / 2.0, width, height)
+    }
+
+}
+
+// draws the dabs all at once, as instances of the stamp
+fn draw_dabs(paint_canvas: &mut PaintCanvas, image: &Image, dabs: &[Dab]) {
+    let instances: Vec<_> = dabs.iter().map(|dab| (dab.rect(image), dab.opacity)).collect();
+    let mut paint = Paint::default();
+    paint.set_anti_alias(true);
+    paint.set_filter_quality(FilterQuality::Medium);
+    paint_canvas.draw_instances(image, &instances, &paint);
+}
+
+// decodes a stamp received from someone else. stamps larger than the brush can ever be are refused before they're
+// decoded, so that a bogus image can't take up all the memory
+fn decode_stamp(png: &[u8]) -> Result<Image, ImageError> {
+    let too_large = || ImageError::Limits(LimitError::from_kind(LimitErrorKind::DimensionError));
+    if png.len() > BrushTool::MAX_STAMP_BYTES {
+        return Err(too_large())
+    }
+    let (width, height) = PngDecoder::new(png)?.dimensions();
+    if width > BrushTool::MAX_STAMP_SIZE || height > BrushTool::MAX_STAMP_SIZE {
+        return Err(too_large())
+    }
+    let image = ::image::load_from_memory_with_format(png, ImageFormat::Png)?.to_rgba8();
+    Ok(rgba_image_to_skia(&image))
+}
+
+impl BrushTool {
+
+    const MIN_SIZE: f32 = 1.0;
+    const MAX_SIZE: f32 = 64.0;
+    // the largest stamps can be. stamps are never painted larger than MAX_SIZE, so anything bigger is only a waste
+    const MAX_STAMP_SIZE: u32 = 1024;
+    const MAX_STAMP_BYTES: usize = 4 * 1024 * 1024;
+    // the length of the string at 100% stabilizer strength
+    const MAX_STRING_LENGTH: f32 = 64.0;
+    // the farthest apart two consecutive points of someone else's stroke can be
+    const MAX_POINT_DISTANCE: f32 = 8192.0;
+    // the distance between dabs, relative to the brush size
+    const DAB_SPACING: f32 = 0.25;
+    // how far behind other people's strokes are painted. this has to be at least as long as the time between network
+    // ticks, otherwise the stroke stutters when a packet comes in late
+    const REMOTE_STROKE_DELAY: Duration = Duration::from_millis(100);
+
+    pub fn new() -> Self {
+        Self {
+            paint_mode: PaintMode::None,
+            paint_color: hex_color4f(COLOR_PALETTE[0]),
+            color_picker: ColorPicker::new(),
+            brush_size_slider: Slider::new(4.0, Self::MIN_SIZE, Self::MAX_SIZE, SliderStep::Discrete(1.0)),
+            opacity_slider: Slider::new(100.0, 1.0, 100.0, SliderStep::Discrete(1.0)),
+            softness_slider: Slider::new(0.0, 0.0, 100.0, SliderStep::Discrete(1.0)),
+            pressure_mode: PressureMode::Off,
+            stabilizer_slider: Slider::new(0.0, 0.0, 100.0, SliderStep::Discrete(1.0)),
+            position: Point::new(0.0, 0.0),
+            curve: Vec::new(),
+            stroke_id: 0,
+            next_stroke_id: 0,
+            stroke_buffer: Vec::new(),
+
+            stamp: None,
+            distance_to_dab: 0.0,
+            dab_buffer: Vec::new(),
+            stamp_sent_to: HashSet::new(),
+            stamps: HashMap::new(),
+
+            remote_strokes: HashMap::new(),
+        }
+    }
+
+    // turns the selected pixels into the brush's stamp
+    fn capture_stamp(
+        &mut self,
+        selection: Option<IRect>,
+        paint_canvas: &mut PaintCanvas,
+        log: &mut Log,
+        language: &Language,
+    ) {
+        let rect = match selection {
+            Some(rect) => rect,
+            None => return,
+        };
+        if rect.width() as u32 > Self::MAX_STAMP_SIZE || rect.height() as u32 > Self::MAX_STAMP_SIZE {
+            notify_tr!(
+                log,
+                language,
+                Error,
+                "notifications.stamp_too_large",
+                Self::MAX_STAMP_SIZE,
+                Self::MAX_STAMP_SIZE
+            );
+            return
+        }
+        let image = paint_canvas.capture(rect);
+        match encode_png(&image) {
+            Ok(png) => {
+                self.stamp = Some(Stamp {
+                    image: rgba_image_to_skia(&image),
+                    png,
+                    sent: false,
+                });
+                self.stamp_sent_to.clear();
+            },
+            Err(error) => notify_tr!(log, language, Error, "notifications.encode_stamp_failed", error),
+        }
+    }
+
+    // stamps dabs along the line from a to b, spaced evenly across consecutive calls
+    fn stamp_line(&mut self, paint_canvas: &mut PaintCanvas, a: Point, b: Point, size: f32, opacity: f32) {
+        let image = match &self.stamp {
+            Some(stamp) => stamp.image.clone(),
+            None => return,
+        };
+        let spacing = f32::max(size * Self::DAB_SPACING, 1.0);
+        let length = (b - a).length();
+        let mut distance = self.distance_to_dab;
+        let first_dab = self.dab_buffer.len();
+        while distance <= length {
+            let t = if length > 0.0 { distance / length } else { 0.0 };
+            let center = a + (b - a) * t;
+            let dab = Dab {
+                center: (center.x, center.y),
+                size,
+                opacity,
+            };
+            self.dab_buffer.push(dab);
+            distance += spacing;
+        }
+        draw_dabs(paint_canvas, &image, &self.dab_buffer[first_dab..]);
+        self.distance_to_dab = distance - length;
+    }
+
+    // paints the curve between the middle two of the four points
+    fn paint_curve(
+        &mut self,
+        paint_canvas: &mut PaintCanvas,
+        history: &mut History,
+        style: StrokeStyle,
+        window: &[VectorPoint],
+    ) {
+        let run = VectorStroke {
+            id: self.stroke_id,
+            style,
+            points: window.to_vec(),
+        };
+        let points = run.stroke_points();
+        for (from, to, brush) in PaintCanvas::curve_segments(&points) {
+            history.save_chunks(paint_canvas, PaintCanvas::chunks_in_stroke(from, to, brush.stroke_width()));
+        }
+        paint_canvas.stroke_curve(&points);
+        match self.stroke_buffer.last_mut() {
+            Some(last) if last.id == run.id
+                && last.style == run.style
+                && last.points[last.points.len() - 3..] == window[..3] => last.points.push(window[3]),
+            _ => self.stroke_buffer.push(run),
+        }
+    }
+
+    // adds a point to the stroke, painting the curve that's now known
+    fn extend_curve(
+        &mut self,
+        paint_canvas: &mut PaintCanvas,
+        history: &mut History,
+        style: StrokeStyle,
+        point: VectorPoint,
+    ) {
+        if self.curve.is_empty() {
+            self.stroke_id = self.next_stroke_id;
+            self.next_stroke_id = self.next_stroke_id.wrapping_add(1);
+            // every stroke starts with a dot, so that clicking without moving the mouse paints something
+            self.paint_curve(paint_canvas, history, style, &[point; 4]);
+            // the first point is repeated, so that the curve starts off towards the second point
+            self.curve = vec![point, point];
+            return
+        }
+        if self.curve.last().is_some_and(|last| (last.x, last.y) == (point.x, point.y)) {
+            return
+        }
+        self.curve.push(point);
+        if self.curve.len() == 4 {
+            let window = std::mem::take(&mut self.curve);
+            self.paint_curve(paint_canvas, history, style, &window);
+            self.curve = window[1..].to_vec();
+        }
+    }
+
+    // moves the brush towards the mouse, painting the stroke along the way
+    fn move_brush(
+        &mut self,
+        paint_canvas: &mut PaintCanvas,
+        history: &mut History,
+        style: StrokeStyle,
+        mouse: Point,
+        pressure: f32,
+    ) {
+        let previous_position = self.position;
+        // the brush only moves once the mouse is farther away from it than the string's length
+        let string_length = self.stabilizer_slider.value() / 100.0 * Self::MAX_STRING_LENGTH;
+        let delta = mouse - self.position;
+        let distance = delta.length();
+        if distance > string_length {
+            self.position.offset(delta * ((distance - string_length) / distance));
+        }
+
+        let position = self.position;
+        if self.stamp.is_some() && matches!(self.paint_mode, PaintMode::Paint) {
+            let (brush_size, opacity) = match self.pressure_mode {
+                PressureMode::Off => (self.brush_size_slider.value(), 1.0),
+                PressureMode::Size => (f32::max(self.brush_size_slider.value() * pressure, 1.0), 1.0),
+                PressureMode::Opacity => (self.brush_size_slider.value(), pressure),
+            };
+            let opacity = opacity * self.opacity_slider.value() / 100.0;
+            // the stamp's longer side is as long as the brush is big, so it never reaches farther than the diagonal
+            let diagonal = brush_size * std::f32::consts::SQRT_2;
+            history.save_chunks(paint_canvas, PaintCanvas::chunks_in_stroke(previous_position, position, diagonal));
+            self.stamp_line(paint_canvas, previous_position, position, brush_size, opacity);
+            return
+        }
+        let point = VectorPoint {
+            x: position.x,
+            y: position.y,
+            pressure,
+        };
+        self.extend_curve(paint_canvas, history, style, point);
+    }
+
+    // holds back a run of someone else's stroke, to be painted by network_tick. the run's segments are assumed to have
+    // been painted evenly over the time since the sender's previous run, and are painted in the same rhythm
+    fn hold_back_run(&mut self, sender: SocketAddr, run: &VectorStroke) {
+        let now = Instant::now();
+        let remote = self.remote_strokes.entry(sender).or_insert_with(|| RemoteStrokes {
+            segments: VecDeque::new(),
+            last_received: now,
+        });
+        // after a pause, only the time that can be made up for within the delay is used
+        let earliest = now.checked_sub(Self::REMOTE_STROKE_DELAY).unwrap_or(now);
+        let from = remote.last_received.max(earliest);
+        let interval = now - from;
+        remote.last_received = now;
+
+        let count = run.points.len().saturating_sub(3) as u32;
+        for (index, points) in run.segments().enumerate() {
+            let offset = interval * (index as u32 + 1) / count;
+            remote.segments.push_back(PendingSegment {
+                paint_at: from + offset + Self::REMOTE_STROKE_DELAY,
+                style: run.style,
+                points,
+            });
+        }
+    }
+
+    // paints someone else's held back segments. if all is false, only the ones that are due are painted
+    fn paint_held_back(&mut self, paint_canvas: &mut PaintCanvas, sender: SocketAddr, all: bool) {
+        let remote = match self.remote_strokes.get_mut(&sender) {
+            Some(remote) => remote,
+            None => return,
+        };
+        let now = Instant::now();
+        while let Some(segment) = remote.segments.front() {
+            if !all && segment.paint_at > now { break }
+            let segment = remote.segments.pop_front().unwrap();
+            let run = VectorStroke {
+                id: 0,
+                style: segment.style,
+                points: segment.points.to_vec(),
+            };
+            paint_canvas.stroke_curve(&run.stroke_points());
+        }
+    }
+
+    // paints the rest of the stroke once it's finished, by repeating its last point
+    fn finish_curve(&mut self, paint_canvas: &mut PaintCanvas, history: &mut History) {
+        let mut window = std::mem::take(&mut self.curve);
+        let style = match self.stroke_buffer.last() {
+            Some(last) if last.id == self.stroke_id => last.style,
+            _ => return,
+        };
+        if window.len() == 3 {
+            window.push(window[2]);
+            self.paint_curve(paint_canvas, history, style, &window);
+        }
+    }
+
+}
+
+impl Tool for BrushTool {
+
+    fn name(&self) -> &'static str {
+        "brush"
+    }
+
+    fn icon<'a>(&self, assets: &'a Assets) -> &'a Image {
+        &assets.icons.tools.brush
+    }
+
+    // version 2 paints strokes as curves, and version 3 sends them as vector strokes
+    fn version(&self) -> u16 {
+        3
+    }
+
+    fn key_shortcut(&self) -> VirtualKeyCode {
+        VirtualKeyCode::B
+    }
+
+    fn process_paint_canvas_input(
+        &mut self,
+        ToolArgs {
+            ui, input, pressure, pointer_events, config, paint_canvas, snap_to_grid, history, log, ..
+        }: ToolArgs,
+        mouse: Point,
+        _previous_mouse: Point,
+    ) {
+        let mouse = match snap_to_grid {
+            Some(cell_size) => snap_point(mouse, cell_size),
+            None => mouse,
+        };
+        // while the eyedropper is out, clicking picks the color under the mouse instead of painting
+        if self.color_picker.eyedropper() {
+            self.position = mouse;
+            if ui.has_mouse(input) && input.mouse_button_just_pressed(MouseButton::Left) {
+                let pixel = IRect::from_xywh(mouse.x.floor() as i32, mouse.y.floor() as i32, 1, 1);
+                let mut image = paint_canvas.capture(pixel);
+                paint_canvas.background().fill_behind(&mut image);
+                let Rgba([r, g, b, _]) = *image.get_pixel(0, 0);
+                self.color_picker.pick(Color4f::from(Color::from_rgb(r, g, b)));
+                self.paint_color = self.color_picker.color();
+            }
+            return
+        }
+        if ui.has_mouse(input) {
+            if input.mouse_button_just_pressed(MouseButton::Left) {
+                self.paint_mode = PaintMode::Paint;
+                if config.brush.use_color(color4f_hex(self.paint_color)) {
+                    ok_or_log!(log, config.save());
+                }
+            } else if input.mouse_button_just_pressed(MouseButton::Right) {
+                self.paint_mode = PaintMode::Erase;
+            }
+        }
+        if input.mouse_button_just_released(MouseButton::Left) || input.mouse_button_just_released(MouseButton::Right) {
+            self.paint_mode = PaintMode::None;
+        }
+        if self.paint_mode != PaintMode::None && !history.is_recording() {
+            history.begin();
+            // every stamped stroke starts with a dab right where it begins
+            self.distance_to_dab = 0.0;
+        } else if self.paint_mode == PaintMode::None && history.is_recording() {
+            self.finish_curve(paint_canvas, history);
+            history.end();
+        }
+
+        if self.paint_mode == PaintMode::None {
+            self.position = mouse;
+            return
+        }
+
+        let color = match self.paint_mode {
+            PaintMode::None => return,
+            // the pressure is applied while painting the stroke, so only the slider's opacity goes into its color
+            PaintMode::Paint => {
+                let alpha = self.paint_color.a * self.opacity_slider.value() / 100.0;
+                Some(color4f_hex(Color4f { a: alpha, ..self.paint_color }))
+            },
+            PaintMode::Erase => None,
+        };
+        let style = StrokeStyle {
+            color,
+            size: self.brush_size_slider.value(),
+            softness: self.softness_slider.value() / 100.0,
+            pressure_mode: self.pressure_mode,
+        };
+        // every movement since the last frame is painted, rather than only where the mouse ended up, so that fast
+        // strokes keep their shape no matter how often frames are drawn
+        let samples: Vec<_> = match pointer_events {
+            [] => vec![(mouse, pressure)],
+            events => events
+                .iter()
+                .map(|event| {
+                    let position = match snap_to_grid {
+                        Some(cell_size) => snap_point(event.position, cell_size),
+                        None => event.position,
+                    };
+                    (position, event.pressure.unwrap_or(1.0))
+                })
+                .collect(),
+        };
+        for (mouse, pressure) in samples {
+            self.move_brush(paint_canvas, history, style, mouse, pressure);
+        }
+    }
+
+    fn process_paint_canvas_overlays(&mut self, canvas: &mut Canvas, _input: &Input, mouse: Point) {
+        let mut paint = Paint::new(Color4f::from(Color::WHITE.with_a(192)), None);
+        paint.set_anti_alias(true);
+        paint.set_blend_mode(BlendMode::Difference);
+        paint.set_style(skpaint::Style::Stroke);
+        if self.paint_mode == PaintMode::None {
+            canvas.draw_circle(mouse, self.brush_size_slider.value() * 0.5, &paint);
+        } else {
+            canvas.draw_circle(self.position, self.brush_size_slider.value() * 0.5, &paint);
+            // the stabilizer's string
+            if self.position != mouse {
+                canvas.draw_line(self.position, mouse, &paint);
+            }
+        }
+    }
+
+    fn process_bottom_bar(
+        &mut self,
+        ToolArgs { ui, input, assets, config, paint_canvas, selection, log, .. }: ToolArgs,
+        canvas: &mut Canvas,
+    ) {
+        let button = ButtonArgs {
+            height: ui.height(),
+            colors: &assets.colors.button,
+        };
+
+        // stamp
+
+        if self.stamp.is_some() {
+            if Button::with_text(ui, canvas, input, button, assets.language.tr("tools.round_tip")).clicked() {
+                self.stamp = None;
+            }
+            ui.space(16.0);
+        } else if selection.is_some() {
+            let stamp_text = assets.language.tr("tools.stamp_from_selection");
+            if Button::with_text(ui, canvas, input, button, stamp_text).clicked() {
+                self.capture_stamp(*selection, paint_canvas, log, &assets.language);
+            }
+            ui.space(16.0);
+        }
+
+        // palette
+
+        for hex_color in COLOR_PALETTE {
+            let color = hex_color4f(*hex_color);
+            if let Some(MouseButton::Left) = process_color_swatch(ui, canvas, input, color, self.paint_color == color) {
+                self.paint_color = color;
+            }
+        }
+        ui.space(8.0);
+
+        // user-defined palette
+        // the + button saves the current color to the palette, and turns into a - button that removes the color if
+        // it's already saved
+
+        for hex_color in config.brush.palette.clone() {
+            let color = hex_color4f(hex_color);
+            if let Some(MouseButton::Left) = process_color_swatch(ui, canvas, input, color, self.paint_color == color) {
+                self.paint_color = color;
+            }
+        }
+        let current_color = color4f_hex(self.paint_color);
+        let color_saved = config.brush.palette.contains(&current_color);
+        if Button::with_text(ui, canvas, input, button, if color_saved { "-" } else { "+" }).clicked() {
+            if color_saved {
+                config.brush.palette.retain(|&c| c != current_color);
+            } else {
+                config.brush.palette.push(current_color);
+            }
+            ok_or_log!(log, config.save());
+        }
+        ui.space(8.0);
+
+        // recently used colors
+
+        for hex_color in config.brush.recent_colors.clone() {
+            let color = hex_color4f(hex_color);
+            if let Some(MouseButton::Left) = process_color_swatch(ui, canvas, input, color, self.paint_color == color) {
+                self.paint_color = color;
+            }
+        }
+        ui.space(8.0);
+
+        // color picker
+
+        if Button::with_text(ui, canvas, input, button, assets.language.tr("tools.color_picker")).clicked() {
+            self.color_picker.toggle();
+        }
+        ui.space(16.0);
+
+        // brush size
+
+        let brush_size_string = self.brush_size_slider.value().to_string();
+        process_labeled_slider(ui, canvas, input, assets, LabeledSlider {
+            label: assets.language.tr("tools.brush_size"),
+            slider: &mut self.brush_size_slider,
+            width: 192.0,
+            value: &brush_size_string,
+        });
+
+        // brush size presets
+        // same deal as with the palette's +/- button
+
+        for preset in config.brush.size_presets.clone() {
+            if Button::with_text(ui, canvas, input, button, &preset.to_string()).clicked() {
+                self.brush_size_slider.set_value(preset);
+            }
+        }
+        let brush_size = self.brush_size_slider.value();
+        let size_saved = config.brush.size_presets.contains(&brush_size);
+        if Button::with_text(ui, canvas, input, button, if size_saved { "-" } else { "+" }).clicked() {
+            if size_saved {
+                config.brush.size_presets.retain(|&s| s != brush_size);
+            } else {
+                config.brush.size_presets.push(brush_size);
+                config.brush.size_presets.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            }
+            ok_or_log!(log, config.save());
+        }
+        ui.space(16.0);
+
+        // pen pressure
+
+        let pressure_text = match self.pressure_mode {
+            PressureMode::Off => "tools.pressure_off",
+            PressureMode::Size => "tools.pressure_size",
+            PressureMode::Opacity => "tools.pressure_opacity",
+        };
+        let pressure_text = assets.language.tr(pressure_text);
+        if Button::with_text(ui, canvas, input, button, pressure_text).clicked() {
+            self.pressure_mode = match self.pressure_mode {
+                PressureMode::Off => PressureMode::Size,
+                PressureMode::Size => PressureMode::Opacity,
+                PressureMode::Opacity => PressureMode::Off,
+            };
+        }
+        ui.space(16.0);
+
+        // opacity and softness
+
+        let opacity_string = format!("{}%", self.opacity_slider.value());
+        process_labeled_slider(ui, canvas, input, assets, LabeledSlider {
+            label: assets.language.tr("tools.opacity"),
+            slider: &mut self.opacity_slider,
+            width: 96.0,
+            value: &opacity_string,
+        });
+        ui.space(16.0);
+
+        let softness_string = format!("{}%", self.softness_slider.value());
+        process_labeled_slider(ui, canvas, input, assets, LabeledSlider {
+            label: assets.language.tr("tools.softness"),
+            slider: &mut self.softness_slider,
+            width: 96.0,
+            value: &softness_string,
+        });
+        ui.space(16.0);
+
+        // stabilizer
+
+        let stabilizer_string = format!("{}%", self.stabilizer_slider.value());
+        process_labeled_slider(ui, canvas, input, assets, LabeledSlider {
+            label: assets.language.tr("tools.stabilizer"),
+            slider: &mut self.stabilizer_slider,
+            width: 96.0,
+            value: &stabilizer_string,
+        });
+    }
+
+    fn process_panel(&mut self, ToolArgs { ui, input, assets, .. }: ToolArgs, canvas: &mut Canvas) {
+        // the color may have been changed from somewhere else, eg. the palette
+        self.color_picker.set_color(self.paint_color);
+        self.color_picker.process(ui, canvas, input, ColorPickerArgs {
+            fill: assets.colors.panel,
+            outline: assets.colors.separator,
+            text: assets.colors.text,
+            slider: assets.colors.slider,
+            text_field: &assets.colors.text_field,
+            button: &assets.colors.button,
+            eyedropper_label: assets.language.tr("tools.eyedropper"),
+            cancel_label: assets.language.tr("tools.cancel"),
+        });
+        if self.color_picker.is_open() {
+            self.paint_color = self.color_picker.color();
+        }
+    }
+
+    fn panel_has_mouse(&self) -> bool {
+        self.color_picker.has_mouse()
+    }
+
+    fn color(&self) -> Option<Color4f> {
+        Some(self.paint_color)
+    }
+
+    fn set_color(&mut self, color: Color4f) {
+        self.paint_color = color;
+    }
+
+    fn network_send(&mut self, peer: &Peer) -> Result<(), net::Error> {
+        let name = self.name();
+        for run in self.stroke_buffer.drain(..) {
+            peer.send_tool(name, &Packet::Stroke(run))?;
+        }
+
+        let mates = peer.mates();
+        self.stamp_sent_to.retain(|addr| mates.contains_key(addr));
+        if let Some(stamp) = &mut self.stamp {
+            // the stamp is sent to everyone at once the first time, so that it ends up in session replays
+            if !stamp.sent {
+                peer.send_tool(name, &Packet::Stamp(stamp.png.clone()))?;
+                stamp.sent = true;
+                self.stamp_sent_to.extend(mates.keys());
+            }
+            for &addr in mates.keys() {
+                if self.stamp_sent_to.insert(addr) {
+                    peer.send_tool_to(addr, name, &Packet::Stamp(stamp.png.clone()))?;
+                }
+            }
+        }
+        if !self.dab_buffer.is_empty() {
+            peer.send_tool(name, &Packet::Dabs(self.dab_buffer.drain(..).collect()))?;
+        }
+        Ok(())
+    }
+
+    fn network_receive(
+        &mut self,
+        paint_canvas: &mut PaintCanvas<'static>,
+        sender: SocketAddr,
+        payload: &[u8],
+    ) -> Result<(), net::Error> {
+        match build::decode(payload)? {
+            Packet::Stamp(png) => {
+                self.stamps.insert(sender, decode_stamp(&png)?);
+            },
+            Packet::Dabs(mut dabs) => {
+                // the strokes before the dabs have to be painted first, so that they end up below them
+                self.paint_held_back(paint_canvas, sender, true);
+                dabs.retain_mut(Dab::sanitize);
+                match self.stamps.get(&sender) {
+                    Some(image) => draw_dabs(paint_canvas, image, &dabs),
+                    None => log::warn!("received dabs from {} without a stamp", sender),
+                }
+            },
+            Packet::Stroke(mut stroke) => {
+                if stroke.sanitize() {
+                    self.hold_back_run(sender, &stroke);
+                } else {
+                    log::warn!("received an invalid stroke from {}", sender);
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn network_tick(&mut self, paint_canvas: &mut PaintCanvas<'static>) -> bool {
+        let senders: Vec<_> = self.remote_strokes.keys().copied().collect();
+        for sender in senders {
+            paint_canvas.set_editor(Some(sender));
+            self.paint_held_back(paint_canvas, sender, false);
+        }
+        self.remote_strokes.retain(|_, remote| !remote.segments.is_empty());
+        !self.remote_strokes.is_empty()
+    }
+
+    fn network_flush(&mut self, paint_canvas: &mut PaintCanvas<'static>, sender: SocketAddr) {
+        self.paint_held_back(paint_canvas, sender, true);
+        self.remote_strokes.remove(&sender);
+    }
+
+    fn network_left(&mut self, paint_canvas: &mut PaintCanvas<'static>, sender: SocketAddr) {
+        self.network_flush(paint_canvas, sender);
+        self.stamps.remove(&sender);
+    }
+
+    // the right mouse button erases
+    fn uses_right_mouse_button(&self) -> bool {
+        true
+    }
+
+    fn cursor_size(&self) -> f32 {
+        self.brush_size_slider.value()
+    }
+
+}
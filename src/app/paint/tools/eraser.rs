@@ -0,0 +1,142 @@
+// the eraser tool. erasing is a regular stroke painted with the Clear blend mode
+
+use skulpin::skia_safe::*;
+use skulpin::skia_safe::paint as skpaint;
+
+use crate::assets::Assets;
+use crate::paint_canvas::*;
+use crate::net::{self, Peer};
+use crate::ui::*;
+use crate::util::*;
+use super::{LabeledSlider, Tool, ToolArgs, process_labeled_slider};
+
+pub struct EraserTool {
+    erasing: bool,
+    size_slider: Slider,
+    // the softness in percent
+    softness_slider: Slider,
+    // whether the pen pressure affects the eraser's size
+    pressure: bool,
+    stroke_buffer: Vec<StrokePoint>,
+}
+
+impl EraserTool {
+
+    pub fn new() -> Self {
+        Self {
+            erasing: false,
+            size_slider: Slider::new(16.0, 1.0, 128.0, SliderStep::Discrete(1.0)),
+            softness_slider: Slider::new(0.0, 0.0, 100.0, SliderStep::Discrete(1.0)),
+            pressure: false,
+            stroke_buffer: Vec::new(),
+        }
+    }
+
+}
+
+impl Tool for EraserTool {
+
+    fn name(&self) -> &'static str {
+        "eraser"
+    }
+
+    fn icon<'a>(&self, assets: &'a Assets) -> &'a Image {
+        &assets.icons.tools.eraser
+    }
+
+    fn key_shortcut(&self) -> VirtualKeyCode {
+        VirtualKeyCode::E
+    }
+
+    fn process_paint_canvas_input(
+        &mut self,
+        ToolArgs { ui, input, pressure, paint_canvas, snap_to_grid, history, .. }: ToolArgs,
+        mouse: Point,
+        previous_mouse: Point,
+    ) {
+        let (mouse, previous_mouse) = match snap_to_grid {
+            Some(cell_size) => (snap_point(mouse, cell_size), snap_point(previous_mouse, cell_size)),
+            None => (mouse, previous_mouse),
+        };
+        if ui.has_mouse(input) && input.mouse_button_just_pressed(MouseButton::Left) {
+            self.erasing = true;
+            history.begin();
+        }
+        if input.mouse_button_just_released(MouseButton::Left) && self.erasing {
+            self.erasing = false;
+            history.end();
+        }
+        if !self.erasing { return }
+
+        let size = self.size_slider.value();
+        let brush = Brush::Erase {
+            stroke_width: if self.pressure { f32::max(size * pressure, 1.0) } else { size },
+            softness: self.softness_slider.value() / 100.0,
+        };
+        history.save_chunks(paint_canvas, PaintCanvas::chunks_in_stroke(previous_mouse, mouse, brush.stroke_width()));
+        paint_canvas.stroke(previous_mouse, mouse, &brush);
+        if self.stroke_buffer.is_empty() {
+            self.stroke_buffer.push(StrokePoint {
+                point: previous_mouse,
+                brush: brush.clone(),
+            });
+        } else if mouse != self.stroke_buffer.last().unwrap().point {
+            self.stroke_buffer.push(StrokePoint {
+                point: mouse,
+                brush,
+            });
+        }
+    }
+
+    fn process_paint_canvas_overlays(&mut self, canvas: &mut Canvas, _input: &Input, mouse: Point) {
+        let mut paint = Paint::new(Color4f::from(Color::WHITE.with_a(192)), None);
+        paint.set_anti_alias(true);
+        paint.set_blend_mode(BlendMode::Difference);
+        paint.set_style(skpaint::Style::Stroke);
+        canvas.draw_circle(mouse, self.size_slider.value() * 0.5, &paint);
+    }
+
+    fn process_bottom_bar(&mut self, ToolArgs { ui, input, assets, .. }: ToolArgs, canvas: &mut Canvas) {
+        let size_string = self.size_slider.value().to_string();
+        process_labeled_slider(ui, canvas, input, assets, LabeledSlider {
+            label: assets.language.tr("tools.eraser_size"),
+            slider: &mut self.size_slider,
+            width: 192.0,
+            value: &size_string,
+        });
+        ui.space(16.0);
+
+        let softness_string = format!("{}%", self.softness_slider.value());
+        process_labeled_slider(ui, canvas, input, assets, LabeledSlider {
+            label: assets.language.tr("tools.softness"),
+            slider: &mut self.softness_slider,
+            width: 96.0,
+            value: &softness_string,
+        });
+        ui.space(16.0);
+
+        // pen pressure
+
+        let button = ButtonArgs {
+            height: ui.height(),
+            colors: &assets.colors.button,
+        };
+        let pressure_text = if self.pressure { "tools.pressure_size" } else { "tools.pressure_off" };
+        let pressure_text = assets.language.tr(pressure_text);
+        if Button::with_text(ui, canvas, input, button, pressure_text).clicked() {
+            self.pressure = !self.pressure;
+        }
+    }
+
+    fn network_send(&mut self, peer: &Peer) -> Result<(), net::Error> {
+        if !self.stroke_buffer.is_empty() {
+            peer.send_stroke(self.stroke_buffer.drain(..))?;
+        }
+        Ok(())
+    }
+
+    fn cursor_size(&self) -> f32 {
+        self.size_slider.value()
+    }
+
+}
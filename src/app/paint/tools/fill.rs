@@ -0,0 +1,147 @@
+// the fill tool. fills the area around the clicked pixel with a color.
+// fills are sent to other people in the room as the pixels that were filled, one packet per chunk, so that everyone
+// ends up with the same result even if their canvases weren't quite the same before.
+
+use std::net::SocketAddr;
+
+use netcanv_protocol::build;
+use serde::{Deserialize, Serialize};
+use skulpin::skia_safe::*;
+
+use crate::assets::Assets;
+use crate::net::{self, Peer};
+use crate::paint_canvas::{FillRegion, PaintCanvas};
+use crate::ui::*;
+use crate::util::*;
+use super::{COLOR_PALETTE, Tool, ToolArgs, process_color_swatch};
+
+#[derive(Serialize, Deserialize)]
+struct Fill {
+    chunk: (i32, i32),
+    // 0xRRGGBBAA, the same format as the palette
+    color: u32,
+    // the filled pixels, see FillRegion::runs
+    runs: Vec<(u32, u32)>,
+}
+
+pub struct FillTool {
+    color: Color4f,
+    // the tolerance in percent
+    tolerance_slider: Slider,
+
+    // fills waiting to be sent in the next network update
+    fills: Vec<Fill>,
+}
+
+impl FillTool {
+
+    pub fn new() -> Self {
+        Self {
+            color: hex_color4f(COLOR_PALETTE[0]),
+            tolerance_slider: Slider::new(10.0, 0.0, 100.0, SliderStep::Discrete(1.0)),
+            fills: Vec::new(),
+        }
+    }
+
+}
+
+impl Tool for FillTool {
+
+    fn name(&self) -> &'static str {
+        "fill"
+    }
+
+    fn icon<'a>(&self, assets: &'a Assets) -> &'a Image {
+        &assets.icons.tools.fill
+    }
+
+    // version 2 sends the filled pixels instead of the seed pixel
+    fn version(&self) -> u16 {
+        2
+    }
+
+    fn key_shortcut(&self) -> VirtualKeyCode {
+        VirtualKeyCode::F
+    }
+
+    fn process_paint_canvas_input(
+        &mut self,
+        ToolArgs { ui, input, paint_canvas, history, .. }: ToolArgs,
+        mouse: Point,
+        _previous_mouse: Point,
+    ) {
+        if ui.has_mouse(input) && input.mouse_button_just_pressed(MouseButton::Left) {
+            let seed = (mouse.x.floor() as i32, mouse.y.floor() as i32);
+            let tolerance = (self.tolerance_slider.value() / 100.0 * 255.0).round() as u8;
+            let color = color4f_hex(self.color);
+            let region = paint_canvas.flood_fill_region(seed, tolerance);
+            history.begin();
+            history.save_chunks(paint_canvas, region.chunks());
+            paint_canvas.fill_region(&region, hex_color4f(color).to_color());
+            history.end();
+            self.fills.extend(region.runs().map(|(chunk, runs)| Fill { chunk, color, runs }));
+        }
+    }
+
+    fn process_bottom_bar(&mut self, ToolArgs { ui, input, assets, config, .. }: ToolArgs, canvas: &mut Canvas) {
+        // palette
+
+        for &hex_color in COLOR_PALETTE.iter().chain(config.brush.palette.iter()) {
+            let color = hex_color4f(hex_color);
+            if let Some(MouseButton::Left) = process_color_swatch(ui, canvas, input, color, self.color == color) {
+                self.color = color;
+            }
+        }
+        ui.space(16.0);
+
+        // tolerance
+
+        ui.push_group((80.0, ui.height()), Layout::Freeform);
+        ui.text(canvas, assets.language.tr("tools.tolerance"), assets.colors.text, (AlignH::Center, AlignV::Middle));
+        ui.pop_group();
+
+        ui.space(8.0);
+        self.tolerance_slider.process(ui, canvas, input, SliderArgs {
+            width: 192.0,
+            color: assets.colors.slider,
+        });
+        ui.space(8.0);
+
+        let tolerance_string = format!("{}%", self.tolerance_slider.value());
+        ui.push_group((ui.height() * 1.5, ui.height()), Layout::Freeform);
+        ui.set_font(assets.sans_bold.clone());
+        ui.text(canvas, &tolerance_string, assets.colors.text, (AlignH::Center, AlignV::Middle));
+        ui.pop_group();
+    }
+
+    fn color(&self) -> Option<Color4f> {
+        Some(self.color)
+    }
+
+    fn set_color(&mut self, color: Color4f) {
+        self.color = color;
+    }
+
+    fn network_send(&mut self, peer: &Peer) -> Result<(), net::Error> {
+        let name = self.name();
+        for fill in self.fills.drain(..) {
+            peer.send_tool(name, &fill)?;
+        }
+        Ok(())
+    }
+
+    fn network_receive(
+        &mut self,
+        paint_canvas: &mut PaintCanvas<'static>,
+        sender: SocketAddr,
+        payload: &[u8],
+    ) -> Result<(), net::Error> {
+        let fill: Fill = build::decode(payload)?;
+        match FillRegion::from_runs(fill.chunk, &fill.runs) {
+            Some(region) => paint_canvas.fill_region(&region, hex_color4f(fill.color).to_color()),
+            None => log::warn!("{} tried to fill an invalid region", sender),
+        }
+        Ok(())
+    }
+
+}
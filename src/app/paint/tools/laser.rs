@@ -0,0 +1,217 @@
+// the laser pointer tool. trails fade away after a couple of seconds and are drawn on top of the canvas, never into
+// any chunk
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use netcanv_protocol::build;
+use serde::{Deserialize, Serialize};
+use skulpin::skia_safe::*;
+use skulpin::skia_safe::paint as skpaint;
+
+use crate::assets::Assets;
+use crate::net::{self, Peer};
+use crate::paint_canvas::PaintCanvas;
+use crate::ui::*;
+use super::{Tool, ToolArgs};
+
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    // points added to the end of the sender's current trail
+    Points(Vec<(f32, f32)>),
+    // the sender's current trail ended, the next points start a new one
+    End,
+}
+
+// a point of a trail, along with when it was drawn
+#[derive(Clone, Copy)]
+struct TrailPoint {
+    position: Point,
+    time: Instant,
+}
+
+type Trail = Vec<TrailPoint>;
+
+pub struct LaserTool {
+    // our own trails, oldest first. the last one is still being drawn while the mouse button is down
+    trails: Vec<Trail>,
+    drawing: bool,
+    // the points drawn since the last network update
+    points: Vec<(f32, f32)>,
+    // packets waiting to be sent in the next network update
+    packets: Vec<Packet>,
+    // other people's trails. the last one of each person's is the one they're drawing, or an empty one if they
+    // aren't drawing
+    others: HashMap<SocketAddr, Vec<Trail>>,
+}
+
+impl LaserTool {
+
+    // how long it takes for a point of a trail to fade away
+    const FADE_TIME: Duration = Duration::from_secs(2);
+    const COLOR: Color = Color::new(0xffff003e);
+    const THICKNESS: f32 = 4.0;
+
+    pub fn new() -> Self {
+        Self {
+            trails: Vec::new(),
+            drawing: false,
+            points: Vec::new(),
+            packets: Vec::new(),
+            others: HashMap::new(),
+        }
+    }
+
+    fn add_point(&mut self, position: Point) {
+        let trail = match self.trails.last_mut() {
+            Some(trail) => trail,
+            None => return,
+        };
+        if trail.last().is_some_and(|last| last.position == position) {
+            return
+        }
+        trail.push(TrailPoint {
+            position,
+            time: Instant::now(),
+        });
+        self.points.push((position.x, position.y));
+    }
+
+    // moves the points drawn since the last network update into a packet
+    fn flush_points(&mut self) {
+        if !self.points.is_empty() {
+            self.packets.push(Packet::Points(std::mem::take(&mut self.points)));
+        }
+    }
+
+    // removes the points that have faded away, along with any trails that are left empty. the last trail can be kept
+    // even if it's empty, as new points are added to it
+    fn fade(trails: &mut Vec<Trail>, keep_last: bool, now: Instant) {
+        let count = trails.len();
+        for trail in trails.iter_mut() {
+            trail.retain(|point| now.duration_since(point.time) < Self::FADE_TIME);
+        }
+        let mut index = 0;
+        trails.retain(|trail| {
+            index += 1;
+            !trail.is_empty() || (keep_last && index == count)
+        });
+    }
+
+    fn draw_trail(canvas: &mut Canvas, trail: &[TrailPoint], now: Instant) {
+        let mut paint = Paint::new(Color4f::from(Self::COLOR), None);
+        paint.set_anti_alias(true);
+        paint.set_style(skpaint::Style::Stroke);
+        paint.set_stroke_cap(skpaint::Cap::Round);
+        paint.set_stroke_width(Self::THICKNESS);
+        if let [point] = trail {
+            paint.set_style(skpaint::Style::Fill);
+            canvas.draw_circle(point.position, Self::THICKNESS / 2.0, &paint);
+            return
+        }
+        for segment in trail.windows(2) {
+            // each segment fades along with the newer of its two points, so the trail disappears from its tail
+            let age = now.duration_since(segment[1].time).as_secs_f32() / Self::FADE_TIME.as_secs_f32();
+            let alpha = (1.0 - age).max(0.0);
+            paint.set_alpha_f(alpha);
+            canvas.draw_line(segment[0].position, segment[1].position, &paint);
+        }
+    }
+
+}
+
+impl Tool for LaserTool {
+
+    fn name(&self) -> &'static str {
+        "laser"
+    }
+
+    fn icon<'a>(&self, assets: &'a Assets) -> &'a Image {
+        &assets.icons.tools.laser
+    }
+
+    fn key_shortcut(&self) -> VirtualKeyCode {
+        VirtualKeyCode::P
+    }
+
+    fn process_paint_canvas_input(
+        &mut self,
+        ToolArgs { ui, input, pointer_events, .. }: ToolArgs,
+        mouse: Point,
+        _previous_mouse: Point,
+    ) {
+        if ui.has_mouse(input) && input.mouse_button_just_pressed(MouseButton::Left) {
+            self.drawing = true;
+            self.trails.push(Vec::new());
+            self.add_point(mouse);
+        }
+        if input.mouse_button_just_released(MouseButton::Left) && self.drawing {
+            self.drawing = false;
+            self.flush_points();
+            self.packets.push(Packet::End);
+        }
+        if !self.drawing { return }
+
+        for event in pointer_events {
+            self.add_point(event.position);
+        }
+    }
+
+    fn process_paint_canvas_overlays(&mut self, canvas: &mut Canvas, _input: &Input, mouse: Point) {
+        let mut paint = Paint::new(Color4f::from(Self::COLOR), None);
+        paint.set_anti_alias(true);
+        canvas.draw_circle(mouse, Self::THICKNESS / 2.0, &paint);
+    }
+
+    fn draw_layers(&self, canvas: &mut Canvas) {
+        let now = Instant::now();
+        for trail in self.others.values().flatten().chain(&self.trails) {
+            Self::draw_trail(canvas, trail, now);
+        }
+    }
+
+    fn network_send(&mut self, peer: &Peer) -> Result<(), net::Error> {
+        let name = self.name();
+        self.flush_points();
+        for packet in self.packets.drain(..) {
+            peer.send_tool(name, &packet)?;
+        }
+        Ok(())
+    }
+
+    fn network_receive(
+        &mut self,
+        _paint_canvas: &mut PaintCanvas<'static>,
+        sender: SocketAddr,
+        payload: &[u8],
+    ) -> Result<(), net::Error> {
+        let trails = self.others.entry(sender).or_default();
+        match build::decode(payload)? {
+            Packet::Points(points) => {
+                if trails.is_empty() {
+                    trails.push(Vec::new());
+                }
+                let time = Instant::now();
+                trails.last_mut().unwrap().extend(points.into_iter().map(|(x, y)| TrailPoint {
+                    position: Point::new(x, y),
+                    time,
+                }));
+            },
+            Packet::End => trails.push(Vec::new()),
+        }
+        Ok(())
+    }
+
+    // the trails aren't painted onto the canvas, but fading them out needs a steady stream of frames
+    fn network_tick(&mut self, _paint_canvas: &mut PaintCanvas<'static>) -> bool {
+        let now = Instant::now();
+        Self::fade(&mut self.trails, self.drawing, now);
+        for trails in self.others.values_mut() {
+            // whether the sender's last trail has ended is only known from their packets, so it's always kept
+            Self::fade(trails, true, now);
+        }
+        self.trails.iter().chain(self.others.values().flatten()).any(|trail| !trail.is_empty())
+    }
+
+}
@@ -0,0 +1,280 @@
+// painting tools.
+// each tool handles its own input on the paint canvas, draws its own overlays on top of it, and fills the bottom bar
+// with its own settings.
+
+mod brush;
+mod eraser;
+mod fill;
+mod laser;
+mod reference;
+mod selection;
+mod shape;
+mod text;
+
+pub use brush::*;
+pub use eraser::*;
+pub use fill::*;
+pub use laser::*;
+pub use reference::*;
+pub use selection::*;
+pub use shape::*;
+pub use text::*;
+
+use std::net::SocketAddr;
+
+use skulpin::skia_safe::*;
+
+use crate::assets::Assets;
+use crate::config::UserConfig;
+use crate::net::{self, Peer};
+use crate::paint_canvas::PaintCanvas;
+use crate::ui::*;
+use super::Log;
+use super::history::History;
+
+pub struct ToolArgs<'a> {
+    pub ui: &'a mut Ui,
+    pub input: &'a Input,
+    // the pen pressure between 0 and 1, or 1 if a mouse or a pen without pressure sensitivity is used
+    pub pressure: f32,
+    // the movements of the mouse or pen since the last frame, in paint canvas coordinates. only filled in for
+    // process_paint_canvas_input
+    pub pointer_events: &'a [PointerEvent],
+    pub assets: &'a Assets,
+    pub config: &'a mut UserConfig,
+    pub paint_canvas: &'a mut PaintCanvas<'static>,
+    // the region selected with the selection tool
+    pub selection: &'a mut Option<IRect>,
+    // the cell size of the grid, if drawing should snap to it
+    pub snap_to_grid: Option<f32>,
+    pub history: &'a mut History,
+    pub log: &'a mut Log,
+}
+
+// creates all tools, in the order they're shown in the toolbar
+pub fn all(assets: &Assets) -> Vec<Box<dyn Tool>> {
+    vec![
+        Box::new(BrushTool::new()),
+        Box::new(EraserTool::new()),
+        Box::new(SelectionTool::new()),
+        Box::new(ShapeTool::new(ShapeKind::Line)),
+        Box::new(ShapeTool::new(ShapeKind::Rectangle)),
+        Box::new(ShapeTool::new(ShapeKind::Ellipse)),
+        Box::new(TextTool::new(assets.sans.clone())),
+        Box::new(FillTool::new()),
+        Box::new(ReferenceTool::new()),
+        Box::new(LaserTool::new()),
+    ]
+}
+
+// the names and versions of the tools, as told to other people in the room
+pub fn capabilities(tools: &[Box<dyn Tool>]) -> Vec<(String, u16)> {
+    tools.iter().map(|tool| (tool.name().into(), tool.version())).collect()
+}
+
+// passes a tool packet received from someone else on to the tool it's meant for
+pub fn receive(
+    tools: &mut [Box<dyn Tool>],
+    paint_canvas: &mut PaintCanvas<'static>,
+    sender: SocketAddr,
+    name: &str,
+    payload: &[u8],
+) -> Result<(), net::Error> {
+    // anything the other tools held back from the sender has to be painted first, so that it all ends up in the order
+    // it was done in
+    for tool in tools.iter_mut().filter(|tool| tool.name() != name) {
+        tool.network_flush(paint_canvas, sender);
+    }
+    match tools.iter_mut().find(|tool| tool.name() == name) {
+        Some(tool) => tool.network_receive(paint_canvas, sender, payload),
+        None => {
+            log::warn!("received packet for unknown tool {:?}", name);
+            Ok(())
+        },
+    }
+}
+
+// lets the tools forget about someone who left the room
+pub fn left(tools: &mut [Box<dyn Tool>], paint_canvas: &mut PaintCanvas<'static>, sender: SocketAddr) {
+    for tool in tools {
+        tool.network_left(paint_canvas, sender);
+    }
+}
+
+// paints what the tools held back from the packets received earlier. returns whether anything is still held back, in
+// which case another frame should be drawn soon
+pub fn tick(tools: &mut [Box<dyn Tool>], paint_canvas: &mut PaintCanvas<'static>) -> bool {
+    let mut holding_back = false;
+    for tool in tools {
+        holding_back |= tool.network_tick(paint_canvas);
+    }
+    holding_back
+}
+
+pub trait Tool {
+    // the name used to identify the tool in network packets
+    fn name(&self) -> &'static str;
+
+    // the version of the tool's packets. this has to be bumped whenever they change in a way that older versions of
+    // the tool can't read, so that people with the older version are told they can't see what the tool does
+    fn version(&self) -> u16 {
+        1
+    }
+
+    // the icon shown in the toolbar
+    fn icon<'a>(&self, assets: &'a Assets) -> &'a Image;
+
+    // the key that switches to this tool
+    fn key_shortcut(&self) -> VirtualKeyCode;
+
+    // handles input on the paint canvas. mouse positions are in paint canvas coordinates
+    fn process_paint_canvas_input(&mut self, args: ToolArgs, mouse: Point, previous_mouse: Point);
+
+    // draws on top of the paint canvas. the canvas is already transformed to paint canvas coordinates
+    fn process_paint_canvas_overlays(&mut self, _canvas: &mut Canvas, _input: &Input, _mouse: Point) {}
+
+    // draws on top of the paint canvas no matter which tool is selected, below everyone's cursors. the canvas is
+    // already transformed to paint canvas coordinates
+    fn draw_layers(&self, _canvas: &mut Canvas) {}
+
+    // fills the bottom bar with the tool's settings
+    fn process_bottom_bar(&mut self, _args: ToolArgs, _canvas: &mut Canvas) {}
+
+    // draws the tool's panel on top of the paint canvas, if it has one open
+    fn process_panel(&mut self, _args: ToolArgs, _canvas: &mut Canvas) {}
+
+    // whether the mouse is over the tool's panel. the tool doesn't receive paint canvas input while this is true
+    fn panel_has_mouse(&self) -> bool {
+        false
+    }
+
+    // the color the tool paints with, if it uses one. this is what the palette window shows as selected
+    fn color(&self) -> Option<Color4f> {
+        None
+    }
+
+    // changes the color the tool paints with, when a color is picked in the palette window
+    fn set_color(&mut self, _color: Color4f) {}
+
+    // sends the tool's pending changes to other people in the room. called on every network update
+    fn network_send(&mut self, _peer: &Peer) -> Result<(), net::Error> {
+        Ok(())
+    }
+
+    // handles a packet sent by the same tool of another person in the room
+    fn network_receive(
+        &mut self,
+        _paint_canvas: &mut PaintCanvas<'static>,
+        _sender: SocketAddr,
+        _payload: &[u8],
+    ) -> Result<(), net::Error> {
+        Ok(())
+    }
+
+    // paints the parts of earlier packets that are due. tools may hold back some of what they receive to smooth out
+    // packets arriving in bursts. called every frame; returns whether anything is still held back
+    fn network_tick(&mut self, _paint_canvas: &mut PaintCanvas<'static>) -> bool {
+        false
+    }
+
+    // paints everything held back from the given person right away. called before a packet from them is passed on
+    // to another tool
+    fn network_flush(&mut self, _paint_canvas: &mut PaintCanvas<'static>, _sender: SocketAddr) {}
+
+    // throws away anything kept around for the given person, who has just left the room. anything held back from
+    // them is painted first
+    fn network_left(&mut self, paint_canvas: &mut PaintCanvas<'static>, sender: SocketAddr) {
+        self.network_flush(paint_canvas, sender);
+    }
+
+    // whether the tool is currently using the keyboard, eg. for typing text. keyboard shortcuts are disabled while
+    // this is true
+    fn captures_keyboard(&self) -> bool {
+        false
+    }
+
+    // whether the tool does something with the right mouse button. right-clicking the paint canvas opens its context
+    // menu otherwise
+    fn uses_right_mouse_button(&self) -> bool {
+        false
+    }
+
+    // the size of the cursor shown to other people in the room
+    fn cursor_size(&self) -> f32 {
+        0.0
+    }
+}
+
+pub const COLOR_PALETTE: &'static [u32] = &[
+    0x100820ff,
+    0xff003eff,
+    0xff7b00ff,
+    0xffff00ff,
+    0x2dd70eff,
+    0x03cbfbff,
+    0x0868ebff,
+    0xa315d7ff,
+    0xffffffff,
+];
+
+// processes a single color swatch in the bar. returns the mouse button the swatch was clicked with, if any
+fn process_color_swatch(
+    ui: &mut Ui,
+    canvas: &mut Canvas,
+    input: &Input,
+    color: Color4f,
+    selected: bool,
+) -> Option<MouseButton> {
+    ui.push_group((16.0, ui.height()), Layout::Freeform);
+    let y_offset = ui.height() *
+        if selected { 0.5 }
+        else if ui.has_mouse(input) { 0.7 }
+        else { 0.8 };
+    let mut clicked = None;
+    if ui.has_mouse(input) {
+        for &button in &[MouseButton::Left, MouseButton::Right] {
+            if input.mouse_button_just_pressed(button) {
+                clicked = Some(button);
+            }
+        }
+    }
+    ui.draw_on_canvas(canvas, |canvas| {
+        let paint = Paint::new(color, None);
+        let rect = Rect::from_point_and_size((0.0, y_offset), ui.size());
+        canvas.draw_rect(rect, &paint);
+    });
+    ui.pop_group();
+    clicked
+}
+
+// a slider in the bar, along with its label and value
+struct LabeledSlider<'a> {
+    label: &'a str,
+    slider: &'a mut Slider,
+    width: f32,
+    value: &'a str,
+}
+
+fn process_labeled_slider(
+    ui: &mut Ui,
+    canvas: &mut Canvas,
+    input: &Input,
+    assets: &Assets,
+    LabeledSlider { label, slider, width, value }: LabeledSlider,
+) {
+    ui.push_group((80.0, ui.height()), Layout::Freeform);
+    ui.text(canvas, label, assets.colors.text, (AlignH::Center, AlignV::Middle));
+    ui.pop_group();
+
+    ui.space(8.0);
+    slider.process(ui, canvas, input, SliderArgs {
+        width,
+        color: assets.colors.slider,
+    });
+    ui.space(8.0);
+
+    ui.push_group((ui.height() * 1.5, ui.height()), Layout::Freeform);
+    ui.set_font(assets.sans_bold.clone());
+    ui.text(canvas, value, assets.colors.text, (AlignH::Center, AlignV::Middle));
+    ui.pop_group();
+}
@@ -0,0 +1,531 @@
+// the reference tool. shows an image opened from a file above the canvas, to draw from.
+// the reference image isn't part of the canvas: it's never painted into any chunk, so it doesn't end up in saves or
+// exports, and it stays visible (dimmed to the chosen opacity) after switching to another tool. with the reference tool
+// selected, the image can be moved by dragging it, and scaled by dragging its bottom right corner.
+// sharing is opt-in on both ends: a reference is only sent to people who turned on showing others' references, and
+// only while its owner has sharing turned on. the image itself is sent once, after that only its placement is. the
+// image is only for looking at, so what's sent is a copy scaled down to at most SHARED_SIZE, which keeps it light.
+
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+
+use ::image::{ImageDecoder, ImageError, ImageFormat, RgbaImage};
+use ::image::codecs::png::PngDecoder;
+use ::image::error::{LimitError, LimitErrorKind};
+use ::image::imageops::{self, FilterType};
+use netcanv_protocol::build;
+use serde::{Deserialize, Serialize};
+use skulpin::skia_safe::*;
+use skulpin::skia_safe::paint as skpaint;
+
+use crate::app::paint::Log;
+use crate::assets::Assets;
+use crate::file_dialog::{DialogResult, PendingDialog};
+use crate::i18n::Language;
+use crate::net::{self, Peer};
+use crate::paint_canvas::PaintCanvas;
+use crate::ui::*;
+use crate::util::*;
+use super::{Tool, ToolArgs};
+use super::selection::encode_png;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Placement {
+    // the top left corner of the image
+    position: (f32, f32),
+    scale: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    // asks for the receiver's reference, now and whenever it changes
+    Subscribe,
+    Unsubscribe,
+    // a new reference image
+    Image {
+        // PNG-encoded, with straight alpha
+        png: Vec<u8>,
+        placement: Placement,
+    },
+    // the reference image was moved or scaled
+    Placement(Placement),
+    // the reference image was removed, or isn't shared anymore
+    Remove,
+}
+
+struct Reference {
+    image: Image,
+    placement: Placement,
+}
+
+#[derive(Clone, Copy)]
+enum Drag {
+    // the vector points from the image's top left corner to the mouse
+    Move(Vector),
+    Scale,
+}
+
+pub struct ReferenceTool {
+    reference: Option<Reference>,
+    // the PNG data of the reference image's scaled down copy, kept around for sending it to people who ask for it
+    png: Vec<u8>,
+    // how many times smaller the copy is than the reference image
+    png_scale: f32,
+    opacity_slider: Slider,
+    drag: Option<Drag>,
+    // the last position of the mouse on the canvas. new references are placed there
+    mouse: Point,
+    // the dialog asking for an image to open, and the mouse position the reference is centered on
+    open_dialog: Option<(PendingDialog, Point)>,
+
+    // whether our reference is shared with others
+    sharing: bool,
+    // the people who asked for our reference
+    subscribers: HashSet<SocketAddr>,
+    placement_changed: bool,
+
+    // whether references shared by others are shown
+    show_shared: bool,
+    // the people we've asked for their references
+    subscribed_to: HashSet<SocketAddr>,
+    shared: HashMap<SocketAddr, Reference>,
+
+    // packets waiting to be sent in the next network update, along with who they should be sent to
+    packets: Vec<(SocketAddr, Packet)>,
+}
+
+impl Placement {
+
+    // whether a placement received from someone else can be drawn with an image of the given size
+    fn is_valid(&self, image: &Image) -> bool {
+        if !self.scale.is_finite() || self.scale <= 0.0 {
+            return false
+        }
+        let rect = self.rect(image);
+        PaintCanvas::is_in_bounds(Point::new(rect.left, rect.top))
+            && PaintCanvas::is_in_bounds(Point::new(rect.right, rect.bottom))
+    }
+
+    fn rect(&self, image: &Image) -> Rect {
+        let (width, height) = (image.width() as f32 * self.scale, image.height() as f32 * self.scale);
+        Rect::from_point_and_size(self.position, (width, height))
+    }
+
+}
+
+impl Reference {
+
+    // decodes a reference received from someone else. references larger than what's ever shared are refused before
+    // they're decoded, so that a bogus image can't take up all the memory
+    fn decode(png: &[u8], placement: Placement) -> Result<Self, ImageError> {
+        let too_large = || ImageError::Limits(LimitError::from_kind(LimitErrorKind::DimensionError));
+        if png.len() > ReferenceTool::MAX_SHARED_BYTES {
+            return Err(too_large())
+        }
+        let (width, height) = PngDecoder::new(png)?.dimensions();
+        if width > ReferenceTool::SHARED_SIZE || height > ReferenceTool::SHARED_SIZE {
+            return Err(too_large())
+        }
+        let image = ::image::load_from_memory_with_format(png, ImageFormat::Png)?.to_rgba8();
+        Ok(Self {
+            image: rgba_image_to_skia(&image),
+            placement,
+        })
+    }
+
+    fn rect(&self) -> Rect {
+        self.placement.rect(&self.image)
+    }
+
+    fn draw(&self, canvas: &mut Canvas, opacity: f32) {
+        let mut paint = Paint::new(Color4f::new(1.0, 1.0, 1.0, opacity), None);
+        paint.set_anti_alias(true);
+        paint.set_filter_quality(FilterQuality::Medium);
+        canvas.draw_image_rect(&self.image, None, self.rect(), &paint);
+    }
+
+}
+
+impl ReferenceTool {
+
+    const HANDLE_SIZE: f32 = 8.0;
+    // references can't be scaled down further than this, so that they don't disappear
+    const MIN_SCALE: f32 = 0.05;
+    const MAX_SCALE: f32 = 16.0;
+    // the most pixels the longer side of a shared reference can have
+    const SHARED_SIZE: u32 = 1024;
+    const MAX_SHARED_BYTES: usize = 8 * 1024 * 1024;
+
+    pub fn new() -> Self {
+        Self {
+            reference: None,
+            png: Vec::new(),
+            png_scale: 1.0,
+            opacity_slider: Slider::new(50.0, 0.0, 100.0, SliderStep::Discrete(1.0)),
+            drag: None,
+            mouse: Point::new(0.0, 0.0),
+            open_dialog: None,
+
+            sharing: false,
+            subscribers: HashSet::new(),
+            placement_changed: false,
+
+            show_shared: false,
+            subscribed_to: HashSet::new(),
+            shared: HashMap::new(),
+
+            packets: Vec::new(),
+        }
+    }
+
+    fn opacity(&self) -> f32 {
+        self.opacity_slider.value() / 100.0
+    }
+
+    // queues up our reference for sending to everyone who asked for it
+    fn send_to_subscribers(&mut self, packet: impl Fn(&Self) -> Packet) {
+        let packets: Vec<_> = self.subscribers.iter().map(|&addr| (addr, packet(self))).collect();
+        self.packets.extend(packets);
+    }
+
+    fn image_packet(&self) -> Packet {
+        match &self.reference {
+            Some(reference) => Packet::Image { png: self.png.clone(), placement: self.shared_placement(reference) },
+            None => Packet::Remove,
+        }
+    }
+
+    // the placement of the reference's scaled down copy, such that it covers the same area as the reference
+    fn shared_placement(&self, reference: &Reference) -> Placement {
+        Placement {
+            scale: reference.placement.scale * self.png_scale,
+            ..reference.placement
+        }
+    }
+
+    // scales the image down to at most SHARED_SIZE and encodes it for sharing. returns the PNG data along with how
+    // many times smaller the copy is
+    fn encode_shared(image: &RgbaImage) -> Result<(Vec<u8>, f32), ImageError> {
+        let longer_side = u32::max(image.width(), image.height());
+        if longer_side <= Self::SHARED_SIZE {
+            return Ok((encode_png(image)?, 1.0))
+        }
+        let scale = longer_side as f32 / Self::SHARED_SIZE as f32;
+        let (width, height) = (image.width() as f32 / scale, image.height() as f32 / scale);
+        let copy = imageops::resize(
+            image,
+            (width.round() as u32).clamp(1, Self::SHARED_SIZE),
+            (height.round() as u32).clamp(1, Self::SHARED_SIZE),
+            FilterType::Triangle,
+        );
+        // the copy's size is rounded, so the scale is worked out from the actual size rather than reused
+        Ok((encode_png(&copy)?, image.width() as f32 / copy.width() as f32))
+    }
+
+    // asks for a file to open. the image in it becomes the reference once the dialog is closed
+    fn show_open_dialog(&mut self) {
+        if self.open_dialog.is_some() { return }
+        let dialog = PendingDialog::spawn(|dialog| {
+            dialog
+                .add_filter("Image", &["png", "jpg", "jpeg", "gif", "bmp", "webp"])
+                .show_open_single_file()
+        });
+        self.open_dialog = Some((dialog, self.mouse));
+    }
+
+    fn poll_open_dialog(&mut self, log: &mut Log, language: &Language) {
+        let path = match self.open_dialog.as_ref().and_then(|(dialog, _)| dialog.poll()) {
+            Some(path) => path,
+            None => return,
+        };
+        let (_, center) = self.open_dialog.take().unwrap();
+        self.open(path, center, log, language);
+    }
+
+    // makes the image in the file picked in the open dialog the reference, centered on the given point
+    fn open(&mut self, path: DialogResult, center: Point, log: &mut Log, language: &Language) {
+        let path = match path {
+            Ok(Some(path)) => path,
+            Ok(None) => return,
+            Err(error) => {
+                notify_tr!(log, language, Error, "notifications.file_dialog_failed", error);
+                return
+            },
+        };
+        let image = match ::image::open(&path) {
+            Ok(image) => image.to_rgba8(),
+            Err(error) => {
+                notify_tr!(log, language, Error, "notifications.open_image_failed", error);
+                return
+            },
+        };
+        let (png, png_scale) = match Self::encode_shared(&image) {
+            Ok(shared) => shared,
+            Err(error) => {
+                notify_tr!(log, language, Error, "notifications.encode_image_failed", error);
+                return
+            },
+        };
+        self.png = png;
+        self.png_scale = png_scale;
+        let position = center - Vector::new(image.width() as f32, image.height() as f32) * 0.5;
+        self.reference = Some(Reference {
+            image: rgba_image_to_skia(&image),
+            placement: Placement {
+                position: (position.x, position.y),
+                scale: 1.0,
+            },
+        });
+        if self.sharing {
+            self.send_to_subscribers(Self::image_packet);
+        }
+    }
+
+    fn remove(&mut self) {
+        self.reference = None;
+        self.png = Vec::new();
+        self.drag = None;
+        if self.sharing {
+            self.send_to_subscribers(|_| Packet::Remove);
+        }
+    }
+
+    fn set_sharing(&mut self, sharing: bool) {
+        self.sharing = sharing;
+        if sharing {
+            self.send_to_subscribers(Self::image_packet);
+        } else {
+            self.send_to_subscribers(|_| Packet::Remove);
+        }
+    }
+
+    fn set_show_shared(&mut self, show_shared: bool) {
+        self.show_shared = show_shared;
+        // subscribing to everyone in the room is done in network_send, as that's where the room is known
+        if !show_shared {
+            for addr in self.subscribed_to.drain() {
+                self.packets.push((addr, Packet::Unsubscribe));
+            }
+            self.shared.clear();
+        }
+    }
+
+    // returns what dragging the reference at the given point would do, or None if the point is outside of it
+    fn drag_at(reference: &Reference, point: Point) -> Option<Drag> {
+        let rect = reference.rect();
+        let radius = Self::HANDLE_SIZE / 2.0;
+        if (point.x - rect.right).abs() <= radius && (point.y - rect.bottom).abs() <= radius {
+            Some(Drag::Scale)
+        } else if point.x >= rect.left && point.x < rect.right && point.y >= rect.top && point.y < rect.bottom {
+            Some(Drag::Move(point - Point::new(rect.left, rect.top)))
+        } else {
+            None
+        }
+    }
+
+    fn apply_drag(reference: &mut Reference, drag: Drag, mouse: Point) {
+        let placement = &mut reference.placement;
+        match drag {
+            Drag::Move(offset) => {
+                let position = mouse - offset;
+                placement.position = (position.x, position.y);
+            },
+            Drag::Scale => {
+                // the aspect ratio is kept, so the corner follows whichever axis the mouse went farther along
+                let (x, y) = placement.position;
+                let scale_x = (mouse.x - x) / reference.image.width() as f32;
+                let scale_y = (mouse.y - y) / reference.image.height() as f32;
+                placement.scale = f32::max(scale_x, scale_y).clamp(Self::MIN_SCALE, Self::MAX_SCALE);
+            },
+        }
+    }
+
+}
+
+impl Tool for ReferenceTool {
+
+    fn name(&self) -> &'static str {
+        "reference"
+    }
+
+    fn icon<'a>(&self, assets: &'a Assets) -> &'a Image {
+        &assets.icons.tools.reference
+    }
+
+    fn key_shortcut(&self) -> VirtualKeyCode {
+        VirtualKeyCode::I
+    }
+
+    fn process_paint_canvas_input(
+        &mut self,
+        ToolArgs { ui, input, .. }: ToolArgs,
+        mouse: Point,
+        _previous_mouse: Point,
+    ) {
+        if ui.has_mouse(input) {
+            self.mouse = mouse;
+        }
+        let reference = match &mut self.reference {
+            Some(reference) => reference,
+            None => return,
+        };
+        if ui.has_mouse(input) && input.mouse_button_just_pressed(MouseButton::Left) {
+            self.drag = Self::drag_at(reference, mouse);
+        }
+        if let Some(drag) = self.drag {
+            let previous = reference.placement;
+            Self::apply_drag(reference, drag, mouse);
+            self.placement_changed |= reference.placement != previous;
+        }
+        if input.mouse_button_just_released(MouseButton::Left) {
+            self.drag = None;
+        }
+    }
+
+    fn process_paint_canvas_overlays(&mut self, canvas: &mut Canvas, _input: &Input, _mouse: Point) {
+        if let Some(reference) = &self.reference {
+            let mut paint = Paint::new(Color4f::from(Color::WHITE.with_a(192)), None);
+            paint.set_anti_alias(true);
+            paint.set_blend_mode(BlendMode::Difference);
+            paint.set_style(skpaint::Style::Stroke);
+            let rect = reference.rect();
+            canvas.draw_rect(rect, &paint);
+            paint.set_style(skpaint::Style::Fill);
+            let size = Self::HANDLE_SIZE;
+            canvas.draw_rect(Rect::from_xywh(rect.right - size / 2.0, rect.bottom - size / 2.0, size, size), &paint);
+        }
+    }
+
+    fn draw_layers(&self, canvas: &mut Canvas) {
+        let opacity = self.opacity();
+        for reference in self.shared.values().chain(self.reference.iter()) {
+            reference.draw(canvas, opacity);
+        }
+    }
+
+    fn process_bottom_bar(&mut self, ToolArgs { ui, input, assets, log, .. }: ToolArgs, canvas: &mut Canvas) {
+        self.poll_open_dialog(log, &assets.language);
+
+        let button = ButtonArgs {
+            height: ui.height(),
+            colors: &assets.colors.button,
+        };
+
+        // the reference image
+
+        if Button::with_text(ui, canvas, input, button, assets.language.tr("tools.open_image")).clicked() {
+            self.show_open_dialog();
+        }
+        if self.reference.is_some() {
+            ui.space(8.0);
+            if Button::with_text(ui, canvas, input, button, assets.language.tr("tools.remove")).clicked() {
+                self.remove();
+            }
+        }
+        ui.space(16.0);
+
+        // opacity
+
+        ui.push_group((80.0, ui.height()), Layout::Freeform);
+        ui.text(canvas, assets.language.tr("tools.opacity"), assets.colors.text, (AlignH::Center, AlignV::Middle));
+        ui.pop_group();
+
+        ui.space(8.0);
+        self.opacity_slider.process(ui, canvas, input, SliderArgs {
+            width: 128.0,
+            color: assets.colors.slider,
+        });
+        ui.space(8.0);
+
+        let opacity_string = format!("{}%", self.opacity_slider.value());
+        ui.push_group((ui.height() * 1.5, ui.height()), Layout::Freeform);
+        ui.set_font(assets.sans_bold.clone());
+        ui.text(canvas, &opacity_string, assets.colors.text, (AlignH::Center, AlignV::Middle));
+        ui.pop_group();
+        ui.space(16.0);
+
+        // sharing
+
+        let sharing_text = assets.language.tr(if self.sharing { "tools.sharing_on" } else { "tools.sharing_off" });
+        if Button::with_text(ui, canvas, input, button, sharing_text).clicked() {
+            self.set_sharing(!self.sharing);
+        }
+        ui.space(8.0);
+        let shared_text =
+            assets.language.tr(if self.show_shared { "tools.shared_shown" } else { "tools.shared_hidden" });
+        if Button::with_text(ui, canvas, input, button, shared_text).clicked() {
+            self.set_show_shared(!self.show_shared);
+        }
+    }
+
+    fn network_send(&mut self, peer: &Peer) -> Result<(), net::Error> {
+        // forget about people who left the room
+        let mates = peer.mates();
+        self.subscribers.retain(|addr| mates.contains_key(addr));
+        self.subscribed_to.retain(|addr| mates.contains_key(addr));
+        self.shared.retain(|addr, _| mates.contains_key(addr));
+
+        // people who joined after we started showing shared references have to be asked for theirs too
+        if self.show_shared {
+            for &addr in mates.keys() {
+                if self.subscribed_to.insert(addr) {
+                    self.packets.push((addr, Packet::Subscribe));
+                }
+            }
+        }
+
+        if self.placement_changed {
+            self.placement_changed = false;
+            if let (true, Some(reference)) = (self.sharing, &self.reference) {
+                let placement = self.shared_placement(reference);
+                self.send_to_subscribers(|_| Packet::Placement(placement));
+            }
+        }
+
+        let name = self.name();
+        for (addr, packet) in self.packets.drain(..) {
+            peer.send_tool_to(addr, name, &packet)?;
+        }
+        Ok(())
+    }
+
+    fn network_receive(
+        &mut self,
+        _paint_canvas: &mut PaintCanvas<'static>,
+        sender: SocketAddr,
+        payload: &[u8],
+    ) -> Result<(), net::Error> {
+        match build::decode(payload)? {
+            Packet::Subscribe => {
+                self.subscribers.insert(sender);
+                if let (true, Some(_)) = (self.sharing, &self.reference) {
+                    let packet = self.image_packet();
+                    self.packets.push((sender, packet));
+                }
+            },
+            Packet::Unsubscribe => {
+                self.subscribers.remove(&sender);
+            },
+            Packet::Image { png, placement } => if self.show_shared {
+                let reference = Reference::decode(&png, placement)?;
+                if reference.placement.is_valid(&reference.image) {
+                    self.shared.insert(sender, reference);
+                } else {
+                    log::warn!("{} shared a reference that's out of bounds", sender);
+                }
+            },
+            Packet::Placement(placement) => {
+                if let Some(reference) = self.shared.get_mut(&sender) {
+                    if placement.is_valid(&reference.image) {
+                        reference.placement = placement;
+                    }
+                }
+            },
+            Packet::Remove => {
+                self.shared.remove(&sender);
+            },
+        }
+        Ok(())
+    }
+
+}
@@ -0,0 +1,918 @@
+// the selection tool. selects a rectangular region of the canvas, and moves, scales, and rotates images floating
+// above it before they're stamped onto the canvas
+
+use std::collections::{HashMap, VecDeque};
+use std::io::Cursor;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use ::image::{ColorType, ImageFormat, ImageError, RgbaImage, codecs::png::PngEncoder};
+use netcanv_protocol::build;
+use serde::{Deserialize, Serialize};
+use skulpin::skia_safe::*;
+use skulpin::skia_safe::paint as skpaint;
+
+use crate::app::paint::Log;
+use crate::assets::Assets;
+use crate::clipboard;
+use crate::file_dialog::{DialogResult, PendingDialog};
+use crate::i18n::Language;
+use crate::net::{self, Peer};
+use crate::paint_canvas::PaintCanvas;
+use crate::ui::*;
+use crate::util::*;
+use super::{Tool, ToolArgs};
+use super::super::history::History;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct Transform {
+    center: (f32, f32),
+    // the size the image is scaled to
+    size: (f32, f32),
+    // in degrees, clockwise
+    rotation: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    // an image started floating
+    Float {
+        // PNG-encoded, with straight alpha
+        png: Vec<u8>,
+        transform: Transform,
+        // the rectangle (left, top, right, bottom) the image was lifted off of, which has to be cleared.
+        // None if the image was pasted
+        lifted: Option<(i32, i32, i32, i32)>,
+    },
+    // the floating image was moved, scaled, or rotated
+    Transform(Transform),
+    // the floating image was stamped onto the canvas
+    Stamp(Transform),
+    // the floating image was thrown away
+    Cancel,
+}
+
+// an image floating above the canvas that hasn't been stamped onto it yet
+struct Floating {
+    image: Image,
+    transform: Transform,
+    // the transform the image was lifted off the canvas with. cancelling puts it back there
+    lifted_from: Option<Transform>,
+}
+
+// an imported image waiting for its turn to float, at the place it was laid out at
+struct Queued {
+    // PNG-encoded, ready to be sent once the image floats
+    png: Vec<u8>,
+    image: Image,
+    transform: Transform,
+}
+
+// how imported images are laid out
+#[derive(Clone, Copy, PartialEq)]
+enum BoardLayout {
+    Grid,
+    Row,
+}
+
+#[derive(Clone, Copy)]
+enum Drag {
+    // the floating image is being moved. the vector points from the image's center to the mouse
+    Move(Vector),
+    // the floating image is being scaled by one of its corners, given as the signs of the corner's local coordinates
+    Scale(f32, f32),
+    Rotate,
+}
+
+pub struct SelectionTool {
+    // the point the selection was started from. this is only Some while the selection is being dragged out
+    anchor: Option<Point>,
+    // a copy of the selection, so that its handles can be drawn
+    selection: Option<IRect>,
+    floating: Option<Floating>,
+    // imported images that float once the current floating image is stamped or thrown away. this is only ever
+    // non-empty while something is floating
+    queued: VecDeque<Queued>,
+    board_layout: BoardLayout,
+    // the last position of the mouse on the canvas. imported images are laid out around it
+    mouse: Point,
+    drag: Option<Drag>,
+    // packets waiting to be sent in the next network update
+    packets: Vec<Packet>,
+    // images other people in the room are transforming
+    previews: HashMap<SocketAddr, Floating>,
+    // the dialog asking for images to import, and the mouse position the images are laid out around
+    import_dialog: Option<(PendingDialog<Vec<PathBuf>>, Point)>,
+    // the dialog asking where to save the selection, and the selected pixels, which are captured when the button's
+    // clicked
+    save_dialog: Option<(PendingDialog, RgbaImage)>,
+}
+
+pub(super) fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, ImageError> {
+    let mut bytes = Vec::new();
+    PngEncoder::new(Cursor::new(&mut bytes)).encode(image, image.width(), image.height(), ColorType::Rgba8)?;
+    Ok(bytes)
+}
+
+fn contains(rect: Rect, point: Point) -> bool {
+    point.x >= rect.left && point.x < rect.right && point.y >= rect.top && point.y < rect.bottom
+}
+
+impl Transform {
+
+    // the corners of the image, as signs of their local coordinates, clockwise from the top left
+    const CORNERS: [(f32, f32); 4] = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+
+    fn from_rect(rect: IRect) -> Self {
+        let rect = Rect::from_irect(rect);
+        Self {
+            center: (rect.center_x(), rect.center_y()),
+            size: (rect.width(), rect.height()),
+            rotation: 0.0,
+        }
+    }
+
+    fn center(&self) -> Point {
+        Point::from(self.center)
+    }
+
+    // the rectangle the image is drawn into, in local coordinates. (0, 0) is the image's center
+    fn local_rect(&self) -> Rect {
+        let (width, height) = self.size;
+        Rect::new(-width / 2.0, -height / 2.0, width / 2.0, height / 2.0)
+    }
+
+    fn corner(&self, (sx, sy): (f32, f32)) -> Point {
+        let (width, height) = self.size;
+        Point::new(sx * width / 2.0, sy * height / 2.0)
+    }
+
+    fn to_canvas(self, local: Point) -> Point {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        self.center() + Point::new(local.x * cos - local.y * sin, local.x * sin + local.y * cos)
+    }
+
+    fn to_local(self, point: Point) -> Point {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let delta = point - self.center();
+        Point::new(delta.x * cos + delta.y * sin, -delta.x * sin + delta.y * cos)
+    }
+
+    // whether the transform received from someone else can be drawn with. the image has to be somewhere on the canvas
+    // and can't be larger than MAX_SIZE, so that drawing it doesn't take forever
+    fn is_valid(&self) -> bool {
+        let (width, height) = self.size;
+        PaintCanvas::is_in_bounds(self.center())
+            && self.rotation.is_finite()
+            && (0.0..=SelectionTool::MAX_SIZE).contains(&width)
+            && (0.0..=SelectionTool::MAX_SIZE).contains(&height)
+    }
+
+    // the rectangle containing all pixels that are touched when drawing the image
+    fn bounds(&self) -> Rect {
+        let corners = Self::CORNERS.iter().map(|&corner| self.to_canvas(self.corner(corner)));
+        let (mut left, mut top, mut right, mut bottom) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+        for corner in corners {
+            left = f32::min(left, corner.x);
+            top = f32::min(top, corner.y);
+            right = f32::max(right, corner.x);
+            bottom = f32::max(bottom, corner.y);
+        }
+        Rect::new(left, top, right, bottom).with_outset((1.0, 1.0))
+    }
+
+    // runs the callback with the canvas transformed to the image's local coordinates
+    fn with_local(&self, canvas: &mut Canvas, callback: impl FnOnce(&mut Canvas)) {
+        canvas.save();
+        canvas.translate(self.center());
+        canvas.rotate(self.rotation, None);
+        callback(canvas);
+        canvas.restore();
+    }
+
+    fn draw(&self, canvas: &mut Canvas, image: &Image) {
+        let mut paint = Paint::default();
+        paint.set_anti_alias(true);
+        paint.set_filter_quality(FilterQuality::Medium);
+        self.with_local(canvas, |canvas| {
+            canvas.draw_image_rect(image, None, self.local_rect(), &paint);
+        });
+    }
+
+    fn draw_onto(&self, paint_canvas: &mut PaintCanvas, image: &Image) {
+        paint_canvas.draw(self.bounds(), |canvas| self.draw(canvas, image));
+    }
+
+}
+
+impl Floating {
+
+    fn decode(png: &[u8], transform: Transform) -> Result<Self, ImageError> {
+        let image = ::image::load_from_memory_with_format(png, ImageFormat::Png)?.to_rgba8();
+        Ok(Self {
+            image: rgba_image_to_skia(&image),
+            transform,
+            lifted_from: None,
+        })
+    }
+
+}
+
+impl SelectionTool {
+
+    const HANDLE_SIZE: f32 = 8.0;
+    // how far above the image the rotation handle is
+    const ROTATION_HANDLE_DISTANCE: f32 = 24.0;
+    // the angle rotation snaps to while Shift is held
+    const ROTATION_STEP: f32 = 15.0;
+    // the space between imported images
+    const BOARD_GAP: f32 = 16.0;
+    // the largest a floating image can be scaled to, and the largest area other people can lift off the canvas
+    const MAX_SIZE: f32 = 16384.0;
+
+    pub fn new() -> Self {
+        Self {
+            anchor: None,
+            selection: None,
+            floating: None,
+            queued: VecDeque::new(),
+            board_layout: BoardLayout::Grid,
+            mouse: Point::new(0.0, 0.0),
+            drag: None,
+            packets: Vec::new(),
+            previews: HashMap::new(),
+            import_dialog: None,
+            save_dialog: None,
+        }
+    }
+
+    // queues a packet for sending. a transform replaces the previous one if it hasn't been sent yet, because only the
+    // latest transform matters
+    fn queue_packet(&mut self, packet: Packet) {
+        if let (Some(Packet::Transform(_)), Packet::Transform(_)) = (self.packets.last(), &packet) {
+            self.packets.pop();
+        }
+        self.packets.push(packet);
+    }
+
+    // copies the selected pixels to the clipboard
+    pub fn copy(selection: Option<IRect>, paint_canvas: &mut PaintCanvas, log: &mut Log, language: &Language) {
+        if let Some(rect) = selection {
+            let image = paint_canvas.capture(rect);
+            match clipboard::copy_image(&image) {
+                Ok(()) => notify_tr!(log, language, Success, "notifications.selection_copied"),
+                Err(error) => notify!(log, Error, "{}", error),
+            }
+        }
+    }
+
+    // copies the selected pixels to the clipboard as an SVG document, with the pixels embedded as a PNG image.
+    // vector graphics editors accept these when pasted as text, unlike raw images which some of them ignore
+    fn copy_as_svg(selection: Option<IRect>, paint_canvas: &mut PaintCanvas, log: &mut Log, language: &Language) {
+        if let Some(rect) = selection {
+            let image = paint_canvas.capture(rect);
+            let png = match encode_png(&image) {
+                Ok(png) => png,
+                Err(error) => {
+                    notify_tr!(log, language, Error, "notifications.encode_image_failed", error);
+                    return
+                },
+            };
+            let (width, height) = (rect.width(), rect.height());
+            let svg = format!(
+                concat!(
+                    r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" "#,
+                    r#"width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+                    r#"<image width="{w}" height="{h}" style="image-rendering: pixelated" "#,
+                    r#"xlink:href="data:image/png;base64,{png}"/></svg>"#,
+                ),
+                w = width,
+                h = height,
+                png = base64::encode(&png),
+            );
+            match clipboard::copy_text(&svg) {
+                Ok(()) => notify_tr!(log, language, Success, "notifications.selection_copied_svg"),
+                Err(error) => notify!(log, Error, "{}", error),
+            }
+        }
+    }
+
+    // asks for a file name to save the selected pixels to as a PNG image. the clipboard can only hold text and raw
+    // images, so this is how a selection is handed over as a file
+    fn show_save_dialog(&mut self, selection: Option<IRect>, paint_canvas: &mut PaintCanvas) {
+        let rect = match selection {
+            Some(rect) if self.save_dialog.is_none() => rect,
+            _ => return,
+        };
+        let dialog = PendingDialog::spawn(|dialog| {
+            dialog
+                .set_filename("selection.png")
+                .add_filter("PNG image", &["png"])
+                .show_save_single_file()
+        });
+        self.save_dialog = Some((dialog, paint_canvas.capture(rect)));
+    }
+
+    // saves the selected pixels to the file picked in the save dialog, if it's been closed
+    fn poll_save_dialog(&mut self, log: &mut Log, language: &Language) {
+        let path = match self.save_dialog.as_ref().and_then(|(dialog, _)| dialog.poll()) {
+            Some(path) => path,
+            None => return,
+        };
+        let (_, image) = self.save_dialog.take().unwrap();
+        Self::save_as_png(&image, path, log, language);
+    }
+
+    fn save_as_png(image: &RgbaImage, path: DialogResult, log: &mut Log, language: &Language) {
+        let path = match path {
+            Ok(Some(path)) if path.extension().is_none() => path.with_extension("png"),
+            Ok(Some(path)) => path,
+            Ok(None) => return,
+            Err(error) => {
+                notify_tr!(log, language, Error, "notifications.file_dialog_failed", error);
+                return
+            },
+        };
+        match image.save_with_format(&path, ImageFormat::Png) {
+            Ok(()) => notify_tr!(log, language, Success, "notifications.selection_saved", path.display()),
+            Err(error) => notify_tr!(log, language, Error, "notifications.save_file_failed", path.display(), error),
+        }
+    }
+
+    // makes the image float above the canvas, and lets others know about it
+    fn float(
+        &mut self,
+        image: &RgbaImage,
+        transform: Transform,
+        lifted: Option<IRect>,
+        log: &mut Log,
+        language: &Language,
+    ) {
+        let png = match encode_png(image) {
+            Ok(png) => png,
+            Err(error) => {
+                notify_tr!(log, language, Error, "notifications.encode_image_failed", error);
+                return
+            },
+        };
+        self.floating = Some(Floating {
+            image: rgba_image_to_skia(image),
+            transform,
+            lifted_from: lifted.map(|_| transform),
+        });
+        self.queue_packet(Packet::Float {
+            png,
+            transform,
+            lifted: lifted.map(|rect| (rect.left, rect.top, rect.right, rect.bottom)),
+        });
+    }
+
+    // creates a floating image from the clipboard, centered on the given point
+    fn paste(&mut self, center: Point, log: &mut Log, language: &Language) {
+        match clipboard::paste_image() {
+            Ok(image) => {
+                let transform = Transform {
+                    center: (center.x, center.y),
+                    size: (image.width() as f32, image.height() as f32),
+                    rotation: 0.0,
+                };
+                self.float(&image, transform, None, log, language);
+            },
+            Err(error) => notify!(log, Error, "{}", error),
+        }
+    }
+
+    // returns the centers of images of the given sizes laid out next to each other, so that the whole board is
+    // centered on the given point. grids are as close to square as possible, and their rows are left-aligned
+    fn lay_out_board(sizes: &[(f32, f32)], layout: BoardLayout, center: Point) -> Vec<Point> {
+        let columns = match layout {
+            BoardLayout::Grid => (sizes.len() as f32).sqrt().ceil() as usize,
+            BoardLayout::Row => sizes.len(),
+        };
+        let mut centers = Vec::with_capacity(sizes.len());
+        let (mut width, mut y) = (0.0_f32, 0.0);
+        for row in sizes.chunks(columns.max(1)) {
+            let height = row.iter().fold(0.0_f32, |height, &(_, image_height)| height.max(image_height));
+            let mut x = 0.0;
+            for &(image_width, _) in row {
+                centers.push(Point::new(x + image_width / 2.0, y + height / 2.0));
+                x += image_width + Self::BOARD_GAP;
+            }
+            width = width.max(x - Self::BOARD_GAP);
+            y += height + Self::BOARD_GAP;
+        }
+        let height = y - Self::BOARD_GAP;
+        // the board's top left corner is kept on a whole pixel, so that images aren't blurred when stamped
+        let offset = Vector::new((center.x - width / 2.0).round(), (center.y - height / 2.0).round());
+        centers.into_iter().map(|center| center + offset).collect()
+    }
+
+    // asks for image files to import. they're imported once the dialog is closed
+    fn show_import_dialog(&mut self) {
+        if self.import_dialog.is_some() { return }
+        let dialog = PendingDialog::spawn(|dialog| {
+            dialog
+                .add_filter("Image", &["png", "jpg", "jpeg", "gif", "bmp", "webp"])
+                .show_open_multiple_file()
+        });
+        self.import_dialog = Some((dialog, self.mouse));
+    }
+
+    // imports the images picked in the import dialog, if it's been closed
+    fn poll_import_dialog(
+        &mut self,
+        paint_canvas: &mut PaintCanvas,
+        history: &mut History,
+        log: &mut Log,
+        language: &Language,
+    ) {
+        let paths = match self.import_dialog.as_ref().and_then(|(dialog, _)| dialog.poll()) {
+            Some(paths) => paths,
+            None => return,
+        };
+        let (_, mouse) = self.import_dialog.take().unwrap();
+        match paths {
+            Ok(paths) => self.import(&paths, mouse, paint_canvas, history, log, language),
+            Err(error) => notify_tr!(log, language, Error, "notifications.file_dialog_failed", error),
+        }
+    }
+
+    // lays the image files out as a board around the given point. the first image floats right away, and the rest are
+    // queued up behind it
+    fn import(
+        &mut self,
+        paths: &[PathBuf],
+        center: Point,
+        paint_canvas: &mut PaintCanvas,
+        history: &mut History,
+        log: &mut Log,
+        language: &Language,
+    ) {
+        let mut images = Vec::new();
+        for path in paths {
+            let image = match ::image::open(path) {
+                Ok(image) => image.to_rgba8(),
+                Err(error) => {
+                    notify_tr!(log, language, Error, "notifications.open_file_failed", path.display(), error);
+                    continue
+                },
+            };
+            match encode_png(&image) {
+                Ok(png) => images.push((png, image)),
+                Err(error) => {
+                    notify_tr!(log, language, Error, "notifications.encode_file_failed", path.display(), error)
+                },
+            }
+        }
+        if images.is_empty() { return }
+
+        self.stamp_all(paint_canvas, history);
+        let sizes: Vec<_> = images.iter().map(|(_, image)| (image.width() as f32, image.height() as f32)).collect();
+        let centers = Self::lay_out_board(&sizes, self.board_layout, center);
+        for ((png, image), center) in images.into_iter().zip(centers) {
+            self.queued.push_back(Queued {
+                png,
+                image: rgba_image_to_skia(&image),
+                transform: Transform {
+                    center: (center.x, center.y),
+                    size: (image.width() as f32, image.height() as f32),
+                    rotation: 0.0,
+                },
+            });
+        }
+        self.float_next();
+    }
+
+    // makes the next imported image float, if there is one
+    fn float_next(&mut self) {
+        if let Some(Queued { png, image, transform }) = self.queued.pop_front() {
+            self.floating = Some(Floating {
+                image,
+                transform,
+                lifted_from: None,
+            });
+            self.queue_packet(Packet::Float { png, transform, lifted: None });
+        }
+    }
+
+    fn clear_rect(paint_canvas: &mut PaintCanvas, rect: IRect) {
+        let rect = Rect::from_irect(rect);
+        let mut paint = Paint::default();
+        paint.set_blend_mode(BlendMode::Clear);
+        paint_canvas.draw(rect, |canvas| { canvas.draw_rect(rect, &paint); });
+    }
+
+    // whether the rectangle someone else lifted an image off of is fine to clear
+    fn is_valid_lift(rect: IRect) -> bool {
+        let rect = Rect::from_irect(rect);
+        PaintCanvas::is_in_bounds(Point::new(rect.left, rect.top))
+            && PaintCanvas::is_in_bounds(Point::new(rect.right, rect.bottom))
+            && (0.0..=Self::MAX_SIZE).contains(&rect.width())
+            && (0.0..=Self::MAX_SIZE).contains(&rect.height())
+    }
+
+    // moves the selected pixels off the canvas into a floating image
+    fn lift(
+        &mut self,
+        rect: IRect,
+        paint_canvas: &mut PaintCanvas,
+        history: &mut History,
+        log: &mut Log,
+        language: &Language,
+    ) {
+        let image = paint_canvas.capture(rect);
+        self.float(&image, Transform::from_rect(rect), Some(rect), log, language);
+        if self.floating.is_some() {
+            history.begin();
+            history.save_chunks(paint_canvas, PaintCanvas::chunks_in_rect(Rect::from_irect(rect)));
+            Self::clear_rect(paint_canvas, rect);
+            history.end();
+        }
+    }
+
+    // puts the floating image onto the canvas
+    fn stamp(&mut self, paint_canvas: &mut PaintCanvas, history: &mut History) {
+        self.drag = None;
+        if let Some(Floating { image, transform, .. }) = self.floating.take() {
+            history.begin();
+            history.save_chunks(paint_canvas, PaintCanvas::chunks_in_rect(transform.bounds()));
+            transform.draw_onto(paint_canvas, &image);
+            history.end();
+            self.queue_packet(Packet::Stamp(transform));
+        }
+        self.float_next();
+    }
+
+    // stamps the floating image, along with all imported images that are still waiting for their turn
+    fn stamp_all(&mut self, paint_canvas: &mut PaintCanvas, history: &mut History) {
+        while self.floating.is_some() {
+            self.stamp(paint_canvas, history);
+        }
+    }
+
+    // throws the floating image away. images that were lifted off the canvas are put back where they came from
+    fn cancel(&mut self, paint_canvas: &mut PaintCanvas, history: &mut History) {
+        match &mut self.floating {
+            Some(Floating { transform, lifted_from: Some(original), .. }) => {
+                *transform = *original;
+                self.stamp(paint_canvas, history);
+            },
+            Some(_) => {
+                self.floating = None;
+                self.drag = None;
+                self.queue_packet(Packet::Cancel);
+                self.float_next();
+            },
+            None => (),
+        }
+    }
+
+    fn rotation_handle(transform: &Transform) -> Point {
+        let (_, height) = transform.size;
+        Point::new(0.0, -height / 2.0 - Self::ROTATION_HANDLE_DISTANCE)
+    }
+
+    // returns what dragging the image at the given point would do, or None if the point is outside of the image and
+    // all of its handles
+    fn drag_at(transform: &Transform, point: Point) -> Option<Drag> {
+        let local = transform.to_local(point);
+        let near = |handle: Point| {
+            let radius = Self::HANDLE_SIZE / 2.0;
+            (local.x - handle.x).abs() <= radius && (local.y - handle.y).abs() <= radius
+        };
+        if near(Self::rotation_handle(transform)) {
+            return Some(Drag::Rotate)
+        }
+        for &corner in &Transform::CORNERS {
+            if near(transform.corner(corner)) {
+                return Some(Drag::Scale(corner.0, corner.1))
+            }
+        }
+        if contains(transform.local_rect(), local) {
+            return Some(Drag::Move(point - transform.center()))
+        }
+        None
+    }
+
+    fn apply_drag(transform: &mut Transform, drag: Drag, mouse: Point, snap_rotation: bool) {
+        match drag {
+            Drag::Move(offset) => {
+                let center = mouse - offset;
+                transform.center = (center.x, center.y);
+            },
+            Drag::Scale(sx, sy) => {
+                // the opposite corner stays where it is
+                let opposite = transform.corner((-sx, -sy));
+                let local = transform.to_local(mouse);
+                let size = (
+                    (sx * (local.x - opposite.x)).clamp(1.0, Self::MAX_SIZE),
+                    (sy * (local.y - opposite.y)).clamp(1.0, Self::MAX_SIZE),
+                );
+                let center = transform.to_canvas(opposite + Point::new(sx * size.0 / 2.0, sy * size.1 / 2.0));
+                transform.center = (center.x, center.y);
+                transform.size = size;
+            },
+            Drag::Rotate => {
+                let delta = mouse - transform.center();
+                // the rotation handle is above the image, so pointing straight up means no rotation
+                let angle = delta.y.atan2(delta.x).to_degrees() + 90.0;
+                transform.rotation =
+                    if snap_rotation { quantize(angle, Self::ROTATION_STEP) }
+                    else { angle };
+            },
+        }
+    }
+
+    fn draw_handles(canvas: &mut Canvas, transform: &Transform) {
+        let mut paint = Paint::new(Color4f::from(Color::WHITE.with_a(192)), None);
+        paint.set_anti_alias(true);
+        paint.set_blend_mode(BlendMode::Difference);
+        transform.with_local(canvas, |canvas| {
+            paint.set_style(skpaint::Style::Stroke);
+            canvas.draw_rect(transform.local_rect(), &paint);
+            let rotation_handle = Self::rotation_handle(transform);
+            canvas.draw_line((0.0, transform.local_rect().top), rotation_handle, &paint);
+            paint.set_style(skpaint::Style::Fill);
+            canvas.draw_circle(rotation_handle, Self::HANDLE_SIZE / 2.0, &paint);
+            for &corner in &Transform::CORNERS {
+                let size = Self::HANDLE_SIZE;
+                let corner = transform.corner(corner);
+                canvas.draw_rect(Rect::from_xywh(corner.x - size / 2.0, corner.y - size / 2.0, size, size), &paint);
+            }
+        });
+    }
+
+}
+
+impl Tool for SelectionTool {
+
+    fn name(&self) -> &'static str {
+        "selection"
+    }
+
+    fn icon<'a>(&self, assets: &'a Assets) -> &'a Image {
+        &assets.icons.tools.selection
+    }
+
+    fn key_shortcut(&self) -> VirtualKeyCode {
+        VirtualKeyCode::S
+    }
+
+    fn process_paint_canvas_input(
+        &mut self,
+        ToolArgs { ui, input, assets, paint_canvas, selection, history, log, .. }: ToolArgs,
+        mouse: Point,
+        _previous_mouse: Point,
+    ) {
+        // selections are snapped to whole pixels
+        let mouse = Point::new(mouse.x.round(), mouse.y.round());
+        self.mouse = mouse;
+        let pressed = ui.has_mouse(input) && input.mouse_button_just_pressed(MouseButton::Left);
+
+        if input.ctrl_is_down() && input.key_just_typed(VirtualKeyCode::V) {
+            self.stamp_all(paint_canvas, history);
+            self.anchor = None;
+            *selection = None;
+            self.paste(mouse, log, &assets.language);
+        }
+
+        // dragging the selection lifts it off the canvas
+        if let (true, Some(rect), true) = (self.floating.is_none(), *selection, pressed) {
+            if let Some(drag) = Self::drag_at(&Transform::from_rect(rect), mouse) {
+                self.lift(rect, paint_canvas, history, log, &assets.language);
+                self.drag = Some(drag);
+                *selection = None;
+            }
+        }
+
+        // the floating image takes priority over selecting. clicking outside of it stamps it onto the canvas, and
+        // starts a new selection right away
+        if let Some(floating) = &mut self.floating {
+            let transform = &mut floating.transform;
+            if pressed && self.drag.is_none() {
+                self.drag = Self::drag_at(transform, mouse);
+            }
+            if let Some(drag) = self.drag {
+                let previous = (transform.center, transform.size, transform.rotation);
+                Self::apply_drag(transform, drag, mouse, input.shift_is_down());
+                if (transform.center, transform.size, transform.rotation) != previous {
+                    let transform = *transform;
+                    self.queue_packet(Packet::Transform(transform));
+                }
+            }
+            if input.mouse_button_just_released(MouseButton::Left) {
+                self.drag = None;
+            }
+
+            if (pressed && self.drag.is_none()) || input.key_just_typed(VirtualKeyCode::Return) {
+                self.stamp(paint_canvas, history);
+            } else if input.key_just_typed(VirtualKeyCode::Escape) {
+                self.cancel(paint_canvas, history);
+            } else {
+                self.selection = None;
+                return
+            }
+            // the next imported image floats right away, so the click doesn't start a new selection
+            if self.floating.is_some() {
+                self.selection = None;
+                return
+            }
+        }
+
+        if pressed {
+            self.anchor = Some(mouse);
+            *selection = None;
+        }
+        if let Some(anchor) = self.anchor {
+            let rect = IRect::new(
+                f32::min(anchor.x, mouse.x) as i32,
+                f32::min(anchor.y, mouse.y) as i32,
+                f32::max(anchor.x, mouse.x) as i32,
+                f32::max(anchor.y, mouse.y) as i32,
+            );
+            *selection = if rect.is_empty() { None } else { Some(rect) };
+        }
+        if input.mouse_button_just_released(MouseButton::Left) {
+            self.anchor = None;
+        }
+
+        if input.key_just_typed(VirtualKeyCode::Escape) {
+            self.anchor = None;
+            *selection = None;
+        }
+        if input.ctrl_is_down() && input.key_just_typed(VirtualKeyCode::C) {
+            Self::copy(*selection, paint_canvas, log, &assets.language);
+        }
+        // the handles aren't shown while the selection is being dragged out
+        self.selection = if self.anchor.is_none() { *selection } else { None };
+    }
+
+    fn process_paint_canvas_overlays(&mut self, canvas: &mut Canvas, _input: &Input, _mouse: Point) {
+        // imported images waiting for their turn are dimmed, so that it's clear which one is being placed
+        let mut paint = Paint::default();
+        paint.set_alpha_f(0.5);
+        paint.set_filter_quality(FilterQuality::Medium);
+        for queued in &self.queued {
+            queued.transform.with_local(canvas, |canvas| {
+                canvas.draw_image_rect(&queued.image, None, queued.transform.local_rect(), &paint);
+            });
+        }
+        if let Some(floating) = &self.floating {
+            floating.transform.draw(canvas, &floating.image);
+            Self::draw_handles(canvas, &floating.transform);
+        } else if let Some(rect) = self.selection {
+            Self::draw_handles(canvas, &Transform::from_rect(rect));
+        }
+    }
+
+    fn draw_layers(&self, canvas: &mut Canvas) {
+        for (&sender, preview) in &self.previews {
+            preview.transform.draw(canvas, &preview.image);
+            // outlined in the color of whoever is moving the selection, to match their cursor
+            let mut paint = Paint::new(Color4f::from(net::mate_color(sender)), None);
+            paint.set_anti_alias(true);
+            paint.set_style(skpaint::Style::Stroke);
+            paint.set_stroke_width(2.0);
+            preview.transform.with_local(canvas, |canvas| {
+                canvas.draw_rect(preview.transform.local_rect(), &paint);
+            });
+        }
+    }
+
+    fn process_bottom_bar(
+        &mut self,
+        ToolArgs { ui, input, assets, paint_canvas, selection, history, log, .. }: ToolArgs,
+        canvas: &mut Canvas,
+    ) {
+        self.poll_import_dialog(paint_canvas, history, log, &assets.language);
+        self.poll_save_dialog(log, &assets.language);
+
+        let selection_size = match (&self.floating, *selection) {
+            (Some(Floating { transform, .. }), _) => {
+                let (width, height) = transform.size;
+                format!("{}×{}, {}°", width.round(), height.round(), transform.rotation.round())
+            },
+            (None, Some(rect)) => format!("{}×{}", rect.width(), rect.height()),
+            (None, None) => assets.language.tr("tools.nothing_selected").into(),
+        };
+        ui.push_group((128.0, ui.height()), Layout::Freeform);
+        ui.text(canvas, &selection_size, assets.colors.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+
+        let button = ButtonArgs {
+            height: ui.height(),
+            colors: &assets.colors.button,
+        };
+        if self.floating.is_some() {
+            if Button::with_text(ui, canvas, input, button, assets.language.tr("tools.stamp")).clicked() {
+                self.stamp(paint_canvas, history);
+            }
+            ui.space(8.0);
+            if Button::with_text(ui, canvas, input, button, assets.language.tr("tools.cancel")).clicked() {
+                self.cancel(paint_canvas, history);
+            }
+            if !self.queued.is_empty() {
+                ui.space(8.0);
+                if Button::with_text(ui, canvas, input, button, assets.language.tr("tools.stamp_all")).clicked() {
+                    self.stamp_all(paint_canvas, history);
+                }
+                ui.space(16.0);
+                let images_left = assets.language.format("tools.images_left", &[&self.queued.len()]);
+                ui.push_group((ui.remaining_width(), ui.height()), Layout::Freeform);
+                ui.text(canvas, &images_left, assets.colors.text, (AlignH::Left, AlignV::Middle));
+                ui.pop_group();
+            }
+            return
+        }
+
+        if selection.is_some() {
+            if Button::with_text(ui, canvas, input, button, assets.language.tr("tools.copy")).clicked() {
+                Self::copy(*selection, paint_canvas, log, &assets.language);
+            }
+            ui.space(8.0);
+            if Button::with_text(ui, canvas, input, button, assets.language.tr("tools.copy_as_svg")).clicked() {
+                Self::copy_as_svg(*selection, paint_canvas, log, &assets.language);
+            }
+            ui.space(8.0);
+            if Button::with_text(ui, canvas, input, button, assets.language.tr("tools.save_as_png")).clicked() {
+                self.show_save_dialog(*selection, paint_canvas);
+            }
+            ui.space(16.0);
+        }
+
+        // importing images
+
+        if Button::with_text(ui, canvas, input, button, assets.language.tr("tools.import_images")).clicked() {
+            self.anchor = None;
+            *selection = None;
+            self.show_import_dialog();
+        }
+        ui.space(8.0);
+        let layout = match self.board_layout {
+            BoardLayout::Grid => "tools.layout_grid",
+            BoardLayout::Row => "tools.layout_row",
+        };
+        if Button::with_text(ui, canvas, input, button, assets.language.tr(layout)).clicked() {
+            self.board_layout = match self.board_layout {
+                BoardLayout::Grid => BoardLayout::Row,
+                BoardLayout::Row => BoardLayout::Grid,
+            };
+        }
+    }
+
+    fn network_send(&mut self, peer: &Peer) -> Result<(), net::Error> {
+        let name = self.name();
+        for packet in self.packets.drain(..) {
+            peer.send_tool(name, &packet)?;
+        }
+        Ok(())
+    }
+
+    fn network_receive(
+        &mut self,
+        paint_canvas: &mut PaintCanvas<'static>,
+        sender: SocketAddr,
+        payload: &[u8],
+    ) -> Result<(), net::Error> {
+        match build::decode(payload)? {
+            Packet::Float { png, transform, lifted } => {
+                let lifted = lifted.map(|(left, top, right, bottom)| IRect::new(left, top, right, bottom));
+                if !transform.is_valid() || !lifted.is_none_or(Self::is_valid_lift) {
+                    log::warn!("{} tried to float an image that's out of bounds", sender);
+                    return Ok(())
+                }
+                if let Some(rect) = lifted {
+                    Self::clear_rect(paint_canvas, rect);
+                }
+                self.previews.insert(sender, Floating::decode(&png, transform)?);
+            },
+            Packet::Transform(transform) => {
+                if let Some(preview) = self.previews.get_mut(&sender).filter(|_| transform.is_valid()) {
+                    preview.transform = transform;
+                }
+            },
+            Packet::Stamp(transform) => {
+                if let Some(preview) = self.previews.remove(&sender) {
+                    if transform.is_valid() {
+                        transform.draw_onto(paint_canvas, &preview.image);
+                    } else {
+                        log::warn!("{} tried to stamp an image out of bounds", sender);
+                    }
+                }
+            },
+            Packet::Cancel => {
+                self.previews.remove(&sender);
+            },
+        }
+        Ok(())
+    }
+
+    fn network_left(&mut self, _paint_canvas: &mut PaintCanvas<'static>, sender: SocketAddr) {
+        self.previews.remove(&sender);
+    }
+
+    fn captures_keyboard(&self) -> bool {
+        self.floating.is_some()
+    }
+
+}
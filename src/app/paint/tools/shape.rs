@@ -0,0 +1,300 @@
+// shape tools: straight lines, rectangles, and ellipses.
+// shapes are dragged out with the left mouse button, and are only drawn onto the canvas once the button is released.
+// until then, other people in the room see a preview of the shape.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use netcanv_protocol::build;
+use serde::{Deserialize, Serialize};
+use skulpin::skia_safe::*;
+use skulpin::skia_safe::paint as skpaint;
+
+use crate::assets::Assets;
+use crate::net::{self, Peer};
+use crate::paint_canvas::PaintCanvas;
+use crate::ui::*;
+use crate::util::*;
+use super::{COLOR_PALETTE, Tool, ToolArgs, process_color_swatch};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ShapeKind {
+    Line,
+    Rectangle,
+    Ellipse,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Shape {
+    start: (f32, f32),
+    end: (f32, f32),
+    // 0xRRGGBBAA, the same format as the palette
+    color: u32,
+    thickness: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    // the shape is still being dragged out
+    Preview(Shape),
+    // the shape was drawn onto the canvas
+    Commit(Shape),
+    // the shape was cancelled before being drawn onto the canvas
+    Cancel,
+}
+
+pub struct ShapeTool {
+    kind: ShapeKind,
+    color: Color4f,
+    thickness_slider: Slider,
+
+    // the shape that's currently being dragged out
+    shape: Option<Shape>,
+    // packets waiting to be sent in the next network update
+    packets: Vec<Packet>,
+    // shapes other people in the room are currently dragging out
+    previews: HashMap<SocketAddr, Shape>,
+}
+
+impl Shape {
+
+    fn rect(&self) -> Rect {
+        Rect::new(
+            f32::min(self.start.0, self.end.0),
+            f32::min(self.start.1, self.end.1),
+            f32::max(self.start.0, self.end.0),
+            f32::max(self.start.1, self.end.1),
+        )
+    }
+
+    // clamps the thickness of a shape received from someone else to what the slider allows, and returns whether the
+    // shape can be drawn. shapes have to be within the canvas's bounds and at most MAX_SIZE large, as drawing them
+    // creates every chunk they span
+    fn sanitize(&mut self) -> bool {
+        if !self.thickness.is_finite() {
+            return false
+        }
+        self.thickness = self.thickness.clamp(ShapeTool::MIN_THICKNESS, ShapeTool::MAX_THICKNESS);
+        let rect = self.rect();
+        PaintCanvas::is_in_bounds(Point::from(self.start))
+            && PaintCanvas::is_in_bounds(Point::from(self.end))
+            && rect.width() <= ShapeTool::MAX_SIZE
+            && rect.height() <= ShapeTool::MAX_SIZE
+    }
+
+    // the rectangle containing all pixels that are touched when drawing the shape
+    fn bounds(&self) -> Rect {
+        let outset = self.thickness / 2.0 + 1.0;
+        self.rect().with_outset((outset, outset))
+    }
+
+    fn draw(&self, kind: ShapeKind, canvas: &mut Canvas) {
+        let mut paint = Paint::new(hex_color4f(self.color), None);
+        paint.set_anti_alias(false);
+        paint.set_style(skpaint::Style::Stroke);
+        paint.set_stroke_width(self.thickness);
+        paint.set_stroke_cap(skpaint::Cap::Round);
+        match kind {
+            ShapeKind::Line => canvas.draw_line(self.start, self.end, &paint),
+            ShapeKind::Rectangle => canvas.draw_rect(self.rect(), &paint),
+            ShapeKind::Ellipse => canvas.draw_oval(self.rect(), &paint),
+        };
+    }
+
+}
+
+impl ShapeTool {
+
+    const MIN_THICKNESS: f32 = 1.0;
+    const MAX_THICKNESS: f32 = 64.0;
+    // the largest other people's shapes can be
+    const MAX_SIZE: f32 = 8192.0;
+
+    pub fn new(kind: ShapeKind) -> Self {
+        Self {
+            kind,
+            color: hex_color4f(COLOR_PALETTE[0]),
+            thickness_slider: Slider::new(4.0, Self::MIN_THICKNESS, Self::MAX_THICKNESS, SliderStep::Discrete(1.0)),
+            shape: None,
+            packets: Vec::new(),
+            previews: HashMap::new(),
+        }
+    }
+
+    // queues a packet for sending. a preview replaces the previous one if it hasn't been sent yet, because only the
+    // latest preview matters
+    fn queue_packet(&mut self, packet: Packet) {
+        if let (Some(Packet::Preview(_)), Packet::Preview(_)) = (self.packets.last(), &packet) {
+            self.packets.pop();
+        }
+        self.packets.push(packet);
+    }
+
+}
+
+impl Tool for ShapeTool {
+
+    fn name(&self) -> &'static str {
+        match self.kind {
+            ShapeKind::Line => "line",
+            ShapeKind::Rectangle => "rectangle",
+            ShapeKind::Ellipse => "ellipse",
+        }
+    }
+
+    fn icon<'a>(&self, assets: &'a Assets) -> &'a Image {
+        match self.kind {
+            ShapeKind::Line => &assets.icons.tools.line,
+            ShapeKind::Rectangle => &assets.icons.tools.rectangle,
+            ShapeKind::Ellipse => &assets.icons.tools.ellipse,
+        }
+    }
+
+    fn key_shortcut(&self) -> VirtualKeyCode {
+        match self.kind {
+            ShapeKind::Line => VirtualKeyCode::L,
+            ShapeKind::Rectangle => VirtualKeyCode::R,
+            ShapeKind::Ellipse => VirtualKeyCode::O,
+        }
+    }
+
+    fn process_paint_canvas_input(
+        &mut self,
+        ToolArgs { ui, input, paint_canvas, snap_to_grid, history, .. }: ToolArgs,
+        mouse: Point,
+        _previous_mouse: Point,
+    ) {
+        let mouse = match snap_to_grid {
+            Some(cell_size) => snap_point(mouse, cell_size),
+            None => mouse,
+        };
+        let mouse = (mouse.x, mouse.y);
+
+        if ui.has_mouse(input) && input.mouse_button_just_pressed(MouseButton::Left) {
+            self.shape = Some(Shape {
+                start: mouse,
+                end: mouse,
+                color: color4f_hex(self.color),
+                thickness: self.thickness_slider.value(),
+            });
+        }
+        if let Some(shape) = &mut self.shape {
+            if shape.end != mouse {
+                shape.end = mouse;
+                let preview = shape.clone();
+                self.queue_packet(Packet::Preview(preview));
+            }
+        }
+
+        if input.key_just_typed(VirtualKeyCode::Escape) && self.shape.is_some() {
+            self.shape = None;
+            self.queue_packet(Packet::Cancel);
+        }
+        if input.mouse_button_just_released(MouseButton::Left) {
+            if let Some(shape) = self.shape.take() {
+                let kind = self.kind;
+                history.begin();
+                history.save_chunks(paint_canvas, PaintCanvas::chunks_in_rect(shape.bounds()));
+                paint_canvas.draw(shape.bounds(), |canvas| shape.draw(kind, canvas));
+                history.end();
+                self.queue_packet(Packet::Commit(shape));
+            }
+        }
+    }
+
+    fn process_paint_canvas_overlays(&mut self, canvas: &mut Canvas, _input: &Input, _mouse: Point) {
+        if let Some(shape) = &self.shape {
+            shape.draw(self.kind, canvas);
+        }
+    }
+
+    fn draw_layers(&self, canvas: &mut Canvas) {
+        for shape in self.previews.values() {
+            shape.draw(self.kind, canvas);
+        }
+    }
+
+    fn process_bottom_bar(&mut self, ToolArgs { ui, input, assets, config, .. }: ToolArgs, canvas: &mut Canvas) {
+        // palette
+
+        for &hex_color in COLOR_PALETTE.iter().chain(config.brush.palette.iter()) {
+            let color = hex_color4f(hex_color);
+            if let Some(MouseButton::Left) = process_color_swatch(ui, canvas, input, color, self.color == color) {
+                self.color = color;
+            }
+        }
+        ui.space(16.0);
+
+        // thickness
+
+        ui.push_group((80.0, ui.height()), Layout::Freeform);
+        ui.text(canvas, assets.language.tr("tools.thickness"), assets.colors.text, (AlignH::Center, AlignV::Middle));
+        ui.pop_group();
+
+        ui.space(8.0);
+        self.thickness_slider.process(ui, canvas, input, SliderArgs {
+            width: 192.0,
+            color: assets.colors.slider,
+        });
+        ui.space(8.0);
+
+        let thickness_string = self.thickness_slider.value().to_string();
+        ui.push_group((ui.height(), ui.height()), Layout::Freeform);
+        ui.set_font(assets.sans_bold.clone());
+        ui.text(canvas, &thickness_string, assets.colors.text, (AlignH::Center, AlignV::Middle));
+        ui.pop_group();
+    }
+
+    fn color(&self) -> Option<Color4f> {
+        Some(self.color)
+    }
+
+    fn set_color(&mut self, color: Color4f) {
+        self.color = color;
+    }
+
+    fn network_send(&mut self, peer: &Peer) -> Result<(), net::Error> {
+        let name = self.name();
+        for packet in self.packets.drain(..) {
+            peer.send_tool(name, &packet)?;
+        }
+        Ok(())
+    }
+
+    fn network_receive(
+        &mut self,
+        paint_canvas: &mut PaintCanvas<'static>,
+        sender: SocketAddr,
+        payload: &[u8],
+    ) -> Result<(), net::Error> {
+        match build::decode(payload)? {
+            Packet::Preview(mut shape) => {
+                if shape.sanitize() {
+                    self.previews.insert(sender, shape);
+                }
+            },
+            Packet::Commit(mut shape) => {
+                let kind = self.kind;
+                self.previews.remove(&sender);
+                if shape.sanitize() {
+                    paint_canvas.draw(shape.bounds(), |canvas| shape.draw(kind, canvas));
+                } else {
+                    log::warn!("{} tried to draw an invalid shape", sender);
+                }
+            },
+            Packet::Cancel => {
+                self.previews.remove(&sender);
+            },
+        }
+        Ok(())
+    }
+
+    fn network_left(&mut self, _paint_canvas: &mut PaintCanvas<'static>, sender: SocketAddr) {
+        self.previews.remove(&sender);
+    }
+
+    fn cursor_size(&self) -> f32 {
+        self.thickness_slider.value()
+    }
+
+}
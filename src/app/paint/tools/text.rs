@@ -0,0 +1,312 @@
+// the text tool. clicking on the canvas starts typing text, which is put onto the canvas after pressing Enter or
+// clicking somewhere else. other people in the room see the text as it's being typed.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use netcanv_protocol::build;
+use serde::{Deserialize, Serialize};
+use skulpin::skia_safe::*;
+use skulpin::skia_safe::paint as skpaint;
+
+use crate::assets::Assets;
+use crate::font_fallback;
+use crate::net::{self, Peer};
+use crate::paint_canvas::PaintCanvas;
+use crate::ui::*;
+use crate::util::*;
+use super::{COLOR_PALETTE, Tool, ToolArgs, process_color_swatch};
+use super::super::history::History;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Text {
+    // the position of the text's baseline
+    position: (f32, f32),
+    text: String,
+    // 0xRRGGBBAA, the same format as the palette
+    color: u32,
+    size: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    // the text is still being typed
+    Preview(Text),
+    // the text was drawn onto the canvas
+    Commit(Text),
+    // the text was cancelled before being drawn onto the canvas
+    Cancel,
+}
+
+pub struct TextTool {
+    font: RcFont,
+    color: Color4f,
+    size_slider: Slider,
+
+    // the text that's currently being typed
+    text: Option<Text>,
+    blink_start: f32,
+    // packets waiting to be sent in the next network update
+    packets: Vec<Packet>,
+    // text other people in the room are currently typing
+    previews: HashMap<SocketAddr, Text>,
+}
+
+impl Text {
+
+    fn font(&self, font: &RcFont) -> Font {
+        let font = font.borrow();
+        font.with_size(self.size).unwrap_or_else(|| font.clone())
+    }
+
+    // clamps the size of text received from someone else to what the slider allows, and returns whether the text can
+    // be drawn
+    fn sanitize(&mut self) -> bool {
+        if !self.size.is_finite() {
+            return false
+        }
+        self.size = self.size.clamp(TextTool::MIN_SIZE, TextTool::MAX_SIZE);
+        PaintCanvas::is_in_bounds(Point::from(self.position)) && self.text.chars().count() <= TextTool::MAX_LENGTH
+    }
+
+    // the rectangle containing all pixels that are touched when drawing the text
+    fn bounds(&self, font: &Font) -> Rect {
+        let advance = font_fallback::measure_str(&self.text, font);
+        let (_, metrics) = font.metrics();
+        let (x, y) = self.position;
+        Rect::new(x, y + metrics.ascent, x + advance, y + metrics.descent).with_outset((2.0, 2.0))
+    }
+
+    fn draw(&self, font: &Font, canvas: &mut Canvas) {
+        let mut paint = Paint::new(hex_color4f(self.color), None);
+        paint.set_anti_alias(true);
+        font_fallback::draw_str(canvas, &self.text, self.position, font, &paint);
+    }
+
+    fn draw_onto(&self, font: &RcFont, paint_canvas: &mut PaintCanvas) {
+        let font = self.font(font);
+        paint_canvas.draw(self.bounds(&font), |canvas| self.draw(&font, canvas));
+    }
+
+}
+
+impl TextTool {
+
+    const BLINK_PERIOD: f32 = 1.0;
+    const HALF_BLINK: f32 = Self::BLINK_PERIOD / 2.0;
+
+    const BACKSPACE: char = '\x08';
+
+    const MIN_SIZE: f32 = 8.0;
+    const MAX_SIZE: f32 = 128.0;
+    // the most characters a single piece of text can have, so that it can't stretch across an endless amount of chunks
+    const MAX_LENGTH: usize = 1024;
+
+    pub fn new(font: RcFont) -> Self {
+        Self {
+            font,
+            color: hex_color4f(COLOR_PALETTE[0]),
+            size_slider: Slider::new(24.0, Self::MIN_SIZE, Self::MAX_SIZE, SliderStep::Discrete(1.0)),
+            text: None,
+            blink_start: 0.0,
+            packets: Vec::new(),
+            previews: HashMap::new(),
+        }
+    }
+
+    // queues a packet for sending. a preview replaces the previous one if it hasn't been sent yet, because only the
+    // latest preview matters
+    fn queue_packet(&mut self, packet: Packet) {
+        if let (Some(Packet::Preview(_)), Packet::Preview(_)) = (self.packets.last(), &packet) {
+            self.packets.pop();
+        }
+        self.packets.push(packet);
+    }
+
+    // puts the text that's being typed onto the canvas
+    fn commit(&mut self, paint_canvas: &mut PaintCanvas, history: &mut History) {
+        match self.text.take() {
+            Some(text) if !text.text.is_empty() => {
+                let font = text.font(&self.font);
+                history.begin();
+                history.save_chunks(paint_canvas, PaintCanvas::chunks_in_rect(text.bounds(&font)));
+                text.draw_onto(&self.font, paint_canvas);
+                history.end();
+                self.queue_packet(Packet::Commit(text));
+            },
+            Some(_) => self.queue_packet(Packet::Cancel),
+            None => (),
+        }
+    }
+
+}
+
+impl Tool for TextTool {
+
+    fn name(&self) -> &'static str {
+        "text"
+    }
+
+    fn icon<'a>(&self, assets: &'a Assets) -> &'a Image {
+        &assets.icons.tools.text
+    }
+
+    fn key_shortcut(&self) -> VirtualKeyCode {
+        VirtualKeyCode::T
+    }
+
+    fn process_paint_canvas_input(
+        &mut self,
+        ToolArgs { ui, input, paint_canvas, history, .. }: ToolArgs,
+        mouse: Point,
+        _previous_mouse: Point,
+    ) {
+        if ui.has_mouse(input) && input.mouse_button_just_pressed(MouseButton::Left) {
+            self.commit(paint_canvas, history);
+            self.text = Some(Text {
+                position: (mouse.x.round(), mouse.y.round()),
+                text: String::new(),
+                color: color4f_hex(self.color),
+                size: self.size_slider.value(),
+            });
+            self.blink_start = input.time_in_seconds();
+        }
+
+        if let Some(text) = &mut self.text {
+            let mut changed = false;
+            for &ch in input.characters_typed() {
+                match ch {
+                    _ if !ch.is_control() && text.text.chars().count() < Self::MAX_LENGTH => text.text.push(ch),
+                    Self::BACKSPACE => { text.text.pop(); },
+                    _ => continue,
+                }
+                changed = true;
+            }
+            // the color and size can be changed in the bottom bar while typing
+            let (color, size) = (color4f_hex(self.color), self.size_slider.value());
+            if text.color != color || text.size != size {
+                text.color = color;
+                text.size = size;
+                changed = true;
+            }
+            if changed {
+                let preview = text.clone();
+                self.blink_start = input.time_in_seconds();
+                self.queue_packet(Packet::Preview(preview));
+            }
+        }
+
+        if input.key_just_typed(VirtualKeyCode::Return) {
+            self.commit(paint_canvas, history);
+        }
+        if input.key_just_typed(VirtualKeyCode::Escape) && self.text.is_some() {
+            self.text = None;
+            self.queue_packet(Packet::Cancel);
+        }
+    }
+
+    fn process_paint_canvas_overlays(&mut self, canvas: &mut Canvas, input: &Input, _mouse: Point) {
+        if let Some(text) = &self.text {
+            text.draw(&text.font(&self.font), canvas);
+            if (input.time_in_seconds() - self.blink_start) % Self::BLINK_PERIOD < Self::HALF_BLINK {
+                let font = text.font(&self.font);
+                let advance = font_fallback::measure_str(&text.text, &font);
+                let (_, metrics) = font.metrics();
+                let (x, y) = text.position;
+                let mut paint = Paint::new(Color4f::from(Color::WHITE.with_a(192)), None);
+                paint.set_blend_mode(BlendMode::Difference);
+                paint.set_style(skpaint::Style::Stroke);
+                canvas.draw_line((x + advance + 1.0, y + metrics.ascent), (x + advance + 1.0, y + metrics.descent), &paint);
+            }
+        }
+    }
+
+    fn draw_layers(&self, canvas: &mut Canvas) {
+        for text in self.previews.values() {
+            text.draw(&text.font(&self.font), canvas);
+        }
+    }
+
+    fn process_bottom_bar(&mut self, ToolArgs { ui, input, assets, config, .. }: ToolArgs, canvas: &mut Canvas) {
+        // palette
+
+        for &hex_color in COLOR_PALETTE.iter().chain(config.brush.palette.iter()) {
+            let color = hex_color4f(hex_color);
+            if let Some(MouseButton::Left) = process_color_swatch(ui, canvas, input, color, self.color == color) {
+                self.color = color;
+            }
+        }
+        ui.space(16.0);
+
+        // font size
+
+        ui.push_group((80.0, ui.height()), Layout::Freeform);
+        ui.text(canvas, assets.language.tr("tools.font_size"), assets.colors.text, (AlignH::Center, AlignV::Middle));
+        ui.pop_group();
+
+        ui.space(8.0);
+        self.size_slider.process(ui, canvas, input, SliderArgs {
+            width: 192.0,
+            color: assets.colors.slider,
+        });
+        ui.space(8.0);
+
+        let size_string = self.size_slider.value().to_string();
+        ui.push_group((ui.height(), ui.height()), Layout::Freeform);
+        ui.set_font(assets.sans_bold.clone());
+        ui.text(canvas, &size_string, assets.colors.text, (AlignH::Center, AlignV::Middle));
+        ui.pop_group();
+    }
+
+    fn color(&self) -> Option<Color4f> {
+        Some(self.color)
+    }
+
+    fn set_color(&mut self, color: Color4f) {
+        self.color = color;
+    }
+
+    fn network_send(&mut self, peer: &Peer) -> Result<(), net::Error> {
+        let name = self.name();
+        for packet in self.packets.drain(..) {
+            peer.send_tool(name, &packet)?;
+        }
+        Ok(())
+    }
+
+    fn network_receive(
+        &mut self,
+        paint_canvas: &mut PaintCanvas<'static>,
+        sender: SocketAddr,
+        payload: &[u8],
+    ) -> Result<(), net::Error> {
+        match build::decode(payload)? {
+            Packet::Preview(mut text) => {
+                if text.sanitize() {
+                    self.previews.insert(sender, text);
+                }
+            },
+            Packet::Commit(mut text) => {
+                self.previews.remove(&sender);
+                if text.sanitize() {
+                    text.draw_onto(&self.font, paint_canvas);
+                } else {
+                    log::warn!("{} tried to put invalid text onto the canvas", sender);
+                }
+            },
+            Packet::Cancel => {
+                self.previews.remove(&sender);
+            },
+        }
+        Ok(())
+    }
+
+    fn network_left(&mut self, _paint_canvas: &mut PaintCanvas<'static>, sender: SocketAddr) {
+        self.previews.remove(&sender);
+    }
+
+    fn captures_keyboard(&self) -> bool {
+        self.text.is_some()
+    }
+
+}
@@ -0,0 +1,161 @@
+// the viewport. keeps track of which part of the canvas is visible on screen, and how far it's zoomed in.
+// jumping to another part of the canvas (eg. by clicking on the minimap) and zooming are animated, by smoothing the
+// pan and zoom towards their targets every frame. the animation can be turned off with the smooth_viewport option in
+// config.toml, in which case both are instant. panning with the mouse is never animated, because it'd feel laggy.
+// points on screen are relative to the top left corner of the area the canvas is shown in.
+
+use skulpin::skia_safe::*;
+
+pub struct Viewport {
+    // where the canvas's origin is on screen
+    pan: Vector,
+    // how many pixels on screen a pixel of the canvas takes up
+    zoom: f32,
+    // the pan that's being animated towards. None if the viewport isn't jumping anywhere
+    target: Option<Vector>,
+    // the zoom that's being animated towards, along with the point on screen that stays in place while zooming. None
+    // if the viewport isn't zooming
+    target_zoom: Option<(f32, Point)>,
+    smooth: bool,
+    // the time of the last tick, used for making the animation independent of the frame rate
+    last_tick: Option<f32>,
+}
+
+impl Viewport {
+
+    // how quickly the animation approaches its target. higher is faster
+    const SMOOTHING: f32 = 12.0;
+    // the distance from the target, in pixels, at which the animation snaps to it
+    const SNAP_DISTANCE: f32 = 0.5;
+    // the ratio between the zoom and its target at which the animation snaps to it
+    const SNAP_ZOOM: f32 = 0.001;
+
+    pub const MIN_ZOOM: f32 = 0.25;
+    pub const MAX_ZOOM: f32 = 8.0;
+    // how much the zoom changes with a single notch of the mouse wheel or a press of the zoom shortcuts
+    pub const ZOOM_STEP: f32 = 1.25;
+
+    pub fn new(smooth: bool) -> Self {
+        Self {
+            pan: Vector::new(0.0, 0.0),
+            zoom: 1.0,
+            target: None,
+            target_zoom: None,
+            smooth,
+            last_tick: None,
+        }
+    }
+
+    pub fn pan(&self) -> Vector {
+        self.pan
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    // converts a point on screen to a point on the canvas
+    pub fn to_canvas(&self, point: Point) -> Point {
+        (point - self.pan) * (1.0 / self.zoom)
+    }
+
+    // converts a point on the canvas to a point on screen
+    pub fn to_screen(&self, point: Point) -> Point {
+        point * self.zoom + self.pan
+    }
+
+    // the area of the canvas that's visible in a window of the given size
+    pub fn rect(&self, size: (f32, f32)) -> Rect {
+        Rect::from_point_and_size(self.to_canvas(Point::new(0.0, 0.0)), (size.0 / self.zoom, size.1 / self.zoom))
+    }
+
+    // whether the viewport is moving towards a point it jumped to, or zooming
+    pub fn is_animating(&self) -> bool {
+        self.target.is_some() || self.target_zoom.is_some()
+    }
+
+    // finishes the zoom animation right away. the pan is only ever animated towards a point that was worked out for
+    // the final zoom, so that one has to be reached first
+    fn finish_zooming(&mut self) {
+        if let Some((zoom, anchor)) = self.target_zoom.take() {
+            self.set_zoom_around(zoom, anchor);
+        }
+    }
+
+    // finishes the pan animation right away. zooming around a point on screen only works out if the pan doesn't change
+    // in the meantime
+    fn finish_jumping(&mut self) {
+        if let Some(target) = self.target.take() {
+            self.pan = target;
+        }
+    }
+
+    // sets the zoom, keeping the given point on screen in place
+    fn set_zoom_around(&mut self, zoom: f32, anchor: Point) {
+        self.pan = anchor - (anchor - self.pan) * (zoom / self.zoom);
+        self.zoom = zoom;
+    }
+
+    // moves the viewport by the given amount right away, cancelling the animation
+    pub fn pan_by(&mut self, delta: Vector) {
+        self.finish_zooming();
+        self.pan.offset(delta);
+        self.target = None;
+    }
+
+    // moves the viewport such that the point on the canvas ends up in the center of a window of the given size
+    pub fn center_on(&mut self, point: Point, size: (f32, f32)) {
+        self.finish_zooming();
+        let pan = Vector::new(size.0 / 2.0 - point.x * self.zoom, size.1 / 2.0 - point.y * self.zoom);
+        if self.smooth {
+            self.target = Some(pan);
+        } else {
+            self.pan = pan;
+        }
+    }
+
+    // zooms to the given level, keeping the given point on screen in place
+    pub fn zoom_to(&mut self, zoom: f32, anchor: Point) {
+        self.finish_jumping();
+        let zoom = zoom.clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+        if self.smooth {
+            self.target_zoom = Some((zoom, anchor));
+        } else {
+            self.set_zoom_around(zoom, anchor);
+        }
+    }
+
+    // multiplies the zoom by the given factor. zooming again while the animation is underway continues from where the
+    // previous zoom was going to end up, so that spinning the mouse wheel quickly doesn't lose any notches
+    pub fn zoom_by(&mut self, factor: f32, anchor: Point) {
+        let zoom = self.target_zoom.map_or(self.zoom, |(zoom, _)| zoom);
+        self.zoom_to(zoom * factor, anchor);
+    }
+
+    // advances the animation. called every frame with the current time
+    pub fn tick(&mut self, time: f32) {
+        let delta_time = self.last_tick.map(|last_tick| time - last_tick).unwrap_or(0.0);
+        self.last_tick = Some(time);
+        // exponential smoothing, so the viewport slows down as it gets closer to the target
+        let t = 1.0 - (-Self::SMOOTHING * delta_time).exp();
+        if let Some(target) = self.target {
+            let difference = target - self.pan;
+            self.pan += difference * t;
+            if (target - self.pan).length() < Self::SNAP_DISTANCE {
+                self.pan = target;
+                self.target = None;
+            }
+        }
+        if let Some((target, anchor)) = self.target_zoom {
+            // the zoom is smoothed in logarithmic space, so that zooming in and out takes equally long
+            let zoom = (self.zoom.ln() + (target.ln() - self.zoom.ln()) * t).exp();
+            if (zoom / target - 1.0).abs() < Self::SNAP_ZOOM {
+                self.set_zoom_around(target, anchor);
+                self.target_zoom = None;
+            } else {
+                self.set_zoom_around(zoom, anchor);
+            }
+        }
+    }
+
+}
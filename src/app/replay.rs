@@ -0,0 +1,256 @@
+// the replay player, opened with `netcanv --replay FILE`. plays back a recorded session onto a blank canvas.
+// see replay.rs for how sessions are recorded.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use netcanv_protocol::client as cl;
+use skulpin::skia_safe::*;
+
+use crate::app::*;
+use crate::app::paint::tools::{self, Tool};
+use crate::app::paint::viewport::Viewport;
+use crate::assets::*;
+use crate::net;
+use crate::paint_canvas::{Background, PaintCanvas};
+use crate::replay::Entry;
+use crate::ui::*;
+use crate::util::*;
+
+pub struct State {
+    assets: Assets,
+    ui: Ui,
+
+    paint_canvas: PaintCanvas<'static>,
+    // tools are needed to apply tool packets, same as in headless mode
+    tools: Vec<Box<dyn Tool>>,
+    viewport: Viewport,
+    panning: bool,
+
+    entries: Vec<Entry>,
+    // the index of the next entry to apply
+    next_entry: usize,
+    // how far into the session the playback is
+    position: Duration,
+    speed: f32,
+    paused: bool,
+    last_frame: Option<f32>,
+}
+
+impl State {
+
+    const BAR_SIZE: f32 = 32.0;
+    // the speeds the speed button cycles through
+    const SPEEDS: &'static [f32] = &[0.5, 1.0, 2.0, 4.0, 8.0, 16.0, 64.0];
+    // our own tool packets don't have a sender, so they're attributed to this address instead
+    const LOCAL_SENDER: ([u8; 4], u16) = ([127, 0, 0, 1], 0);
+
+    pub fn new(assets: Assets, entries: Vec<Entry>, speed: f32) -> Self {
+        let tools = tools::all(&assets);
+        Self {
+            assets,
+            ui: Ui::new(),
+
+            paint_canvas: PaintCanvas::new(),
+            tools,
+            viewport: Viewport::new(false),
+            panning: false,
+
+            entries,
+            next_entry: 0,
+            position: Duration::from_secs(0),
+            speed,
+            paused: false,
+            last_frame: None,
+        }
+    }
+
+    // the length of the whole session
+    fn duration(&self) -> Duration {
+        self.entries.last().map_or(Duration::from_secs(0), |entry| entry.time)
+    }
+
+    fn restart(&mut self) {
+        self.paint_canvas = PaintCanvas::new();
+        // tools keep track of what people are doing with them, which has to be forgotten too
+        self.tools = tools::all(&self.assets);
+        self.next_entry = 0;
+        self.position = Duration::from_secs(0);
+    }
+
+    fn apply(
+        paint_canvas: &mut PaintCanvas<'static>,
+        tools: &mut [Box<dyn Tool>],
+        Entry { sender, packet, .. }: &Entry,
+    ) {
+        let result = match packet.clone() {
+            cl::Packet::Stroke(points) => {
+                paint_canvas.stroke_points(&net::decode_stroke(points));
+                Ok(())
+            },
+            cl::Packet::CanvasData(chunk, png) => paint_canvas.decode_png_data(chunk, &png).map_err(net::Error::from),
+            cl::Packet::ChunkTiles(chunk, tiles) =>
+                paint_canvas.decode_tile_data(chunk, &tiles).map_err(net::Error::from),
+            cl::Packet::Tool(name, payload) => {
+                let sender = sender.unwrap_or_else(|| SocketAddr::from(Self::LOCAL_SENDER));
+                tools::receive(tools, paint_canvas, sender, &name, &payload)
+            },
+            cl::Packet::Background(color) => {
+                paint_canvas.set_background(Background::from_packet(color));
+                Ok(())
+            },
+            _ => Ok(()),
+        };
+        if let Err(error) = result {
+            log::warn!("could not replay packet: {}", error);
+        }
+    }
+
+    // advances the playback, applying everything that happened in the meantime
+    fn advance(&mut self, time: f32) {
+        let delta_time = self.last_frame.map_or(0.0, |last_frame| time - last_frame);
+        self.last_frame = Some(time);
+        if self.paused { return }
+
+        self.position += Duration::from_secs_f32(delta_time * self.speed);
+        while let Some(entry) = self.entries.get(self.next_entry) {
+            if entry.time > self.position { break }
+            Self::apply(&mut self.paint_canvas, &mut self.tools, entry);
+            self.next_entry += 1;
+        }
+        if self.next_entry >= self.entries.len() {
+            self.position = self.duration();
+        }
+    }
+
+    fn process_canvas(&mut self, canvas: &mut Canvas, input: &Input) {
+        self.ui.push_group((self.ui.width(), self.ui.height() - Self::BAR_SIZE), Layout::Freeform);
+
+        // panning and zooming. there's nothing to paint with, so both the left and middle mouse buttons pan
+
+        let pressed = |button| input.mouse_button_just_pressed(button);
+        if self.ui.has_mouse(input) && (pressed(MouseButton::Left) || pressed(MouseButton::Middle)) {
+            self.panning = true;
+        }
+        if !input.mouse_button_is_down(MouseButton::Left) && !input.mouse_button_is_down(MouseButton::Middle) {
+            self.panning = false;
+        }
+        if self.panning {
+            self.viewport.pan_by(input.mouse_position() - input.previous_mouse_position());
+        }
+        let scroll = input.mouse_scroll().y;
+        if scroll != 0.0 && self.ui.has_mouse(input) {
+            self.viewport.zoom_by(Viewport::ZOOM_STEP.powf(scroll), self.ui.mouse_position(input));
+        }
+        let visible_rect = self.viewport.rect(self.ui.size());
+        self.paint_canvas.manage_memory(visible_rect);
+
+        let paint_canvas = &self.paint_canvas;
+        let (pan, zoom) = (self.viewport.pan(), self.viewport.zoom());
+        let size = self.ui.size();
+        self.ui.draw_on_canvas(canvas, |canvas| {
+            canvas.save();
+            canvas.clip_rect(Rect::from_point_and_size((0.0, 0.0), size), ClipOp::Intersect, false);
+            canvas.translate(pan);
+            canvas.scale((zoom, zoom));
+            paint_canvas.background().draw(canvas, visible_rect);
+            paint_canvas.draw_to(canvas);
+            canvas.restore();
+        });
+
+        self.ui.pop_group();
+    }
+
+    fn process_bar(&mut self, canvas: &mut Canvas, input: &Input) {
+        self.ui.push_group((self.ui.width(), self.ui.remaining_height()), Layout::Horizontal);
+        self.ui.fill(canvas, self.assets.colors.panel);
+        self.ui.pad((16.0, 0.0));
+
+        let button = ButtonArgs {
+            height: self.ui.height(),
+            colors: &self.assets.colors.button,
+        };
+
+        // play/pause
+
+        let pause_text = self.assets.language.tr(if self.paused { "replay.play" } else { "replay.pause" });
+        self.ui.push_group((64.0, self.ui.height()), Layout::Freeform);
+        if Button::with_text(&mut self.ui, canvas, input, button, pause_text).clicked() {
+            self.paused = !self.paused;
+        }
+        self.ui.pop_group();
+        self.ui.space(8.0);
+
+        // restart
+
+        self.ui.push_group((64.0, self.ui.height()), Layout::Freeform);
+        let restart_text = self.assets.language.tr("replay.restart");
+        let restart = Button::with_text(&mut self.ui, canvas, input, button, restart_text).clicked();
+        self.ui.pop_group();
+        self.ui.space(8.0);
+
+        // speed
+
+        let speed_text = self.assets.language.format("replay.speed", &[&self.speed]);
+        self.ui.push_group((96.0, self.ui.height()), Layout::Freeform);
+        if Button::with_text(&mut self.ui, canvas, input, button, &speed_text).clicked() {
+            // speeds given on the command line don't have to be one of SPEEDS, so pick the next faster one
+            self.speed = Self::SPEEDS
+                .iter()
+                .copied()
+                .find(|&speed| speed > self.speed)
+                .unwrap_or(Self::SPEEDS[0]);
+        }
+        self.ui.pop_group();
+        self.ui.space(16.0);
+
+        // progress
+
+        let position = format!("{:.1}", self.position.as_secs_f32());
+        let duration = format!("{:.1}", self.duration().as_secs_f32());
+        let progress_text = self.assets.language.format("replay.time", &[&position, &duration]);
+        self.ui.push_group((self.ui.remaining_width(), self.ui.height()), Layout::Freeform);
+        self.ui.text(canvas, &progress_text, self.assets.colors.text, (AlignH::Left, AlignV::Middle));
+        self.ui.pop_group();
+
+        self.ui.pop_group();
+
+        if restart {
+            self.restart();
+        }
+    }
+
+}
+
+impl AppState for State {
+
+    fn process(
+        &mut self,
+        StateArgs {
+            canvas,
+            coordinate_system_helper,
+            input,
+        }: StateArgs,
+    ) {
+        canvas.clear(Color::WHITE);
+
+        self.advance(input.time_in_seconds());
+        let holding_back = tools::tick(&mut self.tools, &mut self.paint_canvas);
+        if holding_back || !self.paused && self.next_entry < self.entries.len() {
+            input.request_redraw();
+        }
+
+        self.ui.begin(get_window_size(coordinate_system_helper, input.ui_scale()), Layout::Vertical);
+        self.ui.process_focus(input);
+        self.ui.set_font(self.assets.sans.clone());
+        self.ui.set_font_size(14.0);
+
+        self.process_canvas(canvas, input);
+        self.process_bar(canvas, input);
+    }
+
+    fn next_state(self: Box<Self>) -> Box<dyn AppState> {
+        self
+    }
+
+}
@@ -16,4 +16,32 @@ pub trait AppState {
     );
 
     fn next_state(self: Box<Self>) -> Box<dyn AppState>;
+
+    // called when the user tries to close the window. returns whether the app can quit right away. if it can't, the
+    // state is expected to sort things out with the user and then quit through wants_exit()
+    fn close_requested(&mut self) -> bool {
+        true
+    }
+
+    // whether the app should quit. checked after every frame
+    fn wants_exit(&self) -> bool {
+        false
+    }
+
+    // whether the state wants the secondary panel window to be open. the window is opened and closed accordingly
+    // after every frame
+    fn wants_panel_window(&self) -> bool {
+        false
+    }
+
+    // draws the contents of the panel window. only called while the window is open
+    fn process_panel_window(&mut self, _args: StateArgs) {}
+
+    // called when the panel window is closed by the user, or couldn't be opened
+    fn panel_window_closed(&mut self) {}
+
+    // how much bigger or smaller the UI is drawn than the system's DPI setting makes it
+    fn ui_scale(&self) -> f32 {
+        1.0
+    }
 }
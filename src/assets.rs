@@ -1,7 +1,10 @@
+use serde::Deserialize;
 use skulpin::skia_safe::*;
 
+use crate::color_schemes::{self, Watcher};
+use crate::i18n::Language;
 use crate::ui::{ButtonColors, ExpandColors, ExpandIcons, TextFieldColors};
-use crate::util::{RcFont, new_rc_font};
+use crate::util::{RcFont, hex_color, new_rc_font};
 
 const SANS_TTF: &[u8] = include_bytes!("assets/fonts/Barlow-Medium.ttf");
 const SANS_BOLD_TTF: &[u8] = include_bytes!("assets/fonts/Barlow-Bold.ttf");
@@ -10,16 +13,38 @@ const CHEVRON_RIGHT_SVG: &[u8] = include_bytes!("assets/icons/chevron-right.svg"
 const CHEVRON_DOWN_SVG: &[u8] = include_bytes!("assets/icons/chevron-down.svg");
 const INFO_SVG: &[u8] = include_bytes!("assets/icons/info.svg");
 const ERROR_SVG: &[u8] = include_bytes!("assets/icons/error.svg");
-
+const BRUSH_SVG: &[u8] = include_bytes!("assets/icons/brush.svg");
+const ERASER_SVG: &[u8] = include_bytes!("assets/icons/eraser.svg");
+const SELECTION_SVG: &[u8] = include_bytes!("assets/icons/selection.svg");
+const LINE_SVG: &[u8] = include_bytes!("assets/icons/line.svg");
+const RECTANGLE_SVG: &[u8] = include_bytes!("assets/icons/rectangle.svg");
+const ELLIPSE_SVG: &[u8] = include_bytes!("assets/icons/ellipse.svg");
+const TEXT_SVG: &[u8] = include_bytes!("assets/icons/text.svg");
+const FILL_SVG: &[u8] = include_bytes!("assets/icons/fill.svg");
+const REFERENCE_SVG: &[u8] = include_bytes!("assets/icons/reference.svg");
+const LASER_SVG: &[u8] = include_bytes!("assets/icons/laser.svg");
+const EXPORT_SVG: &[u8] = include_bytes!("assets/icons/export.svg");
+const STATISTICS_SVG: &[u8] = include_bytes!("assets/icons/statistics.svg");
+const SAVE_SVG: &[u8] = include_bytes!("assets/icons/save.svg");
+const SCREENSHOT_SVG: &[u8] = include_bytes!("assets/icons/screenshot.svg");
+const PRINT_SVG: &[u8] = include_bytes!("assets/icons/print.svg");
+
+#[derive(Deserialize)]
 pub struct ColorScheme {
+    #[serde(with = "hex_color")]
     pub text: Color,
+    #[serde(with = "hex_color")]
     pub panel: Color,
+    #[serde(with = "hex_color")]
     pub panel2: Color,
+    #[serde(with = "hex_color")]
     pub separator: Color,
+    #[serde(with = "hex_color")]
     pub error: Color,
 
     pub button: ButtonColors,
     pub expand: ExpandColors,
+    #[serde(with = "hex_color")]
     pub slider: Color,
     pub text_field: TextFieldColors,
 }
@@ -29,9 +54,32 @@ pub struct StatusIcons {
     pub error: Image,
 }
 
+pub struct ToolIcons {
+    pub brush: Image,
+    pub eraser: Image,
+    pub selection: Image,
+    pub line: Image,
+    pub rectangle: Image,
+    pub ellipse: Image,
+    pub text: Image,
+    pub fill: Image,
+    pub reference: Image,
+    pub laser: Image,
+}
+
+pub struct ActionIcons {
+    pub save: Image,
+    pub export: Image,
+    pub screenshot: Image,
+    pub print: Image,
+    pub statistics: Image,
+}
+
 pub struct Icons {
     pub expand: ExpandIcons,
     pub status: StatusIcons,
+    pub tools: ToolIcons,
+    pub actions: ActionIcons,
 }
 
 pub struct Assets {
@@ -40,6 +88,9 @@ pub struct Assets {
 
     pub colors: ColorScheme,
     pub icons: Icons,
+    pub language: Language,
+
+    color_scheme_watcher: Watcher,
 }
 
 impl Assets {
@@ -66,7 +117,8 @@ impl Assets {
         Image::from_raster_data(&image_info, Data::new_copy(pixmap.data()), stride).unwrap()
     }
 
-    pub fn new(colors: ColorScheme) -> Self {
+    // the name of the color scheme is needed to reload it when its file changes
+    pub fn new(colors: ColorScheme, color_scheme_name: &str, language: &str) -> Self {
         Self {
             sans: new_rc_font(SANS_TTF, 14.0),
             sans_bold: new_rc_font(SANS_BOLD_TTF, 14.0),
@@ -80,8 +132,42 @@ impl Assets {
                     info: Self::load_icon(INFO_SVG),
                     error: Self::load_icon(ERROR_SVG),
                 },
+                tools: ToolIcons {
+                    brush: Self::load_icon(BRUSH_SVG),
+                    eraser: Self::load_icon(ERASER_SVG),
+                    selection: Self::load_icon(SELECTION_SVG),
+                    line: Self::load_icon(LINE_SVG),
+                    rectangle: Self::load_icon(RECTANGLE_SVG),
+                    ellipse: Self::load_icon(ELLIPSE_SVG),
+                    text: Self::load_icon(TEXT_SVG),
+                    fill: Self::load_icon(FILL_SVG),
+                    reference: Self::load_icon(REFERENCE_SVG),
+                    laser: Self::load_icon(LASER_SVG),
+                },
+                actions: ActionIcons {
+                    save: Self::load_icon(SAVE_SVG),
+                    export: Self::load_icon(EXPORT_SVG),
+                    screenshot: Self::load_icon(SCREENSHOT_SVG),
+                    print: Self::load_icon(PRINT_SVG),
+                    statistics: Self::load_icon(STATISTICS_SVG),
+                },
             },
+            language: Language::load(language),
+            color_scheme_watcher: Watcher::new(color_scheme_name),
+        }
+    }
+
+    pub fn set_color_scheme(&mut self, name: &str, colors: ColorScheme) {
+        self.colors = colors;
+        self.color_scheme_watcher = Watcher::new(name);
+    }
+
+    // reloads the color scheme if its file was changed. should be called every frame
+    pub fn reload_color_scheme(&mut self) -> Result<(), color_schemes::Error> {
+        if let Some(colors) = self.color_scheme_watcher.poll() {
+            self.colors = colors?;
         }
+        Ok(())
     }
 
 }
@@ -120,4 +206,36 @@ impl ColorScheme {
         }
     }
 
+    pub fn dark() -> Self {
+        Self {
+            text: Color::new(0xffdddddd),
+            panel: Color::new(0xff252525),
+            panel2: Color::new(0xff1a1a1a),
+            separator: Color::new(0xffb0b0b0),
+            error: Color::new(0xffff6b6b),
+
+            button: ButtonColors {
+                outline: Color::new(0x40ffffff),
+                text: Color::new(0xffdddddd),
+                hover: Color::new(0x20ffffff),
+                pressed: Color::new(0x50ffffff),
+            },
+            slider: Color::new(0xffdddddd),
+            expand: ExpandColors {
+                icon: Color::new(0xffdddddd),
+                text: Color::new(0xffdddddd),
+                hover: Color::new(0x30ffffff),
+                pressed: Color::new(0x60ffffff),
+            },
+            text_field: TextFieldColors {
+                outline: Color::new(0xff707070),
+                outline_focus: Color::new(0xffc0c0c0),
+                fill: Color::new(0xff1a1a1a),
+                text: Color::new(0xffdddddd),
+                text_hint: Color::new(0x7fdddddd),
+                label: Color::new(0xffdddddd),
+            },
+        }
+    }
+
 }
@@ -0,0 +1,91 @@
+// sound cues.
+// short, quiet tones played when someone joins or leaves the room, and when a notification about something that went
+// right or wrong pops up. the tones are synthesized on the fly, so there aren't any sound files to ship.
+// sound is optional: NetCanv can be compiled without it by turning off the `audio` feature, which also gets rid of the
+// dependency on the system's audio libraries (ALSA on Linux). when there's no audio device to play sounds on, NetCanv
+// simply stays silent.
+
+use crate::config::AudioConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cue {
+    Joined,
+    Left,
+    Success,
+    Error,
+}
+
+// how loud a cue is at full volume. sine waves at full amplitude are anything but subtle
+const LOUDNESS: f32 = 0.2;
+
+#[cfg(feature = "audio")]
+mod output {
+    use std::time::Duration;
+
+    use rodio::{OutputStream, OutputStreamHandle, Source};
+    use rodio::source::SineWave;
+
+    use super::Cue;
+
+    const NOTE_LENGTH: Duration = Duration::from_millis(90);
+    // fading the notes in and out avoids clicks at their edges
+    const NOTE_FADE_IN: Duration = Duration::from_millis(5);
+
+    // the notes of the cue, as frequencies in Hz, played one after another
+    fn notes(cue: Cue) -> &'static [u32] {
+        match cue {
+            // rising for people coming in, falling for people going out
+            Cue::Joined => &[660, 880],
+            Cue::Left => &[880, 660],
+            Cue::Success => &[1047],
+            Cue::Error => &[330, 330],
+        }
+    }
+
+    thread_local! {
+        // the audio device is opened the first time a cue is played. the stream has to be kept around for as long as
+        // sounds are played through its handle
+        static OUTPUT: Option<(OutputStream, OutputStreamHandle)> = match OutputStream::try_default() {
+            Ok(output) => Some(output),
+            Err(error) => {
+                log::warn!("could not open the audio device, sounds are turned off: {}", error);
+                None
+            },
+        };
+    }
+
+    pub fn play(cue: Cue, amplitude: f32) {
+        OUTPUT.with(|output| {
+            let handle = match output {
+                Some((_, handle)) => handle,
+                None => return,
+            };
+            for (i, &frequency) in notes(cue).iter().enumerate() {
+                let mut note = SineWave::new(frequency).take_duration(NOTE_LENGTH);
+                note.set_filter_fadeout();
+                let note = note
+                    .fade_in(NOTE_FADE_IN)
+                    .amplify(amplitude)
+                    .delay(NOTE_LENGTH * i as u32);
+                if let Err(error) = handle.play_raw(note) {
+                    log::warn!("could not play a sound: {}", error);
+                    return
+                }
+            }
+        });
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+mod output {
+    use super::Cue;
+
+    pub fn play(_cue: Cue, _amplitude: f32) {}
+}
+
+// plays the cue at the volume set in the config, unless sounds are muted. the sound plays in the background, so this
+// returns right away
+pub fn play(config: &AudioConfig, cue: Cue) {
+    if config.muted || config.volume <= 0.0 { return }
+    output::play(cue, config.volume.min(1.0) * LOUDNESS);
+}
@@ -0,0 +1,181 @@
+// crash recovery.
+// while in a room, the canvas is periodically autosaved as a project to a folder next to config.toml, along with a
+// marker file. each session gets its own folder, which is removed when the room is left normally. if a folder with the
+// marker is still around when NetCanv starts up, that session didn't shut down cleanly and the lobby offers to restore
+// its canvas. autosaves left behind like this are only ever removed when they're restored or discarded in the lobby.
+
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use thiserror::Error;
+
+use crate::config::{self, UserConfig};
+use crate::paint_canvas::PaintCanvas;
+use crate::project;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error while autosaving: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not autosave the canvas: {0}")]
+    Project(#[from] project::Error),
+    #[error("{0}")]
+    Config(#[from] config::Error),
+    #[error("The autosave thread quit unexpectedly")]
+    ThreadQuit,
+}
+
+const MARKER_FILE: &str = "running";
+
+fn dir() -> Result<PathBuf, Error> {
+    Ok(UserConfig::dir()?.join("autosave"))
+}
+
+// returns the autosaves left behind by sessions that didn't shut down cleanly, oldest first.
+// session folders are named after the time the session started, so sorting them by name puts them in order
+fn left_behind() -> Result<Vec<PathBuf>, Error> {
+    let dir = dir()?;
+    if !dir.is_dir() {
+        return Ok(Vec::new())
+    }
+    let mut sessions = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.join(MARKER_FILE).is_file() && project::exists(&path) {
+            sessions.push(path);
+        }
+    }
+    sessions.sort();
+    Ok(sessions)
+}
+
+// returns whether there's an autosaved canvas left behind by a session that didn't shut down cleanly
+pub fn recoverable() -> bool {
+    left_behind().is_ok_and(|sessions| !sessions.is_empty())
+}
+
+// moves the most recent autosaved canvas out of the way, so that it isn't offered for recovery again.
+// returns the folder it was moved to, which can be loaded as a project
+pub fn take_recovered() -> Result<PathBuf, Error> {
+    let session = match left_behind()?.pop() {
+        Some(session) => session,
+        None => return Err(std::io::Error::from(std::io::ErrorKind::NotFound).into()),
+    };
+    let recovered = UserConfig::dir()?.join("recovered");
+    if recovered.is_dir() {
+        std::fs::remove_dir_all(&recovered)?;
+    }
+    std::fs::rename(session, &recovered)?;
+    std::fs::remove_file(recovered.join(MARKER_FILE))?;
+    Ok(recovered)
+}
+
+// throws away all autosaved canvases left behind by sessions that didn't shut down cleanly
+pub fn discard() -> Result<(), Error> {
+    for session in left_behind()? {
+        std::fs::remove_dir_all(session)?;
+    }
+    Ok(())
+}
+
+pub struct Autosave {
+    dir: PathBuf,
+    last_save: Instant,
+    // the canvas revision everything up to which is autosaved
+    saved_revision: u64,
+    // the revision the save that's currently underway is going to bring the autosave up to
+    saving_revision: Option<u64>,
+    // chunks that are still to be saved in the current save
+    unsaved: Vec<(i32, i32)>,
+    // receives the result of saving a batch of chunks on another thread
+    batch_saved: Option<Receiver<Result<(), project::Error>>>,
+}
+
+impl Autosave {
+
+    const INTERVAL: Duration = Duration::from_secs(60);
+    // how many chunks are captured from the canvas and handed to the autosave thread at once. this keeps the captured
+    // images from taking up too much memory, and capturing them from stalling the frame for too long
+    const BATCH_SIZE: usize = 32;
+
+    // starts a new autosave session. autosaves left behind by previous sessions are kept around, so that they can still
+    // be recovered later
+    pub fn start() -> Result<Self, Error> {
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_millis());
+        let dir = dir()?.join(format!("{:016}-{}", started, std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join(MARKER_FILE), "")?;
+        Ok(Self {
+            dir,
+            last_save: Instant::now(),
+            saved_revision: 0,
+            saving_revision: None,
+            unsaved: Vec::new(),
+            batch_saved: None,
+        })
+    }
+
+    // saves the chunks that changed if it's time to. the chunks are saved in batches, which are encoded and written to
+    // disk on another thread. called every frame
+    pub fn tick(&mut self, paint_canvas: &mut PaintCanvas) -> Result<(), Error> {
+        if let Some(batch_saved) = &self.batch_saved {
+            let result = match batch_saved.try_recv() {
+                Ok(result) => result.map_err(Error::from),
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => Err(Error::ThreadQuit),
+            };
+            self.batch_saved = None;
+            if let Err(error) = result {
+                // the save is retried in full the next time around
+                self.saving_revision = None;
+                self.unsaved.clear();
+                return Err(error)
+            }
+        }
+
+        if self.unsaved.is_empty() {
+            if let Some(revision) = self.saving_revision.take() {
+                self.saved_revision = revision;
+            }
+            if self.last_save.elapsed() < Self::INTERVAL {
+                return Ok(())
+            }
+            self.last_save = Instant::now();
+            self.unsaved = paint_canvas.chunks_changed_since(self.saved_revision);
+            if self.unsaved.is_empty() {
+                return Ok(())
+            }
+            self.saving_revision = Some(paint_canvas.revision());
+        }
+
+        let batch = self.unsaved.split_off(self.unsaved.len().saturating_sub(Self::BATCH_SIZE));
+        let images: Vec<_> = batch
+            .into_iter()
+            .map(|position| (position, paint_canvas.capture(PaintCanvas::chunk_rect(position))))
+            .collect();
+        let dir = self.dir.clone();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(project::save_chunk_images(&dir, &images));
+        });
+        self.batch_saved = Some(receiver);
+        Ok(())
+    }
+
+}
+
+impl Drop for Autosave {
+
+    // the room was left normally, so there's nothing to recover
+    fn drop(&mut self) {
+        // the autosave thread would otherwise recreate the folder after it's removed
+        if let Some(batch_saved) = self.batch_saved.take() {
+            let _ = batch_saved.recv();
+        }
+        if let Err(error) = std::fs::remove_dir_all(&self.dir) {
+            log::error!("could not remove the autosave: {}", error);
+        }
+    }
+
+}
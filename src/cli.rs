@@ -0,0 +1,101 @@
+// command line arguments.
+// netcanv can be launched with options that skip the lobby, eg. from a shortcut:
+//   netcanv --join 1234 --matchmaker example.com --nickname Foo
+// or with an invite link as the first argument:
+//   netcanv netcanv://example.com:62137/1234
+// anything that isn't specified is filled in from the user config, just like in the lobby.
+
+use thiserror::Error;
+
+use crate::invite_link;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Unknown argument: {0}")]
+    UnknownArgument(String),
+    #[error("Missing value for {0}")]
+    MissingValue(String),
+    #[error("Invalid invite link: {0}")]
+    InvalidInviteLink(String),
+}
+
+// returns the value that follows an option, eg. the 1234 in --join 1234
+pub fn value(args: &mut impl Iterator<Item = String>, option: &str) -> Result<String, Error> {
+    args.next().ok_or_else(|| Error::MissingValue(option.to_owned()))
+}
+
+#[derive(Default)]
+pub struct JoinOptions {
+    pub nickname: Option<String>,
+    pub matchmaker: Option<String>,
+    // when this is None, the lobby is only pre-filled and nothing is joined automatically
+    pub room_id: Option<String>,
+    pub password: Option<String>,
+}
+
+impl JoinOptions {
+
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, Error> {
+        let mut options = Self::default();
+        let mut args = args.peekable();
+        if let Some(link) = args.next_if(|arg| invite_link::is_invite_link(arg)) {
+            let (matchmaker, room_id) =
+                invite_link::parse(&link).ok_or_else(|| Error::InvalidInviteLink(link.clone()))?;
+            options.matchmaker = Some(matchmaker);
+            options.room_id = Some(room_id);
+        }
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--join" => options.room_id = Some(value(&mut args, &arg)?),
+                "--nickname" => options.nickname = Some(value(&mut args, &arg)?),
+                "--matchmaker" => options.matchmaker = Some(value(&mut args, &arg)?),
+                "--password" => options.password = Some(value(&mut args, &arg)?),
+                _ => return Err(Error::UnknownArgument(arg)),
+            }
+        }
+        Ok(options)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<JoinOptions, Error> {
+        JoinOptions::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn options_fill_in_the_lobby() {
+        let options = parse(&["--join", "1234", "--matchmaker", "example.com", "--nickname", "Foo"]).unwrap();
+        assert_eq!(options.room_id.as_deref(), Some("1234"));
+        assert_eq!(options.matchmaker.as_deref(), Some("example.com"));
+        assert_eq!(options.nickname.as_deref(), Some("Foo"));
+        assert_eq!(options.password, None);
+
+        // without --join, the lobby is only pre-filled
+        let options = parse(&["--nickname", "Foo", "--password", "hunter2"]).unwrap();
+        assert_eq!(options.room_id, None);
+        assert_eq!(options.password.as_deref(), Some("hunter2"));
+    }
+
+    #[test]
+    fn invite_links_join_the_room() {
+        let options = parse(&["netcanv://example.com:62137/1234", "--nickname", "Foo"]).unwrap();
+        assert_eq!(options.matchmaker.as_deref(), Some("example.com:62137"));
+        assert_eq!(options.room_id.as_deref(), Some("1234"));
+        assert_eq!(options.nickname.as_deref(), Some("Foo"));
+        assert!(matches!(parse(&["netcanv://example.com/"]), Err(Error::InvalidInviteLink(_))));
+        // invite links are only recognized as the first argument
+        assert!(matches!(parse(&["--nickname", "Foo", "netcanv://example.com/1234"]), Err(Error::UnknownArgument(_))));
+    }
+
+    #[test]
+    fn bad_arguments_are_refused() {
+        assert!(matches!(parse(&["--join"]), Err(Error::MissingValue(option)) if option == "--join"));
+        assert!(matches!(parse(&["--room", "1234"]), Err(Error::UnknownArgument(arg)) if arg == "--room"));
+        let options = parse(&[]).unwrap();
+        assert!(options.room_id.is_none() && options.matchmaker.is_none() && options.nickname.is_none());
+    }
+}
@@ -0,0 +1,51 @@
+// system clipboard access.
+// the clipboard is kept alive for the entire lifetime of the app, because on X11 the contents of the clipboard
+// disappear as soon as the clipboard that set them is dropped.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+use arboard::{Clipboard, ImageData};
+use ::image::RgbaImage;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Clipboard error: {0}")]
+    Clipboard(#[from] arboard::Error),
+    #[error("The image in the clipboard is invalid")]
+    InvalidImage,
+}
+
+thread_local! {
+    static CLIPBOARD: RefCell<Option<Clipboard>> = const { RefCell::new(None) };
+}
+
+fn with_clipboard<T>(f: impl FnOnce(&mut Clipboard) -> Result<T, arboard::Error>) -> Result<T, Error> {
+    CLIPBOARD.with(|clipboard| {
+        let mut clipboard = clipboard.borrow_mut();
+        if clipboard.is_none() {
+            *clipboard = Some(Clipboard::new()?);
+        }
+        Ok(f(clipboard.as_mut().unwrap())?)
+    })
+}
+
+pub fn copy_text(text: &str) -> Result<(), Error> {
+    with_clipboard(|clipboard| clipboard.set_text(text.to_owned()))
+}
+
+pub fn copy_image(image: &RgbaImage) -> Result<(), Error> {
+    with_clipboard(|clipboard| clipboard.set_image(ImageData {
+        width: image.width() as usize,
+        height: image.height() as usize,
+        bytes: Cow::Borrowed(image.as_raw()),
+    }))
+}
+
+// returns the image that's in the clipboard, with straight alpha
+pub fn paste_image() -> Result<RgbaImage, Error> {
+    let image = with_clipboard(|clipboard| clipboard.get_image())?;
+    RgbaImage::from_raw(image.width as u32, image.height as u32, image.bytes.into_owned())
+        .ok_or(Error::InvalidImage)
+}
@@ -0,0 +1,99 @@
+// color schemes.
+// besides the built-in ones, users can define their own color schemes in TOML files placed in the color_schemes
+// directory next to config.toml. the name of the file (without the .toml extension) is the name of the scheme.
+// user-defined schemes are reloaded automatically when their file changes, which makes tweaking them much easier.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use thiserror::Error;
+
+use crate::assets::ColorScheme;
+use crate::config::{self, UserConfig};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error while loading the color scheme: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse the color scheme: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("{0}")]
+    Config(#[from] config::Error),
+}
+
+const BUILT_IN: &[&str] = &["Light", "Dark"];
+
+fn dir() -> Result<PathBuf, Error> {
+    Ok(UserConfig::dir()?.join("color_schemes"))
+}
+
+fn path(name: &str) -> Result<PathBuf, Error> {
+    Ok(dir()?.join(format!("{}.toml", name)))
+}
+
+// returns the names of all color schemes, the built-in ones first
+pub fn available() -> Vec<String> {
+    let mut user_defined: Vec<String> = dir()
+        .ok()
+        .and_then(|dir| std::fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "toml" { return None }
+            Some(path.file_stem()?.to_str()?.to_owned())
+        })
+        .filter(|name| !BUILT_IN.contains(&name.as_str()))
+        .collect();
+    user_defined.sort();
+    BUILT_IN.iter().map(|&name| name.to_owned()).chain(user_defined).collect()
+}
+
+pub fn load(name: &str) -> Result<ColorScheme, Error> {
+    match name {
+        "Light" => Ok(ColorScheme::light()),
+        "Dark" => Ok(ColorScheme::dark()),
+        _ => Ok(toml::from_str(&std::fs::read_to_string(path(name)?)?)?),
+    }
+}
+
+// watches the file of a user-defined color scheme for changes
+pub struct Watcher {
+    name: String,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl Watcher {
+
+    const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            modified: Self::modification_time(name),
+            last_check: Instant::now(),
+        }
+    }
+
+    fn modification_time(name: &str) -> Option<SystemTime> {
+        if BUILT_IN.contains(&name) { return None }
+        std::fs::metadata(path(name).ok()?).ok()?.modified().ok()
+    }
+
+    // returns the reloaded color scheme if its file changed since the last check
+    pub fn poll(&mut self) -> Option<Result<ColorScheme, Error>> {
+        if self.last_check.elapsed() < Self::CHECK_INTERVAL {
+            return None
+        }
+        self.last_check = Instant::now();
+        let modified = Self::modification_time(&self.name);
+        if modified.is_some() && modified != self.modified {
+            self.modified = modified;
+            Some(load(&self.name))
+        } else {
+            None
+        }
+    }
+
+}
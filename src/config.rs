@@ -0,0 +1,476 @@
+// user configuration, stored as config.toml in the platform's config directory.
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse the config file: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Could not serialize the config: {0}")]
+    Serialize(#[from] toml::ser::Error),
+    #[error("Could not find a directory to store the config in")]
+    NoConfigDir,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LobbyConfig {
+    pub nickname: String,
+    pub matchmaker: String,
+    // the rooms joined most recently, newest first
+    #[serde(default)]
+    pub recent_rooms: Vec<RecentRoom>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecentRoom {
+    pub matchmaker: String,
+    #[serde(deserialize_with = "deserialize_room_id")]
+    pub room_id: String,
+    // when the room was last joined, in seconds since the Unix epoch
+    pub joined_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BrushConfig {
+    // colors are stored as 0xRRGGBBAA, the same format as the built-in palette
+    #[serde(default)]
+    pub recent_colors: Vec<u32>,
+    #[serde(default)]
+    pub palette: Vec<u32>,
+    #[serde(default = "BrushConfig::default_size_presets")]
+    pub size_presets: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UiConfig {
+    // the name of the color scheme, either a built-in one or a user-defined one from the color_schemes directory
+    pub color_scheme: String,
+    // whether zooming and jumping to other parts of the canvas are animated
+    #[serde(default = "UiConfig::default_smooth_viewport")]
+    pub smooth_viewport: bool,
+    // the code of the language the UI is shown in, eg. "en"
+    #[serde(default = "UiConfig::default_language")]
+    pub language: String,
+    // the edge of the window the toolbar is docked to in rooms
+    #[serde(default = "UiConfig::default_toolbar_position")]
+    pub toolbar_position: ToolbarPosition,
+    // the maximum amount of frames drawn per second. 0 means there's no limit other than the display's refresh rate
+    #[serde(default = "UiConfig::default_max_fps")]
+    pub max_fps: u32,
+    // how much bigger or smaller the UI is drawn than the system's DPI setting makes it, between 0.75 and 2
+    #[serde(default = "UiConfig::default_scale")]
+    pub scale: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ToolbarPosition {
+    Left,
+    Top,
+    Right,
+    Bottom,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GridConfig {
+    // the size of the grid's cells, in canvas pixels
+    pub cell_size: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CanvasConfig {
+    // how much memory the canvas's chunks can take up, in megabytes. chunks that haven't been used in a while are
+    // compressed once the canvas gets bigger than this
+    pub memory_budget_mb: usize,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct ReplayConfig {
+    // whether everything that happens on the canvas is recorded to the replays directory, for playing it back later
+    // with --replay
+    pub record_sessions: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PresenceConfig {
+    // how many minutes without any input it takes to be shown as idle to others. 0 never shows you as idle
+    pub idle_minutes: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ConnectionConfig {
+    // how many seconds without hearing from the matchmaker it takes to ping it, to check whether the connection is
+    // still alive
+    pub keepalive_seconds: f32,
+    // how many seconds without hearing from the matchmaker it takes to consider the connection lost.
+    // 0 turns off detecting lost connections
+    pub timeout_seconds: f32,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct UpdatesConfig {
+    // whether the lobby checks GitHub for newer releases of NetCanv. None until the user is asked about it in the
+    // lobby, and nothing is checked before then
+    #[serde(default)]
+    pub check_for_updates: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AudioConfig {
+    // whether sound cues are turned off
+    pub muted: bool,
+    // the volume of sound cues, between 0 and 1
+    pub volume: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UserConfig {
+    pub lobby: LobbyConfig,
+    #[serde(default)]
+    pub brush: BrushConfig,
+    #[serde(default)]
+    pub ui: UiConfig,
+    #[serde(default)]
+    pub grid: GridConfig,
+    #[serde(default)]
+    pub canvas: CanvasConfig,
+    #[serde(default)]
+    pub replay: ReplayConfig,
+    #[serde(default)]
+    pub presence: PresenceConfig,
+    #[serde(default)]
+    pub connection: ConnectionConfig,
+    #[serde(default)]
+    pub updates: UpdatesConfig,
+    #[serde(default)]
+    pub audio: AudioConfig,
+}
+
+impl LobbyConfig {
+
+    // the maximum amount of rooms remembered in recent_rooms
+    pub const MAX_RECENT_ROOMS: usize = 4;
+
+    // moves the room to the front of the recent rooms list
+    pub fn remember_room(&mut self, matchmaker: &str, room_id: &str) {
+        let joined_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+        self.recent_rooms.retain(|room| room.matchmaker != matchmaker || room.room_id != room_id);
+        self.recent_rooms.insert(0, RecentRoom {
+            matchmaker: matchmaker.into(),
+            room_id: room_id.into(),
+            joined_at,
+        });
+        self.recent_rooms.truncate(Self::MAX_RECENT_ROOMS);
+    }
+
+}
+
+// room IDs used to be numbers, so recent rooms remembered back then have them stored as such
+fn deserialize_room_id<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RoomId {
+        Number(u32),
+        String(String),
+    }
+    Ok(match RoomId::deserialize(deserializer)? {
+        RoomId::Number(id) => format!("{:04}", id),
+        RoomId::String(id) => id,
+    })
+}
+
+impl RecentRoom {
+
+    // how long ago the room was last joined
+    pub fn age(&self) -> Duration {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+        Duration::from_secs(now.saturating_sub(self.joined_at))
+    }
+
+}
+
+impl BrushConfig {
+
+    // the maximum amount of colors remembered in recent_colors
+    pub const MAX_RECENT_COLORS: usize = 8;
+
+    fn default_size_presets() -> Vec<f32> {
+        vec![4.0, 8.0, 16.0]
+    }
+
+    // moves the color to the front of the recent colors list. returns whether the list changed
+    pub fn use_color(&mut self, color: u32) -> bool {
+        if self.recent_colors.first() == Some(&color) {
+            return false
+        }
+        self.recent_colors.retain(|&c| c != color);
+        self.recent_colors.insert(0, color);
+        self.recent_colors.truncate(Self::MAX_RECENT_COLORS);
+        true
+    }
+
+}
+
+impl Default for BrushConfig {
+
+    fn default() -> Self {
+        Self {
+            recent_colors: Vec::new(),
+            palette: Vec::new(),
+            size_presets: Self::default_size_presets(),
+        }
+    }
+
+}
+
+impl UiConfig {
+
+    pub const MIN_SCALE: f32 = 0.75;
+    pub const MAX_SCALE: f32 = 2.0;
+
+    fn default_smooth_viewport() -> bool {
+        true
+    }
+
+    fn default_language() -> String {
+        "en".into()
+    }
+
+    fn default_toolbar_position() -> ToolbarPosition {
+        ToolbarPosition::Left
+    }
+
+    fn default_max_fps() -> u32 {
+        60
+    }
+
+    fn default_scale() -> f32 {
+        1.0
+    }
+
+    // the UI scale, kept within the range the settings allow in case the config was edited by hand
+    pub fn scale(&self) -> f32 {
+        self.scale.clamp(Self::MIN_SCALE, Self::MAX_SCALE)
+    }
+
+    // the minimum time between two frames, according to max_fps
+    pub fn frame_time(&self) -> Option<Duration> {
+        if self.max_fps == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(1) / self.max_fps)
+        }
+    }
+
+}
+
+impl Default for UiConfig {
+
+    fn default() -> Self {
+        Self {
+            color_scheme: "Light".into(),
+            smooth_viewport: Self::default_smooth_viewport(),
+            language: Self::default_language(),
+            toolbar_position: Self::default_toolbar_position(),
+            max_fps: Self::default_max_fps(),
+            scale: Self::default_scale(),
+        }
+    }
+
+}
+
+impl ToolbarPosition {
+
+    // whether the toolbar runs along the left or right edge of the window, with its buttons stacked vertically
+    pub fn is_vertical(self) -> bool {
+        matches!(self, Self::Left | Self::Right)
+    }
+
+}
+
+impl GridConfig {
+
+    const MIN_CELL_SIZE: f32 = 1.0;
+    const MAX_CELL_SIZE: f32 = 4096.0;
+
+    // the size of the grid's cells, kept within reason in case the config was edited by hand. cells smaller than a
+    // pixel would make snapping pointless, and zero-sized ones would make drawing the grid loop forever
+    pub fn cell_size(&self) -> f32 {
+        // written with max and min rather than clamp, so that NaN ends up as the minimum
+        self.cell_size.clamp(Self::MIN_CELL_SIZE, Self::MAX_CELL_SIZE)
+    }
+
+}
+
+impl Default for GridConfig {
+
+    fn default() -> Self {
+        Self {
+            cell_size: 16.0,
+        }
+    }
+
+}
+
+impl CanvasConfig {
+
+    // the memory budget in bytes
+    pub fn memory_budget(&self) -> usize {
+        self.memory_budget_mb * 1024 * 1024
+    }
+
+}
+
+impl Default for CanvasConfig {
+
+    fn default() -> Self {
+        Self {
+            memory_budget_mb: 512,
+        }
+    }
+
+}
+
+// converts a number of seconds from the config to a duration. the config may have been edited by hand, so anything
+// that isn't a positive number of seconds that fits into a duration - such as negative numbers, infinity or NaN -
+// gives None
+fn positive_duration(seconds: f32) -> Option<Duration> {
+    // about 30 years, which is plenty for anything in the config while being far from where durations overflow
+    const MAX_SECONDS: f32 = 1.0e9;
+    if seconds > 0.0 && seconds <= MAX_SECONDS {
+        Some(Duration::from_secs_f32(seconds))
+    } else {
+        None
+    }
+}
+
+impl PresenceConfig {
+
+    // how long it takes to become idle, or None if idle detection is turned off
+    pub fn idle_after(&self) -> Option<Duration> {
+        positive_duration(self.idle_minutes * 60.0)
+    }
+
+}
+
+impl Default for PresenceConfig {
+
+    fn default() -> Self {
+        Self {
+            idle_minutes: 5.0,
+        }
+    }
+
+}
+
+impl ConnectionConfig {
+
+    // the keepalive interval and timeout, or None if detecting lost connections is turned off
+    pub fn keepalive(&self) -> Option<(Duration, Duration)> {
+        let timeout = positive_duration(self.timeout_seconds)?;
+        // pinging more often than the connection is checked on doesn't hurt, so a bogus interval pings on every check
+        let interval = positive_duration(self.keepalive_seconds).unwrap_or_default();
+        Some((interval, timeout))
+    }
+
+}
+
+impl Default for ConnectionConfig {
+
+    fn default() -> Self {
+        Self {
+            keepalive_seconds: 10.0,
+            timeout_seconds: 30.0,
+        }
+    }
+
+}
+
+impl Default for AudioConfig {
+
+    fn default() -> Self {
+        Self {
+            muted: false,
+            volume: 0.5,
+        }
+    }
+
+}
+
+impl Default for UserConfig {
+
+    fn default() -> Self {
+        Self {
+            lobby: LobbyConfig {
+                nickname: "Anon".into(),
+                matchmaker: String::new(),
+                recent_rooms: Vec::new(),
+            },
+            brush: Default::default(),
+            ui: Default::default(),
+            grid: Default::default(),
+            canvas: Default::default(),
+            replay: Default::default(),
+            presence: Default::default(),
+            connection: Default::default(),
+            updates: Default::default(),
+            audio: Default::default(),
+        }
+    }
+
+}
+
+impl UserConfig {
+
+    // the directory config.toml is stored in, along with other user files
+    pub fn dir() -> Result<PathBuf, Error> {
+        let dirs = ProjectDirs::from("", "", "NetCanv").ok_or(Error::NoConfigDir)?;
+        Ok(dirs.config_dir().to_owned())
+    }
+
+    pub fn path() -> Result<PathBuf, Error> {
+        Ok(Self::dir()?.join("config.toml"))
+    }
+
+    // loads the config from disk, creating the default one if it doesn't exist yet
+    pub fn load_or_create() -> Result<Self, Error> {
+        let path = Self::path()?;
+        if path.is_file() {
+            let config = std::fs::read_to_string(path)?;
+            Ok(toml::from_str(&config)?)
+        } else {
+            let config = Self::default();
+            config.save()?;
+            Ok(config)
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let path = Self::path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_sensible_durations_are_accepted() {
+        assert_eq!(positive_duration(1.5), Some(Duration::from_millis(1500)));
+        for &seconds in [0.0, -1.0, f32::NAN, f32::INFINITY, 1.0e10].iter() {
+            assert_eq!(positive_duration(seconds), None, "{} was accepted", seconds);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+// crash reports.
+// when NetCanv panics, a report is written to the crashes folder next to config.toml, and the user is told where to
+// find it, so that it can be attached to a bug report. the report is never sent anywhere by NetCanv itself.
+// the report contains the panic message and a backtrace, the OS, and the config, with anything that could identify the
+// user or the rooms they've been in stripped out. the log is left out, as it's full of other people's nicknames and
+// addresses, which can't be reliably told apart from the rest of it.
+
+use std::backtrace::Backtrace;
+use std::fmt::{Display, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use native_dialog::{MessageDialog, MessageType};
+use thiserror::Error;
+
+use crate::config::{self, UserConfig};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error while writing the crash report: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Config(#[from] config::Error),
+}
+
+// the keys in config.toml whose values are replaced with "<stripped>" in crash reports
+const STRIPPED_KEYS: &[&str] = &["nickname", "matchmaker", "room_id"];
+
+fn dir() -> Result<PathBuf, Error> {
+    Ok(UserConfig::dir()?.join("crashes"))
+}
+
+fn strip(value: &mut toml::Value) {
+    match value {
+        toml::Value::Table(table) => for (key, value) in table.iter_mut() {
+            if STRIPPED_KEYS.contains(&key.as_str()) || key.contains("password") {
+                *value = toml::Value::String("<stripped>".into());
+            } else {
+                strip(value);
+            }
+        },
+        toml::Value::Array(array) => array.iter_mut().for_each(strip),
+        _ => (),
+    }
+}
+
+// reads config.toml from disk, rather than using the config in memory, as the panic may have happened while it was
+// being changed
+fn stripped_config() -> String {
+    let config = match UserConfig::path().and_then(|path| Ok(std::fs::read_to_string(path)?)) {
+        Ok(config) => config,
+        Err(error) => return format!("could not read the config: {}\n", error),
+    };
+    match config.parse::<toml::Value>() {
+        Ok(mut config) => {
+            strip(&mut config);
+            config.to_string()
+        },
+        Err(error) => format!("could not parse the config: {}\n", error),
+    }
+}
+
+fn report(info: &dyn Display, backtrace: &Backtrace) -> String {
+    let mut report = String::new();
+    // writing to a String can't fail
+    let _ = writeln!(report, "NetCanv {} crashed.", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report);
+    let _ = writeln!(report, "{}", info);
+    let _ = writeln!(report);
+    let _ = writeln!(report, "# System");
+    let _ = writeln!(report, "os: {} ({})", std::env::consts::OS, std::env::consts::ARCH);
+    let _ = writeln!(report, "renderer: Vulkan, through skulpin");
+    let _ = writeln!(report);
+    let _ = writeln!(report, "# Backtrace");
+    let _ = writeln!(report, "{}", backtrace);
+    let _ = writeln!(report);
+    let _ = writeln!(report, "# Log");
+    let _ = writeln!(report, "Not included, as it contains nicknames and IP addresses. If it's needed, it's in the");
+    let _ = writeln!(report, "logs folder next to config.toml. Check it for anything you'd rather not share first.");
+    let _ = writeln!(report);
+    let _ = writeln!(report, "# config.toml");
+    let _ = write!(report, "{}", stripped_config());
+    report
+}
+
+fn write_report(info: &dyn Display) -> Result<PathBuf, Error> {
+    let dir = dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    let path = dir.join(format!("crash-{}.txt", timestamp));
+    std::fs::write(&path, report(info, &Backtrace::force_capture()))?;
+    Ok(path)
+}
+
+// installs the panic hook that writes crash reports. the default hook still runs first, so the panic is printed to
+// stderr as usual
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        match write_report(info) {
+            Ok(path) => {
+                let text = format!(
+                    "NetCanv has crashed. A crash report was saved to {}\n\
+                     Please attach it when reporting the bug.",
+                    path.display(),
+                );
+                eprintln!("{}", text);
+                let _ = MessageDialog::new()
+                    .set_type(MessageType::Error)
+                    .set_title("NetCanv")
+                    .set_text(&text)
+                    .show_alert();
+            },
+            Err(error) => eprintln!("could not write a crash report: {}", error),
+        }
+    }));
+}
@@ -0,0 +1,47 @@
+// file dialogs shown on a separate thread.
+// native dialogs block the thread they're shown on until they're closed, so showing them on the main thread freezes
+// the window - nothing gets redrawn, not even after the window is resized or uncovered. instead, the dialog is shown on
+// its own thread, and the app checks on it every frame until it's closed.
+
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use native_dialog::FileDialog;
+
+// what was picked in the dialog. for dialogs picking a single path this is None if the dialog was cancelled, and for
+// ones picking multiple paths it's empty
+pub type DialogResult<T = Option<PathBuf>> = Result<T, native_dialog::Error>;
+
+pub struct PendingDialog<T = Option<PathBuf>> {
+    result: Receiver<DialogResult<T>>,
+}
+
+impl<T: Default + Send + 'static> PendingDialog<T> {
+
+    // shows a dialog on a separate thread. the function is given a new dialog to set up and show
+    pub fn spawn(show: impl FnOnce(FileDialog) -> DialogResult<T> + Send + 'static) -> Self {
+        let (sender, result) = mpsc::channel();
+        std::thread::spawn(move || {
+            // the receiver is gone if whoever opened the dialog doesn't care about it anymore
+            let _ = sender.send(show(FileDialog::new()));
+        });
+        Self { result }
+    }
+
+    // returns the dialog's result once it's closed
+    pub fn poll(&self) -> Option<DialogResult<T>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            // the dialog's thread panicked, so there's no result to wait for
+            Err(TryRecvError::Disconnected) => Some(Ok(T::default())),
+        }
+    }
+
+    // blocks until the dialog is closed. this is for dialogs that outlive whatever opened them, and must not be called
+    // on the main thread
+    pub fn wait(self) -> DialogResult<T> {
+        self.result.recv().unwrap_or_else(|_| Ok(T::default()))
+    }
+
+}
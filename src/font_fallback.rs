@@ -0,0 +1,87 @@
+// font fallback, for drawing characters that NetCanv's own fonts don't have - most notably emoji.
+// text is split into runs of characters that can be drawn with the same typeface. characters missing from the font
+// are drawn with a typeface picked by the system's font manager, which for emoji is usually a color emoji font.
+// Skia draws color glyphs (CBDT, sbix and COLR tables) by itself, so they don't need any special treatment beyond
+// picking the right typeface.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use skulpin::skia_safe::*;
+
+thread_local! {
+    // looking up a fallback typeface is slow, so the result is cached for every character. None means that no
+    // installed font has the character, in which case it's drawn with the original font as a missing glyph
+    static FALLBACKS: RefCell<HashMap<char, Option<Typeface>>> = RefCell::new(HashMap::new());
+}
+
+fn fallback_typeface(font: &Font, character: char) -> Option<Typeface> {
+    FALLBACKS.with(|fallbacks| {
+        fallbacks
+            .borrow_mut()
+            .entry(character)
+            .or_insert_with(|| {
+                let style = font.typeface().map_or_else(FontStyle::default, |typeface| typeface.font_style());
+                FontMgr::new().match_family_style_character("", style, &[], character as Unichar)
+            })
+            .clone()
+    })
+}
+
+// whether the character modifies the one before it, and should therefore stay in the same run. this keeps emoji
+// sequences like 🏳️‍🌈 together
+fn is_joining(character: char) -> bool {
+    matches!(character, '\u{200d}' | '\u{fe00}'..='\u{fe0f}' | '\u{1f3fb}'..='\u{1f3ff}')
+}
+
+// splits the text into runs, each along with the font it should be drawn with
+fn runs<'a>(text: &'a str, font: &Font) -> Vec<(&'a str, Font)> {
+    let mut runs = Vec::new();
+    // the start of the current run, and its fallback typeface. None means the run uses the original font
+    let mut start = 0;
+    let mut current: Option<Typeface> = None;
+    for (i, character) in text.char_indices() {
+        if is_joining(character) { continue }
+        let typeface = if font.unichar_to_glyph(character as Unichar) == 0 {
+            fallback_typeface(font, character)
+        } else {
+            None
+        };
+        let id = |typeface: &Option<Typeface>| typeface.as_ref().map(|typeface| typeface.unique_id());
+        if id(&typeface) != id(&current) {
+            if i > start {
+                runs.push((&text[start..i], run_font(font, current.take())));
+            }
+            start = i;
+            current = typeface;
+        }
+    }
+    if start < text.len() || runs.is_empty() {
+        runs.push((&text[start..], run_font(font, current)));
+    }
+    runs
+}
+
+fn run_font(font: &Font, typeface: Option<Typeface>) -> Font {
+    match typeface {
+        Some(typeface) => Font::new(typeface, font.size()),
+        None => font.clone(),
+    }
+}
+
+// measures the advance of the text, taking fallback fonts into account
+pub fn measure_str(text: &str, font: &Font) -> f32 {
+    runs(text, font)
+        .iter()
+        .map(|(run, font)| font.measure_str(run, None).0)
+        .sum()
+}
+
+// draws the text, using fallback fonts for characters the font doesn't have
+pub fn draw_str(canvas: &mut Canvas, text: &str, origin: impl Into<Point>, font: &Font, paint: &Paint) {
+    let mut origin = origin.into();
+    for (run, font) in runs(text, font) {
+        canvas.draw_str(run, origin, &font, paint);
+        origin.x += font.measure_str(run, None).0;
+    }
+}
@@ -0,0 +1,224 @@
+// headless hosting.
+// `netcanv --headless-host` hosts a room without opening a window. the canvas is kept around for as long as the
+// process runs, so people can come and go without the room's contents being lost when the host leaves. the canvas is
+// also saved to disk periodically as a project and loaded back on startup, which means it survives restarts too.
+// the canvas is saved one last time when the process is stopped with SIGINT or SIGTERM, or when hosting fails.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use skulpin::skia_safe::Rect;
+use thiserror::Error;
+
+use crate::app::paint::tools;
+use crate::assets::{Assets, ColorScheme};
+use crate::cli;
+use crate::config::{self, UserConfig};
+use crate::net::{self, Message, Peer};
+use crate::net::transfer::ChunkUploads;
+use crate::paint_canvas::PaintCanvas;
+use crate::project;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{0}")]
+    Args(#[from] cli::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Net(#[from] net::Error),
+    #[error("Could not load the canvas: {0}")]
+    Project(#[from] project::Error),
+    #[error("{0}")]
+    Config(#[from] config::Error),
+    #[error("{0}")]
+    Matchmaker(String),
+}
+
+pub struct Options {
+    nickname: String,
+    matchmaker: String,
+    password: Option<String>,
+    public_name: Option<String>,
+    save_dir: PathBuf,
+}
+
+impl Options {
+
+    // parses the arguments following --headless-host. anything not specified is taken from the user config
+    pub fn parse(mut args: impl Iterator<Item = String>, config: &UserConfig) -> Result<Self, Error> {
+        let mut options = Self {
+            nickname: config.lobby.nickname.clone(),
+            matchmaker: config.lobby.matchmaker.clone(),
+            password: None,
+            public_name: None,
+            save_dir: UserConfig::dir()?.join("headless_canvas"),
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--nickname" => options.nickname = cli::value(&mut args, &arg)?,
+                "--matchmaker" => options.matchmaker = cli::value(&mut args, &arg)?,
+                "--password" => options.password = Some(cli::value(&mut args, &arg)?),
+                "--public" => options.public_name = Some(cli::value(&mut args, &arg)?),
+                "--save-dir" => options.save_dir = PathBuf::from(cli::value(&mut args, &arg)?),
+                _ => return Err(cli::Error::UnknownArgument(arg).into()),
+            }
+        }
+        Ok(options)
+    }
+
+}
+
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+// how long to sleep between network ticks. there's no window to draw to, so there's no point in spinning
+const TICK_INTERVAL: Duration = Duration::from_millis(10);
+
+fn load_canvas(paint_canvas: &mut PaintCanvas, save_dir: &Path) -> Result<(), Error> {
+    if project::exists(save_dir) {
+        project::load(paint_canvas, save_dir)?;
+    }
+    // everything that was just loaded is already saved
+    paint_canvas.take_dirty_chunks();
+    Ok(())
+}
+
+// saves all chunks that changed since the last save
+fn save_canvas(paint_canvas: &mut PaintCanvas, save_dir: &Path) -> Result<(), Error> {
+    let dirty_chunks = paint_canvas.take_dirty_chunks();
+    if dirty_chunks.is_empty() {
+        return Ok(())
+    }
+    project::save_chunks(paint_canvas, save_dir, &dirty_chunks)?;
+    log::info!("saved {} chunks", dirty_chunks.len());
+    Ok(())
+}
+
+pub fn run(options: Options, config: &UserConfig) -> Result<(), Error> {
+    use signal_hook::consts::{SIGINT, SIGTERM};
+
+    let shutdown = Arc::new(AtomicBool::new(false));
+    // a second SIGINT exits immediately, without saving. the conditional shutdown must be registered first, so that it
+    // only sees the flag set by earlier signals
+    signal_hook::flag::register_conditional_shutdown(SIGINT, 1, shutdown.clone())?;
+    signal_hook::flag::register(SIGINT, shutdown.clone())?;
+    signal_hook::flag::register(SIGTERM, shutdown.clone())?;
+
+    let mut paint_canvas = PaintCanvas::new();
+    paint_canvas.set_memory_budget(Some(config.canvas.memory_budget()));
+    load_canvas(&mut paint_canvas, &options.save_dir)?;
+    log::info!("canvas is saved to {}", options.save_dir.display());
+
+    let result = host(&options, config, &mut paint_canvas, &shutdown);
+    // whatever was drawn since the last autosave is saved before quitting, even if hosting failed
+    if let Err(error) = save_canvas(&mut paint_canvas, &options.save_dir) {
+        log::error!("could not save the canvas: {}", error);
+    }
+    result
+}
+
+// hosts the room until a shutdown is requested
+fn host(
+    options: &Options,
+    config: &UserConfig,
+    paint_canvas: &mut PaintCanvas<'static>,
+    shutdown: &AtomicBool,
+) -> Result<(), Error> {
+    // tools are needed to apply what other people do with them, but since nothing is drawn they never get to see
+    // the actual color scheme
+    let assets = Assets::new(ColorScheme::light(), "Light", "en");
+    let mut tools = tools::all(&assets);
+
+    let mut peer = Peer::host(
+        &options.nickname,
+        &options.matchmaker,
+        options.password.as_deref(),
+        options.public_name.as_deref(),
+    )?;
+    peer.set_keepalive(config.connection.keepalive());
+    peer.set_tools(tools::capabilities(&tools));
+    let mut canvas_data_queue = Vec::new();
+    let mut uploads = ChunkUploads::new();
+    let mut last_autosave = Instant::now();
+
+    while !shutdown.load(Ordering::Relaxed) {
+        let mut connected = false;
+        // nobody's edits can reach into regions locked by someone else
+        paint_canvas.set_locks(peer.locks());
+        for message in peer.tick()? {
+            paint_canvas.set_editor(match &message {
+                Message::Stroke(sender, _) |
+                Message::ChunkTiles(sender, ..) |
+                Message::Tool(sender, ..) |
+                Message::Left(sender, _) => Some(*sender),
+                _ => None,
+            });
+            match message {
+                Message::Connected => connected = true,
+                Message::Stroke(_, points) => paint_canvas.stroke_points(&points),
+                Message::NewMate(addr) => canvas_data_queue.push(addr),
+                Message::CanvasData(chunk, png) => {
+                    if let Err(error) = paint_canvas.decode_png_data(chunk, &png) {
+                        log::warn!("could not decode chunk {:?}: {}", chunk, error);
+                    }
+                },
+                Message::ChunkTiles(_, chunk, tiles) => {
+                    if let Err(error) = paint_canvas.decode_tile_data(chunk, &tiles) {
+                        log::warn!("could not decode tiles of chunk {:?}: {}", chunk, error);
+                    }
+                },
+                Message::Tool(sender, name, payload) => {
+                    if let Err(error) = tools::receive(&mut tools, paint_canvas, sender, &name, &payload) {
+                        log::error!("{}", error);
+                    }
+                },
+                Message::Joined(nickname) => log::info!("{} joined the room", nickname),
+                Message::Left(addr, nickname) => {
+                    log::info!("{} has left the room", nickname);
+                    tools::left(&mut tools, paint_canvas, addr);
+                },
+                // nobody's looking at the canvas, so there's nothing worth telling
+                Message::MissingTools(..) | Message::UnsupportedTool(..) => (),
+                // we're the host, so nobody else can change the background or our nickname
+                Message::Background(_) | Message::NicknameTaken(_) => (),
+                // headless rooms are never made persistent
+                Message::Persisted(_) | Message::StoredChunk(..) => (),
+                Message::Error(error) => return Err(Error::Matchmaker(error)),
+            }
+        }
+
+        if connected {
+            // room_id is only None before we're connected
+            log::info!("hosting room {}", peer.room_id().unwrap());
+        }
+
+        for addr in canvas_data_queue.drain(..) {
+            let chunks: Vec<_> = paint_canvas.chunk_positions().collect();
+            peer.send_chunk_count(addr, chunks.len() as u32)?;
+            uploads.start(addr, chunks);
+        }
+        let next_uploads = if peer.can_send_canvas_data() { uploads.next(peer.mates()) } else { vec![] };
+        for (addr, chunk_position) in next_uploads {
+            if let Some(png_data) = paint_canvas.chunk_png_data(chunk_position) {
+                if !peer.send_canvas_data(addr, chunk_position, png_data)? {
+                    uploads.retry(addr, chunk_position);
+                }
+            }
+        }
+
+        if last_autosave.elapsed() >= AUTOSAVE_INTERVAL {
+            last_autosave = Instant::now();
+            if let Err(error) = save_canvas(paint_canvas, &options.save_dir) {
+                log::error!("autosave failed: {}", error);
+            }
+        }
+
+        // nothing is ever visible, so any chunk can be evicted
+        paint_canvas.manage_memory(Rect::new_empty());
+
+        std::thread::sleep(TICK_INTERVAL);
+    }
+    log::info!("shutting down");
+    Ok(())
+}
@@ -0,0 +1,98 @@
+// translations of user-facing strings.
+// each language is a TOML file in assets/languages, with strings grouped into tables by the part of the app they're
+// used in. a string is looked up by its table and key joined with a dot, eg. "lobby.host". strings that are missing
+// from a translation fall back to English, so translations don't break when new strings are added.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Write};
+
+use toml::Value;
+
+const ENGLISH: &str = "en";
+
+// the code and file contents of each built-in language. the first one is the default
+const LANGUAGES: &[(&str, &str)] = &[
+    (ENGLISH, include_str!("assets/languages/en.toml")),
+    ("pl", include_str!("assets/languages/pl.toml")),
+];
+
+pub struct Language {
+    code: &'static str,
+    strings: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+// reads the strings of a language file into a map from "table.key" to the string
+fn parse(source: &str) -> HashMap<String, String> {
+    fn flatten(prefix: &str, value: &Value, strings: &mut HashMap<String, String>) {
+        match value {
+            Value::String(string) => {
+                strings.insert(prefix.to_owned(), string.clone());
+            },
+            Value::Table(table) => for (key, value) in table {
+                let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(&key, value, strings);
+            },
+            _ => log::warn!("translation {:?} is not a string, ignoring", prefix),
+        }
+    }
+
+    let value: Value = source.parse().expect("error while parsing a built-in language file");
+    let mut strings = HashMap::new();
+    flatten("", &value, &mut strings);
+    strings
+}
+
+// returns the codes of all available languages
+pub fn available() -> impl Iterator<Item = &'static str> {
+    LANGUAGES.iter().map(|&(code, _)| code)
+}
+
+impl Language {
+
+    // loads the language with the given code. unknown languages fall back to English
+    pub fn load(code: &str) -> Self {
+        let &(code, source) = LANGUAGES
+            .iter()
+            .find(|&&(c, _)| c == code)
+            .unwrap_or(&LANGUAGES[0]);
+        Self {
+            code,
+            strings: parse(source),
+            fallback: if code == ENGLISH { HashMap::new() } else { parse(LANGUAGES[0].1) },
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    // the name of the language, in the language itself
+    pub fn name(&self) -> &str {
+        self.tr("name")
+    }
+
+    // returns the translation of the string with the given key. if there's no such string, the key itself is
+    // returned, which makes missing strings easy to spot
+    pub fn tr<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings
+            .get(key)
+            .or_else(|| self.fallback.get(key))
+            .map_or(key, |string| string.as_str())
+    }
+
+    // translates a string, replacing each {} in it with the next argument
+    pub fn format(&self, key: &str, args: &[&dyn Display]) -> String {
+        let mut parts = self.tr(key).split("{}");
+        let mut result = parts.next().unwrap_or("").to_owned();
+        let mut args = args.iter();
+        for part in parts {
+            if let Some(arg) = args.next() {
+                let _ = write!(result, "{}", arg);
+            }
+            result.push_str(part);
+        }
+        result
+    }
+
+}
@@ -0,0 +1,51 @@
+// invite links.
+// an invite link contains everything needed to join a room, in the form netcanv://matchmaker.host:port/roomid.
+// they can be copied from the paint screen and passed to netcanv as its first argument to join the room right away.
+
+const SCHEME: &str = "netcanv://";
+
+pub fn is_invite_link(text: &str) -> bool {
+    text.starts_with(SCHEME)
+}
+
+pub fn format(matchmaker: &str, room_id: &str) -> String {
+    format!("{}{}/{}", SCHEME, matchmaker, room_id)
+}
+
+// returns the matchmaker address and room ID the link points to, or None if the link is malformed
+pub fn parse(link: &str) -> Option<(String, String)> {
+    let rest = link.strip_prefix(SCHEME)?.trim_end_matches('/');
+    let (matchmaker, room_id) = rest.split_at(rest.rfind('/')?);
+    let room_id = &room_id[1..];
+    if matchmaker.is_empty() || room_id.is_empty() || !room_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None
+    }
+    Some((matchmaker.to_owned(), room_id.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_round_trip() {
+        let link = format("netcanv.example.com:62137", "Ab12cD");
+        assert!(is_invite_link(&link));
+        assert_eq!(parse(&link), Some(("netcanv.example.com:62137".into(), "Ab12cD".into())));
+        assert_eq!(parse(&(link + "/")), Some(("netcanv.example.com:62137".into(), "Ab12cD".into())));
+    }
+
+    #[test]
+    fn malformed_links_are_refused() {
+        for link in [
+            "https://netcanv.example.com/Ab12cD",
+            "netcanv://",
+            "netcanv://Ab12cD",
+            "netcanv:///Ab12cD",
+            "netcanv://netcanv.example.com/",
+            "netcanv://netcanv.example.com/room-id",
+        ].iter() {
+            assert_eq!(parse(link), None, "{} was accepted", link);
+        }
+    }
+}
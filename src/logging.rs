@@ -0,0 +1,150 @@
+// logging.
+// everything NetCanv logs is printed to stderr and written to netcanv.log in the logs folder next to config.toml, so
+// that there's something to look at when a problem (especially a network one) can't be reproduced. every launch starts
+// a new log, and the logs of the previous few are kept as netcanv.1.log, netcanv.2.log, and so on.
+// NetCanv's own messages are logged from the info level up, or from the debug level up with --verbose. other crates
+// only get to log warnings and errors, as some of them are very chatty.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use thiserror::Error;
+
+use crate::config::{self, UserConfig};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Config(#[from] config::Error),
+    #[error("Could not set up logging: {0}")]
+    SetLogger(#[from] log::SetLoggerError),
+}
+
+// how many logs from previous launches are kept
+const KEPT_LOGS: usize = 4;
+// the size after which the log is rotated even while NetCanv is running, so that a long session with --verbose can't
+// fill up the disk
+const MAX_LOG_SIZE: u64 = 8 * 1024 * 1024;
+
+struct Output {
+    // None if the log file couldn't be opened, in which case messages only go to stderr
+    file: Option<File>,
+    dir: Option<PathBuf>,
+    // the number of bytes written to the current log file
+    written: u64,
+}
+
+struct Logger {
+    verbose: AtomicBool,
+    output: Mutex<Output>,
+}
+
+static LOGGER: Logger = Logger {
+    verbose: AtomicBool::new(false),
+    output: Mutex::new(Output {
+        file: None,
+        dir: None,
+        written: 0,
+    }),
+};
+
+pub fn dir() -> Result<PathBuf, Error> {
+    Ok(UserConfig::dir()?.join("logs"))
+}
+
+fn log_path(dir: &Path, index: usize) -> PathBuf {
+    match index {
+        0 => dir.join("netcanv.log"),
+        _ => dir.join(format!("netcanv.{}.log", index)),
+    }
+}
+
+// moves the existing logs out of the way and starts a new one
+fn rotate(dir: &Path) -> Result<File, Error> {
+    std::fs::create_dir_all(dir)?;
+    let _ = std::fs::remove_file(log_path(dir, KEPT_LOGS));
+    for index in (0..KEPT_LOGS).rev() {
+        let from = log_path(dir, index);
+        if from.is_file() {
+            std::fs::rename(from, log_path(dir, index + 1))?;
+        }
+    }
+    Ok(File::create(log_path(dir, 0))?)
+}
+
+// the current time of day in UTC, as hh:mm:ss
+fn timestamp() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs()) % (24 * 60 * 60);
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+impl Log for Logger {
+
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let max_level = if self.verbose.load(Ordering::Relaxed) { Level::Debug } else { Level::Info };
+        metadata.level() <= max_level && (metadata.target().starts_with("netcanv") || metadata.level() <= Level::Warn)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) { return }
+
+        let line = format!("{} {:<5} {}: {}", timestamp(), record.level(), record.target(), record.args());
+        eprintln!("{}", line);
+        // a panic while logging poisons the mutex, but the output is still perfectly usable
+        let mut output = self.output.lock().unwrap_or_else(|error| error.into_inner());
+        if let Some(file) = &mut output.file {
+            if writeln!(file, "{}", line).is_ok() {
+                output.written += line.len() as u64 + 1;
+            }
+        }
+        if output.written > MAX_LOG_SIZE {
+            output.written = 0;
+            output.file = output.dir.as_deref().and_then(|dir| rotate(dir).ok());
+        }
+    }
+
+    fn flush(&self) {
+        let mut output = self.output.lock().unwrap_or_else(|error| error.into_inner());
+        if let Some(file) = &mut output.file {
+            let _ = file.flush();
+        }
+    }
+
+}
+
+// sets up logging. messages are always printed to stderr, the returned error is only about the log file
+pub fn init(verbose: bool) -> Result<(), Error> {
+    LOGGER.verbose.store(verbose, Ordering::Relaxed);
+    log::set_logger(&LOGGER)?;
+    log::set_max_level(if verbose { LevelFilter::Debug } else { LevelFilter::Info });
+
+    let dir = dir()?;
+    let file = rotate(&dir)?;
+    let mut output = LOGGER.output.lock().unwrap();
+    output.file = Some(file);
+    output.dir = Some(dir);
+    Ok(())
+}
+
+// opens the folder with the logs in the system's file manager
+pub fn open_dir() -> Result<(), Error> {
+    let dir = dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let file_manager = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    Command::new(file_manager).arg(dir).spawn()?;
+    Ok(())
+}
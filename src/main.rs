@@ -1,26 +1,103 @@
 use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use skulpin::*;
 
-use winit::dpi::LogicalSize;
+use winit::dpi::{LogicalPosition, LogicalSize};
 use winit::event::{Event, WindowEvent};
-use winit::event_loop::{ControlFlow, EventLoop};
+use winit::event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget};
 use winit::platform::unix::WindowBuilderExtUnix;
-use winit::window::WindowBuilder;
+use winit::window::{Window, WindowBuilder};
 
 mod app;
 mod assets;
+mod audio;
+mod autosave;
+mod cli;
+mod clipboard;
+mod color_schemes;
+mod config;
+mod crash_report;
+mod file_dialog;
+mod font_fallback;
+mod headless;
+mod i18n;
+mod invite_link;
+mod logging;
 mod net;
 mod paint_canvas;
+mod project;
+mod recording;
+mod replay;
 mod ui;
+mod update_check;
 mod util;
 
 use app::*;
 use assets::*;
+use config::UserConfig;
 use ui::input::*;
 
+// the secondary window some states show panels in, eg. the palette while painting
+struct PanelWindow {
+    // the renderer must be dropped before the window it renders to, which is the order fields are dropped in
+    renderer: Renderer,
+    window: Window,
+    input: Input,
+}
+
+impl PanelWindow {
+
+    fn open(window_target: &EventLoopWindowTarget<()>) -> Result<Self, Box<dyn Error>> {
+        let window = WindowBuilder::new()
+            .with_inner_size(LogicalSize::new(240, 360))
+            .with_title("NetCanv")
+            .with_resizable(true)
+            .build(window_target)?;
+        let renderer = RendererBuilder::new()
+            .use_vulkan_debug_layer(false)
+            .build(&WinitWindow::new(&window))?;
+        Ok(Self {
+            renderer,
+            window,
+            input: Input::new(),
+        })
+    }
+
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
 
+    crash_report::install();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let verbose = args.iter().any(|arg| arg == "--verbose");
+    if let Err(error) = logging::init(verbose) {
+        log::error!("could not start writing the log file: {}", error);
+    }
+
+    let config = UserConfig::load_or_create().unwrap_or_else(|error| {
+        log::warn!("could not load the config, using defaults: {}", error);
+        UserConfig::default()
+    });
+
+    let mut args = args.into_iter().filter(|arg| arg != "--verbose").peekable();
+    if args.peek().map(|arg| arg.as_str()) == Some("--headless-host") {
+        args.next();
+        headless::run(headless::Options::parse(args, &config)?, &config)?;
+        return Ok(())
+    }
+    // a replay is loaded before the window is opened, so that an invalid file doesn't flash an empty window
+    let replay = if args.peek().map(|arg| arg.as_str()) == Some("--replay") {
+        args.next();
+        let options = replay::Options::parse(args.by_ref())?;
+        Some((replay::load(&options.path)?, options.speed))
+    } else {
+        None
+    };
+    let join_options = cli::JoinOptions::parse(args)?;
+
     let event_loop = EventLoop::new();
     let winit_window = {
         let mut b = WindowBuilder::new()
@@ -38,29 +115,100 @@ fn main() -> Result<(), Box<dyn Error>> {
         .use_vulkan_debug_layer(false)
         .build(&window)?;
 
-    let assets = Assets::new(ColorScheme::light());
-    let mut app: Option<Box<dyn AppState>> = Some(Box::new(lobby::State::new(assets, None)) as _);
+    let colors = color_schemes::load(&config.ui.color_scheme).unwrap_or_else(|error| {
+        log::warn!("could not load the color scheme {:?}, using the light one: {}", config.ui.color_scheme, error);
+        ColorScheme::light()
+    });
+    let assets = Assets::new(colors, &config.ui.color_scheme, &config.ui.language);
+    let frame_time = config.ui.frame_time();
+    let mut app: Option<Box<dyn AppState>> = match replay {
+        Some((entries, speed)) => Some(Box::new(app::replay::State::new(assets, entries, speed))),
+        None => Some(Box::new(lobby::State::with_options(assets, config, join_options))),
+    };
     let mut input = Input::new();
+    // the proxy can only be used from one thread at a time
+    let proxy = Mutex::new(event_loop.create_proxy());
+    input.set_waker(Arc::new(move || {
+        if let Ok(proxy) = proxy.lock() {
+            let _ = proxy.send_event(());
+        }
+    }));
+    let mut ime_position = None;
+    let mut panel_window: Option<PanelWindow> = None;
+    // frames are only drawn when there's input, or when the app asks for one. see Input::request_redraw
+    let mut redraw_at = Some(Instant::now());
+    let mut last_frame: Option<Instant> = None;
+    // whether any input came in since the last frame
+    let mut had_input = false;
 
-    event_loop.run(move |event, _, control_flow| {
+    event_loop.run(move |event, window_target, control_flow| {
         let window = WinitWindow::new(&winit_window);
-        *control_flow = ControlFlow::Poll;
+        let earliest = |a: Option<Instant>, b: Instant| Some(a.map_or(b, |a| a.min(b)));
 
         match event {
 
+
             Event::WindowEvent {
+                window_id,
                 event,
-                ..
             } => {
-                if let WindowEvent::CloseRequested = event {
-                    *control_flow = ControlFlow::Exit;
+                if let Some(panel) = panel_window.as_mut().filter(|panel| panel.window.id() == window_id) {
+                    // closing the panel window only closes the panel, not the whole app
+                    if let WindowEvent::CloseRequested = event {
+                        app.as_mut().unwrap().panel_window_closed();
+                    } else {
+                        panel.input.process_event(&event);
+                    }
+                } else if let WindowEvent::CloseRequested = event {
+                    if app.as_mut().unwrap().close_requested() {
+                        *control_flow = ControlFlow::Exit;
+                    }
                 } else {
                     input.process_event(&event);
+                    // strokes should follow the pen as closely as possible, so dragging draws a frame right away,
+                    // without waiting for max_fps to allow one
+                    if input.pointer_is_dragging() {
+                        winit_window.request_redraw();
+                    }
                 }
+                had_input = true;
+                redraw_at = earliest(redraw_at, Instant::now());
             },
 
             Event::MainEventsCleared => {
+                if *control_flow == ControlFlow::Exit { return }
+
+                // don't draw more often than max_fps allows
+                let now = Instant::now();
+                let next_frame = match (redraw_at, last_frame.zip(frame_time)) {
+                    (Some(redraw_at), Some((last_frame, frame_time))) => Some(redraw_at.max(last_frame + frame_time)),
+                    (redraw_at, _) => redraw_at,
+                };
+                match next_frame {
+                    Some(next_frame) if next_frame <= now => (),
+                    Some(next_frame) => {
+                        *control_flow = ControlFlow::WaitUntil(next_frame);
+                        return
+                    },
+                    None => {
+                        *control_flow = ControlFlow::Wait;
+                        return
+                    },
+                }
+                winit_window.request_redraw();
+            },
+
+            // the main window is only ever drawn here, once winit has delivered all the events that came in
+            Event::RedrawRequested(window_id) if window_id == winit_window.id() => {
+                if *control_flow == ControlFlow::Exit { return }
+
+                let now = Instant::now();
+                last_frame = Some(now);
+
+                let ui_scale = app.as_ref().unwrap().ui_scale();
+                input.set_ui_scale(ui_scale);
                 renderer.draw(&window, |canvas, csh| {
+                    canvas.scale((ui_scale, ui_scale));
                     // unwrap always succeeds here as app is never None
                     // i don't really like this method chaining tho
                     app.as_mut().unwrap().process(StateArgs {
@@ -70,9 +218,70 @@ fn main() -> Result<(), Box<dyn Error>> {
                     });
                     app = Some(app.take().unwrap().next_state());
                 }).unwrap();
+                if app.as_ref().unwrap().wants_exit() {
+                    *control_flow = ControlFlow::Exit;
+                    return
+                }
+                if let Some(position) = input.take_ime_position() {
+                    // moving the candidate window is a round trip to the window system, so only do it when needed
+                    if ime_position != Some(position) {
+                        // the position is in UI units, which are scaled by the UI scale to get logical pixels.
+                        // winit then takes care of the display's scale factor
+                        let scale = input.ui_scale();
+                        winit_window.set_ime_position(LogicalPosition::new(position.x * scale, position.y * scale));
+                        ime_position = Some(position);
+                    }
+                }
                 input.finish_frame();
+
+                let wants_panel_window = app.as_ref().unwrap().wants_panel_window();
+                if wants_panel_window && panel_window.is_none() {
+                    match PanelWindow::open(window_target) {
+                        Ok(window) => panel_window = Some(window),
+                        Err(error) => {
+                            log::error!("could not open the panel window: {}", error);
+                            app.as_mut().unwrap().panel_window_closed();
+                        },
+                    }
+                } else if !wants_panel_window {
+                    panel_window = None;
+                }
+                if let Some(panel) = &mut panel_window {
+                    let window = WinitWindow::new(&panel.window);
+                    let panel_input = &mut panel.input;
+                    panel_input.set_ui_scale(ui_scale);
+                    panel.renderer.draw(&window, |canvas, csh| {
+                        canvas.scale((ui_scale, ui_scale));
+                        app.as_mut().unwrap().process_panel_window(StateArgs {
+                            canvas,
+                            coordinate_system_helper: &csh,
+                            input: panel_input,
+                        });
+                    }).unwrap();
+                    redraw_at = panel.input.take_redraw_request();
+                    panel.input.finish_frame();
+                } else {
+                    redraw_at = None;
+                }
+                if let Some(at) = input.take_redraw_request() {
+                    redraw_at = earliest(redraw_at, at);
+                }
+                // the UI is immediate mode, so whatever the input changed only shows up in the frame after it
+                if had_input {
+                    had_input = false;
+                    redraw_at = earliest(redraw_at, now);
+                }
+                *control_flow = match redraw_at {
+                    Some(redraw_at) => ControlFlow::WaitUntil(redraw_at.max(now + frame_time.unwrap_or_default())),
+                    None => ControlFlow::Wait,
+                };
             },
 
+            Event::RedrawRequested(_) => redraw_at = earliest(redraw_at, Instant::now()),
+
+            // sent through the input's waker, eg. when a packet comes in
+            Event::UserEvent(()) => redraw_at = earliest(redraw_at, Instant::now()),
+
             _ => (),
 
         }
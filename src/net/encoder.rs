@@ -0,0 +1,90 @@
+// background encoding of chunks that are about to be sent over the network.
+// encoding a chunk to PNG takes a few milliseconds, which adds up to a noticeable hitch when a lot of them have to be
+// sent at once, eg. when someone joins a room with a big canvas. instead of encoding chunks on the main thread, they're
+// handed off to a small pool of worker threads, and the finished ones are collected once per frame.
+
+use std::net::SocketAddr;
+
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::paint_canvas::PaintCanvas;
+
+struct Job {
+    to: SocketAddr,
+    position: (i32, i32),
+    pixels: Vec<u8>,
+    attempt: u32,
+}
+
+pub struct Encoded {
+    pub to: SocketAddr,
+    pub position: (i32, i32),
+    // the pixels the PNG data was encoded from. the chunk may have changed while it was being encoded, in which case
+    // the PNG data is outdated and the chunk has to be encoded again
+    pub pixels: Vec<u8>,
+    // None if encoding failed
+    pub png_data: Option<Vec<u8>>,
+    // how many times the chunk was encoded before this, because it changed while it was being encoded
+    pub attempt: u32,
+}
+
+pub struct ChunkEncoder {
+    jobs: Sender<Job>,
+    finished: Receiver<Encoded>,
+}
+
+impl ChunkEncoder {
+
+    // a couple of threads is enough to keep up with uploads, while leaving the rest of the CPU to rendering
+    const WORKER_COUNT: usize = 2;
+    // how many times a chunk that keeps changing is encoded in the background, before it's encoded on the main thread
+    pub const MAX_ATTEMPTS: u32 = 3;
+
+    pub fn new() -> Self {
+        let (jobs, worker_jobs) = crossbeam_channel::unbounded::<Job>();
+        let (worker_finished, finished) = crossbeam_channel::unbounded();
+        for i in 0..Self::WORKER_COUNT {
+            let jobs = worker_jobs.clone();
+            let finished = worker_finished.clone();
+            let _ = std::thread::Builder::new().name(format!("chunk encoder {}", i)).spawn(move || {
+                // the loop ends once the encoder is dropped, as that disconnects the job channel
+                for Job { to, position, pixels, attempt } in jobs {
+                    let png_data = PaintCanvas::encode_chunk_pixels(&pixels);
+                    if finished.send(Encoded { to, position, pixels, png_data, attempt }).is_err() {
+                        break
+                    }
+                }
+            });
+        }
+        Self {
+            jobs,
+            finished,
+        }
+    }
+
+    // queues the chunk's pixels up for encoding. the result is returned by finished() once it's ready
+    pub fn encode(&self, to: SocketAddr, position: (i32, i32), pixels: Vec<u8>) {
+        self.queue(Job { to, position, pixels, attempt: 0 });
+    }
+
+    // queues a chunk that changed while it was being encoded up for encoding again, with its current pixels
+    pub fn encode_again(&self, encoded: &Encoded, pixels: Vec<u8>) {
+        self.queue(Job {
+            to: encoded.to,
+            position: encoded.position,
+            pixels,
+            attempt: encoded.attempt + 1,
+        });
+    }
+
+    fn queue(&self, job: Job) {
+        // the workers only stop once the encoder is dropped, so this can't really fail
+        let _ = self.jobs.send(job);
+    }
+
+    // returns the chunks that finished encoding since the last call
+    pub fn finished(&self) -> Vec<Encoded> {
+        self.finished.try_iter().collect()
+    }
+
+}
@@ -1,6 +1,13 @@
+pub mod encoder;
 mod peer;
+pub mod room_list;
+pub mod room_storage;
 pub mod socket;
 pub mod timer;
+pub mod transfer;
+
+#[cfg(test)]
+mod tests;
 
 pub use peer::*;
 pub use timer::*;
@@ -1,16 +1,24 @@
 // use std::net::{SocketAddr, ToSocketAddrs, TcpStream};
 // use std::thread;
 
-use std::collections::HashMap;
-use std::net::{SocketAddr};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
 
-use skulpin::skia_safe::{Color, Color4f, Point};
+use serde::Serialize;
+use skulpin::skia_safe::{Color, Color4f, IRect, Point, Rect};
 use thiserror::Error;
 
-use crate::net::socket::{Remote, Error as NetError};
-use crate::paint_canvas::{Brush, StrokePoint};
+use crate::invite_link;
+use crate::net::socket::{Priority, Remote, SimulatedConditions, TrafficTotals, Waker, Error as NetError};
+use crate::net::transfer::ChunkDownload;
+use crate::paint_canvas::{Brush, PaintCanvas, StrokePoint};
+use crate::replay::Recorder;
+use netcanv_protocol::build;
 use netcanv_protocol::client as cl;
 use netcanv_protocol::matchmaker as mm;
+use netcanv_protocol::version::features;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -18,6 +26,14 @@ pub enum Error {
     Net(#[from] NetError),
     #[error("Data error: {0}")]
     Data(#[from] bincode::Error),
+    #[error("Could not decode image: {0}")]
+    Image(#[from] ::image::ImageError),
+    #[error("{0}")]
+    Matchmaker(String),
+    #[error("This matchmaker only supports numeric room IDs")]
+    NumericRoomIdsOnly,
+    #[error("{0}")]
+    Build(#[from] build::Error),
 }
 
 #[derive(Debug)]
@@ -44,22 +60,122 @@ pub enum Message {
     Joined(String),
 
     // someone has left
-    Left(String),
+    Left(SocketAddr, String),
 
     // a new mate has arrived in the room and needs canvas data
     NewMate(SocketAddr),
 
-    // stroke packet received
-    Stroke(Vec<StrokePoint>),
+    // stroke packet received, along with its sender
+    Stroke(SocketAddr, Vec<StrokePoint>),
 
     // canvas data packet received
-    CanvasData((i32, i32), Vec<u8>)
+    CanvasData((i32, i32), Vec<u8>),
+
+    // the tiles of a chunk that changed, as PNG data, along with who changed them
+    ChunkTiles(SocketAddr, (i32, i32), Vec<(u8, Vec<u8>)>),
+
+    // tool packet received. the string is the name of the tool
+    Tool(SocketAddr, String, Vec<u8>),
+
+    // the host changed the canvas's background
+    Background(Option<u32>),
+
+    // someone who joined doesn't have some of our tools, or has different versions of them, so they won't see what
+    // we do with them. the strings are the person's nickname and the names of the tools
+    MissingTools(String, Vec<String>),
+
+    // someone used a tool we don't have. their packets for it are dropped. only sent the first time they use it
+    UnsupportedTool(String, String),
+
+    // our nickname was already taken by someone in the room, so the host gave us this one instead
+    NicknameTaken(String),
+
+    //
+    // persistent rooms
+    //
+
+    // the matchmaker started or stopped keeping the room's canvas. carries the number of days it's kept for after the
+    // room closes, or None if it isn't kept anymore
+    Persisted(Option<u32>),
+
+    // a chunk of the stored canvas of a room we reopened, as PNG data
+    StoredChunk((i32, i32), Vec<u8>),
 }
 
 pub struct Mate {
     pub cursor: Point,
     pub nickname: String,
     pub brush_size: f32,
+    // None until the mate sends their viewport for the first time
+    pub viewport: Option<Rect>,
+    // when the mate last changed something on the canvas. None if they haven't yet
+    pub last_activity: Option<Instant>,
+    pub idle: bool,
+    // the region of the canvas the mate locked, and when the lock expires
+    lock: Option<(Rect, Instant)>,
+    // the tools the mate has, along with their versions. None if their NetCanv doesn't say
+    pub tools: Option<HashMap<String, u16>>,
+    // the tools the mate used that we don't have, so that we only complain once about each of them
+    unsupported_tools: HashSet<String>,
+}
+
+// what a mate is up to, as shown in the people list
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    Drawing,
+    Watching,
+    Idle,
+}
+
+impl Mate {
+
+    // how long after the last change to the canvas a mate is still considered to be drawing
+    const DRAWING_TIMEOUT: Duration = Duration::from_secs(10);
+    // how long a region stays locked. locks received from others that last longer than this are cut short
+    pub const LOCK_DURATION: Duration = Duration::from_secs(60);
+    // the largest width or height of a locked region
+    pub const MAX_LOCK_SIZE: i32 = 16384;
+
+    pub fn activity(&self) -> Activity {
+        match self.last_activity {
+            _ if self.idle => Activity::Idle,
+            Some(time) if time.elapsed() < Self::DRAWING_TIMEOUT => Activity::Drawing,
+            _ => Activity::Watching,
+        }
+    }
+
+    // the region of the canvas the mate locked, if the lock hasn't expired yet
+    pub fn locked_region(&self) -> Option<Rect> {
+        self.lock.filter(|&(_, expires)| Instant::now() < expires).map(|(region, _)| region)
+    }
+
+}
+
+// whether the region is small enough to be locked, and somewhere on the canvas that can be painted at
+pub fn is_valid_lock(region: Rect) -> bool {
+    let max_size = Mate::MAX_LOCK_SIZE as f32;
+    region.width() > 0.0
+        && region.height() > 0.0
+        && region.width() <= max_size
+        && region.height() <= max_size
+        && PaintCanvas::is_in_bounds(Point::new(region.left, region.top))
+        && PaintCanvas::is_in_bounds(Point::new(region.right, region.bottom))
+}
+
+// the colors mates are told apart by. they're bright enough for white text to be readable on them
+const MATE_COLORS: [u32; 8] = [
+    0xffe53935, 0xff8e24aa, 0xff3949ab, 0xff039be5, 0xff00897b, 0xff7cb342, 0xfff4511e, 0xff6d4c41,
+];
+
+// the color the mate at the given address is shown in. everyone in the room sees the same address for each mate, so
+// they're all shown in the same color for everyone, for as long as they stay in the room
+pub fn mate_color(addr: SocketAddr) -> Color {
+    // FNV-1a, as the standard library's hasher isn't guaranteed to give the same results across Rust versions
+    let hash = addr
+        .to_string()
+        .bytes()
+        .fold(0x811c9dc5_u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193));
+    Color::new(MATE_COLORS[hash as usize % MATE_COLORS.len()])
 }
 
 pub struct Peer {
@@ -68,12 +184,40 @@ pub struct Peer {
     is_host: bool,
     is_relayed: bool,
     nickname: String,
-    room_id: Option<u32>,
+    matchmaker_addr: String,
+    room_id: Option<String>,
+    // the host's address. this is None if we're the host
+    host_addr: Option<SocketAddr>,
     mates: HashMap<SocketAddr, Mate>,
+    // addresses banned by the host. only used when we're the host
+    banned: HashSet<IpAddr>,
+    download: Option<ChunkDownload>,
+    // records the session for replaying it later, if enabled
+    recorder: Option<Recorder>,
+    // the ping sent to the matchmaker that hasn't been answered yet, along with when it was sent
+    ping: Option<(u32, Instant)>,
+    next_ping_id: u32,
+    round_trip_time: Option<Duration>,
+    // how long to wait before pinging a quiet matchmaker, and before giving up on it. None if lost connections
+    // aren't detected
+    keepalive: Option<(Duration, Duration)>,
+    // when the last packet was received from the matchmaker
+    last_received: Instant,
+    connection_lost: bool,
+    // the tools we have, as sent to everyone who joins
+    tools: Vec<(String, u16)>,
+    // the number of days the matchmaker keeps the room's canvas for after the room closes. None if it isn't kept
+    persistence: Option<u32>,
+    // whether packets from the matchmaker wake the event loop up, see set_waker
+    has_waker: bool,
+    // whether a packet that changes the canvas was refused since the last take_paint_refused() call
+    paint_refused: Cell<bool>,
 }
 
 pub struct Messages<'a> {
     peer: &'a mut Peer,
+    // whether the connection was found to be lost during this tick, which is reported before any other messages
+    connection_lost: bool,
 }
 
 macro_rules! try_or_message {
@@ -90,9 +234,15 @@ macro_rules! try_or_message {
 
 impl Peer {
 
-    pub fn host(nickname: &str, matchmaker_addr: &str) -> Result<Self, Error> {
+    // hosts a new room. if the room has a public name, it's shown in the matchmaker's list of public rooms
+    pub fn host(
+        nickname: &str,
+        matchmaker_addr: &str,
+        password: Option<&str>,
+        public_name: Option<&str>,
+    ) -> Result<Self, Error> {
         let mm = Remote::new(matchmaker_addr)?;
-        mm.send(mm::Packet::Host)?;
+        mm.send(mm::Packet::host(password, public_name)?)?;
 
         Ok(Self {
             matchmaker: Some(mm),
@@ -100,14 +250,34 @@ impl Peer {
             is_host: true,
             is_relayed: false,
             nickname: nickname.into(),
+            matchmaker_addr: matchmaker_addr.into(),
             room_id: None,
+            host_addr: None,
             mates: HashMap::new(),
+            banned: HashSet::new(),
+            download: None,
+            recorder: None,
+            ping: None,
+            next_ping_id: 0,
+            round_trip_time: None,
+            keepalive: None,
+            last_received: Instant::now(),
+            connection_lost: false,
+            tools: Vec::new(),
+            persistence: None,
+            has_waker: false,
+            paint_refused: Cell::new(false),
         })
     }
 
-    pub fn join(nickname: &str, matchmaker_addr: &str, room_id: u32) -> Result<Self, Error> {
+    pub fn join(nickname: &str, matchmaker_addr: &str, room_id: &str, password: Option<&str>) -> Result<Self, Error> {
         let mm = Remote::new(matchmaker_addr)?;
-        mm.send(mm::Packet::GetHost(room_id))?;
+        if mm.supports(features::STRING_ROOM_IDS) {
+            mm.send(mm::Packet::join(room_id, password)?)?;
+        } else {
+            let room_id = room_id.parse().map_err(|_| Error::NumericRoomIdsOnly)?;
+            mm.send(mm::Packet::GetHost(room_id, password.map(String::from)))?;
+        }
 
         Ok(Self {
             matchmaker: Some(mm),
@@ -115,8 +285,23 @@ impl Peer {
             is_host: false,
             is_relayed: false,
             nickname: nickname.into(),
-            room_id: None,
+            matchmaker_addr: matchmaker_addr.into(),
+            room_id: Some(room_id.into()),
+            host_addr: None,
             mates: HashMap::new(),
+            banned: HashSet::new(),
+            download: None,
+            recorder: None,
+            ping: None,
+            next_ping_id: 0,
+            round_trip_time: None,
+            keepalive: None,
+            last_received: Instant::now(),
+            connection_lost: false,
+            tools: Vec::new(),
+            persistence: None,
+            has_waker: false,
+            paint_refused: Cell::new(false),
         })
     }
 
@@ -130,12 +315,56 @@ impl Peer {
         Ok(())
     }
 
+    fn send_via(
+        mm: &Remote<mm::Packet>,
+        to: Option<SocketAddr>,
+        packet: cl::Packet,
+        priority: Priority,
+    ) -> Result<(), Error> {
+        mm.send_with_priority(mm::Packet::relay(to, &packet)?, priority)?;
+        Ok(())
+    }
+
+    // the priority of a packet that isn't canvas data for a newcomer, see send_canvas_data
+    fn priority(packet: &cl::Packet) -> Priority {
+        match packet {
+            cl::Packet::Cursor(..) |
+            cl::Packet::Viewport(..) |
+            cl::Packet::Stroke(_) |
+            cl::Packet::CanvasData(..) |
+            cl::Packet::ChunkTiles(..) |
+            cl::Packet::Tool(..) |
+            cl::Packet::Background(_) => Priority::Paint,
+            _ => Priority::Control,
+        }
+    }
+
     fn send(&self, to: Option<SocketAddr>, packet: cl::Packet) -> Result<(), Error> {
+        let priority = Self::priority(&packet);
+        self.send_with_priority(to, packet, priority)
+    }
+
+    fn send_with_priority(&self, to: Option<SocketAddr>, packet: cl::Packet, priority: Priority) -> Result<(), Error> {
+        // only packets sent to everyone end up on everyone's canvas. the ones sent to a single person are canvas data
+        // for newcomers, which is already on ours
+        let recorded = match (to, &self.recorder) {
+            (None, Some(_)) => Some(packet.clone()),
+            _ => None,
+        };
         // TODO: no matchmaker relay
-        self.matchmaker
-            .as_ref()
-            .unwrap()
-            .send(mm::Packet::Relay(to, bincode::serialize(&packet)?))?;
+        match Self::send_via(self.matchmaker.as_ref().unwrap(), to, packet, priority) {
+            // the others' canvases are brought back in sync with ours by whoever owns the canvas, see
+            // take_paint_refused
+            Err(Error::Net(NetError::QueueFull)) if priority == Priority::Paint => {
+                self.paint_refused.set(true);
+                return Ok(())
+            },
+            result => result?,
+        }
+        // packets that didn't fit into the send queue never reached anyone's canvas, so they're only recorded now
+        if let (Some(packet), Some(recorder)) = (recorded, &self.recorder) {
+            recorder.record(None, &packet);
+        }
         Ok(())
     }
 
@@ -144,21 +373,76 @@ impl Peer {
             nickname,
             cursor: Point::new(0.0, 0.0),
             brush_size: 4.0,
+            viewport: None,
+            last_activity: None,
+            idle: false,
+            lock: None,
+            tools: None,
+            unsupported_tools: HashSet::new(),
         });
     }
 
+    // the nickname, with a number appended to it if it's already taken by us or someone else in the room
+    fn unique_nickname(&self, nickname: &str) -> String {
+        let taken = |name: &str| name == self.nickname || self.mates.values().any(|mate| mate.nickname == name);
+        if !taken(nickname) {
+            return nickname.into()
+        }
+        (2..).map(|n| format!("{} {}", nickname, n)).find(|name| !taken(name)).unwrap()
+    }
+
+    // whether we can read the mate's packets for the given tool. if the mate didn't tell us which tools they have,
+    // it's assumed they're the same as ours
+    fn can_receive_tool(&self, addr: SocketAddr, name: &str) -> bool {
+        let ours = match self.tools.iter().find(|(tool, _)| tool == name) {
+            Some((_, version)) => version,
+            None => return false,
+        };
+        match self.mates.get(&addr).and_then(|mate| mate.tools.as_ref()) {
+            Some(theirs) => theirs.get(name) == Some(ours),
+            None => true,
+        }
+    }
+
+    // notes down that the mate has just changed something on the canvas
+    fn mate_active(&mut self, addr: SocketAddr) {
+        if let Some(mate) = self.mates.get_mut(&addr) {
+            mate.last_activity = Some(Instant::now());
+        }
+    }
+
     fn decode_payload(&mut self, sender_addr: SocketAddr, payload: &[u8]) -> Option<Message> {
-        let packet = try_or_message!(bincode::deserialize::<cl::Packet>(payload), "Invalid packet received: {}");
+        let packet = try_or_message!(build::decode::<cl::Packet>(payload), "Invalid packet received: {}");
+        if let Some(recorder) = &self.recorder {
+            recorder.record(Some(sender_addr), &packet);
+        }
 
         match packet {
             cl::Packet::Hello(nickname) => {
-                eprintln!("{} ({}) joined", nickname, sender_addr);
+                if self.is_banned(sender_addr) {
+                    log::info!("{} ({}) is banned, refusing", nickname, sender_addr);
+                    return None
+                }
+                log::info!("{} ({}) joined", nickname, sender_addr);
                 try_or_message!(self.send(Some(sender_addr), cl::Packet::HiThere(self.nickname.clone())));
+                try_or_message!(self.send(Some(sender_addr), cl::Packet::Tools(self.tools.clone())));
+                // the host makes sure nobody shares a nickname, so that people can be told apart
+                let nickname = if self.is_host {
+                    let unique = self.unique_nickname(&nickname);
+                    if unique != nickname {
+                        log::info!("{} is taken, renaming {} to {}", nickname, sender_addr, unique);
+                        try_or_message!(self.send(Some(sender_addr), cl::Packet::NicknameTaken(unique.clone())));
+                        try_or_message!(self.send(None, cl::Packet::Renamed(sender_addr, unique.clone())));
+                    }
+                    unique
+                } else {
+                    nickname
+                };
                 self.add_mate(sender_addr, nickname.clone());
                 return Some(Message::Joined(nickname))
             },
             cl::Packet::HiThere(nickname) => {
-                eprintln!("{} ({}) is in the room", nickname, sender_addr);
+                log::info!("{} ({}) is in the room", nickname, sender_addr);
                 self.add_mate(sender_addr, nickname);
             },
             cl::Packet::Cursor(x, y, brush_size) => {
@@ -166,28 +450,120 @@ impl Peer {
                     mate.cursor = Point::new(cl::from_fixed29p3(x), cl::from_fixed29p3(y));
                     mate.brush_size = cl::from_fixed15p1(brush_size);
                 } else {
-                    eprintln!("{} sus", sender_addr);
+                    log::warn!("{} sus", sender_addr);
+                }
+            },
+            cl::Packet::Viewport(left, top, right, bottom) => {
+                if let Some(mate) = self.mates.get_mut(&sender_addr) {
+                    mate.viewport = Some(Rect::new(left as f32, top as f32, right as f32, bottom as f32));
                 }
             },
             cl::Packet::Stroke(points) => {
-                return Some(Message::Stroke(points.into_iter().map(|p| {
-                    StrokePoint {
-                        point: Point::new(cl::from_fixed29p3(p.x), cl::from_fixed29p3(p.y)),
-                        brush:
-                            if p.color == 0 {
-                                Brush::Erase { stroke_width: cl::from_fixed15p1(p.brush_size) }
-                            } else {
-                                Brush::Draw {
-                                    color: Color4f::from(Color::new(p.color)),
-                                    stroke_width: cl::from_fixed15p1(p.brush_size)
-                                }
-                            }
+                self.mate_active(sender_addr);
+                return Some(Message::Stroke(sender_addr, decode_stroke(points)))
+            },
+            cl::Packet::NicknameTaken(nickname) => {
+                if Some(sender_addr) == self.host_addr {
+                    self.nickname = nickname.clone();
+                    return Some(Message::NicknameTaken(nickname))
+                } else {
+                    log::warn!("{} tried to rename us but isn't the host", sender_addr);
+                }
+            },
+            cl::Packet::Renamed(addr, nickname) => {
+                if Some(sender_addr) == self.host_addr {
+                    // we don't know about ourselves, so this does nothing if we're the one being renamed
+                    if let Some(mate) = self.mates.get_mut(&addr) {
+                        mate.nickname = nickname;
                     }
-                }).collect()));
+                } else {
+                    log::warn!("{} tried to rename {} but isn't the host", sender_addr, addr);
+                }
+            },
+            cl::Packet::Kick(banned) => {
+                if Some(sender_addr) == self.host_addr {
+                    return Some(Message::Error(
+                        if banned { "You have been banned from the room".into() }
+                        else { "You have been kicked from the room".into() }
+                    ))
+                } else {
+                    log::warn!("{} tried to kick us but isn't the host", sender_addr);
+                }
+            },
+            // the canvas is only ever sent by the host. anyone else could start a bogus download, or overwrite
+            // whole chunks of everyone's canvas
+            cl::Packet::ChunkCount(_) | cl::Packet::CanvasData(..) if Some(sender_addr) != self.host_addr => {
+                log::warn!("{} sent canvas data but isn't the host", sender_addr);
+            },
+            cl::Packet::ChunkCount(count) => {
+                if count > 0 {
+                    self.download = Some(ChunkDownload::new(count));
+                }
             },
             cl::Packet::CanvasData(chunk, png_image) => {
+                if let Some(download) = &mut self.download {
+                    download.chunk_received();
+                    if download.is_finished() {
+                        self.download = None;
+                    }
+                }
                 return Some(Message::CanvasData(chunk, png_image));
             },
+            cl::Packet::ChunkTiles(chunk, tiles) => {
+                self.mate_active(sender_addr);
+                return Some(Message::ChunkTiles(sender_addr, chunk, tiles))
+            },
+            cl::Packet::Tool(name, payload) => {
+                self.mate_active(sender_addr);
+                if self.can_receive_tool(sender_addr, &name) {
+                    return Some(Message::Tool(sender_addr, name, payload))
+                }
+                if let Some(mate) = self.mates.get_mut(&sender_addr) {
+                    if mate.unsupported_tools.insert(name.clone()) {
+                        return Some(Message::UnsupportedTool(mate.nickname.clone(), name))
+                    }
+                }
+            },
+            cl::Packet::Idle(idle) => {
+                if let Some(mate) = self.mates.get_mut(&sender_addr) {
+                    mate.idle = idle;
+                }
+            },
+            cl::Packet::Lock(region, expires_in) => {
+                if let Some(mate) = self.mates.get_mut(&sender_addr) {
+                    let expires = Instant::now() + Duration::from_millis(expires_in as u64).min(Mate::LOCK_DURATION);
+                    let region = region.map(|(left, top, right, bottom)| {
+                        Rect::new(left as f32, top as f32, right as f32, bottom as f32)
+                    });
+                    match region {
+                        Some(region) if !is_valid_lock(region) => {
+                            log::warn!("{} tried to lock an invalid region {:?}", sender_addr, region);
+                        },
+                        _ => mate.lock = region.map(|region| (region, expires)),
+                    }
+                }
+            },
+            cl::Packet::Background(color) => {
+                if Some(sender_addr) == self.host_addr {
+                    return Some(Message::Background(color))
+                } else {
+                    log::warn!("{} tried to change the background but isn't the host", sender_addr);
+                }
+            },
+            cl::Packet::Tools(tools) => {
+                if let Some(mate) = self.mates.get_mut(&sender_addr) {
+                    let theirs: HashMap<_, _> = tools.into_iter().collect();
+                    let missing: Vec<_> = self.tools
+                        .iter()
+                        .filter(|(name, version)| theirs.get(name) != Some(version))
+                        .map(|(name, _)| name.clone())
+                        .collect();
+                    mate.tools = Some(theirs);
+                    if !missing.is_empty() {
+                        return Some(Message::MissingTools(mate.nickname.clone(), missing))
+                    }
+                }
+            },
         }
 
         None
@@ -205,14 +581,22 @@ impl Peer {
         if let Some(mm) = &self.matchmaker {
             // give me back my if-let-chaining
             if let Some(packet) = &mm.try_recv() {
+                self.last_received = Instant::now();
                 match packet {
                     mm::Packet::RoomId(id) => {
-                        self.room_id = Some(*id);
+                        self.room_id = Some(format!("{:04}", id));
+                        try_or_message!(mm.send(mm::Packet::RequestRelay(None)));
+                        then = Then::SayHello;
+                        message = Some(Message::Connected);
+                    },
+                    mm::Packet::StringRoomId(id) => {
+                        self.room_id = Some(id.clone());
                         try_or_message!(mm.send(mm::Packet::RequestRelay(None)));
                         then = Then::SayHello;
                         message = Some(Message::Connected);
                     },
                     mm::Packet::HostAddress(addr) => {
+                        self.host_addr = Some(*addr);
                         message = Some(
                             Self::connect_to_host(mm, *addr, &mut self.is_relayed)
                                 .err()
@@ -225,14 +609,56 @@ impl Peer {
                             then = Then::SayHello;
                         }
                     },
-                    mm::Packet::ClientAddress(addr) => return Some(Message::NewMate(*addr)),
+                    mm::Packet::ClientAddress(addr) => {
+                        if self.is_banned(*addr) {
+                            try_or_message!(Self::send_via(mm, Some(*addr), cl::Packet::Kick(true), Priority::Control));
+                            return None
+                        }
+                        return Some(Message::NewMate(*addr))
+                    },
                     mm::Packet::Relayed(from, payload) => then = Then::ReadRelayed(*from, payload.to_vec()),
                     mm::Packet::Disconnected(addr) => {
                         if let Some(mate) = self.mates.remove(&addr) {
-                            return Some(Message::Left(mate.nickname))
+                            return Some(Message::Left(*addr, mate.nickname))
                         }
                     },
+                    mm::Packet::RoomClosed(reason) => return Some(Message::Error(match reason {
+                        mm::CloseReason::Inactivity => "The room was closed due to inactivity".into(),
+                    })),
                     mm::Packet::Error(message) => return Some(Message::Error(message.into())),
+                    // the room we tried to join was closed, but its canvas was kept, so we reopen it as its host
+                    mm::Packet::Reopened(id) => {
+                        log::info!("reopening room {}", id);
+                        self.is_host = true;
+                        self.host_addr = None;
+                        self.room_id = Some(id.clone());
+                        try_or_message!(mm.send(mm::Packet::RequestRelay(None)));
+                        then = Then::SayHello;
+                        message = Some(Message::Connected);
+                    },
+                    mm::Packet::RoomPersisted(days) => {
+                        self.persistence = *days;
+                        return Some(Message::Persisted(*days))
+                    },
+                    mm::Packet::StoredChunk(chunk, png_data) =>
+                        return Some(Message::StoredChunk(*chunk, png_data.clone())),
+                    mm::Packet::ShuttingDown(restart_delay) => return Some(Message::Error(match restart_delay {
+                        Some(seconds) => format!(
+                            "The matchmaker is restarting. Try connecting again in {} seconds",
+                            seconds,
+                        ),
+                        None => "The matchmaker has shut down".into(),
+                    })),
+                    // the matchmaker checking whether we're still there
+                    mm::Packet::Ping(id) => try_or_message!(mm.send(mm::Packet::Pong(*id))),
+                    mm::Packet::Pong(id) => {
+                        if let Some((ping_id, sent)) = self.ping {
+                            if *id == ping_id {
+                                self.round_trip_time = Some(sent.elapsed());
+                                self.ping = None;
+                            }
+                        }
+                    },
                     _ => return None,
                 }
             }
@@ -241,7 +667,10 @@ impl Peer {
         match then {
             Then::Continue => (),
             Then::ReadRelayed(sender, payload) => return self.decode_payload(sender, &payload),
-            Then::SayHello => try_or_message!(self.send(None, cl::Packet::Hello(self.nickname.clone()))),
+            Then::SayHello => {
+                try_or_message!(self.send(None, cl::Packet::Hello(self.nickname.clone())));
+                try_or_message!(self.send(None, cl::Packet::Tools(self.tools.clone())));
+            },
         }
 
         message
@@ -251,11 +680,51 @@ impl Peer {
         if let Some(mm) = &self.matchmaker {
             let _ = mm.tick()?;
         }
+        let connection_lost = self.keep_alive()?;
         Ok(Messages {
             peer: self,
+            connection_lost,
         })
     }
 
+    // pings the matchmaker if it's been quiet for a while. returns true once it's been quiet for so long that the
+    // connection is considered lost, which happens eg. when the computer wakes up from sleep
+    fn keep_alive(&mut self) -> Result<bool, Error> {
+        let (interval, timeout) = match self.keepalive {
+            Some(keepalive) if !self.connection_lost => keepalive,
+            _ => return Ok(false),
+        };
+        // matchmakers too old to answer pings can't be told apart from dead ones, as a room can be quiet for ages
+        if !self.matchmaker.as_ref().is_some_and(|mm| mm.supports(features::PING)) {
+            return Ok(false)
+        }
+        // packets that came in while the frame was stalled (eg. by a file dialog or a debugger) haven't been read yet,
+        // but they still show that the connection is alive
+        if self.matchmaker.as_ref().is_some_and(|mm| mm.has_received()) {
+            self.last_received = Instant::now();
+        }
+        let quiet_for = self.last_received.elapsed();
+        if quiet_for >= timeout {
+            self.connection_lost = true;
+            return Ok(true)
+        }
+        if quiet_for >= interval {
+            self.ping()?;
+        }
+        Ok(false)
+    }
+
+    // sets the tools we tell others we have, as (name, version) pairs
+    pub fn set_tools(&mut self, tools: Vec<(String, u16)>) {
+        self.tools = tools;
+    }
+
+    // turns on detecting lost connections, see ConnectionConfig
+    pub fn set_keepalive(&mut self, keepalive: Option<(Duration, Duration)>) {
+        self.keepalive = keepalive;
+        self.last_received = Instant::now();
+    }
+
     pub fn send_cursor(&self, cursor: Point, brush_size: f32) -> Result<(), Error> {
         self.send(None, cl::Packet::Cursor(
             cl::to_fixed29p3(cursor.x),
@@ -264,52 +733,255 @@ impl Peer {
         ))
     }
 
+    pub fn send_viewport(&self, viewport: Rect) -> Result<(), Error> {
+        let viewport = viewport.round();
+        self.send(None, cl::Packet::Viewport(viewport.left, viewport.top, viewport.right, viewport.bottom))
+    }
+
     pub fn send_stroke(&self, iterator: impl Iterator<Item = StrokePoint>) -> Result<(), Error> {
-        self.send(None, cl::Packet::Stroke(iterator.map(|p| {
-            cl::StrokePoint {
-                x: cl::to_fixed29p3(p.point.x),
-                y: cl::to_fixed29p3(p.point.y),
-                color: match p.brush {
-                    Brush::Draw { ref color, .. } => {
-                        let color = color.to_color();
-                        ((color.a() as u32) << 24) |
-                        ((color.r() as u32) << 16) |
-                        ((color.g() as u32) << 8) |
-                        color.b() as u32
-                    },
-                    Brush::Erase { .. } => 0,
-                },
-                brush_size: cl::to_fixed15p1(match p.brush {
-                    Brush::Draw { stroke_width, .. } | Brush::Erase { stroke_width } => stroke_width,
-                }),
-            }
-        }).collect()))
+        self.send(None, cl::Packet::Stroke(iterator.map(|p| encode_stroke_point(&p)).collect()))
+    }
+
+    pub fn send_chunk_count(&self, to: SocketAddr, count: u32) -> Result<(), Error> {
+        self.send(Some(to), cl::Packet::ChunkCount(count))
+    }
+
+    // sends canvas data to someone who has just joined. returns false if the connection is backed up and the chunk
+    // should be sent again later
+    pub fn send_canvas_data(&self, to: SocketAddr, chunk: (i32, i32), png_data: Vec<u8>) -> Result<bool, Error> {
+        match self.send_with_priority(Some(to), cl::Packet::CanvasData(chunk, png_data), Priority::Bulk) {
+            Ok(()) => Ok(true),
+            Err(Error::Net(NetError::QueueFull)) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    // whether there's room in the send queue for more canvas data for newcomers
+    pub fn can_send_canvas_data(&self) -> bool {
+        self.matchmaker.as_ref().is_some_and(|mm| Priority::Bulk.fits(mm.queued()))
+    }
+
+    // sends new canvas data for a chunk to everyone in the room
+    pub fn send_chunk_update(&self, chunk: (i32, i32), png_data: Vec<u8>) -> Result<(), Error> {
+        self.send(None, cl::Packet::CanvasData(chunk, png_data))
+    }
+
+    // sends the tiles of a chunk that changed to everyone in the room
+    pub fn send_chunk_tiles(&self, chunk: (i32, i32), tiles: Vec<(u8, Vec<u8>)>) -> Result<(), Error> {
+        self.send(None, cl::Packet::ChunkTiles(chunk, tiles))
+    }
+
+    // sends a tool packet to everyone in the room
+    pub fn send_tool(&self, name: &str, payload: &impl Serialize) -> Result<(), Error> {
+        self.send(None, cl::Packet::tool(name, payload)?)
+    }
+
+    // sends a tool packet to one person in the room
+    pub fn send_tool_to(&self, to: SocketAddr, name: &str, payload: &impl Serialize) -> Result<(), Error> {
+        self.send(Some(to), cl::Packet::tool(name, payload)?)
+    }
+
+    // lets everyone know whether we're idle
+    pub fn send_idle(&self, idle: bool) -> Result<(), Error> {
+        self.send(None, cl::Packet::Idle(idle))
+    }
+
+    // the regions of the canvas mates locked, along with who locked them, in the form PaintCanvas::set_locks takes
+    pub fn locks(&self) -> Vec<(Option<SocketAddr>, Rect)> {
+        self.mates
+            .iter()
+            .filter_map(|(&addr, mate)| Some((Some(addr), mate.locked_region()?)))
+            .collect()
+    }
+
+    // lets everyone know which region of the canvas we locked, if any, and when the lock expires
+    pub fn send_lock(&self, region: Option<IRect>, expires_in: Duration) -> Result<(), Error> {
+        let region = region.map(|region| (region.left, region.top, region.right, region.bottom));
+        self.send(None, cl::Packet::Lock(region, expires_in.as_millis() as u32))
+    }
+
+    // sends the canvas's background to someone who has just joined, or to everyone if `to` is None
+    pub fn send_background(&self, to: Option<SocketAddr>, background: Option<u32>) -> Result<(), Error> {
+        self.send(to, cl::Packet::Background(background))
+    }
+
+    // sends a ping to the matchmaker, unless the last one hasn't been answered yet or the matchmaker is too old to
+    // answer it
+    pub fn ping(&mut self) -> Result<(), Error> {
+        if self.ping.is_some() { return Ok(()) }
+        if let Some(mm) = self.matchmaker.as_ref().filter(|mm| mm.supports(features::PING)) {
+            mm.send(mm::Packet::Ping(self.next_ping_id))?;
+            self.ping = Some((self.next_ping_id, Instant::now()));
+            self.next_ping_id = self.next_ping_id.wrapping_add(1);
+        }
+        Ok(())
+    }
+
+    // whether we can ask the matchmaker to keep the room's canvas. only the host can, and only if the matchmaker has
+    // somewhere to keep it
+    pub fn can_persist(&self) -> bool {
+        self.is_host && self.matchmaker.as_ref().is_some_and(|mm| mm.supports(features::PERSISTENT_ROOMS))
+    }
+
+    // asks the matchmaker to keep the room's canvas after the room closes, or to stop keeping it. the matchmaker
+    // answers with a Persisted message
+    pub fn persist(&self, persist: bool) -> Result<(), Error> {
+        self.matchmaker.as_ref().unwrap().send(mm::Packet::PersistRoom(persist))?;
+        Ok(())
+    }
+
+    // the number of days the matchmaker keeps the room's canvas for after the room closes, or None if it isn't kept
+    pub fn persistence(&self) -> Option<u32> {
+        self.persistence
+    }
+
+    // sends a chunk of a persistent room's canvas to the matchmaker to be stored. returns false if the connection is
+    // backed up and the chunk should be sent again later
+    pub fn store_chunk(&self, chunk: (i32, i32), png_data: Vec<u8>) -> Result<bool, Error> {
+        let mm = self.matchmaker.as_ref().unwrap();
+        match mm.send_with_priority(mm::Packet::StoreChunk(chunk, png_data), Priority::Bulk) {
+            Ok(()) => Ok(true),
+            Err(NetError::QueueFull) => Ok(false),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    // returns whether any packets that change the canvas were refused because the connection couldn't keep up, since
+    // the last time this was called. if so, the others' canvases are out of sync with ours
+    pub fn take_paint_refused(&self) -> bool {
+        self.paint_refused.replace(false)
     }
 
-    pub fn send_canvas_data(&self, to: SocketAddr, chunk: (i32, i32), png_data: Vec<u8>) -> Result<(), Error> {
-        self.send(Some(to), cl::Packet::CanvasData(chunk, png_data))
+    // the round trip time to the matchmaker, as measured by the last ping that was answered
+    pub fn round_trip_time(&self) -> Option<Duration> {
+        self.round_trip_time
+    }
+
+    // makes packets from the matchmaker wake the event loop up as soon as they come in, so that it doesn't have to
+    // poll the peer while nothing's happening. only the first waker is used
+    pub fn set_waker(&mut self, waker: &Waker) {
+        if self.has_waker { return }
+        if let Some(mm) = &self.matchmaker {
+            mm.set_waker(waker.clone());
+            self.has_waker = true;
+        }
+    }
+
+    // how much was sent to and received from the matchmaker, which relays everything that's sent to the room
+    pub fn traffic(&self) -> Option<TrafficTotals> {
+        self.matchmaker.as_ref().map(|mm| mm.traffic().totals())
+    }
+
+    // the network conditions being simulated, if any
+    pub fn simulated_conditions(&self) -> Option<SimulatedConditions> {
+        self.matchmaker.as_ref().map(|mm| mm.simulated_conditions()).filter(SimulatedConditions::is_active)
+    }
+
+    // makes someone leave the room, and bans their IP address from joining again if `ban` is true.
+    // bans only last until the room is closed
+    pub fn kick(&mut self, addr: SocketAddr, ban: bool) -> Result<(), Error> {
+        if ban {
+            self.banned.insert(addr.ip());
+        }
+        self.send(Some(addr), cl::Packet::Kick(ban))?;
+        // matchmakers that support it make sure the kick sticks, even if the person doesn't leave on their own
+        if let Some(mm) = self.matchmaker.as_ref().filter(|mm| mm.supports(features::KICK)) {
+            mm.send(mm::Packet::Kick(addr, ban))?;
+        }
+        Ok(())
+    }
+
+    fn is_banned(&self, addr: SocketAddr) -> bool {
+        self.is_host && self.banned.contains(&addr.ip())
+    }
+
+    // starts recording the session. see replay.rs
+    pub fn record_session(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    pub fn recorder(&self) -> Option<&Recorder> {
+        self.recorder.as_ref()
     }
 
     pub fn is_host(&self) -> bool {
         self.is_host
     }
 
-    // this will return None if we're not connected yet
-    pub fn room_id(&self) -> Option<u32> {
-        self.room_id
+    // this will return None if we're the host and aren't connected yet
+    pub fn room_id(&self) -> Option<&str> {
+        self.room_id.as_deref()
+    }
+
+    pub fn matchmaker_addr(&self) -> &str {
+        &self.matchmaker_addr
+    }
+
+    // returns the link other people can use to join the room
+    pub fn invite_link(&self) -> Option<String> {
+        self.room_id.as_ref().map(|room_id| invite_link::format(&self.matchmaker_addr, room_id))
     }
 
     pub fn mates(&self) -> &HashMap<SocketAddr, Mate> {
         &self.mates
     }
 
+    // returns the progress of downloading the canvas from the host, if a download is in progress
+    pub fn download(&self) -> Option<&ChunkDownload> {
+        self.download.as_ref()
+    }
+
 }
 
 impl Iterator for Messages<'_> {
     type Item = Message;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if std::mem::take(&mut self.connection_lost) {
+            return Some(Message::Error("Lost connection to the matchmaker".into()))
+        }
         self.peer.next_packet()
     }
 }
 
+// converts a stroke point to its network representation
+pub fn encode_stroke_point(p: &StrokePoint) -> cl::StrokePoint {
+    cl::StrokePoint {
+        x: cl::to_fixed29p3(p.point.x),
+        y: cl::to_fixed29p3(p.point.y),
+        color: match p.brush {
+            Brush::Draw { ref color, .. } => {
+                let color = color.to_color();
+                ((color.a() as u32) << 24) |
+                ((color.r() as u32) << 16) |
+                ((color.g() as u32) << 8) |
+                color.b() as u32
+            },
+            Brush::Erase { .. } => 0,
+        },
+        brush_size: cl::to_fixed15p1(p.brush.stroke_width()),
+        softness: (p.brush.softness() * 100.0).round() as u8,
+    }
+}
+
+// converts stroke points from their network representation
+pub fn decode_stroke(points: Vec<cl::StrokePoint>) -> Vec<StrokePoint> {
+    points.into_iter().map(|p| {
+        StrokePoint {
+            point: Point::new(cl::from_fixed29p3(p.x), cl::from_fixed29p3(p.y)),
+            brush: {
+                let stroke_width = cl::from_fixed15p1(p.brush_size);
+                let softness = f32::from(p.softness.min(100)) / 100.0;
+                if p.color == 0 {
+                    Brush::Erase { stroke_width, softness }
+                } else {
+                    Brush::Draw {
+                        color: Color4f::from(Color::new(p.color)),
+                        stroke_width,
+                        softness,
+                    }
+                }
+            }
+        }
+    }).collect()
+}
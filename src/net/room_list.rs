@@ -0,0 +1,37 @@
+// the list of public rooms, fetched from the matchmaker for the lobby's room browser.
+
+use netcanv_protocol::matchmaker as mm;
+pub use netcanv_protocol::matchmaker::ListedRoom;
+
+use crate::net::Error;
+use crate::net::socket::Remote;
+
+pub struct RoomList {
+    matchmaker: Remote<mm::Packet>,
+}
+
+impl RoomList {
+
+    pub fn fetch(matchmaker_addr: &str) -> Result<Self, Error> {
+        let matchmaker = Remote::new(matchmaker_addr)?;
+        matchmaker.send(mm::Packet::ListRooms)?;
+        Ok(Self { matchmaker })
+    }
+
+    // returns the rooms once the matchmaker responds with them
+    pub fn poll(&self) -> Result<Option<Vec<ListedRoom>>, Error> {
+        self.matchmaker.tick()?;
+        match self.matchmaker.try_recv() {
+            Some(mm::Packet::ListedRooms(rooms)) => Ok(Some(rooms)),
+            // matchmakers without string room IDs list rooms with numeric IDs
+            Some(mm::Packet::RoomList(rooms)) => Ok(Some(rooms.into_iter().map(ListedRoom::from).collect())),
+            Some(mm::Packet::Error(message)) => Err(Error::Matchmaker(message)),
+            Some(packet) => {
+                log::warn!("unexpected packet while fetching the room list: {:?}", packet);
+                Ok(None)
+            },
+            None => Ok(None),
+        }
+    }
+
+}
@@ -0,0 +1,73 @@
+// keeping a persistent room's canvas on the matchmaker.
+// every few seconds, the host of a persistent room sends the matchmaker the chunks that changed since they were last
+// sent. chunks are compared by a hash of their PNG data, so only the chunks that actually changed are sent again.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use crate::net::{Error, Peer};
+use crate::paint_canvas::PaintCanvas;
+
+pub struct RoomStorage {
+    // hashes of the chunks' PNG data, as it was last sent to the matchmaker
+    stored: HashMap<(i32, i32), u64>,
+    last_upload: Instant,
+}
+
+fn hash(png_data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    png_data.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl RoomStorage {
+
+    const INTERVAL: Duration = Duration::from_secs(10);
+
+    pub fn new() -> Self {
+        Self {
+            stored: HashMap::new(),
+            last_upload: Instant::now(),
+        }
+    }
+
+    // forgets which chunks were sent, so that the whole canvas is sent the next time. used when the room becomes
+    // persistent, as the matchmaker starts out with nothing
+    pub fn clear(&mut self) {
+        self.stored.clear();
+    }
+
+    // notes down a chunk the matchmaker already has, such as one from a reopened room's stored canvas
+    pub fn mark_stored(&mut self, chunk: (i32, i32), png_data: &[u8]) {
+        self.stored.insert(chunk, hash(png_data));
+    }
+
+    // sends the chunks that changed to the matchmaker, if it's time to. called every frame while the room is
+    // persistent
+    pub fn tick(&mut self, paint_canvas: &mut PaintCanvas, peer: &Peer) -> Result<(), Error> {
+        if self.last_upload.elapsed() < Self::INTERVAL {
+            return Ok(())
+        }
+        self.last_upload = Instant::now();
+        let chunks: Vec<_> = paint_canvas.chunk_positions().collect();
+        for chunk in chunks {
+            let png_data = match paint_canvas.chunk_png_data(chunk) {
+                Some(png_data) => png_data,
+                None => continue,
+            };
+            let hash = hash(&png_data);
+            if self.stored.get(&chunk) == Some(&hash) {
+                continue
+            }
+            // when the connection is backed up, the rest of the chunks are sent the next time
+            if !peer.store_chunk(chunk, png_data)? {
+                break
+            }
+            self.stored.insert(chunk, hash);
+        }
+        Ok(())
+    }
+
+}
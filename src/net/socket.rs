@@ -1,11 +1,15 @@
 // socket abstraction.
 
-use std::net::{ToSocketAddrs, SocketAddr, TcpStream};
-use std::sync::Arc;
-use std::time::Duration;
+use std::net::{Shutdown, ToSocketAddrs, SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use std::thread::JoinHandle;
 
 use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use netcanv_protocol::build;
+use netcanv_protocol::version::Handshake;
+use rand::Rng;
 use serde::{Serialize, de::DeserializeOwned};
 use thiserror::Error;
 
@@ -28,7 +32,7 @@ impl ControllableThread {
 
         let _ = std::thread::Builder::new().name(name.into()).spawn(move || {
             match f(rx_abort) {
-                Err(error) => eprintln!("thread '{}' returned with error: {}", name, error),
+                Err(error) => log::error!("thread '{}' returned with error: {}", name, error),
                 _ => (),
             }
             let _ = tx_finished.send(Finished);
@@ -53,12 +57,162 @@ impl ControllableThread {
     }
 }
 
+// how much went through a connection since it was opened. updated by the network threads
+#[derive(Default)]
+pub struct Traffic {
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    packets_sent: AtomicU64,
+    packets_received: AtomicU64,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct TrafficTotals {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+}
+
+impl Traffic {
+
+    pub fn totals(&self) -> TrafficTotals {
+        TrafficTotals {
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            packets_sent: self.packets_sent.load(Ordering::Relaxed),
+            packets_received: self.packets_received.load(Ordering::Relaxed),
+        }
+    }
+
+}
+
+// how important a packet is, which decides whether it's let into the send queue when the connection can't keep up.
+// the queue itself is first in, first out: letting important packets overtake others would change the order in which
+// they're applied on the other end, and eg. a chunk of canvas data arriving after a stroke made on top of it would
+// erase that stroke
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    // packets that keep the connection going, such as joining a room or pings. always let in
+    Control,
+    // strokes, tool packets and everything else that has to reach the others for their canvas to stay in sync.
+    // only refused once the queue gets so long that the connection is hopelessly behind, after which the chunks they
+    // changed are sent again in full, see Peer::take_paint_refused
+    Paint,
+    // canvas data for people who just joined. refused as soon as a few packets are queued up, as it's large and can
+    // be sent again later
+    Bulk,
+}
+
+impl Priority {
+
+    fn queue_limit(self) -> usize {
+        match self {
+            Self::Control => usize::MAX,
+            Self::Paint => 4096,
+            Self::Bulk => 16,
+        }
+    }
+
+    // whether a packet with this priority would be let into a queue of the given length
+    pub fn fits(self, queued: usize) -> bool {
+        queued < self.queue_limit()
+    }
+
+}
+
+// artificial network conditions, for reproducing problems people run into on bad connections. they're applied to
+// everything we send, and set with environment variables in debug builds:
+//  - NETCANV_SIM_LATENCY: milliseconds every packet is held back for
+//  - NETCANV_SIM_JITTER: up to how many milliseconds are randomly added on top of that
+//  - NETCANV_SIM_DROP: the chance of a packet being dropped, in percent
+//  - NETCANV_SIM_REORDER: the chance of a packet being sent after the one following it, in percent
+// only paint and bulk packets are ever dropped or reordered, as those are the ones our own send queue refuses when the
+// connection can't keep up (see Priority). TCP doesn't lose packets on its own, so control packets are never lost
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SimulatedConditions {
+    pub latency: Duration,
+    pub jitter: Duration,
+    pub drop_chance: f32,
+    pub reorder_chance: f32,
+}
+
+impl SimulatedConditions {
+
+    pub fn from_env() -> Self {
+        let mut conditions = Self::default();
+        if !cfg!(debug_assertions) {
+            return conditions
+        }
+        // anything above the maximum would only make the connection useless, and could overflow durations
+        const MAX_MILLISECONDS: f32 = 60_000.0;
+        let var = |name: &str, max: f32| -> Option<f32> {
+            let value = std::env::var(name).ok()?;
+            match value.parse::<f32>() {
+                Ok(number) if (0.0..=max).contains(&number) => Some(number),
+                _ => {
+                    log::warn!("ignoring {}: {:?} is not a number between 0 and {}", name, value, max);
+                    None
+                },
+            }
+        };
+        if let Some(ms) = var("NETCANV_SIM_LATENCY", MAX_MILLISECONDS) {
+            conditions.latency = Duration::from_secs_f32(ms / 1000.0);
+        }
+        if let Some(ms) = var("NETCANV_SIM_JITTER", MAX_MILLISECONDS) {
+            conditions.jitter = Duration::from_secs_f32(ms / 1000.0);
+        }
+        if let Some(percent) = var("NETCANV_SIM_DROP", 100.0) {
+            conditions.drop_chance = percent / 100.0;
+        }
+        if let Some(percent) = var("NETCANV_SIM_REORDER", 100.0) {
+            conditions.reorder_chance = percent / 100.0;
+        }
+        if conditions.is_active() {
+            log::warn!("simulating network conditions: {:?}", conditions);
+        }
+        conditions
+    }
+
+    pub fn is_active(&self) -> bool {
+        *self != Self::default()
+    }
+
+    // how long a packet should be held back for
+    fn delay(&self) -> Duration {
+        self.latency + self.jitter.mul_f32(rand::thread_rng().gen())
+    }
+
+    fn roll(chance: f32, priority: Priority) -> bool {
+        priority != Priority::Control && chance > 0.0 && rand::thread_rng().gen::<f32>() < chance
+    }
+
+}
+
+// wakes the event loop up from a network thread, so that packets are handled as soon as they arrive instead of whenever
+// the next frame happens to be drawn
+pub type Waker = Arc<dyn Fn() + Send + Sync>;
+
+// a packet waiting in the send queue
+struct Queued<P> {
+    packet: P,
+    priority: Priority,
+    queued_at: Instant,
+}
+
 // P is the packet type
 pub struct Remote<P: Serialize + DeserializeOwned + Send + 'static> {
+    stream: Arc<TcpStream>,
     rx: Receiver<P>,
-    tx: Sender<P>,
+    tx: Sender<Queued<P>>,
     send: ControllableThread,
     recv: ControllableThread,
+    traffic: Arc<Traffic>,
+    // called by the receiving thread for every packet that comes in
+    waker: Arc<Mutex<Option<Waker>>>,
+    // the optional protocol features supported by both us and the matchmaker
+    features: u32,
+    conditions: SimulatedConditions,
 }
 
 #[derive(Debug, Error)]
@@ -67,66 +221,177 @@ pub enum Error {
     Io(#[from] std::io::Error),
     #[error("Serialization error: {0}")]
     Serialize(#[from] bincode::Error),
+    #[error("{0}")]
+    Build(#[from] build::Error),
     #[error("Error while sending data across threads")]
     ThreadSend,
     #[error("Error while receiving data from the network thread")]
     ThreadRecv,
+    #[error("The matchmaker didn't respond to the handshake. It's probably running an older version of NetCanv")]
+    NoHandshake,
+    #[error("The connection is too slow to keep up, some data could not be sent")]
+    QueueFull,
 }
 
 impl<P: Serialize + DeserializeOwned + Send + core::fmt::Debug + 'static> Remote<P> {
 
+    // how long to wait for the matchmaker's handshake before giving up
+    const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    // exchanges handshakes with the matchmaker, returning the features supported by both sides.
+    // whether our version is supported is up to the matchmaker; if it isn't, it follows the handshake with an Error
+    // packet explaining why, and closes the connection
+    fn handshake(stream: &TcpStream) -> Result<u32, Error> {
+        bincode::serialize_into(stream, &Handshake::ours())?;
+        stream.set_read_timeout(Some(Self::HANDSHAKE_TIMEOUT))?;
+        let theirs: Handshake = bincode::deserialize_from(stream).map_err(|error| match *error {
+            // matchmakers from before versioning was introduced don't answer at all
+            bincode::ErrorKind::Io(ref io) if matches!(
+                io.kind(),
+                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+            ) => Error::NoHandshake,
+            _ => Error::from(error),
+        })?;
+        stream.set_read_timeout(None)?;
+        if theirs.magic != Handshake::ours().magic {
+            return Err(Error::NoHandshake)
+        }
+        Ok(Handshake::ours().negotiate(&theirs))
+    }
+
     pub fn new(addr: impl ToSocketAddrs) -> Result<Self, Error> {
         let stream_arc = Arc::new(TcpStream::connect(addr)?);
         stream_arc.set_nodelay(true)?;
+        let features = Self::handshake(&stream_arc)?;
+        let conditions = SimulatedConditions::from_env();
 
         let (to_thread, from_main) = crossbeam_channel::unbounded();
         let (to_main, from_thread) = crossbeam_channel::unbounded();
+        let traffic_arc = Arc::new(Traffic::default());
 
         let stream = stream_arc.clone();
+        let traffic = traffic_arc.clone();
         let send = ControllableThread::new("network send thread", move |abort| -> Result<(), Error> {
             loop {
                 if let Ok(_) | Err(TryRecvError::Disconnected) = abort.try_recv() {
                     break;
                 }
-                while let Ok(packet) = from_main.recv() {
-                    bincode::serialize_into(&*stream, &packet)?;
+                let write = |packet: &P| -> Result<(), Error> {
+                    bincode::serialize_into(&*stream, packet)?;
+                    traffic.bytes_sent.fetch_add(bincode::serialized_size(packet)?, Ordering::Relaxed);
+                    traffic.packets_sent.fetch_add(1, Ordering::Relaxed);
+                    Ok(())
+                };
+                // a packet picked to be reordered, which is sent right after the next one
+                let mut held_back: Option<P> = None;
+                while let Ok(Queued { packet, priority, queued_at }) = from_main.recv() {
+                    if conditions.is_active() {
+                        let send_at = queued_at + conditions.delay();
+                        let now = Instant::now();
+                        if send_at > now {
+                            std::thread::sleep(send_at - now);
+                        }
+                        if held_back.is_none() && SimulatedConditions::roll(conditions.reorder_chance, priority) {
+                            held_back = Some(packet);
+                            continue
+                        }
+                    }
+                    write(&packet)?;
+                    if let Some(packet) = held_back.take() {
+                        write(&packet)?;
+                    }
                 }
             }
             Ok(())
         });
 
+        let waker_arc: Arc<Mutex<Option<Waker>>> = Arc::new(Mutex::new(None));
+
         let stream = stream_arc.clone();
+        let traffic = traffic_arc.clone();
+        let waker = waker_arc.clone();
         let recv = ControllableThread::new("network recv thread", move |abort| -> Result<(), Error> {
             loop {
                 if let Ok(_) | Err(TryRecvError::Disconnected) = abort.try_recv() {
                     break;
                 }
-                let packet = bincode::deserialize_from(&*stream)?;
+                let packet = build::decode_from(&*stream)?;
+                traffic.bytes_received.fetch_add(bincode::serialized_size(&packet)?, Ordering::Relaxed);
+                traffic.packets_received.fetch_add(1, Ordering::Relaxed);
                 to_main.send(packet).map_err(|_| Error::ThreadSend)?;
+                if let Some(wake) = &*waker.lock().map_err(|_| Error::ThreadSend)? {
+                    wake();
+                }
             }
             Ok(())
         });
 
         Ok(Self {
+            stream: stream_arc,
             rx: from_thread,
             tx: to_thread,
             send,
             recv,
+            traffic: traffic_arc,
+            waker: waker_arc,
+            features,
+            conditions,
         })
     }
 
     pub fn send(&self, packet: P) -> Result<(), Error> {
-        self.tx.send(packet).map_err(|_| Error::ThreadSend)
+        self.send_with_priority(packet, Priority::Control)
+    }
+
+    // queues the packet up for sending, unless there are already too many packets waiting for one of its priority
+    pub fn send_with_priority(&self, packet: P, priority: Priority) -> Result<(), Error> {
+        if !priority.fits(self.queued()) {
+            return Err(Error::QueueFull)
+        }
+        if SimulatedConditions::roll(self.conditions.drop_chance, priority) {
+            return Ok(())
+        }
+        self.tx.send(Queued { packet, priority, queued_at: Instant::now() }).map_err(|_| Error::ThreadSend)
+    }
+
+    // the number of packets waiting to be sent
+    pub fn queued(&self) -> usize {
+        self.tx.len()
     }
 
     pub fn try_recv(&self) -> Option<P> {
         self.rx.try_recv().ok()
     }
 
+    // whether any packets were received that haven't been read with try_recv yet
+    pub fn has_received(&self) -> bool {
+        !self.rx.is_empty()
+    }
+
     pub fn tick(&self) -> Result<bool, Error> {
         Ok(self.send.tick()? && self.recv.tick()?)
     }
 
+    // sets what's called whenever a packet comes in
+    pub fn set_waker(&self, waker: Waker) {
+        if let Ok(mut slot) = self.waker.lock() {
+            *slot = Some(waker);
+        }
+    }
+
+    pub fn traffic(&self) -> &Traffic {
+        &self.traffic
+    }
+
+    pub fn simulated_conditions(&self) -> SimulatedConditions {
+        self.conditions
+    }
+
+    // whether both we and the matchmaker support the given feature from netcanv_protocol::version::features
+    pub fn supports(&self, feature: u32) -> bool {
+        self.features & feature != 0
+    }
+
 }
 
 impl<P: Serialize + DeserializeOwned + Send> Drop for Remote<P> {
@@ -137,6 +402,9 @@ impl<P: Serialize + DeserializeOwned + Send> Drop for Remote<P> {
         // disconnected.
         let _ = self.send.abort();
         let _ = self.recv.abort();
+        // the receiving thread is most likely blocked on reading from the stream, so the connection needs to be shut
+        // down for it to notice that it should quit
+        let _ = self.stream.shutdown(Shutdown::Both);
     }
 
 }
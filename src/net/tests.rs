@@ -0,0 +1,229 @@
+// tests for the client's networking code, run against a real matchmaker.
+// each test starts its own matchmaker on a port picked by the OS and connects peers to it through the same Peer the
+// app uses, so everything from the handshake to relaying packets goes through the actual sockets. the matchmaker's
+// binary is built alongside the tests by `cargo test --workspace`.
+
+use std::time::{Duration, Instant};
+
+use skulpin::skia_safe::{Color4f, Point};
+
+use crate::net::{encode_stroke_point, Message, Peer};
+use crate::paint_canvas::{Brush, StrokePoint};
+
+#[path = "../../netcanv-matchmaker/tests/harness/process.rs"]
+mod process;
+
+use process::Matchmaker;
+
+// how long to wait for a message before failing the test, so that a lost packet doesn't hang the whole test suite
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+// how long to sleep between ticks while waiting for a message
+const TICK_INTERVAL: Duration = Duration::from_millis(5);
+
+impl Matchmaker {
+
+    // the matchmaker's binary is put next to the folder the test binary is in
+    fn start() -> Self {
+        let exe = std::env::current_exe().unwrap();
+        let dir = exe.parent().and_then(|deps| deps.parent()).unwrap();
+        let binary = dir.join(format!("netcanv-matchmaker{}", std::env::consts::EXE_SUFFIX));
+        assert!(binary.is_file(), "the matchmaker isn't built, run the tests with `cargo test --workspace`");
+        Self::spawn(&binary, &[])
+    }
+
+}
+
+// ticks the peer until it receives a message the function picks out, skipping over everything else
+fn wait_for<T>(peer: &mut Peer, mut pick: impl FnMut(Message) -> Option<T>) -> T {
+    let deadline = Instant::now() + RECEIVE_TIMEOUT;
+    while Instant::now() < deadline {
+        for message in peer.tick().unwrap() {
+            if let Some(picked) = pick(message) {
+                return picked
+            }
+        }
+        std::thread::sleep(TICK_INTERVAL);
+    }
+    panic!("timed out waiting for a message");
+}
+
+// ticks the peer until the function says it's in the state that's waited for. messages are only received up to the
+// point where that happens, so anything that comes after is left for later
+fn wait_until(peer: &mut Peer, mut done: impl FnMut(&Peer) -> bool) {
+    let deadline = Instant::now() + RECEIVE_TIMEOUT;
+    while !done(peer) {
+        assert!(Instant::now() < deadline, "timed out waiting for the peer");
+        for message in peer.tick().unwrap() {
+            if let Message::Error(error) = message {
+                panic!("{}", error);
+            }
+        }
+        std::thread::sleep(TICK_INTERVAL);
+    }
+}
+
+// ticks the peer until it's in the room
+fn wait_until_connected(peer: &mut Peer) {
+    wait_for(peer, |message| match message {
+        Message::Connected => Some(()),
+        Message::Error(error) => panic!("could not connect: {}", error),
+        _ => None,
+    });
+}
+
+// hosts a room and waits until it's open
+fn host(matchmaker: &Matchmaker, password: Option<&str>) -> Peer {
+    let mut host = Peer::host("host", &matchmaker.addr.to_string(), password, None).unwrap();
+    wait_until_connected(&mut host);
+    host
+}
+
+// joins the room and waits until the host has greeted the newcomer back
+fn join(matchmaker: &Matchmaker, host: &mut Peer, nickname: &str) -> Peer {
+    let room_id = host.room_id().unwrap().to_owned();
+    let mut peer = Peer::join(nickname, &matchmaker.addr.to_string(), &room_id, None).unwrap();
+    wait_until_connected(&mut peer);
+    wait_for(host, |message| match message {
+        Message::Joined(_) => Some(()),
+        _ => None,
+    });
+    // the host's greeting doesn't come with a message, so the mate showing up is what's waited for
+    wait_until(&mut peer, |peer| !peer.mates().is_empty());
+    peer
+}
+
+fn line(y: f32, color: Color4f) -> Vec<StrokePoint> {
+    (0..16)
+        .map(|x| StrokePoint {
+            point: Point::new(x as f32, y),
+            brush: Brush::Draw { color, stroke_width: 4.0, softness: 0.0 },
+        })
+        .collect()
+}
+
+fn wait_for_stroke(peer: &mut Peer) -> Vec<StrokePoint> {
+    wait_for(peer, |message| match message {
+        Message::Stroke(_, points) => Some(points),
+        _ => None,
+    })
+}
+
+fn assert_same_stroke(a: &[StrokePoint], b: &[StrokePoint]) {
+    let encode = |points: &[StrokePoint]| points.iter().map(encode_stroke_point).collect::<Vec<_>>();
+    assert_eq!(encode(a), encode(b));
+}
+
+#[test]
+fn hosting_opens_a_room() {
+    let matchmaker = Matchmaker::start();
+    let host = host(&matchmaker, None);
+    assert!(host.is_host());
+    assert!(host.room_id().is_some());
+}
+
+#[test]
+fn joining_introduces_the_host_and_the_newcomer() {
+    let matchmaker = Matchmaker::start();
+    let mut host = host(&matchmaker, None);
+    let alice = join(&matchmaker, &mut host, "alice");
+    assert!(!alice.is_host());
+    assert_eq!(host.mates().values().map(|mate| mate.nickname.as_str()).collect::<Vec<_>>(), ["alice"]);
+    assert_eq!(alice.mates().values().map(|mate| mate.nickname.as_str()).collect::<Vec<_>>(), ["host"]);
+}
+
+#[test]
+fn taken_nicknames_are_made_unique() {
+    let matchmaker = Matchmaker::start();
+    let mut host = host(&matchmaker, None);
+    let mut impostor = join(&matchmaker, &mut host, "host");
+    let nickname = wait_for(&mut impostor, |message| match message {
+        Message::NicknameTaken(nickname) => Some(nickname),
+        _ => None,
+    });
+    assert_eq!(nickname, "host 2");
+}
+
+#[test]
+fn strokes_reach_everyone_in_the_room() {
+    let matchmaker = Matchmaker::start();
+    let mut host = host(&matchmaker, None);
+    let alice = join(&matchmaker, &mut host, "alice");
+    let mut bob = join(&matchmaker, &mut host, "bob");
+
+    let stroke = line(0.0, Color4f::new(1.0, 0.0, 0.0, 1.0));
+    alice.send_stroke(stroke.iter().map(|p| StrokePoint { point: p.point, brush: p.brush.clone() })).unwrap();
+    assert_same_stroke(&wait_for_stroke(&mut host), &stroke);
+    assert_same_stroke(&wait_for_stroke(&mut bob), &stroke);
+}
+
+#[test]
+fn wrong_passwords_are_refused() {
+    let matchmaker = Matchmaker::start();
+    let host = host(&matchmaker, Some("hunter2"));
+    let room_id = host.room_id().unwrap();
+    let mut peer = Peer::join("alice", &matchmaker.addr.to_string(), room_id, Some("hunter3")).unwrap();
+    let error = wait_for(&mut peer, |message| match message {
+        Message::Error(error) => Some(error),
+        Message::Connected => panic!("joined with the wrong password"),
+        _ => None,
+    });
+    assert!(!error.is_empty());
+}
+
+#[test]
+fn leaving_is_noticed_by_the_host() {
+    let matchmaker = Matchmaker::start();
+    let mut host = host(&matchmaker, None);
+    let alice = join(&matchmaker, &mut host, "alice");
+    drop(alice);
+    let nickname = wait_for(&mut host, |message| match message {
+        Message::Left(_, nickname) => Some(nickname),
+        _ => None,
+    });
+    assert_eq!(nickname, "alice");
+    assert!(host.mates().is_empty());
+}
+
+#[test]
+fn only_the_host_can_send_the_canvas() {
+    let matchmaker = Matchmaker::start();
+    let mut host = host(&matchmaker, None);
+    let alice = join(&matchmaker, &mut host, "alice");
+    let mut bob = join(&matchmaker, &mut host, "bob");
+    let bob_addr = *host.mates().iter().find(|(_, mate)| mate.nickname == "bob").unwrap().0;
+
+    // packets from one sender arrive in order, so once bob receives alice's stroke, her chunk has been dealt with too
+    alice.send_canvas_data(bob_addr, (1, 1), vec![1]).unwrap();
+    alice.send_stroke(line(0.0, Color4f::new(0.0, 0.0, 1.0, 1.0)).into_iter()).unwrap();
+    wait_for(&mut bob, |message| match message {
+        Message::CanvasData(..) => panic!("received canvas data from someone other than the host"),
+        Message::Stroke(..) => Some(()),
+        _ => None,
+    });
+
+    host.send_canvas_data(bob_addr, (2, 2), vec![2]).unwrap();
+    let chunk = wait_for(&mut bob, |message| match message {
+        Message::CanvasData(chunk, png_data) => Some((chunk, png_data)),
+        _ => None,
+    });
+    assert_eq!(chunk, ((2, 2), vec![2]));
+}
+
+#[test]
+fn packets_received_during_a_stall_keep_the_connection_alive() {
+    let matchmaker = Matchmaker::start();
+    let mut host = host(&matchmaker, None);
+    let timeout = Duration::from_millis(200);
+    host.set_keepalive(Some((timeout, timeout)));
+
+    // someone joins while the host isn't ticking, and the host only gets around to reading it after the timeout
+    let mut alice = Peer::join("alice", &matchmaker.addr.to_string(), host.room_id().unwrap(), None).unwrap();
+    wait_until_connected(&mut alice);
+    std::thread::sleep(timeout * 2);
+    let joined = wait_for(&mut host, |message| match message {
+        Message::Error(error) => panic!("{}", error),
+        Message::Joined(nickname) => Some(nickname),
+        _ => None,
+    });
+    assert_eq!(joined, "alice");
+}
@@ -0,0 +1,111 @@
+// progress tracking for canvas chunks sent by the host to a newly joined client.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use skulpin::skia_safe::{Point, Rect};
+
+use crate::net::Mate;
+use crate::paint_canvas::PaintCanvas;
+
+pub struct ChunkDownload {
+    expected: u32,
+    received: u32,
+}
+
+impl ChunkDownload {
+
+    pub fn new(expected: u32) -> Self {
+        Self {
+            expected,
+            received: 0,
+        }
+    }
+
+    pub fn chunk_received(&mut self) {
+        self.received = u32::min(self.received + 1, self.expected);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.received >= self.expected
+    }
+
+    pub fn received(&self) -> u32 {
+        self.received
+    }
+
+    pub fn expected(&self) -> u32 {
+        self.expected
+    }
+
+    // returns the progress as a number from 0 to 1
+    pub fn fraction(&self) -> f32 {
+        if self.expected == 0 { 1.0 }
+        else { self.received as f32 / self.expected as f32 }
+    }
+
+}
+
+// chunks the host still has to send to people who just joined. chunks are sent a few at a time, the ones closest to
+// the receiver's viewport first, so that they get to see what's on their screen as soon as possible
+pub struct ChunkUploads {
+    pending: HashMap<SocketAddr, Vec<(i32, i32)>>,
+}
+
+impl ChunkUploads {
+
+    // the maximum amount of chunks sent to a single person per tick
+    const CHUNKS_PER_TICK: usize = 4;
+
+    pub fn new() -> Self {
+        Self {
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn start(&mut self, to: SocketAddr, chunks: Vec<(i32, i32)>) {
+        if !chunks.is_empty() {
+            self.pending.insert(to, chunks);
+        }
+    }
+
+    // puts a chunk that couldn't be sent back into the queue. it's sent again as soon as it's among the closest
+    // ones to the receiver's viewport
+    pub fn retry(&mut self, to: SocketAddr, chunk: (i32, i32)) {
+        self.pending.entry(to).or_default().push(chunk);
+    }
+
+    // whether there are any chunks left to send
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    // the number of chunks left to send, to everyone combined
+    pub fn len(&self) -> usize {
+        self.pending.values().map(|chunks| chunks.len()).sum()
+    }
+
+    // returns the chunks that should be sent next, along with who they should be sent to
+    pub fn next(&mut self, mates: &HashMap<SocketAddr, Mate>) -> Vec<(SocketAddr, (i32, i32))> {
+        let mut next = Vec::new();
+        for (&addr, chunks) in &mut self.pending {
+            // until the receiver tells us where they're looking, assume they're looking at the origin, which is
+            // where everyone starts out
+            let center = mates
+                .get(&addr)
+                .and_then(|mate| mate.viewport)
+                .map_or(Point::new(0.0, 0.0), |viewport| viewport.center());
+            let distance = |chunk: &(i32, i32)| {
+                (Rect::from_irect(PaintCanvas::chunk_rect(*chunk)).center() - center).length()
+            };
+            // the viewport can move between ticks, so the chunks have to be sorted again every time.
+            // the closest chunks end up at the end, so that they can be popped off cheaply
+            chunks.sort_by(|a, b| distance(b).partial_cmp(&distance(a)).unwrap());
+            let count = usize::min(Self::CHUNKS_PER_TICK, chunks.len());
+            next.extend(chunks.drain(chunks.len() - count..).rev().map(|chunk| (addr, chunk)));
+        }
+        self.pending.retain(|_, chunks| !chunks.is_empty());
+        next
+    }
+
+}
@@ -1,13 +1,22 @@
-use std::collections::{HashMap, HashSet, hash_map};
+use std::collections::{HashMap, HashSet};
 use std::io::Cursor;
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
 
 use skulpin::skia_safe::*;
-use ::image::{ColorType, ImageDecoder, ImageError, codecs::png::{PngDecoder, PngEncoder}};
+use ::image::{ColorType, ImageDecoder, ImageError, Rgba, RgbaImage, codecs::png::{PngDecoder, PngEncoder}};
+use ::image::imageops;
 
+use crate::util::{color4f_hex, hex_color4f, rgba_image_to_skia};
+
+// the softness of a brush goes from 0, which is a hard edge, to 1, which feathers the edge all the way to the center of
+// the stroke
 #[derive(Clone, Debug)]
 pub enum Brush {
-    Draw { color: Color4f, stroke_width: f32 },
-    Erase { stroke_width: f32 },
+    Draw { color: Color4f, stroke_width: f32, softness: f32 },
+    Erase { stroke_width: f32, softness: f32 },
 }
 
 #[derive(Debug)]
@@ -16,23 +25,109 @@ pub struct StrokePoint {
     pub brush: Brush,
 }
 
+// what's shown behind the paint. the host decides on it, and it's sent to everyone in the room
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Background {
+    Solid(Color),
+    // shown as a checkerboard, and left transparent in exported images
+    Transparent,
+}
+
+impl Background {
+
+    // the size of a checkerboard cell, in canvas pixels
+    const CHECKERBOARD_CELL: u32 = 8;
+
+    // converts the background from its network representation, a 0xRRGGBBAA color or None if it's transparent
+    pub fn from_packet(color: Option<u32>) -> Self {
+        match color {
+            Some(color) => Self::Solid(hex_color4f(color).to_color()),
+            None => Self::Transparent,
+        }
+    }
+
+    pub fn to_packet(self) -> Option<u32> {
+        match self {
+            Self::Solid(color) => Some(color4f_hex(Color4f::from(color))),
+            Self::Transparent => None,
+        }
+    }
+
+    // draws the background over the given area of the canvas
+    pub fn draw(self, canvas: &mut Canvas, rect: Rect) {
+        let mut paint = Paint::default();
+        match self {
+            Self::Solid(color) => {
+                paint.set_color(color);
+            },
+            Self::Transparent => {
+                let cell = Self::CHECKERBOARD_CELL;
+                let pattern = RgbaImage::from_fn(cell * 2, cell * 2, |x, y| {
+                    if (x / cell + y / cell) % 2 == 0 { Rgba([255, 255, 255, 255]) } else { Rgba([204, 204, 204, 255]) }
+                });
+                let pattern = rgba_image_to_skia(&pattern);
+                paint.set_shader(pattern.to_shader((TileMode::Repeat, TileMode::Repeat), None));
+            },
+        }
+        canvas.draw_rect(rect, &paint);
+    }
+
+    // fills the transparent parts of the image with the background color. transparent backgrounds leave the image
+    // as it is
+    pub fn fill_behind(self, image: &mut RgbaImage) {
+        if let Self::Solid(color) = self {
+            let color = Rgba([color.r(), color.g(), color.b(), color.a()]);
+            let mut filled = RgbaImage::from_pixel(image.width(), image.height(), color);
+            imageops::overlay(&mut filled, image, 0, 0);
+            *image = filled;
+        }
+    }
+
+}
+
+impl Default for Background {
+
+    // the canvas has always been white, so that's what it is unless the host says otherwise
+    fn default() -> Self {
+        Self::Solid(Color::WHITE)
+    }
+
+}
+
 impl Brush {
 
+    pub fn stroke_width(&self) -> f32 {
+        match *self {
+            Self::Draw { stroke_width, .. } | Self::Erase { stroke_width, .. } => stroke_width,
+        }
+    }
+
+    pub fn softness(&self) -> f32 {
+        match *self {
+            Self::Draw { softness, .. } | Self::Erase { softness, .. } => softness,
+        }
+    }
+
     pub fn as_paint(&self) -> Paint {
         let mut paint = Paint::new(Color4f::from(Color::TRANSPARENT), None);
         paint.set_anti_alias(false);
         paint.set_style(paint::Style::Stroke);
         paint.set_stroke_cap(paint::Cap::Round);
+        paint.set_stroke_width(self.stroke_width());
 
         match self {
-            Self::Draw { color, stroke_width } => {
-                paint.set_color(color.to_color());
-                paint.set_stroke_width(*stroke_width);
-            },
-            Self::Erase { stroke_width } => {
-                paint.set_blend_mode(BlendMode::Clear);
-                paint.set_stroke_width(*stroke_width);
-            },
+            Self::Draw { color, .. } => paint.set_color(color.to_color()),
+            // the eraser clears pixels in proportion to its coverage, so soft edges erase partially
+            Self::Erase { .. } => paint.set_blend_mode(BlendMode::Clear),
+        };
+
+        let (stroke_width, softness) = (self.stroke_width(), self.softness());
+        if softness > 0.0 {
+            // the soft edge is made by blurring a thinner stroke, such that the blur fades out right at the edge of
+            // the full stroke width. a blur fades out after about 3 sigma
+            paint.set_anti_alias(true);
+            paint.set_stroke_width(stroke_width * (1.0 - softness / 2.0));
+            paint.set_mask_filter(MaskFilter::blur(BlurStyle::Normal, stroke_width * softness / 12.0, None));
         }
 
         paint
@@ -45,10 +140,16 @@ pub struct Chunk<'a> {
     bitmap: Bitmap,
     canvas: OwnedCanvas<'a>,
     png_data: Option<Vec<u8>>,
+    // the last time the chunk was drawn to, read from, or visible on screen
+    last_used: Instant,
 }
 
 impl<'a> Chunk<'a> {
     const SIZE: (i32, i32) = (256, 256);
+    // chunks are split into tiles when only parts of them need to be sent over the network
+    const TILE_SIZE: i32 = 32;
+    const TILES_PER_ROW: i32 = Self::SIZE.0 / Self::TILE_SIZE;
+    const TILE_COUNT: u8 = (Self::TILES_PER_ROW * Self::SIZE.1 / Self::TILE_SIZE) as u8;
 
     fn new() -> Self {
         let mut bitmap = Bitmap::new();
@@ -59,9 +160,13 @@ impl<'a> Chunk<'a> {
             bitmap,
             canvas,
             png_data: None,
+            last_used: Instant::now(),
         }
     }
 
+    // the amount of memory the chunk's pixels take up
+    const BYTE_SIZE: usize = (Self::SIZE.0 * Self::SIZE.1 * 4) as usize;
+
     fn screen_position(chunk_position: (i32, i32)) -> Point {
         Point::new(
             (chunk_position.0 * Self::SIZE.0) as _,
@@ -77,29 +182,90 @@ impl<'a> Chunk<'a> {
         }
     }
 
+    // converts a color to the chunk's pixel format
+    fn color_to_pixel(&self, color: Color) -> [u8; 4] {
+        // chunks store premultiplied alpha
+        let premultiply = |c: u8| (c as u32 * color.a() as u32 / 255) as u8;
+        let (r, g, b) = (premultiply(color.r()), premultiply(color.g()), premultiply(color.b()));
+        // N32 is BGRA on little endian machines
+        if self.bitmap.color_type() == skulpin::skia_safe::ColorType::BGRA8888 {
+            [b, g, r, color.a()]
+        } else {
+            [r, g, b, color.a()]
+        }
+    }
+
+    // whether all of the chunk's pixels are fully transparent, eg. after everything in it was erased
+    fn is_empty(&mut self) -> bool {
+        self.pixels_mut().chunks_exact(4).all(|pixel| pixel[3] == 0)
+    }
+
     // reencodes PNG data if necessary.
     // PNG data is reencoded upon outside request, but invalidated if the chunk is modified
     fn png_data(&mut self) -> Option<&[u8]> {
         if self.png_data.is_none() {
-            let pixels = self.pixels_mut();
-            let (width, height) = (self.bitmap.width() as u32, self.bitmap.height() as u32);
-            let mut bytes: Vec<u8> = Vec::new();
-            if PngEncoder::new(Cursor::new(&mut bytes)).encode(pixels, width, height, ColorType::Rgba8).is_err() {
-                return None
-            }
-            self.png_data = Some(bytes);
+            self.png_data = Some(Self::encode_png_data(self.pixels_mut())?);
         }
         Some(self.png_data.as_ref().unwrap())
     }
 
+    fn encode_png_data(pixels: &[u8]) -> Option<Vec<u8>> {
+        let (width, height) = (Self::SIZE.0 as u32, Self::SIZE.1 as u32);
+        let mut bytes: Vec<u8> = Vec::new();
+        PngEncoder::new(Cursor::new(&mut bytes)).encode(pixels, width, height, ColorType::Rgba8).ok()?;
+        Some(bytes)
+    }
+
+    // returns the byte ranges of the tile's rows within the chunk's pixels
+    fn tile_rows(index: u8) -> impl Iterator<Item = Range<usize>> {
+        let (x, y) = (index as i32 % Self::TILES_PER_ROW, index as i32 / Self::TILES_PER_ROW);
+        let row_bytes = Self::SIZE.0 as usize * 4;
+        let tile_row_bytes = Self::TILE_SIZE as usize * 4;
+        let left = x as usize * tile_row_bytes;
+        let top = (y * Self::TILE_SIZE) as usize;
+        (top..top + Self::TILE_SIZE as usize).map(move |row| {
+            let start = row * row_bytes + left;
+            start..start + tile_row_bytes
+        })
+    }
+
+    fn tile_png_data(&mut self, index: u8) -> Option<Vec<u8>> {
+        let pixels = self.pixels_mut();
+        let tile: Vec<u8> = Self::tile_rows(index).flat_map(|row| pixels[row].iter().copied()).collect();
+        let size = Self::TILE_SIZE as u32;
+        let mut bytes: Vec<u8> = Vec::new();
+        PngEncoder::new(Cursor::new(&mut bytes)).encode(&tile, size, size, ColorType::Rgba8).ok()?;
+        Some(bytes)
+    }
+
+    fn decode_tile_png_data(&mut self, index: u8, data: &[u8]) -> Result<(), ImageError> {
+        if index >= Self::TILE_COUNT {
+            log::warn!("received tile with invalid index {}, ignoring", index);
+            return Ok(())
+        }
+        let decoder = PngDecoder::new(Cursor::new(data))?;
+        let size = Self::TILE_SIZE as u32;
+        if decoder.color_type() != ColorType::Rgba8 || decoder.dimensions() != (size, size) {
+            log::warn!("received tile with invalid format, ignoring");
+            return Ok(())
+        }
+        let mut tile = vec![0; decoder.total_bytes() as usize];
+        decoder.read_image(&mut tile)?;
+        let pixels = self.pixels_mut();
+        for (row, tile_row) in Self::tile_rows(index).zip(tile.chunks_exact(size as usize * 4)) {
+            pixels[row].copy_from_slice(tile_row);
+        }
+        Ok(())
+    }
+
     fn decode_png_data(&mut self, data: &[u8]) -> Result<(), ImageError> {
         let decoder = PngDecoder::new(Cursor::new(data))?;
         if decoder.color_type() != ColorType::Rgba8 {
-            eprintln!("received non-RGBA image data, ignoring");
+            log::warn!("received non-RGBA image data, ignoring");
             return Ok(())
         }
         if decoder.dimensions() != (Self::SIZE.0 as u32, Self::SIZE.1 as u32) {
-            eprintln!("received chunk with invalid size. got: {:?}, expected: {:?}", decoder.dimensions(), Self::SIZE);
+            log::warn!("received chunk with invalid size. got: {:?}, expected: {:?}", decoder.dimensions(), Self::SIZE);
             return Ok(())
         }
         decoder.read_image(self.pixels_mut())?;
@@ -108,31 +274,330 @@ impl<'a> Chunk<'a> {
 
 }
 
+// keeps track of which chunks were modified. every modification gets a number counting up from 1, so that anything
+// that needs to know which chunks changed since some point in time can remember the number instead of taking the chunks
+// away from everything else
+// a chunk that was encoded to be evicted: its position, its PNG data, and the pixels it was encoded from
+type EncodedChunk = ((i32, i32), Vec<u8>, Option<Vec<u8>>);
+
+struct DirtyChunks {
+    // chunks that were modified since the last take() call
+    positions: HashSet<(i32, i32)>,
+    // the number of each chunk's last modification
+    revisions: HashMap<(i32, i32), u64>,
+    revision: u64,
+}
+
+impl DirtyChunks {
+
+    fn new() -> Self {
+        Self {
+            positions: HashSet::new(),
+            revisions: HashMap::new(),
+            revision: 0,
+        }
+    }
+
+    fn insert(&mut self, position: (i32, i32)) {
+        self.revision += 1;
+        self.revisions.insert(position, self.revision);
+        self.positions.insert(position);
+    }
+
+    fn take(&mut self) -> Vec<(i32, i32)> {
+        self.positions.drain().collect()
+    }
+
+}
+
+// chunks that haven't been used in a while are evicted from memory once the canvas goes over its memory budget. evicted
+// chunks are kept around as PNG data, and brought back into memory as soon as they're needed again
 pub struct PaintCanvas<'a> {
     chunks: HashMap<(i32, i32), Chunk<'a>>,
+    // the PNG data of chunks that were evicted from memory
+    evicted: HashMap<(i32, i32), Vec<u8>>,
+    // the maximum amount of memory chunks can take up, in bytes. None means there's no limit
+    memory_budget: Option<usize>,
+    // when chunks were last picked out to be evicted
+    last_eviction: Instant,
+    // receives the chunks that are being encoded on another thread to be evicted
+    evicting: Option<Receiver<Vec<EncodedChunk>>>,
     // this set contains all chunks that have already been visited in the current stroke() call
     stroked_chunks: HashSet<(i32, i32)>,
+    dirty_chunks: DirtyChunks,
+    // whether anything changed since the canvas was last saved to or loaded from a project
+    has_unsaved_changes: bool,
+    // when that last happened
+    last_saved: Option<Instant>,
+    background: Background,
+    // regions of the canvas people locked, along with who locked them, see set_locks
+    locks: Vec<(Option<SocketAddr>, Rect)>,
+    // whose edits are being painted, see set_editor
+    editor: Option<SocketAddr>,
+}
+
+// numbers describing the canvas, for showing to the user
+pub struct Statistics {
+    pub chunk_count: usize,
+    // chunks that exist but have nothing in them. evicted chunks are never counted as empty, as they'd have to be
+    // decoded to find out
+    pub empty_chunk_count: usize,
+    pub evicted_chunk_count: usize,
+    pub bounds: Option<IRect>,
+    // the memory taken up by the pixels of chunks in memory
+    pub pixel_bytes: usize,
+    // the memory taken up by the PNG data of chunks, both cached and evicted
+    pub png_bytes: usize,
+    // the last time the canvas was saved to or loaded from a project
+    pub last_saved: Option<Instant>,
+}
+
+// a set of pixels found by PaintCanvas::flood_fill_region, grouped by chunk
+pub struct FillRegion {
+    // byte offsets of the pixels within each chunk
+    pixels: HashMap<(i32, i32), Vec<usize>>,
 }
 
-pub struct PngData<'a, 'b> {
-    iter: hash_map::IterMut<'a, (i32, i32), Chunk<'b>>,
+// how a chunk changed, in the form it should be sent to other people in the room
+pub enum ChunkDelta {
+    Unchanged,
+    // only some tiles changed. these are the tiles' indices and PNG data
+    Tiles(Vec<(u8, Vec<u8>)>),
+    // enough of the chunk changed that it's cheaper to send the PNG data of all of it
+    Full(Vec<u8>),
 }
 
 impl<'a> PaintCanvas<'a> {
 
+    // the length of the straight lines curves are made of, in pixels
+    const CURVE_STEP: f32 = 4.0;
+    // how far away from the origin things received from other people may be painted. anything farther away could make
+    // the canvas create more chunks than there's memory for
+    pub const MAX_COORDINATE: f32 = 1_048_576.0;
+
+    // how often the canvas is checked for being over its memory budget
+    const EVICTION_INTERVAL: Duration = Duration::from_secs(1);
+    // the most chunks that are encoded at once to be evicted. their pixels are copied for the encoding thread, so this
+    // keeps the copies from taking up too much memory themselves
+    const EVICTION_BATCH_SIZE: usize = 64;
+
     pub fn new() -> Self {
         Self {
             chunks: HashMap::new(),
+            evicted: HashMap::new(),
+            memory_budget: None,
+            last_eviction: Instant::now(),
+            evicting: None,
             stroked_chunks: HashSet::new(),
+            dirty_chunks: DirtyChunks::new(),
+            has_unsaved_changes: false,
+            last_saved: None,
+            background: Background::default(),
+            locks: Vec::new(),
+            editor: None,
+        }
+    }
+
+    pub fn background(&self) -> Background {
+        self.background
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        if background != self.background {
+            self.has_unsaved_changes = true;
         }
+        self.background = background;
+    }
+
+    // sets the regions of the canvas people locked, along with who locked each of them, None being us. strokes,
+    // drawing, fills, tiles and pixels written by the editor leave the regions locked by anyone else untouched. whole
+    // chunks received from the host or loaded from a project are unaffected
+    pub fn set_locks(&mut self, locks: Vec<(Option<SocketAddr>, Rect)>) {
+        self.locks = locks;
+    }
+
+    // sets whose edits are painted from now on, None being us
+    pub fn set_editor(&mut self, editor: Option<SocketAddr>) {
+        self.editor = editor;
+    }
+
+    // the regions the editor can't paint into
+    fn locked_regions<'l>(
+        locks: &'l [(Option<SocketAddr>, Rect)],
+        editor: Option<SocketAddr>,
+    ) -> impl Iterator<Item = Rect> + 'l {
+        locks.iter().filter(move |&&(owner, _)| owner != editor).map(|&(_, region)| region)
+    }
+
+    // clips the regions the editor can't paint into out of the chunk's canvas. the canvas's state has to be saved
+    // beforehand, and restored once the drawing is done
+    fn clip_locks(
+        canvas: &mut Canvas,
+        locks: &[(Option<SocketAddr>, Rect)],
+        editor: Option<SocketAddr>,
+        chunk_position: (i32, i32),
+    ) {
+        let screen_position = Chunk::screen_position(chunk_position);
+        for region in Self::locked_regions(locks, editor) {
+            canvas.clip_rect(region.with_offset(-screen_position), ClipOp::Difference, false);
+        }
+    }
+
+    // the byte ranges of the chunk's pixels that lie in regions the editor can't paint into. the regions are rounded
+    // to whole pixels the same way clip_locks rounds them
+    fn locked_ranges(&self, chunk_position: (i32, i32)) -> Vec<Range<usize>> {
+        let origin = Chunk::screen_position(chunk_position);
+        let (width, height) = (Chunk::SIZE.0 as f32, Chunk::SIZE.1 as f32);
+        let row_bytes = Chunk::SIZE.0 as usize * 4;
+        let mut ranges = Vec::new();
+        for region in Self::locked_regions(&self.locks, self.editor) {
+            let region = region.with_offset(-origin);
+            let clamp = |x: f32, max: f32| x.round().max(0.0).min(max) as usize;
+            let (left, right) = (clamp(region.left, width), clamp(region.right, width));
+            let (top, bottom) = (clamp(region.top, height), clamp(region.bottom, height));
+            if left < right {
+                ranges.extend((top..bottom).map(|row| row * row_bytes + left * 4 .. row * row_bytes + right * 4));
+            }
+        }
+        ranges
+    }
+
+    // overwrites the chunk's pixels with the given function, putting back the pixels in regions the editor can't paint
+    // into afterwards
+    fn write_pixels<T>(&mut self, position: (i32, i32), write: impl FnOnce(&mut Chunk<'a>) -> T) -> T {
+        let locked = self.locked_ranges(position);
+        self.ensure_chunk_exists(position);
+        let chunk = self.chunks.get_mut(&position).unwrap();
+        let kept: Vec<Vec<u8>> = {
+            let pixels = chunk.pixels_mut();
+            locked.iter().map(|range| pixels[range.clone()].to_vec()).collect()
+        };
+        let result = write(chunk);
+        let pixels = chunk.pixels_mut();
+        for (range, kept) in locked.into_iter().zip(kept) {
+            pixels[range].copy_from_slice(&kept);
+        }
+        chunk.png_data = None;
+        self.dirty_chunks.insert(position);
+        self.has_unsaved_changes = true;
+        result
+    }
+
+    // brings an evicted chunk back into memory
+    fn restore_chunk(
+        chunks: &mut HashMap<(i32, i32), Chunk<'a>>,
+        evicted: &mut HashMap<(i32, i32), Vec<u8>>,
+        position: (i32, i32),
+    ) {
+        if let Some(png_data) = evicted.remove(&position) {
+            let mut chunk = Chunk::new();
+            if let Err(error) = chunk.decode_png_data(&png_data) {
+                log::error!("could not restore evicted chunk {:?}: {}", position, error);
+            }
+            chunk.png_data = Some(png_data);
+            chunks.insert(position, chunk);
+        }
+    }
+
+    // returns the chunk at the given position, bringing it back into memory if it was evicted
+    fn chunk_mut(&mut self, position: (i32, i32)) -> Option<&mut Chunk<'a>> {
+        Self::restore_chunk(&mut self.chunks, &mut self.evicted, position);
+        let chunk = self.chunks.get_mut(&position)?;
+        chunk.last_used = Instant::now();
+        Some(chunk)
     }
 
     fn ensure_chunk_exists(&mut self, position: (i32, i32)) {
-        if !self.chunks.contains_key(&position) {
+        if self.chunk_mut(position).is_none() {
             self.chunks.insert(position, Chunk::new());
         }
     }
 
+    pub fn set_memory_budget(&mut self, budget: Option<usize>) {
+        self.memory_budget = budget;
+    }
+
+    // keeps the chunks in the visible area in memory, and evicts the least recently used chunks outside of it while
+    // the canvas is over its memory budget. called every frame.
+    // the budget is only checked every so often, and chunks are encoded to PNG on another thread before they're
+    // evicted, so that going over it doesn't stall the frame
+    pub fn manage_memory(&mut self, visible: Rect) {
+        let visible: HashSet<_> = Self::chunks_in_rect(visible).collect();
+        for &position in &visible {
+            self.chunk_mut(position);
+        }
+
+        let mut batch_done = false;
+        if let Some(evicting) = &self.evicting {
+            let encoded = match evicting.try_recv() {
+                Ok(encoded) => encoded,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => Vec::new(),
+            };
+            self.evicting = None;
+            batch_done = true;
+            for (position, pixels, png_data) in encoded {
+                // chunks that came into view or were drawn to while they were being encoded are kept in memory
+                if visible.contains(&position) { continue }
+                let chunk = match self.chunks.get_mut(&position) {
+                    Some(chunk) => chunk,
+                    None => continue,
+                };
+                if chunk.pixels_mut() != &pixels[..] { continue }
+                // chunks that can't be encoded are kept in memory, so that nothing is lost
+                if let Some(png_data) = png_data {
+                    self.chunks.remove(&position);
+                    self.evicted.insert(position, png_data);
+                }
+            }
+        }
+
+        let max_chunks = match self.memory_budget {
+            Some(budget) => budget / Chunk::BYTE_SIZE,
+            None => return,
+        };
+        if self.chunks.len() <= max_chunks || (!batch_done && self.last_eviction.elapsed() < Self::EVICTION_INTERVAL) {
+            return
+        }
+        self.last_eviction = Instant::now();
+        let mut least_recently_used: Vec<_> = self.chunks
+            .iter()
+            .filter(|(position, _)| !visible.contains(position))
+            .map(|(&position, chunk)| (chunk.last_used, position))
+            .collect();
+        least_recently_used.sort_unstable();
+        let excess = self.chunks.len() - max_chunks;
+        let mut unencoded = Vec::new();
+        for (_, position) in least_recently_used.into_iter().take(excess) {
+            let chunk = self.chunks.get_mut(&position).unwrap();
+            match chunk.png_data.take() {
+                Some(png_data) => {
+                    self.chunks.remove(&position);
+                    self.evicted.insert(position, png_data);
+                },
+                None if unencoded.len() < Self::EVICTION_BATCH_SIZE => {
+                    unencoded.push((position, Vec::from(&*chunk.pixels_mut())));
+                },
+                None => (),
+            }
+        }
+        if unencoded.is_empty() {
+            return
+        }
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let encoded = unencoded
+                .into_iter()
+                .map(|(position, pixels)| {
+                    let png_data = PaintCanvas::encode_chunk_pixels(&pixels);
+                    (position, pixels, png_data)
+                })
+                .collect();
+            let _ = sender.send(encoded);
+        });
+        self.evicting = Some(receiver);
+    }
+
     pub fn stroke(
         &mut self,
         from: impl Into<Point>,
@@ -143,7 +608,7 @@ impl<'a> PaintCanvas<'a> {
         let b = to.into();
         let step_count = i32::max((Point::distance(a, b) / 4.0) as _, 2);
         let paint = brush.as_paint();
-        let stroke_width = paint.stroke_width();
+        let stroke_width = brush.stroke_width();
         let half_stroke_width = stroke_width / 2.0;
 
         let mut delta = b - a;
@@ -171,8 +636,13 @@ impl<'a> PaintCanvas<'a> {
                         self.ensure_chunk_exists(chunk_position);
                         let chunk = self.chunks.get_mut(&chunk_position).unwrap();
                         let screen_position = Chunk::screen_position(chunk_position);
+                        chunk.canvas.save();
+                        Self::clip_locks(&mut chunk.canvas, &self.locks, self.editor, chunk_position);
                         chunk.canvas.draw_line(a - screen_position, b - screen_position, &paint);
+                        chunk.canvas.restore();
                         chunk.png_data = None;
+                        self.dirty_chunks.insert(chunk_position);
+                        self.has_unsaved_changes = true;
                     }
                     self.stroked_chunks.insert(chunk_position);
                     p.offset(delta);
@@ -182,6 +652,407 @@ impl<'a> PaintCanvas<'a> {
 
     }
 
+    // strokes through all the points, as received from someone else in the room
+    pub fn stroke_points(&mut self, points: &[StrokePoint]) {
+        if points.is_empty() { return; } // failsafe
+
+        let mut from = points[0].point;
+        let first_index = if points.len() > 1 { 1 } else { 0 };
+        for point in &points[first_index..] {
+            self.stroke(from, point.point, &point.brush);
+            from = point.point;
+        }
+    }
+
+    // returns the Catmull-Rom spline through the points, flattened into straight lines. the spline goes through all
+    // points except the first and the last, which only steer the direction it starts and ends in. like in
+    // stroke_points, each line is painted with the brush of the point it leads to
+    pub fn curve_segments(points: &[StrokePoint]) -> Vec<(Point, Point, &Brush)> {
+        let mut segments = Vec::new();
+        for window in points.windows(4) {
+            let (p0, p1, p2, p3) = (window[0].point, window[1].point, window[2].point, window[3].point);
+            let step_count = i32::max((Point::distance(p1, p2) / Self::CURVE_STEP).ceil() as i32, 1);
+            let mut from = p1;
+            for step in 1..=step_count {
+                let t = step as f32 / step_count as f32;
+                let spline = |a: f32, b: f32, c: f32, d: f32| {
+                    0.5 * (2.0 * b
+                        + (c - a) * t
+                        + (2.0 * a - 5.0 * b + 4.0 * c - d) * t * t
+                        + (3.0 * b - a - 3.0 * c + d) * t * t * t)
+                };
+                let to = Point::new(spline(p0.x, p1.x, p2.x, p3.x), spline(p0.y, p1.y, p2.y, p3.y));
+                segments.push((from, to, &window[2].brush));
+                from = to;
+            }
+        }
+        segments
+    }
+
+    // strokes along the Catmull-Rom spline through the points, see curve_segments
+    pub fn stroke_curve(&mut self, points: &[StrokePoint]) {
+        for (from, to, brush) in Self::curve_segments(points) {
+            self.stroke(from, to, brush);
+        }
+    }
+
+    // returns the positions of all chunks that could be touched by a stroke going from a to b
+    pub fn chunks_in_stroke(a: Point, b: Point, stroke_width: f32) -> impl Iterator<Item = (i32, i32)> {
+        let half_stroke_width = stroke_width / 2.0;
+        Self::chunks_in_rect(Rect::new(
+            f32::min(a.x, b.x) - half_stroke_width,
+            f32::min(a.y, b.y) - half_stroke_width,
+            f32::max(a.x, b.x) + half_stroke_width,
+            f32::max(a.y, b.y) + half_stroke_width,
+        ))
+    }
+
+    // whether the point is somewhere it's okay to paint at, see MAX_COORDINATE
+    pub fn is_in_bounds(point: Point) -> bool {
+        point.x.is_finite()
+            && point.y.is_finite()
+            && point.x.abs() <= Self::MAX_COORDINATE
+            && point.y.abs() <= Self::MAX_COORDINATE
+    }
+
+    // returns the positions of all chunks that intersect the given rectangle
+    pub fn chunks_in_rect(rect: Rect) -> impl Iterator<Item = (i32, i32)> {
+        let top_left_chunk = (
+            (rect.left / Chunk::SIZE.0 as f32).floor() as i32,
+            (rect.top / Chunk::SIZE.1 as f32).floor() as i32,
+        );
+        let bottom_right_chunk = (
+            (rect.right / Chunk::SIZE.0 as f32).floor() as i32,
+            (rect.bottom / Chunk::SIZE.1 as f32).floor() as i32,
+        );
+        (top_left_chunk.1 ..= bottom_right_chunk.1)
+            .flat_map(move |y| (top_left_chunk.0 ..= bottom_right_chunk.0).map(move |x| (x, y)))
+    }
+
+    // draws onto all chunks that intersect the given rectangle. the callback is run once for every chunk, with the
+    // chunk's canvas translated such that it can draw in paint canvas coordinates
+    pub fn draw(&mut self, rect: Rect, callback: impl Fn(&mut Canvas)) {
+        for chunk_position in Self::chunks_in_rect(rect) {
+            self.ensure_chunk_exists(chunk_position);
+            let chunk = self.chunks.get_mut(&chunk_position).unwrap();
+            let screen_position = Chunk::screen_position(chunk_position);
+            chunk.canvas.save();
+            Self::clip_locks(&mut chunk.canvas, &self.locks, self.editor, chunk_position);
+            chunk.canvas.translate(-screen_position);
+            callback(&mut chunk.canvas);
+            chunk.canvas.restore();
+            chunk.png_data = None;
+            self.dirty_chunks.insert(chunk_position);
+            self.has_unsaved_changes = true;
+        }
+    }
+
+    // draws copies of the image, each scaled uniformly to fit its rectangle, and faded out to its opacity. all the
+    // copies landing on a chunk are drawn in a single draw_atlas call, which is a lot cheaper than drawing them one
+    // by one when there are thousands of them, as there are in long stamped strokes
+    pub fn draw_instances(&mut self, image: &Image, instances: &[(Rect, f32)], paint: &Paint) {
+        let mut batches: HashMap<(i32, i32), (Vec<RSXform>, Vec<Color>)> = HashMap::new();
+        for &(rect, opacity) in instances {
+            let scale = rect.width() / image.width() as f32;
+            let color = Color4f::new(1.0, 1.0, 1.0, opacity).to_color();
+            for chunk_position in Self::chunks_in_rect(rect.with_outset((1.0, 1.0))) {
+                let screen_position = Chunk::screen_position(chunk_position);
+                let (transforms, colors) = batches.entry(chunk_position).or_default();
+                transforms.push(RSXform::new(scale, 0.0, Point::new(rect.left, rect.top) - screen_position));
+                colors.push(color);
+            }
+        }
+        let bounds = Rect::from_wh(image.width() as f32, image.height() as f32);
+        for (chunk_position, (transforms, colors)) in batches {
+            self.ensure_chunk_exists(chunk_position);
+            let chunk = self.chunks.get_mut(&chunk_position).unwrap();
+            let sources = vec![bounds; transforms.len()];
+            chunk.canvas.save();
+            Self::clip_locks(&mut chunk.canvas, &self.locks, self.editor, chunk_position);
+            // the image is multiplied by the color, which is white with the instance's opacity
+            chunk.canvas.draw_atlas(image, &transforms, &sources, &colors[..], BlendMode::Modulate, None, paint);
+            chunk.canvas.restore();
+            chunk.png_data = None;
+            self.dirty_chunks.insert(chunk_position);
+            self.has_unsaved_changes = true;
+        }
+    }
+
+    // returns a copy of the chunk's pixels, or None if the chunk doesn't exist
+    pub fn chunk_pixels(&mut self, position: (i32, i32)) -> Option<Vec<u8>> {
+        self.chunk_mut(position).map(|chunk| Vec::from(&*chunk.pixels_mut()))
+    }
+
+    // overwrites the chunk's pixels, except in regions the editor can't paint into. None clears the chunk to
+    // transparency
+    pub fn set_chunk_pixels(&mut self, position: (i32, i32), pixels: Option<&[u8]>) {
+        self.write_pixels(position, |chunk| {
+            let dest = chunk.pixels_mut();
+            match pixels {
+                Some(pixels) => dest.copy_from_slice(pixels),
+                None => dest.iter_mut().for_each(|x| *x = 0),
+            }
+        });
+    }
+
+    pub fn chunk_png_data(&mut self, position: (i32, i32)) -> Option<Vec<u8>> {
+        if let Some(png_data) = self.evicted.get(&position) {
+            return Some(png_data.clone())
+        }
+        self.chunk_mut(position)?.png_data().map(Vec::from)
+    }
+
+    // returns the chunk's PNG data if it doesn't have to be encoded first
+    pub fn cached_png_data(&self, position: (i32, i32)) -> Option<Vec<u8>> {
+        match self.evicted.get(&position) {
+            Some(png_data) => Some(png_data.clone()),
+            None => self.chunks.get(&position)?.png_data.clone(),
+        }
+    }
+
+    // encodes pixels returned by chunk_pixels to PNG. this doesn't need the canvas, so it can be done on another thread
+    pub fn encode_chunk_pixels(pixels: &[u8]) -> Option<Vec<u8>> {
+        Chunk::encode_png_data(pixels)
+    }
+
+    // caches PNG data that was encoded from the given pixels on another thread. returns false if the chunk has changed
+    // since, in which case the PNG data is outdated and isn't cached
+    pub fn cache_png_data(&mut self, position: (i32, i32), pixels: &[u8], png_data: &[u8]) -> bool {
+        let chunk = match self.chunk_mut(position) {
+            Some(chunk) => chunk,
+            None => return false,
+        };
+        if chunk.pixels_mut() != pixels {
+            return false
+        }
+        chunk.png_data = Some(Vec::from(png_data));
+        true
+    }
+
+    // compares the chunk against its previous pixels, as returned by chunk_pixels, to figure out which parts of it
+    // have to be sent to others. if `allow_full` is false, the changed tiles are returned even when it'd be cheaper to
+    // send all of the chunk
+    pub fn chunk_delta(&mut self, position: (i32, i32), previous: Option<&[u8]>, allow_full: bool) -> ChunkDelta {
+        let chunk = match self.chunk_mut(position) {
+            Some(chunk) => chunk,
+            None => return ChunkDelta::Unchanged,
+        };
+        let pixels = chunk.pixels_mut();
+        let changed: Vec<u8> = (0..Chunk::TILE_COUNT)
+            .filter(|&index| Chunk::tile_rows(index).any(|row| match previous {
+                Some(previous) => previous[row.clone()] != pixels[row],
+                // chunks that didn't exist before were fully transparent
+                None => pixels[row].iter().any(|&x| x != 0),
+            }))
+            .collect();
+        if changed.is_empty() {
+            ChunkDelta::Unchanged
+        } else if allow_full && changed.len() > Chunk::TILE_COUNT as usize / 2 {
+            match chunk.png_data() {
+                Some(png_data) => ChunkDelta::Full(png_data.into()),
+                None => ChunkDelta::Unchanged,
+            }
+        } else {
+            let tiles = changed
+                .into_iter()
+                .filter_map(|index| Some((index, chunk.tile_png_data(index)?)))
+                .collect();
+            ChunkDelta::Tiles(tiles)
+        }
+    }
+
+    // returns the PNG data of all of the chunk's tiles, which overwrite all of it on others' canvases
+    pub fn chunk_tiles(&mut self, position: (i32, i32)) -> Vec<(u8, Vec<u8>)> {
+        let chunk = match self.chunk_mut(position) {
+            Some(chunk) => chunk,
+            None => return Vec::new(),
+        };
+        (0..Chunk::TILE_COUNT).filter_map(|index| Some((index, chunk.tile_png_data(index)?))).collect()
+    }
+
+    // overwrites the chunk's pixels with an image with straight alpha, like the ones returned by capture().
+    // the image must be exactly as large as the chunk
+    pub fn set_chunk_image(&mut self, position: (i32, i32), image: &RgbaImage) {
+        self.ensure_chunk_exists(position);
+        let chunk = self.chunks.get_mut(&position).unwrap();
+        let pixels = chunk.pixels_mut();
+        for (pixel, &Rgba([r, g, b, a])) in pixels.chunks_exact_mut(4).zip(image.pixels()) {
+            pixel.copy_from_slice(&chunk.color_to_pixel(Color::from_argb(a, r, g, b)));
+        }
+        chunk.png_data = None;
+        self.dirty_chunks.insert(position);
+        self.has_unsaved_changes = true;
+    }
+
+    // copies the pixels inside of the given rectangle into an image with straight (non-premultiplied) alpha.
+    // areas without any chunks end up transparent
+    pub fn capture(&mut self, rect: IRect) -> RgbaImage {
+        let mut image = RgbaImage::new(rect.width() as u32, rect.height() as u32);
+        let (chunk_width, chunk_height) = Chunk::SIZE;
+        let top_left_chunk = (rect.left.div_euclid(chunk_width), rect.top.div_euclid(chunk_height));
+        let bottom_right_chunk = ((rect.right - 1).div_euclid(chunk_width), (rect.bottom - 1).div_euclid(chunk_height));
+
+        for chunk_y in top_left_chunk.1 ..= bottom_right_chunk.1 {
+            for chunk_x in top_left_chunk.0 ..= bottom_right_chunk.0 {
+                let mut decoded;
+                let chunk = match self.chunks.get_mut(&(chunk_x, chunk_y)) {
+                    Some(chunk) => chunk,
+                    // evicted chunks are only decoded temporarily, so that capturing a large area (eg. when saving
+                    // the whole canvas) doesn't bring all of it back into memory
+                    None => match self.evicted.get(&(chunk_x, chunk_y)) {
+                        Some(png_data) => {
+                            decoded = Chunk::new();
+                            if let Err(error) = decoded.decode_png_data(png_data) {
+                                log::error!("could not decode evicted chunk {:?}: {}", (chunk_x, chunk_y), error);
+                                continue
+                            }
+                            &mut decoded
+                        },
+                        None => continue,
+                    },
+                };
+                // N32 is BGRA on little endian machines
+                let bgra = chunk.bitmap.color_type() == skulpin::skia_safe::ColorType::BGRA8888;
+                let pixels = chunk.pixels_mut();
+
+                let (chunk_left, chunk_top) = (chunk_x * chunk_width, chunk_y * chunk_height);
+                let left = i32::max(rect.left, chunk_left);
+                let top = i32::max(rect.top, chunk_top);
+                let right = i32::min(rect.right, chunk_left + chunk_width);
+                let bottom = i32::min(rect.bottom, chunk_top + chunk_height);
+                for y in top..bottom {
+                    for x in left..right {
+                        let index = (((y - chunk_top) * chunk_width + (x - chunk_left)) * 4) as usize;
+                        let pixel = &pixels[index..index + 4];
+                        let (r, g, b, a) =
+                            if bgra { (pixel[2], pixel[1], pixel[0], pixel[3]) }
+                            else { (pixel[0], pixel[1], pixel[2], pixel[3]) };
+                        let unpremultiply = |c: u8| {
+                            if a == 0 { 0 } else { u32::min(c as u32 * 255 / a as u32, 255) as u8 }
+                        };
+                        let position = ((x - rect.left) as u32, (y - rect.top) as u32);
+                        image.put_pixel(position.0, position.1, Rgba([
+                            unpremultiply(r),
+                            unpremultiply(g),
+                            unpremultiply(b),
+                            a,
+                        ]));
+                    }
+                }
+            }
+        }
+
+        image
+    }
+
+    // the rectangle containing all chunks that exist, or None if the canvas is empty
+    pub fn bounds(&self) -> Option<IRect> {
+        self.chunk_positions().fold(None, |bounds, position| {
+            let chunk = Self::chunk_rect(position);
+            Some(match bounds {
+                Some(bounds) => IRect::new(
+                    i32::min(bounds.left, chunk.left),
+                    i32::min(bounds.top, chunk.top),
+                    i32::max(bounds.right, chunk.right),
+                    i32::max(bounds.bottom, chunk.bottom),
+                ),
+                None => chunk,
+            })
+        })
+    }
+
+    // finds all pixels connected to the seed pixel whose color differs from the seed's color by at most `tolerance`
+    // in every channel.
+    // the fill doesn't spread into chunks that don't exist (as that would make it infinite), except for the chunk
+    // the seed pixel is in. seeds out of bounds (see MAX_COORDINATE) fill nothing
+    pub fn flood_fill_region(&mut self, seed: (i32, i32), tolerance: u8) -> FillRegion {
+        let mut region = FillRegion { pixels: HashMap::new() };
+        if !Self::is_in_bounds(Point::new(seed.0 as f32, seed.1 as f32)) {
+            return region
+        }
+        let (chunk_width, chunk_height) = Chunk::SIZE;
+        let locate = |(x, y): (i32, i32)| (
+            (x.div_euclid(chunk_width), y.div_euclid(chunk_height)),
+            ((y.rem_euclid(chunk_height) * chunk_width + x.rem_euclid(chunk_width)) * 4) as usize,
+        );
+        let (seed_chunk, _) = locate(seed);
+        let pixel_at = |
+            chunks: &mut HashMap<(i32, i32), Chunk<'a>>,
+            evicted: &mut HashMap<(i32, i32), Vec<u8>>,
+            chunk_position,
+            index: usize,
+        | {
+            Self::restore_chunk(chunks, evicted, chunk_position);
+            match chunks.get_mut(&chunk_position) {
+                Some(chunk) => {
+                    let pixels = chunk.pixels_mut();
+                    Some([pixels[index], pixels[index + 1], pixels[index + 2], pixels[index + 3]])
+                },
+                None if chunk_position == seed_chunk => Some([0; 4]),
+                None => None,
+            }
+        };
+
+        let (_, seed_index) = locate(seed);
+        let target = pixel_at(&mut self.chunks, &mut self.evicted, seed_chunk, seed_index).unwrap();
+        let mut visited: HashMap<(i32, i32), Vec<bool>> = HashMap::new();
+        let mut stack = vec![seed];
+        while let Some((x, y)) = stack.pop() {
+            let (chunk_position, index) = locate((x, y));
+            let pixel = match pixel_at(&mut self.chunks, &mut self.evicted, chunk_position, index) {
+                Some(pixel) => pixel,
+                None => continue,
+            };
+            let visited = visited
+                .entry(chunk_position)
+                .or_insert_with(|| vec![false; (chunk_width * chunk_height) as usize]);
+            if visited[index / 4] { continue }
+            visited[index / 4] = true;
+            let similar = pixel.iter().zip(&target).all(|(&a, &b)| (a as i16 - b as i16).abs() <= tolerance as i16);
+            if !similar { continue }
+
+            region.pixels.entry(chunk_position).or_default().push(index);
+            // pixels at the very edge of the i32 range have no neighbors past it
+            let neighbors = [
+                (x.checked_add(1), Some(y)),
+                (x.checked_sub(1), Some(y)),
+                (Some(x), y.checked_add(1)),
+                (Some(x), y.checked_sub(1)),
+            ];
+            stack.extend(neighbors.iter().filter_map(|&(x, y)| Some((x?, y?))));
+        }
+
+        region
+    }
+
+    // fills all pixels in the region with the given color
+    pub fn fill_region(&mut self, region: &FillRegion, color: Color) {
+        for (&chunk_position, indices) in &region.pixels {
+            self.ensure_chunk_exists(chunk_position);
+            let chunk = self.chunks.get_mut(&chunk_position).unwrap();
+            let pixel = chunk.color_to_pixel(color);
+            let pixels = chunk.pixels_mut();
+            let screen_position = Chunk::screen_position(chunk_position);
+            let (locks, editor) = (&self.locks, self.editor);
+            let is_locked = |index: usize| {
+                let (x, y) = ((index / 4) as i32 % Chunk::SIZE.0, (index / 4) as i32 / Chunk::SIZE.0);
+                let point = screen_position + Point::new(x as f32 + 0.5, y as f32 + 0.5);
+                Self::locked_regions(locks, editor).any(|region| {
+                    point.x >= region.left && point.x < region.right && point.y >= region.top && point.y < region.bottom
+                })
+            };
+            for &index in indices {
+                if !is_locked(index) {
+                    pixels[index..index + 4].copy_from_slice(&pixel);
+                }
+            }
+            chunk.png_data = None;
+            self.dirty_chunks.insert(chunk_position);
+            self.has_unsaved_changes = true;
+        }
+    }
+
+    // draws all chunks that are in memory. manage_memory() makes sure that the visible ones are
     pub fn draw_to(
         &self,
         canvas: &mut Canvas,
@@ -192,29 +1063,225 @@ impl<'a> PaintCanvas<'a> {
         }
     }
 
-    pub fn png_data(&mut self) -> PngData<'_, 'a> {
-        PngData {
-            iter: self.chunks.iter_mut(),
-        }
-    }
-
     pub fn decode_png_data(&mut self, to_chunk: (i32, i32), data: &[u8]) -> Result<(), ImageError> {
         self.ensure_chunk_exists(to_chunk);
         let chunk = self.chunks.get_mut(&to_chunk).unwrap();
+        chunk.png_data = None;
+        self.dirty_chunks.insert(to_chunk);
+        self.has_unsaved_changes = true;
         chunk.decode_png_data(data)
     }
 
+    // decodes tiles of the chunk sent by the editor. anyone can send tiles, so they're kept out of regions the editor
+    // can't paint into
+    pub fn decode_tile_data(&mut self, to_chunk: (i32, i32), tiles: &[(u8, Vec<u8>)]) -> Result<(), ImageError> {
+        self.write_pixels(to_chunk, |chunk| {
+            for (index, data) in tiles {
+                chunk.decode_tile_png_data(*index, data)?;
+            }
+            Ok(())
+        })
+    }
+
+    // returns the positions of all chunks that exist
+    pub fn chunk_positions(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.chunks.keys().chain(self.evicted.keys()).copied()
+    }
+
+    pub fn chunk_size() -> (i32, i32) {
+        Chunk::SIZE
+    }
+
+    // returns the rectangle a chunk covers, in paint canvas coordinates
+    pub fn chunk_rect(position: (i32, i32)) -> IRect {
+        let (width, height) = Chunk::SIZE;
+        IRect::from_xywh(position.0 * width, position.1 * height, width, height)
+    }
+
+    // returns the positions of all chunks that were modified since the last call, and forgets about them
+    pub fn take_dirty_chunks(&mut self) -> Vec<(i32, i32)> {
+        self.dirty_chunks.take()
+    }
+
+    // the number of the last modification made to the canvas, to be passed to chunks_changed_since later
+    pub fn revision(&self) -> u64 {
+        self.dirty_chunks.revision
+    }
+
+    // returns the positions of all chunks that were modified after the given revision. unlike take_dirty_chunks, this
+    // doesn't affect anyone else keeping track of what changed
+    pub fn chunks_changed_since(&self, revision: u64) -> Vec<(i32, i32)> {
+        self.dirty_chunks.revisions
+            .iter()
+            .filter(|&(_, &changed)| changed > revision)
+            .map(|(&position, _)| position)
+            .collect()
+    }
+
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.has_unsaved_changes
+    }
+
+    // called after the canvas is saved to or loaded from a project
+    pub fn mark_saved(&mut self) {
+        self.has_unsaved_changes = false;
+        self.last_saved = Some(Instant::now());
+    }
+
+    // collects the canvas's statistics. this goes through every pixel of every chunk in memory, so it shouldn't be
+    // called every frame
+    pub fn statistics(&mut self) -> Statistics {
+        let cached_png_bytes: usize = self.chunks
+            .values()
+            .filter_map(|chunk| chunk.png_data.as_ref())
+            .map(Vec::len)
+            .sum();
+        Statistics {
+            chunk_count: self.chunks.len() + self.evicted.len(),
+            empty_chunk_count: self.chunks.values_mut().map(Chunk::is_empty).filter(|&empty| empty).count(),
+            evicted_chunk_count: self.evicted.len(),
+            bounds: self.bounds(),
+            pixel_bytes: self.chunks.len() * Chunk::BYTE_SIZE,
+            png_bytes: cached_png_bytes + self.evicted.values().map(Vec::len).sum::<usize>(),
+            last_saved: self.last_saved,
+        }
+    }
+
 }
 
-impl Iterator for PngData<'_, '_> {
-    type Item = ((i32, i32), Vec<u8>);
+impl FillRegion {
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if let Some((position, chunk)) = self.iter.next() {
-            if let Some(png_data) = chunk.png_data() {
-                return Some((*position, Vec::from(png_data)))
+    pub fn chunks(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.pixels.keys().copied()
+    }
+
+    // the region's pixels in each chunk, as sorted runs of (first pixel, length). pixels are numbered row by row from
+    // the chunk's top left corner. this is the form fills are sent to other people in
+    pub fn runs(&self) -> impl Iterator<Item = ((i32, i32), Vec<(u32, u32)>)> + '_ {
+        self.pixels.iter().map(|(&chunk_position, indices)| {
+            let mut pixels: Vec<u32> = indices.iter().map(|&index| (index / 4) as u32).collect();
+            pixels.sort_unstable();
+            let mut runs: Vec<(u32, u32)> = Vec::new();
+            for pixel in pixels {
+                match runs.last_mut() {
+                    Some((start, length)) if *start + *length == pixel => *length += 1,
+                    _ => runs.push((pixel, 1)),
+                }
             }
+            (chunk_position, runs)
+        })
+    }
+
+    // the inverse of runs(), for a single chunk. returns None if the chunk is out of bounds, or the runs aren't sorted,
+    // overlap, or don't fit in the chunk, which can only happen if someone sent them on purpose
+    pub fn from_runs(chunk_position: (i32, i32), runs: &[(u32, u32)]) -> Option<Self> {
+        let (chunk_width, chunk_height) = (Chunk::SIZE.0 as f32, Chunk::SIZE.1 as f32);
+        let top_left = Point::new(chunk_position.0 as f32 * chunk_width, chunk_position.1 as f32 * chunk_height);
+        let bottom_right = top_left + Point::new(chunk_width, chunk_height);
+        if !PaintCanvas::is_in_bounds(top_left) || !PaintCanvas::is_in_bounds(bottom_right) {
+            return None
+        }
+        let mut indices = Vec::new();
+        let mut end = 0;
+        for &(start, length) in runs {
+            if start < end { return None }
+            end = start.checked_add(length)?;
+            if end > (Chunk::SIZE.0 * Chunk::SIZE.1) as u32 { return None }
+            indices.extend((start..end).map(|pixel| pixel as usize * 4));
         }
-        None
+        let mut pixels = HashMap::new();
+        if !indices.is_empty() {
+            pixels.insert(chunk_position, indices);
+        }
+        Some(Self { pixels })
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+
+    fn tile_png(color: [u8; 4]) -> Vec<u8> {
+        let size = Chunk::TILE_SIZE as u32;
+        let pixels: Vec<u8> = color.iter().copied().cycle().take((size * size * 4) as usize).collect();
+        let mut png_data = Vec::new();
+        PngEncoder::new(Cursor::new(&mut png_data)).encode(&pixels, size, size, ColorType::Rgba8).unwrap();
+        png_data
+    }
+
+    fn pixel(paint_canvas: &mut PaintCanvas, (x, y): (usize, usize)) -> [u8; 4] {
+        let pixels = paint_canvas.chunk_pixels((0, 0)).unwrap();
+        let offset = (y * Chunk::SIZE.0 as usize + x) * 4;
+        [pixels[offset], pixels[offset + 1], pixels[offset + 2], pixels[offset + 3]]
+    }
+
+    #[test]
+    fn tiles_stay_out_of_other_peoples_locks() {
+        let (owner, sender) = (SocketAddr::from(([127, 0, 0, 1], 1)), SocketAddr::from(([127, 0, 0, 1], 2)));
+        let mut paint_canvas = PaintCanvas::new();
+        // the lock covers the left half of the first tile
+        paint_canvas.set_locks(vec![(Some(owner), Rect::from_xywh(0.0, 0.0, 16.0, 32.0))]);
+        paint_canvas.set_editor(Some(sender));
+        paint_canvas.decode_tile_data((0, 0), &[(0, tile_png(RED)), (1, tile_png(RED))]).unwrap();
+        assert_eq!(pixel(&mut paint_canvas, (0, 0)), [0; 4]);
+        assert_eq!(pixel(&mut paint_canvas, (15, 31)), [0; 4]);
+        assert_eq!(pixel(&mut paint_canvas, (16, 0)), RED);
+        assert_eq!(pixel(&mut paint_canvas, (40, 10)), RED);
+
+        // the owner of the lock can still paint into it
+        paint_canvas.set_editor(Some(owner));
+        paint_canvas.decode_tile_data((0, 0), &[(0, tile_png(RED))]).unwrap();
+        assert_eq!(pixel(&mut paint_canvas, (0, 0)), RED);
+    }
+
+    #[test]
+    fn undoing_leaves_other_peoples_locks_alone() {
+        let owner = SocketAddr::from(([127, 0, 0, 1], 1));
+        let mut paint_canvas = PaintCanvas::new();
+        paint_canvas.decode_tile_data((0, 0), &[(0, tile_png(RED))]).unwrap();
+        paint_canvas.set_locks(vec![(Some(owner), Rect::from_xywh(8.0, 8.0, 8.0, 8.0))]);
+        paint_canvas.set_chunk_pixels((0, 0), None);
+        assert_eq!(pixel(&mut paint_canvas, (8, 8)), RED);
+        assert_eq!(pixel(&mut paint_canvas, (15, 15)), RED);
+        assert_eq!(pixel(&mut paint_canvas, (16, 16)), [0; 4]);
+        assert_eq!(pixel(&mut paint_canvas, (0, 0)), [0; 4]);
+    }
+
+    #[test]
+    fn seeds_out_of_bounds_fill_nothing() {
+        let mut paint_canvas = PaintCanvas::new();
+        for &seed in &[(i32::MAX, i32::MAX), (i32::MIN, 0), (0, i32::MAX - 1)] {
+            assert_eq!(paint_canvas.flood_fill_region(seed, 255).chunks().count(), 0);
+        }
+    }
+
+    #[test]
+    fn fill_regions_survive_being_sent_as_runs() {
+        let mut paint_canvas = PaintCanvas::new();
+        // a red tile in an otherwise empty chunk, so the fill goes around it
+        paint_canvas.decode_tile_data((0, 0), &[(0, tile_png(RED))]).unwrap();
+        let region = paint_canvas.flood_fill_region((100, 100), 0);
+        let runs: Vec<_> = region.runs().collect();
+        assert_eq!(runs.len(), 1);
+        let (chunk, runs) = &runs[0];
+        let received = FillRegion::from_runs(*chunk, runs).unwrap();
+        paint_canvas.fill_region(&received, Color::from_rgb(0, 0, 255));
+        assert_eq!(pixel(&mut paint_canvas, (0, 0)), RED);
+        assert_eq!(pixel(&mut paint_canvas, (31, 31)), RED);
+        assert_eq!(pixel(&mut paint_canvas, (32, 0)), [0, 0, 255, 255]);
+        assert_eq!(pixel(&mut paint_canvas, (255, 255)), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn invalid_fill_runs_are_refused() {
+        let size = (Chunk::SIZE.0 * Chunk::SIZE.1) as u32;
+        assert!(FillRegion::from_runs((0, 0), &[(0, size)]).is_some());
+        assert!(FillRegion::from_runs((0, 0), &[(1, size)]).is_none());
+        assert!(FillRegion::from_runs((0, 0), &[(u32::MAX, 2)]).is_none());
+        assert!(FillRegion::from_runs((0, 0), &[(10, 10), (15, 10)]).is_none());
+        assert!(FillRegion::from_runs((i32::MAX, 0), &[(0, 1)]).is_none());
     }
 }
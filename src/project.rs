@@ -0,0 +1,171 @@
+// the NetCanv project format.
+// a project is a folder containing a canvas.toml manifest and one PNG image per chunk, named after the chunk's
+// position, eg. 0,0.png or -1,2.png. the images are ordinary PNGs with straight alpha, so they can be opened and
+// edited in other programs too.
+
+use std::path::{Path, PathBuf};
+
+use ::image::{ImageError, ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::paint_canvas::PaintCanvas;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Image error: {0}")]
+    Image(#[from] ImageError),
+    #[error("Invalid canvas.toml: {0}")]
+    InvalidManifest(#[from] toml::de::Error),
+    #[error("Could not write canvas.toml: {0}")]
+    Serialize(#[from] toml::ser::Error),
+    #[error("{0} is not a NetCanv canvas folder (there's no canvas.toml inside)")]
+    InvalidCanvasFolder(PathBuf),
+    #[error("The canvas was saved by a newer version of NetCanv (format version {0})")]
+    UnsupportedVersion(u32),
+    #[error("The canvas uses {0}×{1} chunks, which aren't supported by this version of NetCanv")]
+    UnsupportedChunkSize(i32, i32),
+    #[error("The chunk image {0} has the wrong size")]
+    InvalidChunk(PathBuf),
+}
+
+const MANIFEST_FILE: &str = "canvas.toml";
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    chunk_size: (i32, i32),
+}
+
+fn chunk_path(dir: &Path, (x, y): (i32, i32)) -> PathBuf {
+    dir.join(format!("{},{}.png", x, y))
+}
+
+// returns the position of the chunk stored in the given file, or None if it's not a chunk image
+fn chunk_position(path: &Path) -> Option<(i32, i32)> {
+    if path.extension()? != "png" { return None }
+    let stem = path.file_stem()?.to_str()?;
+    let (x, y) = stem.split_at(stem.find(',')?);
+    Some((x.parse().ok()?, y[1..].parse().ok()?))
+}
+
+// returns whether the folder contains a project
+pub fn exists(dir: &Path) -> bool {
+    dir.join(MANIFEST_FILE).is_file()
+}
+
+pub fn load(paint_canvas: &mut PaintCanvas, dir: &Path) -> Result<(), Error> {
+    let manifest_path = dir.join(MANIFEST_FILE);
+    if !manifest_path.is_file() {
+        return Err(Error::InvalidCanvasFolder(dir.to_owned()))
+    }
+    let manifest: Manifest = toml::from_str(&std::fs::read_to_string(manifest_path)?)?;
+    if manifest.version > VERSION {
+        return Err(Error::UnsupportedVersion(manifest.version))
+    }
+    if manifest.chunk_size != PaintCanvas::chunk_size() {
+        let (width, height) = manifest.chunk_size;
+        return Err(Error::UnsupportedChunkSize(width, height))
+    }
+
+    let (width, height) = PaintCanvas::chunk_size();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(position) = chunk_position(&path) {
+            let image = ::image::open(&path)?.to_rgba8();
+            if image.dimensions() != (width as u32, height as u32) {
+                return Err(Error::InvalidChunk(path))
+            }
+            paint_canvas.set_chunk_image(position, &image);
+        }
+    }
+    Ok(())
+}
+
+fn save_manifest(dir: &Path) -> Result<(), Error> {
+    let manifest = Manifest {
+        version: VERSION,
+        chunk_size: PaintCanvas::chunk_size(),
+    };
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(MANIFEST_FILE), toml::to_string(&manifest)?)?;
+    Ok(())
+}
+
+// saves only the given chunks. this is much faster than saving the whole canvas, which makes it better suited for
+// autosaving a project that was saved in full before
+pub fn save_chunks(paint_canvas: &mut PaintCanvas, dir: &Path, chunks: &[(i32, i32)]) -> Result<(), Error> {
+    save_manifest(dir)?;
+    for &position in chunks {
+        let image = paint_canvas.capture(PaintCanvas::chunk_rect(position));
+        image.save_with_format(chunk_path(dir, position), ImageFormat::Png)?;
+    }
+    Ok(())
+}
+
+// saves chunk images that were already captured from the canvas. this doesn't need the canvas, so the slow part of
+// saving, which is encoding the images, can be done on another thread
+pub fn save_chunk_images(dir: &Path, images: &[((i32, i32), RgbaImage)]) -> Result<(), Error> {
+    save_manifest(dir)?;
+    for (position, image) in images {
+        image.save_with_format(chunk_path(dir, *position), ImageFormat::Png)?;
+    }
+    Ok(())
+}
+
+// saves the whole canvas, replacing any project that was previously saved to the folder
+pub fn save(paint_canvas: &mut PaintCanvas, dir: &Path) -> Result<(), Error> {
+    let was_project = exists(dir);
+    let chunks: Vec<_> = paint_canvas.chunk_positions().collect();
+    save_chunks(paint_canvas, dir, &chunks)?;
+    // chunks left over from a previous save would come back when the project is loaded. images in folders that weren't
+    // projects before are the user's own, even if they happen to be named like chunks, so they're left alone
+    if !was_project {
+        return Ok(())
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(position) = chunk_position(&path) {
+            if !chunks.contains(&position) {
+                std::fs::remove_file(path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk_image(color: [u8; 4]) -> RgbaImage {
+        let (width, height) = PaintCanvas::chunk_size();
+        RgbaImage::from_pixel(width as u32, height as u32, ::image::Rgba(color))
+    }
+
+    #[test]
+    fn saved_projects_load_back_the_same() {
+        let dir = std::env::temp_dir().join(format!("netcanv-project-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut paint_canvas = PaintCanvas::new();
+        let mut image = chunk_image([255, 0, 0, 255]);
+        image.put_pixel(3, 5, ::image::Rgba([0, 0, 0, 0]));
+        paint_canvas.set_chunk_image((0, 0), &image);
+        paint_canvas.set_chunk_image((-1, 2), &chunk_image([0, 128, 255, 255]));
+        save(&mut paint_canvas, &dir).unwrap();
+        assert!(exists(&dir));
+
+        let mut loaded = PaintCanvas::new();
+        load(&mut loaded, &dir).unwrap();
+        let mut chunks: Vec<_> = loaded.chunk_positions().collect();
+        chunks.sort_unstable();
+        assert_eq!(chunks, [(-1, 2), (0, 0)]);
+        for chunk in chunks {
+            assert!(loaded.chunk_pixels(chunk) == paint_canvas.chunk_pixels(chunk), "chunk {:?} differs", chunk);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+// timelapse recording.
+// while a recording is running, the chunks that changed are snapshotted on an interval. once the recording is
+// stopped, the snapshots are played back one after another and saved as an animated GIF.
+
+use std::fs::File;
+use std::io::{BufWriter, Cursor};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use ::image::{ColorType, Delay, Frame, ImageError, ImageFormat, Rgba, RgbaImage};
+use ::image::codecs::gif::{GifEncoder, Repeat};
+use ::image::codecs::png::PngEncoder;
+use ::image::imageops::{self, FilterType};
+use skulpin::skia_safe::IRect;
+use thiserror::Error;
+
+use crate::paint_canvas::PaintCanvas;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Nothing was painted during the recording")]
+    NothingRecorded,
+    #[error("Could not save the timelapse: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not encode the timelapse: {0}")]
+    Image(#[from] ImageError),
+}
+
+// all chunks that changed since the previous snapshot, as PNG data
+struct Snapshot {
+    chunks: Vec<((i32, i32), Vec<u8>)>,
+}
+
+pub struct Recording {
+    last_snapshot: Instant,
+    snapshots: Vec<Snapshot>,
+}
+
+impl Recording {
+
+    const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(1);
+    // how long each snapshot is shown in the timelapse
+    const FRAME_DELAY_MS: u32 = 100;
+    // how long the final result is shown before the timelapse loops
+    const LAST_FRAME_DELAY_MS: u32 = 2000;
+    // the maximum width or height of the timelapse. larger canvases are scaled down to fit
+    const MAX_SIZE: u32 = 640;
+
+    pub fn start(paint_canvas: &mut PaintCanvas) -> Self {
+        // the first snapshot contains everything that was painted before the recording was started
+        paint_canvas.take_dirty_chunks();
+        let chunks: Vec<_> = paint_canvas.chunk_positions().collect();
+        let mut recording = Self {
+            last_snapshot: Instant::now(),
+            snapshots: Vec::new(),
+        };
+        recording.snapshot(paint_canvas, chunks);
+        recording
+    }
+
+    // takes a snapshot of the chunks that changed, if it's time for one. called every frame
+    pub fn tick(&mut self, paint_canvas: &mut PaintCanvas) {
+        if self.last_snapshot.elapsed() >= Self::SNAPSHOT_INTERVAL {
+            self.last_snapshot = Instant::now();
+            let chunks = paint_canvas.take_dirty_chunks();
+            self.snapshot(paint_canvas, chunks);
+        }
+    }
+
+    // takes one last snapshot, so that nothing painted after the previous one is lost
+    pub fn stop(&mut self, paint_canvas: &mut PaintCanvas) {
+        let chunks = paint_canvas.take_dirty_chunks();
+        self.snapshot(paint_canvas, chunks);
+    }
+
+    fn snapshot(&mut self, paint_canvas: &mut PaintCanvas, chunks: Vec<(i32, i32)>) {
+        // nothing changed, so there's no need for a new frame
+        if chunks.is_empty() { return }
+
+        let chunks = chunks
+            .into_iter()
+            .filter_map(|position| {
+                let image = paint_canvas.capture(PaintCanvas::chunk_rect(position));
+                let mut png_data = Vec::new();
+                match PngEncoder::new(Cursor::new(&mut png_data))
+                    .encode(&image, image.width(), image.height(), ColorType::Rgba8)
+                {
+                    Ok(()) => Some((position, png_data)),
+                    Err(error) => {
+                        log::error!("could not snapshot chunk {:?}: {}", position, error);
+                        None
+                    },
+                }
+            })
+            .collect();
+        self.snapshots.push(Snapshot { chunks });
+    }
+
+    // the rectangle containing all chunks that were recorded
+    fn bounds(&self) -> Option<IRect> {
+        self.snapshots
+            .iter()
+            .flat_map(|snapshot| snapshot.chunks.iter())
+            .map(|&(position, _)| PaintCanvas::chunk_rect(position))
+            .fold(None, |bounds, chunk| Some(match bounds {
+                Some(bounds) => IRect::new(
+                    i32::min(bounds.left, chunk.left),
+                    i32::min(bounds.top, chunk.top),
+                    i32::max(bounds.right, chunk.right),
+                    i32::max(bounds.bottom, chunk.bottom),
+                ),
+                None => chunk,
+            }))
+    }
+
+    // plays back the recording and saves it as an animated GIF. this can take a while for long recordings, so it's
+    // best done on a separate thread
+    pub fn save_gif(&self, path: &Path) -> Result<(), Error> {
+        let bounds = self.bounds().ok_or(Error::NothingRecorded)?;
+        let longest_side = i32::max(bounds.width(), bounds.height()) as f32;
+        let scale = f32::min(Self::MAX_SIZE as f32 / longest_side, 1.0);
+        let to_frame = |x: i32, origin: i32| ((x - origin) as f32 * scale).round() as u32;
+
+        let white = Rgba([255, 255, 255, 255]);
+        let mut frame = RgbaImage::from_pixel(
+            to_frame(bounds.right, bounds.left),
+            to_frame(bounds.bottom, bounds.top),
+            white,
+        );
+
+        let mut encoder = GifEncoder::new_with_speed(BufWriter::new(File::create(path)?), 10);
+        encoder.set_repeat(Repeat::Infinite)?;
+        for snapshot in &self.snapshots {
+            for (position, png_data) in &snapshot.chunks {
+                let rect = PaintCanvas::chunk_rect(*position);
+                let chunk = ::image::load_from_memory_with_format(png_data, ImageFormat::Png)?.to_rgba8();
+                // the canvas is shown on a white background, so the timelapse should be too
+                let mut tile = RgbaImage::from_pixel(chunk.width(), chunk.height(), white);
+                imageops::overlay(&mut tile, &chunk, 0, 0);
+
+                let (left, top) = (to_frame(rect.left, bounds.left), to_frame(rect.top, bounds.top));
+                let (right, bottom) = (to_frame(rect.right, bounds.left), to_frame(rect.bottom, bounds.top));
+                if scale < 1.0 {
+                    let (width, height) = (u32::max(right - left, 1), u32::max(bottom - top, 1));
+                    tile = imageops::resize(&tile, width, height, FilterType::Triangle);
+                }
+                imageops::replace(&mut frame, &tile, left, top);
+            }
+            let delay = Delay::from_numer_denom_ms(Self::FRAME_DELAY_MS, 1);
+            encoder.encode_frame(Frame::from_parts(frame.clone(), 0, 0, delay))?;
+        }
+        let delay = Delay::from_numer_denom_ms(Self::LAST_FRAME_DELAY_MS, 1);
+        encoder.encode_frame(Frame::from_parts(frame, 0, 0, delay))?;
+
+        Ok(())
+    }
+
+}
@@ -0,0 +1,209 @@
+// session replays.
+// when record_sessions is enabled in config.toml, every packet that changes the canvas - strokes, chunk updates and
+// tool packets, both our own and everyone else's - is logged along with the time it was sent or received. the log can
+// then be played back with `netcanv --replay FILE`, which applies the packets onto a blank canvas in the same order.
+// this is useful for tracking down desyncs, and for watching how a drawing came to be.
+//
+// the file starts with MAGIC, followed by the entries. each entry is a bincode-encoded Header followed by the packet.
+// entries are appended as they come in, so a log cut short by a crash is still readable up to the last full entry.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crossbeam_channel::Sender;
+use netcanv_protocol::build;
+use netcanv_protocol::client as cl;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::cli;
+use crate::config::{self, UserConfig};
+use crate::paint_canvas::PaintCanvas;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not read the replay: {0}")]
+    Deserialize(#[from] build::Error),
+    #[error("{0}")]
+    Config(#[from] config::Error),
+    #[error("{0}")]
+    Args(#[from] cli::Error),
+    #[error("Invalid replay speed: {0}")]
+    InvalidSpeed(String),
+    #[error("{0} is not a NetCanv replay")]
+    NotAReplay(String),
+}
+
+const MAGIC: &[u8] = b"NetCanv replay 1\n";
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    // milliseconds since the recording was started
+    time: u32,
+    // None for packets we sent ourselves
+    sender: Option<SocketAddr>,
+}
+
+pub struct Entry {
+    pub time: Duration,
+    pub sender: Option<SocketAddr>,
+    pub packet: cl::Packet,
+}
+
+fn dir() -> Result<PathBuf, Error> {
+    Ok(UserConfig::dir()?.join("replays"))
+}
+
+// whether the packet changes the canvas, and should therefore end up in the replay
+fn changes_canvas(packet: &cl::Packet) -> bool {
+    matches!(
+        packet,
+        cl::Packet::Stroke(_) |
+        cl::Packet::CanvasData(..) |
+        cl::Packet::ChunkTiles(..) |
+        cl::Packet::Tool(..) |
+        cl::Packet::Background(_)
+    )
+}
+
+pub struct Recorder {
+    start: Instant,
+    // entries are written to the file on a separate thread, so that disk I/O doesn't slow down painting
+    entries: Sender<Vec<u8>>,
+}
+
+impl Recorder {
+
+    // starts recording to a new file in the replays directory, named after the current time
+    pub fn start() -> Result<Self, Error> {
+        let dir = dir()?;
+        std::fs::create_dir_all(&dir)?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+        let path = dir.join(format!("{}.ncreplay", timestamp));
+        let mut file = BufWriter::new(File::create(&path)?);
+        file.write_all(MAGIC)?;
+        log::info!("recording the session to {}", path.display());
+
+        let (entries, receiver) = crossbeam_channel::unbounded::<Vec<u8>>();
+        std::thread::spawn(move || {
+            // the loop ends once the recorder is dropped
+            for entry in receiver.iter() {
+                let result = file.write_all(&entry).and_then(|_| {
+                    // flush whenever we've caught up, so that as little as possible is lost if NetCanv crashes
+                    if receiver.is_empty() { file.flush() } else { Ok(()) }
+                });
+                if let Err(error) = result {
+                    log::error!("could not write to the replay: {}", error);
+                    return
+                }
+            }
+        });
+
+        Ok(Self {
+            start: Instant::now(),
+            entries,
+        })
+    }
+
+    // records the packet, if it changes the canvas
+    pub fn record(&self, sender: Option<SocketAddr>, packet: &cl::Packet) {
+        if !changes_canvas(packet) { return }
+        let header = Header {
+            time: self.start.elapsed().as_millis() as u32,
+            sender,
+        };
+        let mut entry = Vec::new();
+        // serializing into a Vec can only fail if the packet itself can't be serialized, and then it wouldn't have
+        // made it through the network either
+        let serialized = bincode::serialize_into(&mut entry, &header)
+            .and_then(|_| bincode::serialize_into(&mut entry, packet));
+        if serialized.is_ok() {
+            let _ = self.entries.send(entry);
+        }
+    }
+
+    // records everything that's already on the canvas, so that the replay doesn't start out blank when a saved
+    // canvas is hosted
+    pub fn record_canvas(&self, paint_canvas: &mut PaintCanvas) {
+        let chunks: Vec<_> = paint_canvas.chunk_positions().collect();
+        for chunk in chunks {
+            if let Some(png_data) = paint_canvas.chunk_png_data(chunk) {
+                self.record(None, &cl::Packet::CanvasData(chunk, png_data));
+            }
+        }
+    }
+
+}
+
+// reads all entries from a replay file
+pub fn load(path: &Path) -> Result<Vec<Entry>, Error> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut magic = vec![0; MAGIC.len()];
+    if file.read_exact(&mut magic).is_err() || magic != MAGIC {
+        return Err(Error::NotAReplay(path.display().to_string()))
+    }
+
+    let mut entries = Vec::new();
+    // replays are just as untrusted as packets coming from the network, so a bogus length in one mustn't make us
+    // allocate gigabytes either
+    let is_io = |error: &build::Error| match error {
+        build::Error::Encode(error) => matches!(**error, bincode::ErrorKind::Io(_)),
+        _ => false,
+    };
+    loop {
+        let header: Header = match build::decode_from(&mut file) {
+            Ok(header) => header,
+            // the end of the file
+            Err(error) if is_io(&error) => break,
+            Err(error) => return Err(error.into()),
+        };
+        let packet = match build::decode_from(&mut file) {
+            Ok(packet) => packet,
+            // the recording was cut short in the middle of an entry
+            Err(error) if is_io(&error) => break,
+            Err(error) => return Err(error.into()),
+        };
+        entries.push(Entry {
+            time: Duration::from_millis(header.time.into()),
+            sender: header.sender,
+            packet,
+        });
+    }
+    Ok(entries)
+}
+
+pub struct Options {
+    pub path: PathBuf,
+    pub speed: f32,
+}
+
+impl Options {
+
+    const MAX_SPEED: f32 = 1000.0;
+
+    // parses the arguments following --replay: the path to the replay, optionally followed by --speed
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, Error> {
+        let path = PathBuf::from(cli::value(&mut args, "--replay")?);
+        let mut speed = 1.0;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--speed" => {
+                    let value = cli::value(&mut args, &arg)?;
+                    speed = match value.parse::<f32>() {
+                        // huge speeds would overflow the playback position
+                        Ok(speed) if speed > 0.0 && speed <= Self::MAX_SPEED => speed,
+                        _ => return Err(Error::InvalidSpeed(value)),
+                    };
+                },
+                _ => return Err(cli::Error::UnknownArgument(arg).into()),
+            }
+        }
+        Ok(Self { path, speed })
+    }
+
+}
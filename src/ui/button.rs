@@ -1,13 +1,20 @@
+use serde::Deserialize;
 use skulpin::skia_safe::*;
 
 use crate::ui::*;
+use crate::util::hex_color;
 
 pub struct Button;
 
+#[derive(Deserialize)]
 pub struct ButtonColors {
+    #[serde(with = "hex_color")]
     pub outline: Color,
+    #[serde(with = "hex_color")]
     pub text: Color,
+    #[serde(with = "hex_color")]
     pub hover: Color,
+    #[serde(with = "hex_color")]
     pub pressed: Color,
 }
 
@@ -44,6 +51,10 @@ impl Button {
             ui.fill(canvas, fill_color);
             clicked = input.mouse_button_just_released(MouseButton::Left);
         }
+        if ui.focusable() {
+            ui.focus_outline(canvas, colors.text);
+            clicked |= input.key_just_typed(VirtualKeyCode::Return);
+        }
 
         ui.pop_group();
 
@@ -0,0 +1,370 @@
+// the color picker. a panel for picking any color rather than just the ones in the palette: a square for picking the
+// saturation and brightness with a strip for picking the hue next to it, sliders for the red, green and blue
+// components, and a text field for the color's hex code.
+// the picker also has an eyedropper button, but as it doesn't know anything about the canvas, taking the color from
+// under the mouse is left to whoever opened it.
+
+use ::image::{Rgba, RgbaImage};
+use skulpin::skia_safe::*;
+
+use crate::ui::*;
+use crate::util::rgba_image_to_skia;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Dragging {
+    Square,
+    HueStrip,
+}
+
+pub struct ColorPicker {
+    open: bool,
+    // the color is kept as HSV, so that the hue isn't lost once the color turns gray
+    hue: f32,
+    saturation: f32,
+    value: f32,
+    alpha: f32,
+    dragging: Option<Dragging>,
+    // the square for the hue it was last rendered for
+    square: Option<(f32, Image)>,
+    hue_strip: Image,
+    red_slider: Slider,
+    green_slider: Slider,
+    blue_slider: Slider,
+    hex_field: TextField,
+    eyedropper: bool,
+    // whether the mouse was over the picker in the last frame, or a mouse button pressed over it is still held down
+    has_mouse: bool,
+    pressed: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct ColorPickerArgs<'a> {
+    pub fill: Color,
+    pub outline: Color,
+    pub text: Color,
+    pub slider: Color,
+    pub text_field: &'a TextFieldColors,
+    pub button: &'a ButtonColors,
+    // the text on the eyedropper button, while it's put away and while it's being used
+    pub eyedropper_label: &'a str,
+    pub cancel_label: &'a str,
+}
+
+// converts a color from HSV to RGB. all components are between 0 and 1
+fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> (f32, f32, f32) {
+    let h = (hue.fract() + 1.0).fract() * 6.0;
+    let c = value * saturation;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let m = value - c;
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    (r + m, g + m, b + m)
+}
+
+// converts a color from RGB to HSV. all components are between 0 and 1
+fn rgb_to_hsv(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let hue =
+        if delta == 0.0 { 0.0 }
+        else if max == r { ((g - b) / delta).rem_euclid(6.0) / 6.0 }
+        else if max == g { ((b - r) / delta + 2.0) / 6.0 }
+        else { ((r - g) / delta + 4.0) / 6.0 };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    (hue, saturation, max)
+}
+
+fn to_rgba(r: f32, g: f32, b: f32) -> Rgba<u8> {
+    let channel = |x: f32| (x * 255.0).round() as u8;
+    Rgba([channel(r), channel(g), channel(b), 255])
+}
+
+impl ColorPicker {
+
+    const SQUARE_SIZE: f32 = 160.0;
+    // the square is rendered at a lower resolution and scaled up, which is fine as it's a smooth gradient anyway
+    const SQUARE_RESOLUTION: u32 = 64;
+    const HUE_STRIP_WIDTH: f32 = 16.0;
+    const SPACING: f32 = 8.0;
+    const ROW_HEIGHT: f32 = 24.0;
+    const LABEL_WIDTH: f32 = 16.0;
+    const VALUE_WIDTH: f32 = 32.0;
+
+    pub fn new() -> Self {
+        let component_slider = || Slider::new(0.0, 0.0, 255.0, SliderStep::Discrete(1.0));
+        Self {
+            open: false,
+            hue: 0.0,
+            saturation: 0.0,
+            value: 0.0,
+            alpha: 1.0,
+            dragging: None,
+            square: None,
+            hue_strip: Self::render_hue_strip(),
+            red_slider: component_slider(),
+            green_slider: component_slider(),
+            blue_slider: component_slider(),
+            hex_field: TextField::new(Some("000000")),
+            eyedropper: false,
+            has_mouse: false,
+            pressed: false,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.eyedropper = false;
+    }
+
+    // whether the mouse is over the picker. the canvas shouldn't be painted on while this is true
+    pub fn has_mouse(&self) -> bool {
+        self.open && self.has_mouse
+    }
+
+    // whether the next click on the canvas should pick the color under the mouse
+    pub fn eyedropper(&self) -> bool {
+        self.open && self.eyedropper
+    }
+
+    pub fn color(&self) -> Color4f {
+        let (r, g, b) = hsv_to_rgb(self.hue, self.saturation, self.value);
+        Color4f::new(r, g, b, self.alpha)
+    }
+
+    // changes the color shown in the picker. colors that only differ by rounding are considered the same, so that
+    // this can be called every frame to keep the picker in sync without nudging the hue around
+    pub fn set_color(&mut self, color: Color4f) {
+        if color.to_color() == self.color().to_color() { return }
+        let (hue, saturation, value) = rgb_to_hsv(color.r, color.g, color.b);
+        // grays don't have a hue, so the last one is kept
+        if saturation > 0.0 {
+            self.hue = hue;
+        }
+        self.saturation = saturation;
+        self.value = value;
+        self.alpha = color.a;
+        self.update_fields();
+    }
+
+    // sets the color picked with the eyedropper, and puts the eyedropper away
+    pub fn pick(&mut self, color: Color4f) {
+        self.set_color(Color4f { a: self.alpha, ..color });
+        self.eyedropper = false;
+    }
+
+    fn hex_code(&self) -> String {
+        let color = self.color().to_color();
+        format!("{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+    }
+
+    // brings the sliders and the hex code in line with the color
+    fn update_fields(&mut self) {
+        let color = self.color().to_color();
+        self.red_slider.set_value(color.r() as f32);
+        self.green_slider.set_value(color.g() as f32);
+        self.blue_slider.set_value(color.b() as f32);
+        // don't pull the text from under someone who's typing it in
+        if !self.hex_field.focused() {
+            self.hex_field.set_text(&self.hex_code());
+        }
+    }
+
+    fn set_rgb(&mut self, r: f32, g: f32, b: f32) {
+        self.set_color(Color4f::new(r, g, b, self.alpha));
+    }
+
+    fn render_hue_strip() -> Image {
+        let height = Self::SQUARE_SIZE as u32;
+        let image = RgbaImage::from_fn(1, height, |_, y| {
+            let (r, g, b) = hsv_to_rgb(y as f32 / height as f32, 1.0, 1.0);
+            to_rgba(r, g, b)
+        });
+        rgba_image_to_skia(&image)
+    }
+
+    fn square_image(&mut self) -> &Image {
+        if !matches!(self.square, Some((hue, _)) if hue == self.hue) {
+            let size = Self::SQUARE_RESOLUTION;
+            let hue = self.hue;
+            let image = RgbaImage::from_fn(size, size, |x, y| {
+                let saturation = x as f32 / (size - 1) as f32;
+                let value = 1.0 - y as f32 / (size - 1) as f32;
+                let (r, g, b) = hsv_to_rgb(hue, saturation, value);
+                to_rgba(r, g, b)
+            });
+            self.square = Some((hue, rgba_image_to_skia(&image)));
+        }
+        &self.square.as_ref().unwrap().1
+    }
+
+    fn process_square(&mut self, ui: &mut Ui, canvas: &mut Canvas, input: &Input) {
+        ui.push_group((Self::SQUARE_SIZE, Self::SQUARE_SIZE), Layout::Freeform);
+        if ui.has_mouse(input) && input.mouse_button_just_pressed(MouseButton::Left) {
+            self.dragging = Some(Dragging::Square);
+        }
+        if self.dragging == Some(Dragging::Square) {
+            let mouse = ui.mouse_position(input);
+            self.saturation = (mouse.x / Self::SQUARE_SIZE).clamp(0.0, 1.0);
+            self.value = 1.0 - (mouse.y / Self::SQUARE_SIZE).clamp(0.0, 1.0);
+            self.update_fields();
+        }
+        let (saturation, value) = (self.saturation, self.value);
+        let image = self.square_image();
+        ui.draw_on_canvas(canvas, |canvas| {
+            let mut paint = Paint::default();
+            paint.set_filter_quality(FilterQuality::Medium);
+            let rect = Rect::from_wh(Self::SQUARE_SIZE, Self::SQUARE_SIZE);
+            canvas.draw_image_rect(image, None, rect, &paint);
+            // the ring around the current color is drawn in black or white, whichever stands out more
+            let ring_color = if value > 0.5 { Color::BLACK } else { Color::WHITE };
+            let mut paint = Paint::new(Color4f::from(ring_color), None);
+            paint.set_anti_alias(true);
+            paint.set_style(paint::Style::Stroke);
+            paint.set_stroke_width(2.0);
+            let center = Point::new(saturation * Self::SQUARE_SIZE, (1.0 - value) * Self::SQUARE_SIZE);
+            canvas.draw_circle(center, 5.0, &paint);
+        });
+        ui.pop_group();
+    }
+
+    fn process_hue_strip(&mut self, ui: &mut Ui, canvas: &mut Canvas, input: &Input, args: ColorPickerArgs) {
+        ui.push_group((Self::HUE_STRIP_WIDTH, Self::SQUARE_SIZE), Layout::Freeform);
+        if ui.has_mouse(input) && input.mouse_button_just_pressed(MouseButton::Left) {
+            self.dragging = Some(Dragging::HueStrip);
+        }
+        if self.dragging == Some(Dragging::HueStrip) {
+            // the bottom of the strip is the same red as the top, so it's kept just below a full turn
+            self.hue = (ui.mouse_position(input).y / Self::SQUARE_SIZE).clamp(0.0, 0.999);
+            self.update_fields();
+        }
+        let hue = self.hue;
+        let hue_strip = &self.hue_strip;
+        ui.draw_on_canvas(canvas, |canvas| {
+            let rect = Rect::from_wh(Self::HUE_STRIP_WIDTH, Self::SQUARE_SIZE);
+            canvas.draw_image_rect(hue_strip, None, rect, &Paint::default());
+            let mut paint = Paint::new(Color4f::from(args.outline), None);
+            paint.set_style(paint::Style::Stroke);
+            paint.set_stroke_width(2.0);
+            let y = hue * Self::SQUARE_SIZE;
+            canvas.draw_rect(Rect::from_xywh(-1.0, y - 2.0, Self::HUE_STRIP_WIDTH + 2.0, 4.0), &paint);
+        });
+        ui.pop_group();
+    }
+
+    fn process_component_slider(
+        ui: &mut Ui,
+        canvas: &mut Canvas,
+        input: &Input,
+        args: ColorPickerArgs,
+        label: &str,
+        slider: &mut Slider,
+    ) {
+        ui.push_group((ui.width(), Self::ROW_HEIGHT), Layout::Horizontal);
+        ui.push_group((Self::LABEL_WIDTH, Self::ROW_HEIGHT), Layout::Freeform);
+        ui.text(canvas, label, args.text, (AlignH::Left, AlignV::Middle));
+        ui.pop_group();
+        slider.process(ui, canvas, input, SliderArgs {
+            width: ui.width() - Self::LABEL_WIDTH - Self::VALUE_WIDTH,
+            color: args.slider,
+        });
+        ui.push_group((Self::VALUE_WIDTH, Self::ROW_HEIGHT), Layout::Freeform);
+        ui.text(canvas, &slider.value().to_string(), args.text, (AlignH::Right, AlignV::Middle));
+        ui.pop_group();
+        ui.pop_group();
+    }
+
+    // processes the picker, with its bottom left corner in the bottom left corner of the current group
+    pub fn process(&mut self, ui: &mut Ui, canvas: &mut Canvas, input: &Input, args: ColorPickerArgs) {
+        if !self.open { return }
+
+        let width = 16.0 + Self::SQUARE_SIZE + Self::SPACING + Self::HUE_STRIP_WIDTH + 16.0;
+        let height = 16.0 +
+            Self::SQUARE_SIZE + Self::SPACING +
+            3.0 * Self::ROW_HEIGHT + Self::SPACING +
+            TextField::height(ui) + 16.0;
+
+        ui.push_group(ui.size(), Layout::Freeform);
+        ui.pad((32.0, 32.0));
+        ui.push_group((width, height), Layout::Vertical);
+        ui.align((AlignH::Left, AlignV::Bottom));
+        ui.fill(canvas, args.fill);
+        ui.outline(canvas, args.outline, 1.0);
+
+        let hovered = ui.has_mouse(input);
+        if hovered && input.mouse_button_just_pressed(MouseButton::Left) {
+            self.pressed = true;
+        }
+        if !input.mouse_button_is_down(MouseButton::Left) {
+            self.pressed = false;
+            self.dragging = None;
+        }
+        self.has_mouse = hovered || self.pressed;
+
+        ui.pad((32.0, 32.0));
+
+        // saturation, value and hue
+
+        ui.push_group((ui.width(), Self::SQUARE_SIZE), Layout::Horizontal);
+        self.process_square(ui, canvas, input);
+        ui.space(Self::SPACING);
+        self.process_hue_strip(ui, canvas, input, args);
+        ui.pop_group();
+        ui.space(Self::SPACING);
+
+        // red, green and blue
+
+        let before = self.color().to_color();
+        Self::process_component_slider(ui, canvas, input, args, "R", &mut self.red_slider);
+        Self::process_component_slider(ui, canvas, input, args, "G", &mut self.green_slider);
+        Self::process_component_slider(ui, canvas, input, args, "B", &mut self.blue_slider);
+        let (r, g, b) = (self.red_slider.value(), self.green_slider.value(), self.blue_slider.value());
+        if (r, g, b) != (before.r() as f32, before.g() as f32, before.b() as f32) {
+            self.set_rgb(r / 255.0, g / 255.0, b / 255.0);
+        }
+        ui.space(Self::SPACING);
+
+        // hex code and eyedropper
+
+        let button = ButtonArgs {
+            height: TextField::height(ui),
+            colors: args.button,
+        };
+        let eyedropper_text = if self.eyedropper { args.cancel_label } else { args.eyedropper_label };
+        let eyedropper_width = ui.text_size(eyedropper_text).0 + button.height;
+        ui.push_group((ui.width(), TextField::height(ui)), Layout::Horizontal);
+        self.hex_field.process(ui, canvas, input, TextFieldArgs {
+            width: ui.width() - eyedropper_width - Self::SPACING,
+            colors: args.text_field,
+            hint: Some("RRGGBB"),
+        });
+        ui.space(Self::SPACING);
+        if Button::with_text(ui, canvas, input, button, eyedropper_text).clicked() {
+            self.eyedropper = !self.eyedropper;
+        }
+        ui.pop_group();
+
+        // the color only changes once a full, valid hex code has been typed in
+        let hex_code = self.hex_field.text().trim_start_matches('#');
+        if self.hex_field.focused() && hex_code.len() == 6 && hex_code != self.hex_code() {
+            if let Ok(hex) = u32::from_str_radix(hex_code, 16) {
+                let color = Color::new(0xff000000 | hex);
+                self.set_rgb(color.r() as f32 / 255.0, color.g() as f32 / 255.0, color.b() as f32 / 255.0);
+            }
+        }
+
+        ui.pop_group();
+        ui.pop_group();
+    }
+
+}
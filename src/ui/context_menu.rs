@@ -0,0 +1,96 @@
+// context menus. a list of items that pops up next to the mouse, eg. after right-clicking something.
+// the menu closes when one of its items is clicked, when the mouse is clicked anywhere outside of it, or when Escape is
+// pressed.
+
+use skulpin::skia_safe::*;
+
+use crate::ui::*;
+
+pub struct ContextMenu {
+    // None if the menu is closed
+    position: Option<Point>,
+}
+
+#[derive(Clone, Copy)]
+pub struct ContextMenuArgs<'a> {
+    pub width: f32,
+    pub item_height: f32,
+    pub fill: Color,
+    pub colors: &'a ButtonColors,
+}
+
+impl ContextMenu {
+
+    pub fn new() -> Self {
+        Self {
+            position: None,
+        }
+    }
+
+    // opens the menu with its top left corner at the given position, in window coordinates
+    pub fn open(&mut self, position: Point) {
+        self.position = Some(position);
+    }
+
+    pub fn close(&mut self) {
+        self.position = None;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.position.is_some()
+    }
+
+    // processes the menu and returns the index of the item that was clicked, if any. like tooltips, the menu must be
+    // processed after everything it should be drawn on top of
+    #[must_use]
+    pub fn process(
+        &mut self,
+        ui: &mut Ui,
+        canvas: &mut Canvas,
+        input: &Input,
+        ContextMenuArgs { width, item_height, fill, colors }: ContextMenuArgs,
+        items: &[&str],
+    ) -> Option<usize> {
+        let position = self.position?;
+        let size = (width, item_height * items.len() as f32);
+
+        ui.push_overlay_group();
+        // keep the menu inside of the window
+        let x = position.x.min(ui.width() - size.0).max(0.0);
+        let y = position.y.min(ui.height() - size.1).max(0.0);
+        ui.offset((x, y));
+        ui.push_group(size, Layout::Vertical);
+        ui.fill(canvas, fill);
+        let has_mouse = ui.has_mouse(input);
+
+        let mut clicked = None;
+        for (i, item) in items.iter().enumerate() {
+            ui.push_group((ui.width(), item_height), Layout::Freeform);
+            if ui.has_mouse(input) {
+                let fill_color =
+                    if input.mouse_button_is_down(MouseButton::Left) { colors.pressed }
+                    else { colors.hover };
+                ui.fill(canvas, fill_color);
+                if input.mouse_button_just_released(MouseButton::Left) {
+                    clicked = Some(i);
+                }
+            }
+            ui.pad((16.0, 0.0));
+            ui.text(canvas, item, colors.text, (AlignH::Left, AlignV::Middle));
+            ui.pop_group();
+        }
+
+        ui.outline(canvas, colors.outline, 1.0);
+        ui.pop_group();
+        ui.pop_group();
+
+        let pressed = |button| input.mouse_button_just_pressed(button);
+        let clicked_outside =
+            !has_mouse && (pressed(MouseButton::Left) || pressed(MouseButton::Right) || pressed(MouseButton::Middle));
+        if clicked.is_some() || clicked_outside || input.key_just_typed(VirtualKeyCode::Escape) {
+            self.close();
+        }
+        clicked
+    }
+
+}
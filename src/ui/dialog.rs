@@ -0,0 +1,100 @@
+// modal dialogs. a message with a row of buttons, shown in the middle of the window. everything behind the dialog is
+// dimmed, as it can't be used until one of the buttons is clicked. pressing Escape clicks the last button, which should
+// therefore be the one that cancels.
+
+use skulpin::skia_safe::*;
+
+use crate::ui::*;
+
+pub struct Dialog {
+    open: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct DialogArgs<'a> {
+    pub fill: Color,
+    pub outline: Color,
+    pub text: Color,
+    pub button: ButtonArgs<'a>,
+}
+
+impl Dialog {
+
+    const WIDTH: f32 = 384.0;
+    const BUTTON_SPACING: f32 = 8.0;
+
+    pub fn new() -> Self {
+        Self {
+            open: false,
+        }
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    // processes the dialog and returns the index of the button that was clicked, if any. the dialog closes once a
+    // button is clicked. the dialog must be processed after everything it should be drawn on top of, and whatever is
+    // behind it shouldn't receive mouse input while it's open
+    #[must_use]
+    pub fn process(
+        &mut self,
+        ui: &mut Ui,
+        canvas: &mut Canvas,
+        input: &Input,
+        DialogArgs { fill, outline, text, button }: DialogArgs,
+        message: &str,
+        buttons: &[&str],
+    ) -> Option<usize> {
+        if !self.open { return None }
+
+        let lines: Vec<_> = message.lines().collect();
+        let text_height = (ui.font_size() * 1.2 * lines.len() as f32).round();
+        let height = 16.0 + text_height + 16.0 + button.height + 16.0;
+
+        ui.push_overlay_group();
+        ui.fill(canvas, Color::BLACK.with_a(96));
+        ui.push_group((Self::WIDTH, height), Layout::Vertical);
+        ui.align((AlignH::Center, AlignV::Middle));
+        ui.fill(canvas, fill);
+        ui.outline(canvas, outline, 1.0);
+        ui.pad((32.0, 32.0));
+
+        ui.paragraph(canvas, text, AlignH::Left, None, &lines);
+        ui.space(16.0);
+
+        // the buttons are aligned to the right
+        let mut clicked = None;
+        let buttons_width: f32 = buttons
+            .iter()
+            .map(|label| ui.text_size(label).0 + button.height + Self::BUTTON_SPACING)
+            .sum::<f32>() - Self::BUTTON_SPACING;
+        ui.push_group((ui.width(), button.height), Layout::Horizontal);
+        ui.space(ui.width() - buttons_width);
+        for (i, label) in buttons.iter().enumerate() {
+            if i > 0 {
+                ui.space(Self::BUTTON_SPACING);
+            }
+            if Button::with_text(ui, canvas, input, button, label).clicked() {
+                clicked = Some(i);
+            }
+        }
+        ui.pop_group();
+
+        ui.pop_group();
+        ui.pop_group();
+
+        if clicked.is_none() && input.key_just_typed(VirtualKeyCode::Escape) && !buttons.is_empty() {
+            clicked = Some(buttons.len() - 1);
+        }
+        if clicked.is_some() {
+            self.open = false;
+        }
+        clicked
+    }
+
+}
@@ -1,6 +1,8 @@
+use serde::Deserialize;
 use skulpin::skia_safe::*;
 
 use crate::ui::*;
+use crate::util::hex_color;
 
 pub struct Expand {
     expanded: bool,
@@ -11,10 +13,15 @@ pub struct ExpandIcons {
     pub shrink: Image,
 }
 
+#[derive(Deserialize)]
 pub struct ExpandColors {
+    #[serde(with = "hex_color")]
     pub text: Color,
+    #[serde(with = "hex_color")]
     pub icon: Color,
+    #[serde(with = "hex_color")]
     pub hover: Color,
+    #[serde(with = "hex_color")]
     pub pressed: Color,
 }
 
@@ -72,6 +79,13 @@ impl Expand {
 
         // visible area
         ui.push_group((width, ui.height()), Layout::Freeform);
+        if ui.focusable() {
+            ui.focus_outline(canvas, colors.icon);
+            if input.key_just_typed(VirtualKeyCode::Return) {
+                self.expanded = !self.expanded;
+                result.just_clicked = true;
+            }
+        }
         if ui.has_mouse(input) {
             let pressed = input.mouse_button_is_down(MouseButton::Left);
             // underline
@@ -1,14 +1,27 @@
-use std::time::Instant;
+use std::cell::Cell;
+use std::time::{Duration, Instant};
 
 use skulpin::skia_safe::*;
 
+use crate::net::socket::Waker;
+
 use winit::dpi::PhysicalPosition;
-pub use winit::event::{ElementState, MouseButton, VirtualKeyCode};
-use winit::event::{WindowEvent, KeyboardInput};
+pub use winit::event::{ElementState, ModifiersState, MouseButton, VirtualKeyCode};
+use winit::event::{WindowEvent, KeyboardInput, MouseScrollDelta, Touch, TouchPhase};
 
 const MOUSE_BUTTON_COUNT: usize = 8;
 const KEY_CODE_COUNT: usize = 256;
 
+// a single movement of the mouse or pen
+#[derive(Clone, Copy, Debug)]
+pub struct PointerEvent {
+    pub position: Point,
+    // the pen pressure at the time of the movement, or None if no pressure-sensitive pen is touching the tablet
+    pub pressure: Option<f32>,
+    // when the movement happened, in seconds. see Input::time_in_seconds
+    pub time: f32,
+}
+
 pub struct Input {
     // mouse input
 
@@ -19,19 +32,48 @@ pub struct Input {
     mouse_button_just_pressed: [bool; MOUSE_BUTTON_COUNT],
     mouse_button_just_released: [bool; MOUSE_BUTTON_COUNT],
     mouse_buttons_locked: bool,
+    // the UI scale set in the config. the mouse position is divided by it, so that it matches the scaled UI
+    ui_scale: f32,
+    // every movement of the mouse or pen since the last frame, oldest first. the window system usually reports
+    // movements a lot more often than frames are drawn, and drawing only the last one of them would cut the corners
+    // of fast strokes
+    pointer_events: Vec<PointerEvent>,
+    // how far the mouse wheel was scrolled since the last frame, in lines. positive y is up
+    mouse_scroll: Vector,
+
+    // pen input
+
+    // the pressure of the pen touching the tablet, between 0 and 1. None if no pressure-sensitive pen is touching it
+    pen_pressure: Option<f32>,
+    // when the pen pressure was last reported, in seconds
+    pen_pressure_time: f32,
 
     // keyboard input
 
     char_buffer: Vec<char>,
     key_just_typed: [bool; KEY_CODE_COUNT],
+    modifiers: ModifiersState,
+    // where the input method's candidate window should be placed. set by the focused text field, which only gets to
+    // see the input immutably
+    ime_position: Cell<Option<Point>>,
 
     // time
 
     time_origin: Instant,
+    // when the mouse, keyboard, or pen was last used
+    last_input: Instant,
+    // the time the next frame was asked to be drawn at, in seconds since time_origin. frames are only drawn when
+    // something happens, so anything that changes over time - animations, network polling - has to ask for them
+    redraw_at: Cell<Option<f32>>,
+    // wakes the event loop up from other threads, eg. when packets come in
+    waker: Option<Waker>,
 }
 
 impl Input {
 
+    // how many pixels of scrolling on a touchpad count as scrolling by a line
+    const PIXELS_PER_LINE: f32 = 40.0;
+
     pub fn new() -> Self {
         Self {
             mouse_position: Point::new(0.0, 0.0),
@@ -40,9 +82,19 @@ impl Input {
             mouse_button_just_pressed: [false; MOUSE_BUTTON_COUNT],
             mouse_button_just_released: [false; MOUSE_BUTTON_COUNT],
             mouse_buttons_locked: false,
+            ui_scale: 1.0,
+            pointer_events: Vec::new(),
+            mouse_scroll: Vector::new(0.0, 0.0),
+            pen_pressure: None,
+            pen_pressure_time: 0.0,
             char_buffer: Vec::new(),
             key_just_typed: [false; KEY_CODE_COUNT],
+            modifiers: ModifiersState::empty(),
+            ime_position: Cell::new(None),
             time_origin: Instant::now(),
+            last_input: Instant::now(),
+            redraw_at: Cell::new(None),
+            waker: None,
         }
     }
 
@@ -50,6 +102,15 @@ impl Input {
         self.mouse_position
     }
 
+    // the mouse position only follows a new scale once the mouse is moved
+    pub fn set_ui_scale(&mut self, scale: f32) {
+        self.ui_scale = scale;
+    }
+
+    pub fn ui_scale(&self) -> f32 {
+        self.ui_scale
+    }
+
     pub fn previous_mouse_position(&self) -> Point {
         self.previous_mouse_position
     }
@@ -89,6 +150,27 @@ impl Input {
         self.mouse_buttons_locked = false;
     }
 
+    pub fn mouse_scroll(&self) -> Vector {
+        if self.mouse_buttons_locked { return Vector::new(0.0, 0.0) }
+        self.mouse_scroll
+    }
+
+    pub fn pen_pressure(&self) -> Option<f32> {
+        self.pen_pressure
+    }
+
+    pub fn pointer_events(&self) -> &[PointerEvent] {
+        &self.pointer_events
+    }
+
+    // whether the mouse or pen was moved with a button held down since the last frame. this is when latency is the
+    // most noticeable, so frames are drawn right away rather than waiting for max_fps to allow them
+    pub fn pointer_is_dragging(&self) -> bool {
+        !self.mouse_buttons_locked &&
+        !self.pointer_events.is_empty() &&
+        self.mouse_button_is_down.iter().any(|&is_down| is_down)
+    }
+
     pub fn characters_typed(&self) -> &[char] {
         &self.char_buffer
     }
@@ -101,22 +183,106 @@ impl Input {
         }
     }
 
+    pub fn ctrl_is_down(&self) -> bool {
+        self.modifiers.ctrl()
+    }
+
+    pub fn shift_is_down(&self) -> bool {
+        self.modifiers.shift()
+    }
+
+    // asks for the input method's candidate window to be placed at the given position on screen.
+    // winit 0.24 doesn't report text that's still being composed, so composed text only shows up in text fields once
+    // it's committed, but at least the candidate window can be shown next to where it's going to end up
+    pub fn set_ime_position(&self, position: Point) {
+        self.ime_position.set(Some(position));
+    }
+
+    pub fn take_ime_position(&self) -> Option<Point> {
+        self.ime_position.take()
+    }
+
     pub fn time_in_seconds(&self) -> f32 {
         let now = self.time_origin.elapsed();
-        now.as_millis() as f32 / 1_000.0
+        now.as_secs_f32()
+    }
+
+    // asks for another frame to be drawn as soon as possible
+    pub fn request_redraw(&self) {
+        self.request_redraw_after(0.0);
+    }
+
+    // asks for another frame to be drawn after the given amount of seconds at the latest
+    pub fn request_redraw_after(&self, seconds: f32) {
+        let time = self.time_in_seconds() + seconds;
+        let time = self.redraw_at.get().map_or(time, |redraw_at| redraw_at.min(time));
+        self.redraw_at.set(Some(time));
+    }
+
+    // returns when the next frame was asked to be drawn, if at all
+    pub fn take_redraw_request(&self) -> Option<Instant> {
+        self.redraw_at.take().map(|time| self.time_origin + Duration::from_secs_f32(time.max(0.0)))
+    }
+
+    pub fn set_waker(&mut self, waker: Waker) {
+        self.waker = Some(waker);
+    }
+
+    // returns what wakes the event loop up from other threads. None if the window doesn't have an event loop of its
+    // own to wake up, such as the panel window
+    pub fn waker(&self) -> Option<&Waker> {
+        self.waker.as_ref()
+    }
+
+    // how long it's been since the mouse, keyboard, or pen was last used
+    pub fn time_since_input(&self) -> Duration {
+        self.last_input.elapsed()
     }
 
     pub fn process_event(&mut self, event: &WindowEvent) {
+        if matches!(
+            event,
+            WindowEvent::CursorMoved { .. } |
+            WindowEvent::MouseInput { .. } |
+            WindowEvent::MouseWheel { .. } |
+            WindowEvent::Touch(_) |
+            WindowEvent::ReceivedCharacter(_) |
+            WindowEvent::KeyboardInput { .. }
+        ) {
+            self.last_input = Instant::now();
+        }
+
         match event {
 
             WindowEvent::CursorMoved { position, .. } => {
                 let PhysicalPosition { x, y } = position;
-                self.mouse_position = Point::new(*x as _, *y as _);
+                self.mouse_position = Point::new(*x as _, *y as _) * (1.0 / self.ui_scale);
+                self.pointer_events.push(PointerEvent {
+                    position: self.mouse_position,
+                    pressure: self.pen_pressure,
+                    time: self.time_in_seconds(),
+                });
             },
 
             WindowEvent::MouseInput { button, state, .. } =>
                 self.process_mouse_input(*button, *state),
 
+            WindowEvent::MouseWheel { delta, .. } => self.mouse_scroll += match *delta {
+                MouseScrollDelta::LineDelta(x, y) => Vector::new(x, y),
+                // touchpads scroll by pixels rather than lines
+                MouseScrollDelta::PixelDelta(PhysicalPosition { x, y }) =>
+                    Vector::new(x as f32, y as f32) * (1.0 / Self::PIXELS_PER_LINE),
+            },
+
+            // pens are also reported as a mouse, so touch events are only used for the pressure
+            WindowEvent::Touch(Touch { phase, force, .. }) => {
+                let pressure = match phase {
+                    TouchPhase::Ended | TouchPhase::Cancelled => None,
+                    _ => force.map(|force| force.normalized().min(1.0) as f32),
+                };
+                self.process_pen_pressure(pressure);
+            },
+
             WindowEvent::ReceivedCharacter(c) => self.char_buffer.push(*c),
 
             WindowEvent::KeyboardInput {
@@ -128,6 +294,8 @@ impl Input {
                 ..
             } => self.process_keyboard_input(*key, *state),
 
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = *modifiers,
+
             _ => (),
 
         }
@@ -141,6 +309,8 @@ impl Input {
             *state = false;
         }
         self.previous_mouse_position = self.mouse_position;
+        self.pointer_events.clear();
+        self.mouse_scroll = Vector::new(0.0, 0.0);
         for state in &mut self.key_just_typed {
             *state = false;
         }
@@ -177,6 +347,24 @@ impl Input {
         }
     }
 
+    // the pressure is reported separately from the movements, and usually less often, so the movements made since the
+    // previous pressure was reported get a pressure interpolated between the two, based on when they happened.
+    // otherwise the width of a stroke would change in steps
+    fn process_pen_pressure(&mut self, pressure: Option<f32>) {
+        let time = self.time_in_seconds();
+        if let (Some(from), Some(to)) = (self.pen_pressure, pressure) {
+            let duration = time - self.pen_pressure_time;
+            for event in &mut self.pointer_events {
+                if event.time > self.pen_pressure_time && duration > 0.0 {
+                    let t = (event.time - self.pen_pressure_time) / duration;
+                    event.pressure = Some(from + (to - from) * t);
+                }
+            }
+        }
+        self.pen_pressure = pressure;
+        self.pen_pressure_time = time;
+    }
+
     fn key_index(key: VirtualKeyCode) -> Option<usize> {
         let i = key as usize;
         if i < KEY_CODE_COUNT {
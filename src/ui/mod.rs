@@ -2,19 +2,30 @@ use std::cell::{Ref, RefMut};
 
 use skulpin::skia_safe::*;
 
+use crate::font_fallback;
 use crate::util::RcFont;
 
 pub mod input;
 mod button;
+mod color_picker;
+mod context_menu;
+mod dialog;
 mod expand;
 mod slider;
 mod textfield;
+mod toasts;
+mod tooltip;
 
 pub use button::*;
+pub use color_picker::*;
+pub use context_menu::*;
+pub use dialog::*;
 pub use expand::*;
 pub use input::*;
 pub use slider::*;
 pub use textfield::*;
+pub use toasts::*;
+pub use tooltip::*;
 
 #[derive(Copy, Clone, Debug)]
 pub enum AlignH {
@@ -45,11 +56,23 @@ struct Group {
     layout_position: Point,
     font: Option<RcFont>,
     font_size: f32,
-    font_height_in_pixels: f32
+    font_height_in_pixels: f32,
+    // overlays don't take up any space in their parent's layout
+    overlay: bool,
 }
 
 pub struct Ui {
     group_stack: Vec<Group>,
+    // keyboard focus. widgets that can be focused are numbered in the order they're processed in every frame, and the
+    // one whose number this is has the focus
+    focused: Option<usize>,
+    // how many focusable widgets were processed so far in this frame, and in the whole previous frame
+    focusable_count: usize,
+    previous_focusable_count: usize,
+    // whether the focus was moved with the keyboard in this frame
+    focus_moved: bool,
+    // while this is true, widgets can't be focused. this is for widgets hidden behind something else
+    focus_locked: bool,
 }
 
 impl Ui {
@@ -57,6 +80,11 @@ impl Ui {
     pub fn new() -> Self {
         Self {
             group_stack: Vec::new(),
+            focused: None,
+            focusable_count: 0,
+            previous_focusable_count: 0,
+            focus_moved: false,
+            focus_locked: false,
         }
     }
 
@@ -95,6 +123,10 @@ impl Ui {
 
     pub fn begin(&mut self, window_size: (f32, f32), layout: Layout) {
         self.group_stack.clear();
+        self.previous_focusable_count = self.focusable_count;
+        self.focusable_count = 0;
+        self.focus_moved = false;
+        self.focus_locked = false;
         let group = Group {
             rect: Rect::from_point_and_size((0.0, 0.0), window_size),
             layout,
@@ -102,6 +134,7 @@ impl Ui {
             font: None,
             font_size: -1.0, // invalid font size by default to trigger an error in text()
             font_height_in_pixels: 0.0,
+            overlay: false,
         };
         self.group_stack.push(group);
     }
@@ -113,6 +146,21 @@ impl Ui {
             layout,
             layout_position: Point::new(0.0, 0.0),
             font: self.top().font.clone(),
+            overlay: false,
+            .. *self.top()
+        };
+        self.group_stack.push(group);
+    }
+
+    // pushes a Freeform group that covers the whole window, no matter where the current group is. this is used for
+    // things drawn on top of everything else, like tooltips and context menus
+    pub fn push_overlay_group(&mut self) {
+        let group = Group {
+            rect: self.group_stack[0].rect,
+            layout: Layout::Freeform,
+            layout_position: Point::new(0.0, 0.0),
+            font: self.top().font.clone(),
+            overlay: true,
             .. *self.top()
         };
         self.group_stack.push(group);
@@ -120,6 +168,7 @@ impl Ui {
 
     pub fn pop_group(&mut self) {
         let group = self.group_stack.pop().expect("unbalanced group stack");
+        if group.overlay { return }
         match self.top().layout {
             Layout::Freeform => (),
             Layout::Horizontal => {
@@ -206,7 +255,7 @@ impl Ui {
     fn text_size_impl(&self, text: &str, font: &mut Font) -> (f32, f32) {
         let original_size = font.size();
         font.set_size(self.top().font_size);
-        let (advance, _) = font.measure_str(text, None);
+        let advance = font_fallback::measure_str(text, font);
         font.set_size(original_size);
         (advance, self.top().font_height_in_pixels)
     }
@@ -281,7 +330,7 @@ impl Ui {
         let mut paint = Paint::new(color.into(), None);
         let (origin, advance) = self.text_origin_impl(text, alignment, &mut font);
         paint.set_anti_alias(true);
-        canvas.draw_str(text, origin, &font, &paint);
+        font_fallback::draw_str(canvas, text, origin, &font, &paint);
 
         font.set_size(original_size);
 
@@ -347,39 +396,94 @@ impl Ui {
         canvas.restore();
     }
 
+    // the position of the current group's top left corner on screen
+    pub fn position(&self) -> Point {
+        self.top().rect.to_quad()[0]
+    }
+
     pub fn mouse_position(&self, input: &Input) -> Point {
         input.mouse_position() - self.top().rect.to_quad()[0]
     }
 
+    pub fn previous_mouse_position(&self, input: &Input) -> Point {
+        input.previous_mouse_position() - self.top().rect.to_quad()[0]
+    }
+
     pub fn has_mouse(&self, input: &Input) -> bool {
         let mouse = self.mouse_position(input);
         let Size { width, height } = self.top().rect.size();
         mouse.x >= 0.0 && mouse.x <= width && mouse.y >= 0.0 && mouse.y <= height
     }
 
+    // moves the keyboard focus to the next widget with Tab, or to the previous one with Shift+Tab, in the order the
+    // widgets were processed in on the last frame. clicking anywhere takes the focus away, unless what's clicked takes
+    // it back (as text fields do). this must be called right after begin on every frame the focus can be moved in
+    pub fn process_focus(&mut self, input: &Input) {
+        if input.mouse_button_just_pressed(MouseButton::Left) {
+            self.focused = None;
+        }
+        let count = self.previous_focusable_count;
+        if input.key_just_typed(VirtualKeyCode::Tab) && count > 0 {
+            self.focused = Some(match (self.focused, input.shift_is_down()) {
+                (None, false) => 0,
+                (None, true) => count - 1,
+                (Some(index), false) => (index + 1) % count,
+                (Some(index), true) => (index + count - 1) % count,
+            });
+            self.focus_moved = true;
+        }
+    }
+
+    // takes the keyboard focus away from whichever widget has it
+    pub fn clear_focus(&mut self) {
+        if self.focused.is_some() {
+            self.focused = None;
+            self.focus_moved = true;
+        }
+    }
+
+    // stops widgets from being focused until unlock_focus is called, eg. while they're covered up by a dialog
+    pub fn lock_focus(&mut self) {
+        self.focus_locked = true;
+    }
+
+    pub fn unlock_focus(&mut self) {
+        self.focus_locked = false;
+    }
+
+    // registers a widget that can be focused with the keyboard. returns whether it has the focus
+    pub fn focusable(&mut self) -> bool {
+        if self.focus_locked {
+            return false
+        }
+        let index = self.focusable_count;
+        self.focusable_count += 1;
+        self.focused == Some(index)
+    }
+
+    // gives the keyboard focus to the widget registered last, eg. after it was clicked
+    pub fn focus_last(&mut self) {
+        self.focused = self.focusable_count.checked_sub(1);
+    }
+
+    // whether the focus was moved to another widget with the keyboard in this frame
+    pub fn focus_moved(&self) -> bool {
+        self.focus_moved
+    }
+
+    // draws an outline around the current group, to show that the widget in it has the keyboard focus
+    pub fn focus_outline(&self, canvas: &mut Canvas, color: impl Into<Color4f>) {
+        let mut paint = Paint::new(color.into(), None);
+        paint.set_anti_alias(false);
+        paint.set_style(paint::Style::Stroke);
+        paint.set_stroke_width(2.0);
+        canvas.draw_rect(self.top().rect.with_outset((3.0, 3.0)), &paint);
+    }
+
 }
 
 pub trait Focus {
     fn focused(&self) -> bool;
-    fn set_focus(&mut self, focused: bool);
 }
 
-pub fn chain_focus(input: &Input, fields: &mut [&mut dyn Focus]) {
-    if input.key_just_typed(VirtualKeyCode::Tab) {
-        let mut had_focus = false;
-        for text_field in fields.iter_mut() {
-            if had_focus {
-                text_field.set_focus(true);
-                return
-            }
-            if text_field.focused() {
-                text_field.set_focus(false);
-                had_focus = true;
-            }
-        }
-        if !fields.is_empty() {
-            fields[0].set_focus(true);
-        }
-    }
-}
 
@@ -64,6 +64,21 @@ impl Slider {
             self.value = self.value.clamp(0.0, 1.0);
         }
 
+        // with the keyboard focus, the arrow keys move the slider by a step, or by a hundredth if it's smooth
+        if ui.focusable() {
+            ui.focus_outline(canvas, color);
+            let step = match self.step {
+                SliderStep::Smooth => 0.01,
+                SliderStep::Discrete(step) => step / (self.max - self.min),
+            };
+            if input.key_just_typed(VirtualKeyCode::Left) {
+                self.value = (self.value - step).clamp(0.0, 1.0);
+            }
+            if input.key_just_typed(VirtualKeyCode::Right) {
+                self.value = (self.value + step).clamp(0.0, 1.0);
+            }
+        }
+
         ui.draw_on_canvas(canvas, |canvas| {
             let transparent = Color4f::from(color.with_a(96));
             let mut paint = Paint::new(transparent, None);
@@ -97,6 +112,10 @@ impl Slider {
         ui.pop_group();
     }
 
+    pub fn set_value(&mut self, value: f32) {
+        self.value = ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0);
+    }
+
     pub fn value(&self) -> f32 {
         let raw = (self.value * (self.max - self.min)) + self.min;
         match self.step {
@@ -1,23 +1,42 @@
 // quite simplistic text field implementation.
 
+use std::ops::Range;
+
+use serde::Deserialize;
 use skulpin::skia_safe::*;
 
 use crate::ui::*;
+use crate::util::hex_color;
 
 pub struct TextField {
     text: Vec<char>,
     text_utf8: String,
+    masked: bool,
     focused: bool,
     blink_start: f32,
+    // the index of the character the caret is in front of
+    caret: usize,
+    // the other end of the selection. when nothing is selected, this is the same as the caret
+    anchor: usize,
+    // whether the mouse is being dragged to select text
+    selecting: bool,
+    // the time of the last click, for detecting double clicks
+    last_click: f32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize)]
 pub struct TextFieldColors {
+    #[serde(with = "hex_color")]
     pub outline: Color,
+    #[serde(with = "hex_color")]
     pub outline_focus: Color,
+    #[serde(with = "hex_color")]
     pub fill: Color,
+    #[serde(with = "hex_color")]
     pub text: Color,
+    #[serde(with = "hex_color")]
     pub text_hint: Color,
+    #[serde(with = "hex_color")]
     pub label: Color,
 }
 
@@ -32,14 +51,30 @@ impl TextField {
 
     const BLINK_PERIOD: f32 = 1.0;
     const HALF_BLINK: f32 = Self::BLINK_PERIOD / 2.0;
+    const PADDING: f32 = 16.0;
+    const DOUBLE_CLICK_TIME: f32 = 0.4;
 
     pub fn new(initial_text: Option<&str>) -> Self {
         let text_utf8: String = initial_text.unwrap_or("").into();
+        let text: Vec<char> = text_utf8.chars().collect();
         Self {
-            text: text_utf8.chars().collect(),
+            caret: text.len(),
+            anchor: text.len(),
+            text,
             text_utf8,
+            masked: false,
             focused: false,
             blink_start: 0.0,
+            selecting: false,
+            last_click: f32::NEG_INFINITY,
+        }
+    }
+
+    // creates a text field that hides its contents, for passwords and the like
+    pub fn new_masked() -> Self {
+        Self {
+            masked: true,
+            .. Self::new(None)
         }
     }
 
@@ -47,6 +82,43 @@ impl TextField {
         self.text_utf8 = self.text.iter().collect();
     }
 
+    // the text as it's shown in the field
+    fn displayed_text(&self) -> String {
+        if self.masked {
+            self.text.iter().map(|_| '•').collect()
+        } else {
+            self.text_utf8.clone()
+        }
+    }
+
+    // the horizontal position of the character with the given index, relative to the start of the text
+    fn character_x(ui: &Ui, displayed_text: &str, index: usize) -> f32 {
+        let before: String = displayed_text.chars().take(index).collect();
+        ui.text_size(&before).0
+    }
+
+    // returns the index of the character boundary closest to the given horizontal position
+    fn character_at(&self, ui: &Ui, x: f32) -> usize {
+        let displayed_text = self.displayed_text();
+        (0..=self.text.len())
+            .map(|index| (index, (Self::character_x(ui, &displayed_text, index) - x).abs()))
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .map_or(0, |(index, _)| index)
+    }
+
+    // the selected range of characters, which is empty if nothing is selected
+    fn selection(&self) -> Range<usize> {
+        usize::min(self.caret, self.anchor)..usize::max(self.caret, self.anchor)
+    }
+
+    // the range of the word around the character with the given index
+    fn word_at(&self, index: usize) -> Range<usize> {
+        let is_word = |ch: &char| ch.is_alphanumeric() || *ch == '_';
+        let start = index - self.text[..index].iter().rev().take_while(|ch| is_word(ch)).count();
+        let end = index + self.text[index..].iter().take_while(|ch| is_word(ch)).count();
+        start..end
+    }
+
     pub fn height(ui: &Ui) -> f32 {
         f32::round(16.0/7.0 * ui.font_size())
     }
@@ -60,6 +132,18 @@ impl TextField {
     ) {
         ui.push_group((width, Self::height(ui)), Layout::Freeform);
 
+        // the field's own outline shows when it's focused, so it doesn't need the focus outline other widgets have.
+        // the whole text gets selected when it's tabbed into, so that it can be replaced right away
+        let keyboard_focused = ui.focusable();
+        if ui.focus_moved() {
+            if keyboard_focused && !self.focused {
+                self.anchor = 0;
+                self.caret = self.text.len();
+                self.reset_blink(input);
+            }
+            self.focused = keyboard_focused;
+        }
+
         // rendering: box
         ui.draw_on_canvas(canvas, |canvas| {
             let mut paint = Paint::new(Color4f::from(colors.fill), None);
@@ -74,7 +158,7 @@ impl TextField {
 
         // rendering: text
         ui.push_group(ui.size(), Layout::Freeform);
-        ui.pad((16.0, 0.0));
+        ui.pad((Self::PADDING, 0.0));
         canvas.save();
         ui.clip(canvas);
 
@@ -82,14 +166,32 @@ impl TextField {
         if hint.is_some() && self.text.len() == 0 {
             ui.text(canvas, hint.unwrap(), colors.text_hint, (AlignH::Left, AlignV::Middle));
         }
-        let text_advance = ui.text(canvas, &self.text_utf8, colors.text, (AlignH::Left, AlignV::Middle));
+        let displayed_text = self.displayed_text();
+        let selection = self.selection();
+        if self.focused && !selection.is_empty() {
+            let start = Self::character_x(ui, &displayed_text, selection.start);
+            let end = Self::character_x(ui, &displayed_text, selection.end);
+            let height = Self::height(ui);
+            ui.draw_on_canvas(canvas, |canvas| {
+                let paint = Paint::new(Color4f::from(colors.text.with_a(64)), None);
+                canvas.draw_rect(Rect::new(start, height * 0.2, end, height * 0.8), &paint);
+            });
+        }
+        ui.text(canvas, &displayed_text, colors.text, (AlignH::Left, AlignV::Middle));
+        let caret_x = Self::character_x(ui, &displayed_text, self.caret);
 
+        if self.focused {
+            input.set_ime_position(ui.position() + Point::new(caret_x, Self::height(ui)));
+            // redraw when the caret blinks next
+            let phase = (input.time_in_seconds() - self.blink_start) % Self::HALF_BLINK;
+            input.request_redraw_after(Self::HALF_BLINK - phase);
+        }
         if self.focused && (input.time_in_seconds() - self.blink_start) % Self::BLINK_PERIOD < Self::HALF_BLINK {
             ui.draw_on_canvas(canvas, |canvas| {
                 let mut paint = Paint::new(Color4f::from(colors.text), None);
                 paint.set_anti_alias(false);
                 paint.set_style(paint::Style::Stroke);
-                let x = text_advance + 1.0;
+                let x = caret_x + 1.0;
                 let y1 = Self::height(ui) * 0.2;
                 let y2 = Self::height(ui) * 0.8;
                 canvas.draw_line((x, y1), (x, y2), &paint);
@@ -109,37 +211,118 @@ impl TextField {
         self.blink_start = input.time_in_seconds();
     }
 
-    fn append(&mut self, ch: char) {
-        self.text.push(ch);
+    fn move_caret(&mut self, to: usize, extend_selection: bool) {
+        self.caret = to;
+        if !extend_selection {
+            self.anchor = to;
+        }
+    }
+
+    // removes the selected text. returns false if nothing was selected
+    fn delete_selection(&mut self) -> bool {
+        let selection = self.selection();
+        if selection.is_empty() {
+            return false
+        }
+        self.text.drain(selection.clone());
+        self.move_caret(selection.start, false);
         self.update_utf8();
+        true
     }
 
-    fn backspace(&mut self) {
-        self.text.pop();
+    fn insert(&mut self, ch: char) {
+        self.delete_selection();
+        self.text.insert(self.caret, ch);
+        self.move_caret(self.caret + 1, false);
         self.update_utf8();
     }
 
+    fn backspace(&mut self) {
+        if !self.delete_selection() && self.caret > 0 {
+            self.text.remove(self.caret - 1);
+            self.move_caret(self.caret - 1, false);
+            self.update_utf8();
+        }
+    }
+
+    fn delete(&mut self) {
+        if !self.delete_selection() && self.caret < self.text.len() {
+            self.text.remove(self.caret);
+            self.update_utf8();
+        }
+    }
+
     const BACKSPACE: char = '\x08';
     const TAB: char = '\x09';
 
-    fn process_events(&mut self, ui: &Ui, input: &Input) {
+    fn process_events(&mut self, ui: &mut Ui, input: &Input) {
+        let mouse_x = ui.mouse_position(input).x - Self::PADDING;
         if input.mouse_button_just_pressed(MouseButton::Left) {
             self.focused = ui.has_mouse(input);
             if self.focused {
+                // so that Tab moves on to whatever comes after the field
+                ui.focus_last();
                 self.reset_blink(input);
+                let index = self.character_at(ui, mouse_x);
+                let time = input.time_in_seconds();
+                if time - self.last_click < Self::DOUBLE_CLICK_TIME && index == self.caret {
+                    // double click: select the word under the mouse
+                    let word = self.word_at(index);
+                    self.anchor = word.start;
+                    self.caret = word.end;
+                } else {
+                    self.move_caret(index, input.shift_is_down());
+                    self.selecting = true;
+                }
+                self.last_click = time;
             }
         }
+        if input.mouse_button_just_released(MouseButton::Left) {
+            self.selecting = false;
+        }
+        if self.selecting && input.mouse_position() != input.previous_mouse_position() {
+            let index = self.character_at(ui, mouse_x);
+            self.move_caret(index, true);
+            self.reset_blink(input);
+        }
+
         if self.focused {
             if !input.characters_typed().is_empty() {
                 self.reset_blink(input);
             }
             for ch in input.characters_typed() {
                 match *ch {
-                    _ if !ch.is_control() => self.append(*ch),
+                    _ if !ch.is_control() => self.insert(*ch),
                     Self::BACKSPACE => self.backspace(),
                     _ => (),
                 }
             }
+
+            let shift = input.shift_is_down();
+            let caret = self.caret;
+            let moved_to = if input.key_just_typed(VirtualKeyCode::Left) {
+                Some(caret.saturating_sub(1))
+            } else if input.key_just_typed(VirtualKeyCode::Right) {
+                Some(usize::min(caret + 1, self.text.len()))
+            } else if input.key_just_typed(VirtualKeyCode::Home) {
+                Some(0)
+            } else if input.key_just_typed(VirtualKeyCode::End) {
+                Some(self.text.len())
+            } else {
+                None
+            };
+            if let Some(index) = moved_to {
+                self.move_caret(index, shift);
+                self.reset_blink(input);
+            }
+            if input.key_just_typed(VirtualKeyCode::Delete) {
+                self.delete();
+                self.reset_blink(input);
+            }
+            if input.ctrl_is_down() && input.key_just_typed(VirtualKeyCode::A) {
+                self.anchor = 0;
+                self.caret = self.text.len();
+            }
         }
     }
 
@@ -172,14 +355,16 @@ impl TextField {
         &self.text_utf8
     }
 
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.chars().collect();
+        self.move_caret(self.text.len(), false);
+        self.update_utf8();
+    }
+
 }
 
 impl Focus for TextField {
     fn focused(&self) -> bool {
         self.focused
     }
-
-    fn set_focus(&mut self, focused: bool) {
-        self.focused = focused;
-    }
 }
@@ -0,0 +1,156 @@
+// toasts. short notifications that pop up in the bottom left corner and go away on their own after a while, or when
+// they're clicked. only a few are shown at a time, the rest wait in a queue until there's room for them.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use skulpin::skia_safe::*;
+
+use crate::ui::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Success,
+    Error,
+}
+
+struct Toast {
+    text: String,
+    severity: Severity,
+    // when the toast was first shown. None while it's waiting in the queue
+    shown: Option<Instant>,
+}
+
+pub struct Toasts {
+    // oldest first
+    toasts: VecDeque<Toast>,
+    // whether the mouse was over one of the toasts in the last frame
+    has_mouse: bool,
+    // the most severe toast pushed since the last call to take_alert, not counting plain information
+    alert: Option<Severity>,
+}
+
+#[derive(Clone, Copy)]
+pub struct ToastArgs {
+    pub fill: Color,
+    pub outline: Color,
+    pub text: Color,
+    pub error: Color,
+}
+
+impl Toasts {
+
+    const MAX_SHOWN: usize = 4;
+    const HEIGHT: f32 = 32.0;
+    const SPACING: f32 = 8.0;
+    const PADDING: f32 = 12.0;
+    // the width of the strip on the left, colored by the toast's severity
+    const STRIP_WIDTH: f32 = 4.0;
+
+    pub fn new() -> Self {
+        Self {
+            toasts: VecDeque::new(),
+            has_mouse: false,
+            alert: None,
+        }
+    }
+
+    pub fn push(&mut self, severity: Severity, text: String) {
+        self.toasts.push_back(Toast {
+            text,
+            severity,
+            shown: None,
+        });
+        if severity == Severity::Error || (severity == Severity::Success && self.alert.is_none()) {
+            self.alert = Some(severity);
+        }
+    }
+
+    // returns whether a success or an error was pushed since the last call, and which one. errors take priority.
+    // this is used for playing sound cues, so that a burst of notifications only makes one sound
+    pub fn take_alert(&mut self) -> Option<Severity> {
+        self.alert.take()
+    }
+
+    // whether the mouse is over one of the toasts. the current tool doesn't receive input while this is true, so that
+    // clicking a toast away doesn't also paint under it
+    pub fn has_mouse(&self) -> bool {
+        self.has_mouse
+    }
+
+    // how long a toast stays on screen. errors stay longer, as they usually need more reading
+    fn duration(severity: Severity) -> Duration {
+        match severity {
+            Severity::Error => Duration::from_secs(10),
+            _ => Duration::from_secs(5),
+        }
+    }
+
+    fn strip_color(severity: Severity, args: ToastArgs) -> Color {
+        match severity {
+            Severity::Info => args.text,
+            Severity::Success => Color::new(0xff2dd70e),
+            Severity::Error => args.error,
+        }
+    }
+
+    // draws the toasts on top of the current group. this must be called once per frame, after everything they should
+    // be drawn over
+    pub fn process(&mut self, ui: &mut Ui, canvas: &mut Canvas, input: &Input, args: ToastArgs) {
+        let now = Instant::now();
+        self.toasts.retain(|toast| toast.shown.is_none_or(|shown| now - shown < Self::duration(toast.severity)));
+        for toast in self.toasts.iter_mut().take(Self::MAX_SHOWN) {
+            toast.shown.get_or_insert(now);
+        }
+        self.has_mouse = false;
+        if self.toasts.is_empty() { return }
+
+        // redraw once the next toast is due to go away
+        let next_expiry = self.toasts
+            .iter()
+            .filter_map(|toast| toast.shown.map(|shown| shown + Self::duration(toast.severity)))
+            .min()
+            .unwrap_or(now);
+        input.request_redraw_after((next_expiry - now).as_secs_f32());
+
+        let count = usize::min(self.toasts.len(), Self::MAX_SHOWN);
+        let height = count as f32 * (Self::HEIGHT + Self::SPACING) - Self::SPACING;
+        let mut dismissed = None;
+        ui.push_group(ui.size(), Layout::Freeform);
+        ui.pad((16.0, 16.0));
+        ui.push_group((ui.width(), height), Layout::Vertical);
+        ui.align((AlignH::Left, AlignV::Bottom));
+        for (i, toast) in self.toasts.iter().take(count).enumerate() {
+            let width = Self::STRIP_WIDTH + Self::PADDING * 2.0 + ui.text_size(&toast.text).0;
+            ui.push_group((width, Self::HEIGHT), Layout::Horizontal);
+            ui.fill(canvas, args.fill);
+            ui.outline(canvas, args.outline, 1.0);
+            let strip_color = Self::strip_color(toast.severity, args);
+            ui.draw_on_canvas(canvas, |canvas| {
+                let paint = Paint::new(Color4f::from(strip_color), None);
+                canvas.draw_rect(Rect::from_xywh(0.0, 0.0, Self::STRIP_WIDTH, Self::HEIGHT), &paint);
+            });
+            ui.space(Self::STRIP_WIDTH + Self::PADDING);
+            ui.push_group((ui.remaining_width(), Self::HEIGHT), Layout::Freeform);
+            ui.text(canvas, &toast.text, args.text, (AlignH::Left, AlignV::Middle));
+            ui.pop_group();
+            if ui.has_mouse(input) {
+                self.has_mouse = true;
+                if input.mouse_button_just_pressed(MouseButton::Left) {
+                    dismissed = Some(i);
+                }
+            }
+            ui.pop_group();
+            ui.space(Self::SPACING);
+        }
+        ui.pop_group();
+        ui.pop_group();
+
+        if let Some(i) = dismissed {
+            self.toasts.remove(i);
+            input.request_redraw();
+        }
+    }
+
+}
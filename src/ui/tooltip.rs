@@ -0,0 +1,86 @@
+// tooltips. widgets call hover() while they're being processed, and if the mouse rests over one of them for a moment,
+// its tooltip is shown next to the mouse cursor.
+
+use skulpin::skia_safe::*;
+
+use crate::ui::*;
+
+pub struct Tooltip {
+    // the text of the tooltip under the mouse, and the time the mouse started hovering over it
+    hovered: Option<(String, f32)>,
+    // the text of the tooltip under the mouse in the current frame
+    next: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+pub struct TooltipArgs {
+    pub fill: Color,
+    pub outline: Color,
+    pub text: Color,
+}
+
+impl Tooltip {
+
+    // how long the mouse has to rest over a widget before its tooltip is shown, in seconds
+    const DELAY: f32 = 0.5;
+    const PADDING: (f32, f32) = (16.0, 8.0);
+    // so that the tooltip isn't covered by the mouse cursor
+    const MOUSE_OFFSET: (f32, f32) = (12.0, 20.0);
+
+    pub fn new() -> Self {
+        Self {
+            hovered: None,
+            next: None,
+        }
+    }
+
+    // sets the text of the tooltip shown for the current group, if it has the mouse
+    pub fn hover(&mut self, ui: &Ui, input: &Input, text: &str) {
+        if ui.has_mouse(input) {
+            self.next = Some(text.to_owned());
+        }
+    }
+
+    // draws the tooltip, if the mouse has been resting over a widget for long enough. this must be called once per
+    // frame, after all the widgets with tooltips have been processed, so that the tooltip ends up on top of them
+    pub fn process(&mut self, ui: &mut Ui, canvas: &mut Canvas, input: &Input, args: TooltipArgs) {
+        let time = input.time_in_seconds();
+        let next = self.next.take();
+        let same = matches!((&self.hovered, &next), (Some((text, _)), Some(next)) if text == next);
+        if !same {
+            self.hovered = next.map(|text| (text, time));
+        }
+
+        let (text, since) = match &self.hovered {
+            Some(hovered) => hovered,
+            None => return,
+        };
+        // clicking the widget usually means its tooltip isn't needed anymore
+        if time - since < Self::DELAY {
+            input.request_redraw_after(Self::DELAY - (time - since));
+            return
+        }
+        if input.mouse_button_is_down(MouseButton::Left) {
+            return
+        }
+
+        let (text_width, text_height) = ui.text_size(text);
+        let size = (text_width + Self::PADDING.0, text_height + Self::PADDING.1);
+        ui.push_overlay_group();
+        let mouse = ui.mouse_position(input);
+        // keep the tooltip inside of the window, flipping it above the cursor if there's no space below it
+        let x = (mouse.x + Self::MOUSE_OFFSET.0).min(ui.width() - size.0).max(0.0);
+        let mut y = mouse.y + Self::MOUSE_OFFSET.1;
+        if y + size.1 > ui.height() {
+            y = mouse.y - size.1;
+        }
+        ui.offset((x, y.max(0.0)));
+        ui.push_group(size, Layout::Freeform);
+        ui.fill(canvas, args.fill);
+        ui.outline(canvas, args.outline, 1.0);
+        ui.text(canvas, text, args.text, (AlignH::Center, AlignV::Middle));
+        ui.pop_group();
+        ui.pop_group();
+    }
+
+}
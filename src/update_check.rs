@@ -0,0 +1,117 @@
+// checking for new releases of NetCanv.
+// the latest release is fetched from GitHub's API on a separate thread when the lobby is opened, so that a slow or
+// missing internet connection doesn't hold up the UI. nothing is sent other than the request itself, and nothing is
+// checked until the user agrees to it in the lobby. the answer is kept as check_for_updates in the [updates] section
+// of config.toml.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Duration;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/liquidev/netcanv/releases/latest";
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Could not check for updates: {0}")]
+    Http(#[from] Box<ureq::Error>),
+    #[error("Could not read the latest release: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+// the parts of GitHub's release object that are shown in the app
+#[derive(Clone, Deserialize)]
+pub struct Release {
+    #[serde(rename = "tag_name")]
+    pub version: String,
+    // the changelog, in Markdown
+    #[serde(rename = "body", default)]
+    pub changelog: String,
+    #[serde(rename = "html_url")]
+    pub url: String,
+}
+
+pub struct UpdateCheck {
+    result: Receiver<Result<Release, Error>>,
+}
+
+// parses a version like v0.1.0 or 0.2, ignoring anything after the numbers such as -beta
+fn parse_version(version: &str) -> Vec<u32> {
+    version
+        .trim_start_matches('v')
+        .split(|c: char| !c.is_ascii_digit() && c != '.')
+        .next()
+        .unwrap_or("")
+        .split('.')
+        .map(|number| number.parse().unwrap_or(0))
+        .collect()
+}
+
+// whether the version is newer than the one that's running
+fn is_newer(version: &str) -> bool {
+    parse_version(version) > parse_version(env!("CARGO_PKG_VERSION"))
+}
+
+fn fetch_latest_release() -> Result<Release, Error> {
+    let response = ureq::get(LATEST_RELEASE_URL)
+        // GitHub refuses requests without a user agent
+        .set("User-Agent", concat!("NetCanv/", env!("CARGO_PKG_VERSION")))
+        .set("Accept", "application/vnd.github.v3+json")
+        .timeout(TIMEOUT)
+        .call()
+        .map_err(Box::new)?;
+    Ok(response.into_json()?)
+}
+
+impl UpdateCheck {
+
+    // starts checking for updates, unless that's already been done since NetCanv was launched. the lobby is opened
+    // again every time a room is left, and there's no point in asking GitHub each time
+    pub fn start_once() -> Option<Self> {
+        if STARTED.swap(true, Ordering::Relaxed) {
+            return None
+        }
+        let (sender, result) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(fetch_latest_release());
+        });
+        Some(Self { result })
+    }
+
+    // returns the latest release once it's been fetched, or None while the check is still running. the release is
+    // only returned if it's newer than the running version
+    pub fn poll(&self) -> Option<Result<Option<Release>, Error>> {
+        match self.result.try_recv() {
+            Ok(Ok(release)) if is_newer(&release.version) => Some(Ok(Some(release))),
+            Ok(Ok(_)) => Some(Ok(None)),
+            Ok(Err(error)) => Some(Err(error)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Ok(None)),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_parsed() {
+        assert_eq!(parse_version("v0.5.1"), [0, 5, 1]);
+        assert_eq!(parse_version("0.6"), [0, 6]);
+        assert_eq!(parse_version("v1.0.0-beta.2"), [1, 0, 0]);
+    }
+
+    #[test]
+    fn only_newer_versions_are_offered() {
+        assert!(!is_newer(env!("CARGO_PKG_VERSION")));
+        assert!(!is_newer("v0.0.1"));
+        assert!(is_newer("v999.0.0"));
+    }
+}
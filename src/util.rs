@@ -1,6 +1,7 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use ::image::RgbaImage;
 use skulpin::CoordinateSystemHelper;
 use skulpin::skia_safe::*;
 
@@ -14,11 +15,52 @@ pub fn hex_color4f(hex: u32) -> Color4f {
     Color4f::new(r, g, b, a)
 }
 
+pub fn color4f_hex(color: Color4f) -> u32 {
+    let color = color.to_color();
+    ((color.r() as u32) << 24) |
+    ((color.g() as u32) << 16) |
+    ((color.b() as u32) << 8) |
+    color.a() as u32
+}
+
+// deserializes colors from "#RRGGBB" or "#RRGGBBAA" strings.
+// meant to be used with #[serde(with = "hex_color")]
+pub mod hex_color {
+    use serde::{Deserialize, Deserializer};
+    use serde::de::Error;
+    use skulpin::skia_safe::Color;
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        let invalid = || D::Error::custom(format!("invalid color {:?}, expected #RRGGBB or #RRGGBBAA", string));
+        let digits = string.strip_prefix('#').ok_or_else(invalid)?;
+        let hex = u32::from_str_radix(digits, 16).map_err(|_| invalid())?;
+        match digits.len() {
+            6 => Ok(Color::new(0xff000000 | hex)),
+            8 => Ok(Color::new(hex.rotate_right(8))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
 // conversions
 
-pub fn get_window_size(coordinate_system_helper: &CoordinateSystemHelper) -> (f32, f32) {
+// converts an image with straight alpha, like the ones returned by PaintCanvas::capture, into a Skia image
+pub fn rgba_image_to_skia(image: &RgbaImage) -> Image {
+    let image_info = ImageInfo::new(
+        (image.width() as i32, image.height() as i32),
+        ColorType::RGBA8888,
+        AlphaType::Unpremul,
+        ColorSpace::new_srgb(),
+    );
+    let stride = image.width() as usize * 4;
+    Image::from_raster_data(&image_info, Data::new_copy(image.as_raw()), stride).unwrap()
+}
+
+// the size of the window in UI units. with a UI scale above 1 everything is drawn bigger, so fewer units fit in it
+pub fn get_window_size(coordinate_system_helper: &CoordinateSystemHelper, ui_scale: f32) -> (f32, f32) {
     let logical_size = coordinate_system_helper.window_logical_size();
-    (logical_size.width as _, logical_size.height as _)
+    (logical_size.width as f32 / ui_scale, logical_size.height as f32 / ui_scale)
 }
 
 // resources
@@ -35,3 +77,8 @@ pub fn new_rc_font(data: &[u8], default_size: f32) -> RcFont {
 pub fn quantize(value: f32, step: f32) -> f32 {
     step * (value / step + 0.5).floor()
 }
+
+// moves the point to the nearest intersection of a grid with the given cell size
+pub fn snap_point(point: Point, cell_size: f32) -> Point {
+    Point::new(quantize(point.x, cell_size), quantize(point.y, cell_size))
+}